@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ropey::Rope;
+
+use zee_edit::graphemes::{grapheme_metrics, width_at, GraphemeMetrics, RopeGraphemes};
+
+const TAB_WIDTH: usize = 4;
+
+/// Source code with a mix of tabs, wide characters and multi char emoji, the
+/// size of a few screens
+fn text() -> Rope {
+    let mut text = String::new();
+    for index in 0..200 {
+        text.push_str(&format!(
+            "\tlet value_{} = format!(\"{{}} 世界 👨‍👨‍👧‍👧 café\", index); // e\u{301}tude\n",
+            index
+        ));
+    }
+    Rope::from(text)
+}
+
+/// Walks the graphemes of every line as the text area does when drawing, the
+/// result being the total width and chars
+fn segment_lines(text: &Rope) -> (usize, usize) {
+    text.lines()
+        .fold((0, 0), |(total_width, total_chars), line| {
+            let mut column = 0;
+            let mut chars = 0;
+            for grapheme in RopeGraphemes::new(&line) {
+                column += width_at(TAB_WIDTH, column, &grapheme).max(1);
+                chars += grapheme.len_chars();
+            }
+            (total_width + column, total_chars + chars)
+        })
+}
+
+fn measured_lines(metrics: &[Vec<GraphemeMetrics>]) -> (usize, usize) {
+    metrics
+        .iter()
+        .fold((0, 0), |(total_width, total_chars), line| {
+            let mut column = 0;
+            let mut chars = 0;
+            for grapheme in line.iter() {
+                column += grapheme.width_at(TAB_WIDTH, column).max(1);
+                chars += grapheme.len_chars;
+            }
+            (total_width + column, total_chars + chars)
+        })
+}
+
+fn draw_lines(criterion: &mut Criterion) {
+    let text = text();
+    let metrics: Vec<_> = text
+        .lines()
+        .map(|line| grapheme_metrics(TAB_WIDTH, &line).collect())
+        .collect();
+    assert_eq!(segment_lines(&text), measured_lines(&metrics));
+
+    let mut group = criterion.benchmark_group("draw lines");
+    group.bench_function("segmenting", |bencher| {
+        bencher.iter(|| black_box(segment_lines(&text)))
+    });
+    group.bench_function("with metrics", |bencher| {
+        bencher.iter(|| black_box(measured_lines(&metrics)))
+    });
+    group.bench_function("measuring", |bencher| {
+        bencher.iter(|| {
+            for line in text.lines() {
+                black_box(grapheme_metrics(TAB_WIDTH, &line).collect::<Vec<_>>());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, draw_lines);
+criterion_main!(benches);
@@ -0,0 +1,188 @@
+use ropey::Rope;
+use std::{error, fmt, ops::Range};
+
+use crate::{graphemes::CharIndex, Cursor, OpaqueDiff};
+
+/// Replaces a range of the original text, one of a batch applied together
+/// with [`apply_edits`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub range: Range<CharIndex>,
+    pub text: Rope,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<CharIndex>, text: impl Into<Rope>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    /// An edit ends past the end of the text, or ends before it starts
+    InvalidRange(Range<CharIndex>),
+    /// Two edits replace some of the same text
+    Overlapping(Range<CharIndex>, Range<CharIndex>),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditError::InvalidRange(range) => {
+                write!(formatter, "Invalid edit range {:?}", range)
+            }
+            EditError::Overlapping(first, second) => {
+                write!(formatter, "Edits {:?} and {:?} overlap", first, second)
+            }
+        }
+    }
+}
+
+impl error::Error for EditError {}
+
+/// Applies a batch of edits to a text, all or nothing. The edit ranges refer to
+/// the text before any of the edits, may come in any order, but must not
+/// overlap. Insertions at the same index are applied in the order given.
+///
+/// The cursors are moved along with the text around them. Returns a single
+/// diff spanning all the edits, from the start of the first one to the end of
+/// the last one.
+pub fn apply_edits<'a>(
+    text: &mut Rope,
+    mut edits: Vec<TextEdit>,
+    cursors: impl IntoIterator<Item = &'a mut Cursor>,
+) -> Result<OpaqueDiff, EditError> {
+    // A stable sort keeps insertions at the same index in order
+    edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+    for edit in edits.iter() {
+        if edit.range.start > edit.range.end || edit.range.end > text.len_chars() {
+            return Err(EditError::InvalidRange(edit.range.clone()));
+        }
+    }
+    for pair in edits.windows(2) {
+        if pair[0].range.end > pair[1].range.start {
+            return Err(EditError::Overlapping(
+                pair[0].range.clone(),
+                pair[1].range.clone(),
+            ));
+        }
+    }
+    let (first, last) = match (edits.first(), edits.last()) {
+        (Some(first), Some(last)) => (first.range.start, last.range.end),
+        _ => return Ok(OpaqueDiff::empty()),
+    };
+
+    let byte_index = text.char_to_byte(first);
+    let old_byte_length = text.char_to_byte(last) - byte_index;
+    let old_char_length = last - first;
+    let (mut new_byte_length, mut new_char_length) = (old_byte_length, old_char_length);
+
+    // Going backwards, the ranges of the edits left still point at the right
+    // text. Cursors are reconciled after each edit, as they are with typing.
+    let mut cursors: Vec<_> = cursors.into_iter().collect();
+    for TextEdit { range, text: new } in edits.into_iter().rev() {
+        let diff = OpaqueDiff::new(
+            text.char_to_byte(range.start),
+            text.char_to_byte(range.end) - text.char_to_byte(range.start),
+            new.len_bytes(),
+            range.start,
+            range.len(),
+            new.len_chars(),
+        );
+        text.remove(range.clone());
+        let mut char_index = range.start;
+        for chunk in new.chunks() {
+            text.insert(char_index, chunk);
+            char_index += chunk.chars().count();
+        }
+        for cursor in cursors.iter_mut() {
+            cursor.reconcile(text, &diff);
+        }
+        new_byte_length = new_byte_length + diff.new_byte_length - diff.old_byte_length;
+        new_char_length = new_char_length + diff.new_char_length - diff.old_char_length;
+    }
+
+    Ok(OpaqueDiff::new(
+        byte_index,
+        old_byte_length,
+        new_byte_length,
+        first,
+        old_char_length,
+        new_char_length,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RopeExt;
+
+    fn cursor_at(text: &Rope, char_index: CharIndex) -> Cursor {
+        Cursor::with_range(char_index..text.next_grapheme_boundary(char_index))
+    }
+
+    #[test]
+    fn apply_edits_in_any_order() {
+        let mut text = Rope::from("let a = 1;\nlet b = 2;\n");
+        let mut cursors = [
+            cursor_at(&text, 0),
+            cursor_at(&text, 15),
+            cursor_at(&text, 20),
+        ];
+        let diff = apply_edits(
+            &mut text,
+            vec![
+                TextEdit::new(15..16, "beta"),
+                TextEdit::new(4..5, "alpha"),
+                TextEdit::new(22..22, "// two\n"),
+            ],
+            cursors.iter_mut(),
+        )
+        .unwrap();
+
+        assert_eq!(text, "let alpha = 1;\nlet beta = 2;\n// two\n");
+        assert_eq!(diff, OpaqueDiff::new(4, 18, 32, 4, 18, 32));
+        let ranges: Vec<_> = cursors.iter().map(Cursor::range).collect();
+        assert_eq!(ranges, [0..1, 19..20, 27..28]);
+    }
+
+    #[test]
+    fn apply_edits_all_or_nothing() {
+        let mut text = Rope::from("abcdef");
+        let mut cursor = cursor_at(&text, 3);
+        assert_eq!(
+            apply_edits(
+                &mut text,
+                vec![
+                    TextEdit::new(0..1, "x"),
+                    TextEdit::new(3..5, ""),
+                    TextEdit::new(4..6, "")
+                ],
+                Some(&mut cursor),
+            ),
+            Err(EditError::Overlapping(3..5, 4..6))
+        );
+        assert_eq!(
+            apply_edits(&mut text, vec![TextEdit::new(5..7, "")], Some(&mut cursor)),
+            Err(EditError::InvalidRange(5..7))
+        );
+        assert_eq!(text, "abcdef");
+        assert_eq!(cursor.range(), 3..4);
+    }
+
+    #[test]
+    fn apply_insertions_at_the_same_index_in_order() {
+        let mut text = Rope::from("()");
+        let diff = apply_edits(
+            &mut text,
+            vec![TextEdit::new(1..1, "a"), TextEdit::new(1..1, ", b")],
+            None,
+        )
+        .unwrap();
+        assert_eq!(text, "(a, b)");
+        assert_eq!(diff, OpaqueDiff::new(1, 0, 4, 1, 0, 4));
+    }
+}
@@ -58,6 +58,70 @@ impl OpaqueDiff {
             new_char_length: self.old_char_length,
         }
     }
+
+    /// The smallest single edit turning `old` into `new`, which replaces the
+    /// text between their common prefix and suffix. Empty if the texts are
+    /// equal.
+    pub fn between(old: &Rope, new: &Rope) -> Self {
+        let prefix = old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let max_suffix = old.len_chars().min(new.len_chars()) - prefix;
+        let (mut old_chars, mut new_chars) =
+            (old.chars_at(old.len_chars()), new.chars_at(new.len_chars()));
+        let mut suffix = 0;
+        while suffix < max_suffix {
+            match (old_chars.prev(), new_chars.prev()) {
+                (Some(old), Some(new)) if old == new => suffix += 1,
+                _ => break,
+            }
+        }
+
+        let (old_end, new_end) = (old.len_chars() - suffix, new.len_chars() - suffix);
+        if old_end == prefix && new_end == prefix {
+            return Self::empty();
+        }
+        let byte_index = old.char_to_byte(prefix);
+        Self {
+            byte_index,
+            old_byte_length: old.char_to_byte(old_end) - byte_index,
+            new_byte_length: new.char_to_byte(new_end) - byte_index,
+            char_index: prefix,
+            old_char_length: old_end - prefix,
+            new_char_length: new_end - prefix,
+        }
+    }
+
+    /// Combines this diff with one applied right after it, if the second
+    /// edit touches the text changed by the first, e.g. typing two characters
+    /// in a row or deleting backward twice. Returns `None` otherwise.
+    pub fn merge(&self, next: &OpaqueDiff) -> Option<OpaqueDiff> {
+        if self.is_empty()
+            || next.is_empty()
+            || next.char_index > self.char_index + self.new_char_length
+            || next.char_index + next.old_char_length < self.char_index
+        {
+            return None;
+        }
+
+        // The span covering both edits, in the text between the two edits
+        let char_index = self.char_index.min(next.char_index);
+        let char_end =
+            (self.char_index + self.new_char_length).max(next.char_index + next.old_char_length);
+        let byte_index = self.byte_index.min(next.byte_index);
+        let byte_end =
+            (self.byte_index + self.new_byte_length).max(next.byte_index + next.old_byte_length);
+        Some(Self {
+            byte_index,
+            old_byte_length: byte_end - byte_index - self.new_byte_length + self.old_byte_length,
+            new_byte_length: byte_end - byte_index - next.old_byte_length + next.new_byte_length,
+            char_index,
+            old_char_length: char_end - char_index - self.new_char_length + self.old_char_length,
+            new_char_length: char_end - char_index - next.old_char_length + next.new_char_length,
+        })
+    }
 }
 
 pub struct DeleteOperation {
@@ -79,6 +143,52 @@ mod tests {
     use super::*;
     use ropey::Rope;
 
+    fn insert(char_index: usize, length: usize) -> OpaqueDiff {
+        OpaqueDiff::new(char_index, 0, length, char_index, 0, length)
+    }
+
+    fn delete(char_index: usize, length: usize) -> OpaqueDiff {
+        OpaqueDiff::new(char_index, length, 0, char_index, length, 0)
+    }
+
+    #[test]
+    fn merge_adjacent_edits() {
+        // Typing forward
+        assert_eq!(insert(3, 1).merge(&insert(4, 1)), Some(insert(3, 2)));
+        // Deleting backward and forward
+        assert_eq!(delete(5, 1).merge(&delete(4, 1)), Some(delete(4, 2)));
+        assert_eq!(delete(5, 1).merge(&delete(5, 1)), Some(delete(5, 2)));
+        // Typing and deleting the last character typed
+        assert_eq!(
+            insert(3, 2).merge(&delete(4, 1)),
+            Some(OpaqueDiff::new(3, 0, 1, 3, 0, 1))
+        );
+    }
+
+    #[test]
+    fn do_not_merge_distant_or_empty_edits() {
+        assert_eq!(insert(3, 1).merge(&insert(5, 1)), None);
+        assert_eq!(delete(5, 1).merge(&delete(2, 1)), None);
+        assert_eq!(OpaqueDiff::empty().merge(&insert(0, 1)), None);
+        assert_eq!(insert(0, 1).merge(&OpaqueDiff::empty()), None);
+    }
+
+    #[test]
+    fn diff_between_texts() {
+        let diff = |old: &str, new: &str| OpaqueDiff::between(&old.into(), &new.into());
+        assert!(diff("abc", "abc").is_empty());
+        assert_eq!(diff("a  \nb\n", "a\nb\n"), delete(1, 2));
+        assert_eq!(diff("ab", "aXb"), insert(1, 1));
+        assert_eq!(diff("aaa", "aa"), delete(2, 1));
+        assert_eq!(diff("", "new"), insert(0, 3));
+        assert_eq!(diff("x é y", "x è y"), OpaqueDiff::new(2, 2, 2, 2, 1, 1));
+        // Changes far apart are replaced as one span
+        assert_eq!(
+            diff("a \nb\nc \n", "a\nb\nc\n"),
+            OpaqueDiff::new(1, 6, 4, 1, 6, 4)
+        );
+    }
+
     #[test]
     fn mem_size_of_diffs() {
         assert_eq!(std::mem::size_of::<OpaqueDiff>(), 48);
@@ -1,4 +1,5 @@
 use ropey::{iter::Chunks, str_utils, Rope, RopeSlice};
+use std::ops::Range;
 use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
 use unicode_width::UnicodeWidthStr;
 
@@ -17,6 +18,89 @@ pub fn width(tab_width: usize, slice: &RopeSlice) -> usize {
     })
 }
 
+/// The width of a grapheme starting at `column`. Tabs extend to the next
+/// multiple of `tab_width`.
+pub fn width_at(tab_width: usize, column: usize, slice: &RopeSlice) -> usize {
+    if *slice == "\t" {
+        let tab_width = tab_width.max(1);
+        tab_width - column % tab_width
+    } else {
+        width(tab_width, slice)
+    }
+}
+
+/// The width of text starting at the beginning of a line, with tabs extending
+/// to tab stops. Long lines are measured the same way they're drawn, so the
+/// cursor's column matches what's on screen.
+pub fn line_width(tab_width: usize, slice: &RopeSlice) -> usize {
+    RopeGraphemes::new(slice).fold(0, |column, grapheme| {
+        column + width_at(tab_width, column, &grapheme.slice)
+    })
+}
+
+/// Lines longer than this many characters, e.g. in minified code, are too slow
+/// to highlight or measure in full on every change, so they're drawn as plain
+/// text and only measured as far as they're shown
+pub const LONG_LINE_LENGTH: usize = 10_000;
+
+pub fn is_long_line(line: &RopeSlice) -> bool {
+    line.len_chars() > LONG_LINE_LENGTH
+}
+
+/// A grapheme of a line, measured once so the line can be drawn again without
+/// segmenting it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphemeMetrics {
+    /// The bytes before the grapheme on its line
+    pub byte_start: ByteIndex,
+    /// The chars before the grapheme on its line
+    pub char_start: CharIndex,
+    pub len_chars: usize,
+    /// The width of the grapheme, `None` for a tab which extends to the next
+    /// tab stop
+    width: Option<usize>,
+}
+
+impl GraphemeMetrics {
+    #[inline]
+    pub fn chars(&self) -> Range<CharIndex> {
+        self.char_start..self.char_start + self.len_chars
+    }
+
+    #[inline]
+    pub fn is_tab(&self) -> bool {
+        self.width.is_none()
+    }
+
+    /// The width of the grapheme starting at `column`, like [`width_at`]
+    #[inline]
+    pub fn width_at(&self, tab_width: usize, column: usize) -> usize {
+        self.width.unwrap_or_else(|| {
+            let tab_width = tab_width.max(1);
+            tab_width - column % tab_width
+        })
+    }
+}
+
+/// Segments a line into graphemes and measures each of them
+pub fn grapheme_metrics<'a>(
+    tab_width: usize,
+    line: &RopeSlice<'a>,
+) -> impl Iterator<Item = GraphemeMetrics> + 'a {
+    let mut char_start = 0;
+    RopeGraphemes::new(line).map(move |grapheme| {
+        let len_chars = grapheme.len_chars();
+        let metrics = GraphemeMetrics {
+            byte_start: grapheme.byte_start,
+            char_start,
+            len_chars,
+            width: (grapheme.slice != "\t").then(|| width(tab_width, &grapheme)),
+        };
+        char_start += len_chars;
+        metrics
+    })
+}
+
 pub fn rope_slice_as_str<T>(slice: &RopeSlice, closure: impl FnOnce(&str) -> T) -> T {
     if let Some(text) = slice.as_str() {
         closure(text)
@@ -291,5 +375,50 @@ mod tests {
         assert_eq!(text.len_chars(), grapheme_end);
     }
 
+    #[test]
+    fn tabs_extend_to_tab_stops() {
+        let tab = RopeSlice::from("\t");
+        assert_eq!(width_at(4, 0, &tab), 4);
+        assert_eq!(width_at(4, 1, &tab), 3);
+        assert_eq!(width_at(8, 4, &tab), 4);
+        assert_eq!(width_at(4, 3, &RopeSlice::from("a")), 1);
+
+        assert_eq!(line_width(8, &RopeSlice::from("\t\tx")), 17);
+        assert_eq!(line_width(4, &RopeSlice::from("ab\tc\t")), 8);
+        assert_eq!(line_width(4, &RopeSlice::from("  \t")), 4);
+    }
+
+    #[test]
+    fn grapheme_metrics_measure_graphemes() {
+        let line = Rope::from(format!("a\t{}b\n", MULTI_CHAR_EMOJI));
+        let metrics: Vec<_> = grapheme_metrics(4, &line.slice(..)).collect();
+        let emoji_chars = MULTI_CHAR_EMOJI.chars().count();
+        assert_eq!(metrics.len(), 5);
+        assert_eq!(metrics[2].chars(), 2..2 + emoji_chars);
+        assert_eq!(metrics[3].byte_start, 2 + MULTI_CHAR_EMOJI.len());
+        assert!(metrics[1].is_tab());
+        assert_eq!(metrics[1].width_at(4, 1), 3);
+        assert_eq!(
+            metrics[2].width_at(4, 4),
+            width(4, &RopeSlice::from(MULTI_CHAR_EMOJI))
+        );
+
+        let column = metrics
+            .iter()
+            .fold(0, |column, grapheme| column + grapheme.width_at(4, column));
+        assert_eq!(column, line_width(4, &line.slice(..)));
+    }
+
+    #[test]
+    fn long_lines_are_measured_like_they_are_drawn() {
+        let line = format!("{}\tx", "字".repeat(LONG_LINE_LENGTH));
+        let line = RopeSlice::from(line.as_str());
+        assert!(is_long_line(&line));
+        let drawn = grapheme_metrics(4, &line)
+            .fold(0, |column, grapheme| column + grapheme.width_at(4, column));
+        assert_eq!(line_width(4, &line), drawn);
+        assert_eq!(drawn, 2 * LONG_LINE_LENGTH + 4 + 1);
+    }
+
     const MULTI_CHAR_EMOJI: &str = r#"👨‍👨‍👧‍👧"#;
 }
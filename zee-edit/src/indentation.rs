@@ -0,0 +1,100 @@
+use ropey::Rope;
+
+/// How a text is indented, as guessed by [`detect`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetectedIndentation {
+    Tabs,
+    Spaces(usize),
+}
+
+/// Guesses how a text is indented from the leading whitespace of its first
+/// lines. Returns `None` if too few lines are indented to tell.
+///
+/// Lines indented with tabs are counted against lines indented with spaces.
+/// For spaces, the width is the most common increase in indentation from one
+/// non-blank line to the next.
+pub fn detect(text: &Rope) -> Option<DetectedIndentation> {
+    let mut num_tab_lines = 0;
+    let mut num_space_lines = 0;
+    // How often each width from 1 to `MAX_WIDTH` is the increase in indentation
+    let mut increases = [0usize; MAX_WIDTH + 1];
+    let mut previous_spaces = 0;
+
+    for line in text.lines().take(MAX_LINES_SCANNED) {
+        let leading_spaces = line
+            .chars()
+            .take_while(|character| *character == ' ')
+            .count();
+        match line.chars_at(leading_spaces).next() {
+            // Blank lines don't tell anything
+            None | Some('\n' | '\r') => continue,
+            Some('\t') if leading_spaces == 0 => {
+                num_tab_lines += 1;
+                previous_spaces = 0;
+                continue;
+            }
+            _ => {}
+        }
+        if leading_spaces > 0 {
+            num_space_lines += 1;
+        }
+        if let Some(increase) = leading_spaces.checked_sub(previous_spaces) {
+            if (1..=MAX_WIDTH).contains(&increase) {
+                increases[increase] += 1;
+            }
+        }
+        previous_spaces = leading_spaces;
+    }
+
+    if num_tab_lines + num_space_lines < MIN_INDENTED_LINES {
+        None
+    } else if num_tab_lines > num_space_lines {
+        Some(DetectedIndentation::Tabs)
+    } else {
+        // Ties go to the smaller width, e.g. nested 2 and 4 space indents
+        (1..=MAX_WIDTH)
+            .rev()
+            .max_by_key(|width| increases[*width])
+            .filter(|width| increases[*width] > 0)
+            .map(DetectedIndentation::Spaces)
+    }
+}
+
+const MAX_LINES_SCANNED: usize = 1000;
+const MAX_WIDTH: usize = 8;
+const MIN_INDENTED_LINES: usize = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_spaces() {
+        let text = Rope::from("fn main() {\n  if x {\n    y();\n  }\n}\n");
+        assert_eq!(detect(&text), Some(DetectedIndentation::Spaces(2)));
+
+        let text = Rope::from("def f():\n    if x:\n        y()\n\n    return 1\n");
+        assert_eq!(detect(&text), Some(DetectedIndentation::Spaces(4)));
+    }
+
+    #[test]
+    fn detect_tabs() {
+        let text = Rope::from("func main() {\n\tif x {\n\t\ty()\n\t}\n}\n");
+        assert_eq!(detect(&text), Some(DetectedIndentation::Tabs));
+    }
+
+    #[test]
+    fn detect_nothing_without_indented_lines() {
+        assert_eq!(detect(&Rope::from("")), None);
+        assert_eq!(detect(&Rope::from("a\nb\n\n    \nc\n")), None);
+        assert_eq!(detect(&Rope::from("a\n  b\n")), None);
+    }
+
+    #[test]
+    fn alignment_does_not_outweigh_indentation() {
+        let text = Rope::from(
+            "fn f(\n    a: u8,\n    b: u8,\n) {\n    g(a,\n      b);\n    h();\n    i();\n}\n",
+        );
+        assert_eq!(detect(&text), Some(DetectedIndentation::Spaces(4)));
+    }
+}
@@ -1,11 +1,17 @@
+pub mod batch;
 pub mod graphemes;
+pub mod indentation;
+pub mod line_diff;
 pub mod movement;
 pub mod tree;
 
 mod diff;
 
 use ropey::{Rope, RopeSlice};
-use std::{cmp, ops::Range};
+use std::{
+    cmp,
+    ops::{Range, RangeInclusive},
+};
 
 pub use self::{
     diff::{DeleteOperation, OpaqueDiff},
@@ -47,8 +53,14 @@ pub struct Cursor {
     /// clusters.
     selection: Option<CharIndex>,
     visual_horizontal_offset: Option<usize>,
+    /// The mark ring, positions where selections were begun, the latest last.
+    /// The latest is the mark, the others can be jumped back to.
+    marks: Vec<CharIndex>,
 }
 
+/// How many marks a cursor remembers
+const MAX_MARKS: usize = 16;
+
 impl Default for Cursor {
     fn default() -> Self {
         Self::new()
@@ -61,6 +73,7 @@ impl Cursor {
             range: 0..0,
             selection: None,
             visual_horizontal_offset: None,
+            marks: Vec::new(),
         }
     }
 
@@ -77,6 +90,7 @@ impl Cursor {
             range: text.prev_grapheme_boundary(text.len_chars())..text.len_chars(),
             visual_horizontal_offset: None,
             selection: None,
+            marks: Vec::new(),
         }
     }
 
@@ -96,12 +110,19 @@ impl Cursor {
         }
     }
 
+    /// Whether text is selected, i.e. the selection was begun somewhere other
+    /// than where the cursor is now
+    pub fn has_selection(&self) -> bool {
+        matches!(self.selection, Some(selection) if selection != self.range.start)
+    }
+
     pub fn column_offset(&self, tab_width: usize, text: &Rope) -> usize {
         let char_line_start = text.line_to_char(text.cursor_to_line(self));
-        graphemes::width(tab_width, &text.slice(char_line_start..self.range.start))
+        graphemes::line_width(tab_width, &text.slice(char_line_start..self.range.start))
     }
 
     pub fn reconcile(&mut self, new_text: &Rope, diff: &OpaqueDiff) {
+        self.reconcile_marks(new_text, diff);
         let OpaqueDiff {
             char_index,
             old_char_length,
@@ -109,7 +130,8 @@ impl Cursor {
             ..
         } = *diff;
 
-        let modified_range = char_index..cmp::max(old_char_length, new_char_length);
+        // The range of text the edit replaced, before the edit
+        let modified_range = char_index..char_index + old_char_length;
 
         // The edit starts after the end of the cursor, nothing to do
         if modified_range.start >= self.range.end {
@@ -135,17 +157,98 @@ impl Cursor {
         self.range = grapheme_start..grapheme_end
     }
 
+    /// Moves the marks with the text after an edit. A mark inside the text
+    /// replaced moves to the start of the edit.
+    pub fn reconcile_marks(&mut self, new_text: &Rope, diff: &OpaqueDiff) {
+        let OpaqueDiff {
+            char_index,
+            old_char_length,
+            new_char_length,
+            ..
+        } = *diff;
+        for mark in self.marks.iter_mut() {
+            if *mark >= char_index + old_char_length {
+                *mark = *mark - old_char_length + new_char_length;
+            } else if *mark > char_index {
+                *mark = char_index;
+            }
+            *mark = cmp::min(*mark, new_text.len_chars());
+        }
+    }
+
     pub fn begin_selection(&mut self) {
-        self.selection = Some(self.range.start)
+        self.selection = Some(self.range.start);
+        self.push_mark(self.range.start);
+    }
+
+    /// The latest mark, where the last selection was begun
+    pub fn mark(&self) -> Option<CharIndex> {
+        self.marks.last().copied()
+    }
+
+    /// Moves the cursor to the latest mark and makes the one before it the
+    /// mark, moving the latest to the back of the ring. Repeating it cycles
+    /// through the marks. Returns false if there are no marks.
+    pub fn pop_mark(&mut self, text: &Rope) -> bool {
+        let mark = match self.marks.pop() {
+            Some(mark) => mark,
+            None => return false,
+        };
+        self.marks.insert(0, mark);
+        self.clear_selection();
+        movement::move_to_char(text, self, mark);
+        true
+    }
+
+    /// Swaps the cursor and the mark, selecting the text in between. Returns
+    /// false if there's no mark.
+    pub fn exchange_point_and_mark(&mut self, text: &Rope) -> bool {
+        let mark = match self.selection.or_else(|| self.mark()) {
+            Some(mark) => mark,
+            None => return false,
+        };
+        let point = self.range.start;
+        match self.marks.last_mut() {
+            Some(latest) => *latest = point,
+            None => self.marks.push(point),
+        }
+        movement::move_to_char(text, self, mark);
+        self.selection = Some(point);
+        true
+    }
+
+    fn push_mark(&mut self, mark: CharIndex) {
+        if self.mark() == Some(mark) {
+            return;
+        }
+        if self.marks.len() == MAX_MARKS {
+            self.marks.remove(0);
+        }
+        self.marks.push(mark);
+    }
+
+    /// Moves the cursor to `range`, ending any selection but keeping the marks
+    fn reset(&mut self, range: Range<CharIndex>) {
+        self.range = range;
+        self.selection = None;
+        self.visual_horizontal_offset = None;
     }
 
     pub fn clear_selection(&mut self) {
         self.selection = None;
     }
 
+    /// Moves the start of the selection, if there is one, inside `bounds`
+    pub fn restrict_selection(&mut self, bounds: RangeInclusive<CharIndex>) {
+        if let Some(selection) = self.selection.as_mut() {
+            *selection = (*selection).clamp(*bounds.start(), *bounds.end());
+        }
+    }
+
     pub fn select_all(&mut self, text: &Rope) {
         movement::move_to_start_of_buffer(text, self);
         self.selection = Some(text.len_chars());
+        self.push_mark(text.len_chars());
     }
 
     // Editing
@@ -209,7 +312,7 @@ impl Cursor {
         let grapheme_end = text.next_grapheme_boundary(self.range.start);
         let deleted = text.slice(grapheme_start..grapheme_end).into();
 
-        *self = Cursor::with_range(grapheme_start..grapheme_end);
+        self.reset(grapheme_start..grapheme_end);
 
         DeleteOperation { diff, deleted }
     }
@@ -248,7 +351,71 @@ impl Cursor {
             text.line_to_char(cmp::min(line_index, text.len_lines().saturating_sub(2)));
         let grapheme_end = text.next_grapheme_boundary(grapheme_start);
 
-        *self = Cursor::with_range(grapheme_start..grapheme_end);
+        self.reset(grapheme_start..grapheme_end);
+
+        DeleteOperation { diff, deleted }
+    }
+
+    /// Deletes from the cursor to the end of the line, keeping the newline.
+    /// If the cursor is at the end of the line already, deletes the newline,
+    /// joining the next line.
+    pub fn delete_to_end_of_line(&mut self, text: &mut Rope) -> DeleteOperation {
+        let delete_range_start = self.range.start;
+        let line_index = text.char_to_line(delete_range_start);
+        let line = text.line(line_index);
+        let line_end = text.line_to_char(line_index) + line.len_chars();
+        let content_end = if line.len_chars() > 0 && line.char(line.len_chars() - 1) == '\n' {
+            line_end - 1
+        } else {
+            line_end
+        };
+        let delete_range_end = if delete_range_start < content_end {
+            content_end
+        } else {
+            line_end
+        };
+        if delete_range_start == delete_range_end {
+            return DeleteOperation::empty();
+        }
+
+        let deleted = text.slice(delete_range_start..delete_range_end).into();
+        let diff = OpaqueDiff::new(
+            text.char_to_byte(delete_range_start),
+            text.char_to_byte(delete_range_end) - text.char_to_byte(delete_range_start),
+            0,
+            delete_range_start,
+            delete_range_end - delete_range_start,
+            0,
+        );
+        text.remove(delete_range_start..delete_range_end);
+
+        // Update cursor position
+        let grapheme_end = text.next_grapheme_boundary(delete_range_start);
+        self.reset(delete_range_start..grapheme_end);
+
+        DeleteOperation { diff, deleted }
+    }
+
+    /// Deletes a range of text, leaving the cursor where it started
+    pub fn delete_range(&mut self, text: &mut Rope, range: Range<CharIndex>) -> DeleteOperation {
+        if range.is_empty() {
+            return DeleteOperation::empty();
+        }
+
+        let deleted = text.slice(range.start..range.end).into();
+        let diff = OpaqueDiff::new(
+            text.char_to_byte(range.start),
+            text.char_to_byte(range.end) - text.char_to_byte(range.start),
+            0,
+            range.start,
+            range.end - range.start,
+            0,
+        );
+        text.remove(range.start..range.end);
+
+        // Update cursor position
+        let grapheme_end = text.next_grapheme_boundary(range.start);
+        self.reset(range.start..grapheme_end);
 
         DeleteOperation { diff, deleted }
     }
@@ -278,7 +445,7 @@ impl Cursor {
         );
         let grapheme_end = text.next_grapheme_boundary(grapheme_start);
 
-        *self = Cursor::with_range(grapheme_start..grapheme_end);
+        self.reset(grapheme_start..grapheme_end);
 
         DeleteOperation { diff, deleted }
     }
@@ -296,7 +463,10 @@ impl Cursor {
             new_text.next_grapheme_boundary(new_text.line_to_char(new_line) + new_line_offset);
         let grapheme_start = new_text.prev_grapheme_boundary(grapheme_end);
 
-        *self = Cursor::with_range(grapheme_start..grapheme_end);
+        self.reset(grapheme_start..grapheme_end);
+        for mark in self.marks.iter_mut() {
+            *mark = cmp::min(*mark, new_text.len_chars());
+        }
     }
 }
 
@@ -323,6 +493,73 @@ mod tests {
         assert_eq!(Cursor::new(), cursor);
     }
 
+    #[test]
+    fn restrict_selection_to_bounds() {
+        let text = Rope::from(TEXT);
+        let mut cursor = Cursor::new();
+        cursor.select_all(&text);
+        cursor.restrict_selection(2..=10);
+        assert_eq!(0..10, cursor.selection());
+
+        // Without a selection, nothing changes
+        let mut cursor = Cursor::new();
+        cursor.restrict_selection(2..=10);
+        assert_eq!(Cursor::new(), cursor);
+    }
+
+    // Marks
+    #[test]
+    fn pop_mark_cycles_through_the_marks() {
+        let (text, mut cursor) = text_with_cursor("Buy a milk goat\nAt the market\n");
+        for char_index in [2, 6, 17] {
+            movement::move_to_char(&text, &mut cursor, char_index);
+            cursor.begin_selection();
+            cursor.clear_selection();
+        }
+        movement::move_to_start_of_buffer(&text, &mut cursor);
+
+        for char_index in [17, 6, 2, 17] {
+            assert!(cursor.pop_mark(&text));
+            assert_eq!(char_index, cursor.range().start);
+        }
+        assert!(!Cursor::new().pop_mark(&text));
+    }
+
+    #[test]
+    fn exchange_point_and_mark_selects_in_between() {
+        let (text, mut cursor) = text_with_cursor("Buy a milk goat\nAt the market\n");
+        movement::move_to_char(&text, &mut cursor, 4);
+        cursor.begin_selection();
+        cursor.clear_selection();
+        movement::move_to_char(&text, &mut cursor, 10);
+
+        assert!(cursor.exchange_point_and_mark(&text));
+        assert_eq!(4, cursor.range().start);
+        assert_eq!(Some(10), cursor.mark());
+        assert_eq!(4..10, cursor.selection());
+
+        // Exchanging again swaps them back, keeping the selection
+        assert!(cursor.exchange_point_and_mark(&text));
+        assert_eq!(10, cursor.range().start);
+        assert_eq!(4..10, cursor.selection());
+    }
+
+    #[test]
+    fn marks_move_with_edits() {
+        let (mut text, mut cursor) = text_with_cursor("let x = 1;\n");
+        movement::move_to_char(&text, &mut cursor, 8);
+        cursor.begin_selection();
+        movement::move_to_start_of_buffer(&text, &mut cursor);
+        cursor.insert_chars(&mut text, "    ".chars());
+        let diff = OpaqueDiff::new(0, 0, 4, 0, 0, 4);
+        cursor.reconcile_marks(&text, &diff);
+        assert_eq!(Some(12), cursor.mark());
+
+        // Deleting keeps the marks
+        cursor.delete_forward(&mut text);
+        assert_eq!(Some(12), cursor.mark());
+    }
+
     // Delete forward
     #[test]
     fn delete_forward_at_the_end() {
@@ -361,6 +598,71 @@ mod tests {
         assert_eq!(Rope::from("// Hello world"), text);
     }
 
+    #[test]
+    fn reconcile_with_edits() {
+        let text = Rope::from("let x = 1;  \nlet y = 2;\n");
+        let new_text = Rope::from("let x = 1;\nlet y = 2;\n");
+        let diff = OpaqueDiff::between(&text, &new_text);
+
+        // After the edit, the cursor moves back with the text
+        let mut cursor = Cursor::new();
+        movement::move_horizontally(&text, &mut cursor, Direction::Forward, 17);
+        cursor.reconcile(&new_text, &diff);
+        assert_eq!(cursor.range(), 15..16);
+
+        // Inside the edit, the cursor stays put
+        let mut cursor = Cursor::new();
+        movement::move_horizontally(&text, &mut cursor, Direction::Forward, 11);
+        cursor.reconcile(&new_text, &diff);
+        assert_eq!(cursor.range(), 11..12);
+
+        // Before the edit, nothing changes
+        let mut cursor = Cursor::new();
+        movement::move_horizontally(&text, &mut cursor, Direction::Forward, 4);
+        cursor.reconcile(&new_text, &diff);
+        assert_eq!(cursor.range(), 4..5);
+    }
+
+    // Delete to end of line
+    #[test]
+    fn delete_to_end_of_line_keeps_the_newline() {
+        let (mut text, mut cursor) = text_with_cursor("let x = 1;\nlet y = 2;\n");
+        movement::move_horizontally(&text, &mut cursor, Direction::Forward, 4);
+        let operation = cursor.delete_to_end_of_line(&mut text);
+        assert_eq!(Rope::from("let \nlet y = 2;\n"), text);
+        assert_eq!("x = 1;", operation.deleted.to_string());
+        assert_eq!(4..5, cursor.range());
+
+        // At the end of the line, the newline goes
+        let operation = cursor.delete_to_end_of_line(&mut text);
+        assert_eq!(Rope::from("let let y = 2;\n"), text);
+        assert_eq!("\n", operation.deleted.to_string());
+    }
+
+    #[test]
+    fn delete_to_end_of_line_at_the_end() {
+        let (mut text, mut cursor) = text_with_cursor("// Hello world!");
+        movement::move_to_end_of_buffer(&text, &mut cursor);
+        let operation = cursor.delete_to_end_of_line(&mut text);
+        assert!(operation.diff.is_empty());
+        assert_eq!(Rope::from("// Hello world!"), text);
+
+        let (mut text, mut cursor) = text_with_cursor("");
+        cursor.delete_to_end_of_line(&mut text);
+        assert_eq!(cursor, Cursor::new());
+    }
+
+    #[test]
+    fn delete_range_leaves_the_cursor_at_its_start() {
+        let (mut text, mut cursor) = text_with_cursor("foo(bar, baz);\n");
+        let operation = cursor.delete_range(&mut text, 4..9);
+        assert_eq!(Rope::from("foo(baz);\n"), text);
+        assert_eq!("bar, ", operation.deleted.to_string());
+        assert_eq!(4..5, cursor.range());
+
+        assert!(cursor.delete_range(&mut text, 2..2).diff.is_empty());
+    }
+
     #[test]
     fn delete_backward_empty_text() {
         let (mut text, mut cursor) = text_with_cursor("");
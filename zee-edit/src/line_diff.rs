@@ -0,0 +1,341 @@
+use ropey::Rope;
+use std::{fmt::Write, iter, ops::Range};
+
+use crate::graphemes::LineIndex;
+
+/// A region where two texts differ, the `old` lines are replaced by the `new`
+/// ones. One of the ranges is empty for pure insertions and deletions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<LineIndex>,
+    pub new: Range<LineIndex>,
+}
+
+/// The number of lines in a text, not counting the empty line which ropey
+/// reports after a trailing new line
+pub fn num_lines(text: &Rope) -> usize {
+    let len_chars = text.len_chars();
+    if len_chars == 0 || text.char(len_chars - 1) == '\n' {
+        text.len_lines() - 1
+    } else {
+        text.len_lines()
+    }
+}
+
+/// Computes a minimal line based diff between two texts, returning the hunks
+/// in order. Texts differing in more than `MAX_EDIT_DEPTH` lines, once their
+/// common prefix and suffix are set aside, are reported as a single hunk
+/// replacing everything in between.
+pub fn diff_lines(old: &Rope, new: &Rope) -> Vec<Hunk> {
+    let (old_len, new_len) = (num_lines(old), num_lines(new));
+    let equal = |old_index, new_index| old.line(old_index) == new.line(new_index);
+
+    // Strip the common prefix and suffix, usually most of the text, which
+    // keeps the search below small
+    let prefix = (0..old_len.min(new_len))
+        .take_while(|&index| equal(index, index))
+        .count();
+    let suffix = (0..old_len.min(new_len) - prefix)
+        .take_while(|&index| equal(old_len - 1 - index, new_len - 1 - index))
+        .count();
+    let (old_len, new_len) = (old_len - prefix - suffix, new_len - prefix - suffix);
+
+    let matches = shortest_edit(old_len, new_len, |old_index, new_index| {
+        equal(prefix + old_index, prefix + new_index)
+    })
+    .unwrap_or_default();
+
+    let mut hunks = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_match, new_match) in matches.into_iter().chain(iter::once((old_len, new_len))) {
+        if old_match > old_index || new_match > new_index {
+            hunks.push(Hunk {
+                old: prefix + old_index..prefix + old_match,
+                new: prefix + new_index..prefix + new_match,
+            });
+        }
+        old_index = old_match + 1;
+        new_index = new_match + 1;
+    }
+    hunks
+}
+
+/// Formats the hunks of a diff like `diff -u` would, with `context` unchanged
+/// lines around each change. Hunks with overlapping context are merged.
+pub fn unified_diff(old: &Rope, new: &Rope, hunks: &[Hunk], context: usize) -> String {
+    let (old_len, new_len) = (num_lines(old), num_lines(new));
+    let mut output = String::new();
+    let mut hunks = hunks.iter().peekable();
+    while let Some(first) = hunks.next() {
+        let mut group = vec![first];
+        while let Some(hunk) =
+            hunks.next_if(|hunk| hunk.old.start - group[group.len() - 1].old.end <= 2 * context)
+        {
+            group.push(hunk);
+        }
+        let last = group[group.len() - 1];
+
+        let before = context.min(first.old.start).min(first.new.start);
+        let after = context
+            .min(old_len - last.old.end)
+            .min(new_len - last.new.end);
+        let old_range = first.old.start - before..last.old.end + after;
+        let new_range = first.new.start - before..last.new.end + after;
+        writeln!(
+            output,
+            "@@ -{} +{} @@",
+            format_range(&old_range),
+            format_range(&new_range)
+        )
+        .unwrap();
+
+        let mut old_index = old_range.start;
+        for hunk in group {
+            for line_index in old_index..hunk.old.start {
+                push_line(&mut output, ' ', old, line_index);
+            }
+            for line_index in hunk.old.clone() {
+                push_line(&mut output, '-', old, line_index);
+            }
+            for line_index in hunk.new.clone() {
+                push_line(&mut output, '+', new, line_index);
+            }
+            old_index = hunk.old.end;
+        }
+        for line_index in old_index..old_range.end {
+            push_line(&mut output, ' ', old, line_index);
+        }
+    }
+    output
+}
+
+fn push_line(output: &mut String, prefix: char, text: &Rope, line_index: LineIndex) {
+    let line = text.line(line_index);
+    output.push(prefix);
+    output.extend(line.chunks());
+    if line.len_chars() == 0 || line.char(line.len_chars() - 1) != '\n' {
+        output.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+fn format_range(range: &Range<LineIndex>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => format!("{}", range.start + 1),
+        len => format!("{},{}", range.start + 1, len),
+    }
+}
+
+/// Myers' O(ND) diff algorithm. Returns the pairs of equal lines `(old, new)`
+/// along a shortest edit script, in order, or `None` if the script is longer
+/// than `MAX_EDIT_DEPTH`.
+fn shortest_edit(
+    old_len: usize,
+    new_len: usize,
+    equal: impl Fn(usize, usize) -> bool,
+) -> Option<Vec<(usize, usize)>> {
+    let max_depth = (old_len + new_len).min(MAX_EDIT_DEPTH) as isize;
+    let index = |diagonal: isize| (diagonal + max_depth + 1) as usize;
+    let (old_len, new_len) = (old_len as isize, new_len as isize);
+
+    // `frontier[index(k)]` is the furthest x reached on diagonal k = x - y.
+    // Before searching at each depth, the diagonals reached at the previous
+    // one are kept to walk back along: `-(depth - 1)..=depth - 1`, every
+    // other one.
+    let mut frontier = vec![0isize; 2 * max_depth as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = false;
+    'search: for depth in 0..=max_depth {
+        trace.push(
+            (1 - depth..depth)
+                .step_by(2)
+                .map(|diagonal| frontier[index(diagonal)])
+                .collect(),
+        );
+        for diagonal in (-depth..=depth).step_by(2) {
+            let mut x = if diagonal == -depth
+                || (diagonal != depth
+                    && frontier[index(diagonal - 1)] < frontier[index(diagonal + 1)])
+            {
+                frontier[index(diagonal + 1)]
+            } else {
+                frontier[index(diagonal - 1)] + 1
+            };
+            let mut y = x - diagonal;
+            while x < old_len && y < new_len && equal(x as usize, y as usize) {
+                x += 1;
+                y += 1;
+            }
+            frontier[index(diagonal)] = x;
+            if x >= old_len && y >= new_len {
+                found = true;
+                break 'search;
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    // Walk back from the end, collecting the diagonal moves
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (old_len, new_len);
+    for (depth, reached) in trace.iter().enumerate().rev() {
+        let depth = depth as isize;
+        // Nothing was reached before the first depth, the search starts at 0
+        let furthest = |diagonal: isize| {
+            reached
+                .get(((diagonal + depth - 1) / 2) as usize)
+                .copied()
+                .unwrap_or(0)
+        };
+        let diagonal = x - y;
+        let previous_diagonal = if diagonal == -depth
+            || (diagonal != depth && furthest(diagonal - 1) < furthest(diagonal + 1))
+        {
+            diagonal + 1
+        } else {
+            diagonal - 1
+        };
+        let previous_x = furthest(previous_diagonal);
+        let previous_y = previous_x - previous_diagonal;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+/// The longest edit script searched for, beyond which texts are too
+/// different for a line by line diff to be useful
+const MAX_EDIT_DEPTH: usize = 2048;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> Vec<Hunk> {
+        diff_lines(&old.into(), &new.into())
+    }
+
+    #[test]
+    fn num_lines_ignores_trailing_new_line() {
+        assert_eq!(num_lines(&"".into()), 0);
+        assert_eq!(num_lines(&"a".into()), 1);
+        assert_eq!(num_lines(&"a\n".into()), 1);
+        assert_eq!(num_lines(&"a\nb".into()), 2);
+        assert_eq!(num_lines(&"a\n\n".into()), 2);
+    }
+
+    #[test]
+    fn diff_identical_texts() {
+        assert_eq!(diff("", ""), vec![]);
+        assert_eq!(diff("a\nb\nc\n", "a\nb\nc\n"), vec![]);
+    }
+
+    #[test]
+    fn diff_insertions_and_deletions() {
+        assert_eq!(
+            diff("", "a\nb\n"),
+            vec![Hunk {
+                old: 0..0,
+                new: 0..2
+            }]
+        );
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nc\n"),
+            vec![Hunk {
+                old: 1..2,
+                new: 1..1
+            }]
+        );
+        assert_eq!(
+            diff("a\nb\nc\nd\ne\n", "x\na\nb\nd\ne\ny\n"),
+            vec![
+                Hunk {
+                    old: 0..0,
+                    new: 0..1
+                },
+                Hunk {
+                    old: 2..3,
+                    new: 3..3
+                },
+                Hunk {
+                    old: 5..5,
+                    new: 5..6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_replacements() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nB\nc\n"),
+            vec![Hunk {
+                old: 1..2,
+                new: 1..2
+            }]
+        );
+        assert_eq!(
+            diff("a\nb", "a\nb\n"),
+            vec![Hunk {
+                old: 1..2,
+                new: 1..2
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_disjoint_texts_in_one_hunk() {
+        let old: String = (0..10_000).map(|line| format!("old {}\n", line)).collect();
+        let new: String = (0..10_000).map(|line| format!("new {}\n", line)).collect();
+        assert_eq!(
+            diff(&old, &new),
+            vec![Hunk {
+                old: 0..10_000,
+                new: 0..10_000
+            }]
+        );
+
+        // Within the limit, the diff is still line by line
+        let old: String = (0..1000).map(|line| format!("{}\n", line)).collect();
+        let new: String = (0..1000)
+            .map(|line| match line % 10 {
+                0 => format!("changed {}\n", line),
+                _ => format!("{}\n", line),
+            })
+            .collect();
+        let hunks = diff(&old, &new);
+        assert_eq!(hunks.len(), 100);
+        assert_eq!(
+            hunks[1],
+            Hunk {
+                old: 10..11,
+                new: 10..11
+            }
+        );
+    }
+
+    #[test]
+    fn format_unified_diff() {
+        let old: Rope = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n".into();
+        let new: Rope = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\neleven".into();
+        let hunks = diff_lines(&old, &new);
+        assert_eq!(
+            unified_diff(&old, &new, &hunks, 1),
+            "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n\
+             @@ -10 +10,2 @@\n 10\n+eleven\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified_diff(&old, &new, &hunks, 4),
+            "@@ -1,10 +1,11 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n 7\n 8\n 9\n 10\n\
+             +eleven\n\\ No newline at end of file\n"
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use ropey::Rope;
+use std::cmp;
 
 use crate::{
-    graphemes::{RopeExt, RopeGraphemes},
+    graphemes::{CharIndex, RopeExt, RopeGraphemes},
     Cursor,
 };
 
@@ -64,7 +65,7 @@ pub fn move_vertically(
     let current_visual_x = cursor.visual_horizontal_offset.get_or_insert_with(|| {
         let current_line_start = text.line_to_char(current_line_index);
         let line_to_cursor = text.slice(current_line_start..cursor.range.start);
-        crate::graphemes::width(tab_width, &line_to_cursor)
+        crate::graphemes::line_width(tab_width, &line_to_cursor)
     });
 
     let new_line = text.line(new_line_index);
@@ -72,7 +73,7 @@ pub fn move_vertically(
     let mut new_visual_x = 0;
     let mut char_offset = text.line_to_char(new_line_index);
     for grapheme in &mut graphemes {
-        let width = crate::graphemes::width(tab_width, &grapheme);
+        let width = crate::graphemes::width_at(tab_width, new_visual_x, &grapheme);
         if new_visual_x + width > *current_visual_x || grapheme.slice == "\n" {
             break;
         }
@@ -185,6 +186,27 @@ pub fn move_to_start_of_line(text: &Rope, cursor: &mut Cursor) {
     cursor.visual_horizontal_offset = None;
 }
 
+/// Move the cursor to the first character of the current line which isn't
+/// a space or a tab, the end of the line if there's none
+#[inline]
+pub fn move_to_indentation(text: &Rope, cursor: &mut Cursor) {
+    let indentation_end = indentation_end(text, text.char_to_line(cursor.range.start));
+    cursor.range = indentation_end..text.next_grapheme_boundary(indentation_end);
+    cursor.visual_horizontal_offset = None;
+}
+
+/// Move the cursor to the indentation of the current line, or to the
+/// beginning of the line if it's there already. Repeating it toggles between
+/// the two.
+#[inline]
+pub fn move_to_indentation_or_start_of_line(text: &Rope, cursor: &mut Cursor) {
+    if cursor.range.start == indentation_end(text, text.char_to_line(cursor.range.start)) {
+        move_to_start_of_line(text, cursor);
+    } else {
+        move_to_indentation(text, cursor);
+    }
+}
+
 /// Move the cursor to the end of the current line
 #[inline]
 pub fn move_to_end_of_line(text: &Rope, cursor: &mut Cursor) {
@@ -222,6 +244,26 @@ pub fn move_to_end_of_buffer(text: &Rope, cursor: &mut Cursor) {
     cursor.visual_horizontal_offset = None;
 }
 
+/// Move the cursor to the grapheme starting at `char_index`. Indices past the
+/// end of the text move the cursor to the end of the buffer.
+#[inline]
+pub fn move_to_char(text: &Rope, cursor: &mut Cursor, char_index: CharIndex) {
+    let start = cmp::min(char_index, text.len_chars());
+    cursor.range = start..text.next_grapheme_boundary(start);
+    cursor.visual_horizontal_offset = None;
+}
+
+/// Where the spaces and tabs at the start of a line end
+#[inline]
+fn indentation_end(text: &Rope, line_index: usize) -> CharIndex {
+    text.line_to_char(line_index)
+        + text
+            .line(line_index)
+            .chars()
+            .take_while(|&character| character == ' ' || character == '\t')
+            .count()
+}
+
 #[inline]
 fn skip_while_forward(
     text: &Rope,
@@ -341,6 +383,59 @@ mod tests {
         assert_eq!(text.slice_cursor(&cursor), "T");
     }
 
+    #[test]
+    fn move_to_char_in_text() {
+        let (text, mut cursor) = text_with_cursor("Fn 👨‍👨‍👧‍👧 and more\n");
+        move_to_char(&text, &mut cursor, 3);
+        assert_eq!(text.slice_cursor(&cursor), MULTI_CHAR_EMOJI);
+        move_to_char(&text, &mut cursor, 1);
+        assert_eq!(cursor, Cursor::with_range(1..2));
+    }
+
+    #[test]
+    fn move_to_indentation_skips_spaces_and_tabs() {
+        let (text, mut cursor) = text_with_cursor("fn main() {\n \t  let x;\n}\n");
+        move_to_char(&text, &mut cursor, 20);
+        move_to_indentation(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(16..17));
+        assert_eq!(text.slice_cursor(&cursor), "l");
+
+        // A blank line's indentation ends with it
+        let (text, mut cursor) = text_with_cursor("a\n    \nb");
+        move_to_char(&text, &mut cursor, 3);
+        move_to_indentation(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(6..7));
+    }
+
+    #[test]
+    fn move_to_indentation_or_start_of_line_toggles() {
+        let (text, mut cursor) = text_with_cursor("fn main() {\n    let x;\n}\n");
+        move_to_char(&text, &mut cursor, 20);
+        move_to_indentation_or_start_of_line(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(16..17));
+        move_to_indentation_or_start_of_line(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(12..13));
+        move_to_indentation_or_start_of_line(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(16..17));
+    }
+
+    #[test]
+    fn move_to_indentation_or_start_of_line_without_indentation() {
+        let (text, mut cursor) = text_with_cursor("fn main() {}\n");
+        move_to_char(&text, &mut cursor, 5);
+        move_to_indentation_or_start_of_line(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(0..1));
+        move_to_indentation_or_start_of_line(&text, &mut cursor);
+        assert_eq!(cursor, Cursor::with_range(0..1));
+    }
+
+    #[test]
+    fn move_to_char_past_the_end() {
+        let (text, mut cursor) = text_with_cursor("Bloom\n");
+        move_to_char(&text, &mut cursor, 100);
+        assert_eq!(cursor, Cursor::with_range(6..6));
+    }
+
     const TEXT: &str = r#"
 Basic Latin
     ! " # $ % & ' ( ) *+,-./012ABCDEFGHI` a m  t u v z { | } ~
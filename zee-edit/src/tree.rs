@@ -1,7 +1,10 @@
 use euclid::default::Vector2D;
 use ropey::Rope;
 use smallvec::SmallVec;
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
 
 use crate::{movement, Cursor, OpaqueDiff};
 
@@ -12,6 +15,10 @@ pub struct Revision {
     pub parent: Option<Reference>,
     pub children: SmallVec<[Reference; 1]>,
     pub redo_index: usize,
+    /// When the revision was made, or last extended by a grouped edit
+    pub created: Instant,
+    /// A name given to the revision by the user
+    pub label: Option<String>,
 }
 
 impl Revision {
@@ -24,8 +31,15 @@ impl Revision {
             parent: None,
             children: SmallVec::new(),
             redo_index: 0,
+            created: Instant::now(),
+            label: None,
         }
     }
+
+    /// The edit which made this revision from its parent, `None` for the root
+    pub fn diff(&self) -> Option<OpaqueDiff> {
+        self.parent.as_ref().map(|parent| parent.diff.reverse())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +54,16 @@ pub struct EditTree {
     pub head_index: usize,
     staged: Rope,
     has_staged_changes: bool,
+    /// The group of edits the head revision was made from, if more can be
+    /// added to it
+    open_group: Option<UndoGroup>,
+}
+
+/// Edits merged into a single revision, so they're undone together
+#[derive(Debug, Clone, Copy)]
+struct UndoGroup {
+    last_edit: Instant,
+    num_edits: usize,
 }
 
 impl EditTree {
@@ -49,6 +73,7 @@ impl EditTree {
             head_index: 0,
             staged: text,
             has_staged_changes: false,
+            open_group: None,
         }
     }
 
@@ -66,7 +91,79 @@ impl EditTree {
         }
     }
 
+    /// Creates a revision for an edit which can be grouped with the edits
+    /// right before and after it, e.g. typing or deleting a character. The
+    /// edit is merged into the head revision if it touches the text changed
+    /// by the group, no more than `UNDO_GROUP_TIMEOUT` passed since the last
+    /// edit in the group and the group isn't full.
+    pub fn create_grouped_revision(&mut self, diff: OpaqueDiff, cursor: Cursor, now: Instant) {
+        if let Some(group) = self.open_group {
+            let in_time = now.saturating_duration_since(group.last_edit) <= UNDO_GROUP_TIMEOUT;
+            if in_time
+                && group.num_edits < MAX_GROUPED_EDITS
+                && self.extend_head(&diff, cursor.clone(), now)
+            {
+                self.open_group = Some(UndoGroup {
+                    last_edit: now,
+                    num_edits: group.num_edits + 1,
+                });
+                return;
+            }
+        }
+        self.create_revision(diff, cursor);
+        self.open_group = Some(UndoGroup {
+            last_edit: now,
+            num_edits: 1,
+        });
+    }
+
+    /// Stops grouping edits, so the next one creates a new revision
+    pub fn close_group(&mut self) {
+        self.open_group = None;
+    }
+
+    pub fn has_open_group(&self) -> bool {
+        self.open_group.is_some()
+    }
+
+    /// Merges the staged changes made by an edit into the head revision.
+    /// Returns false if the head has children or the edit doesn't touch the
+    /// text changed by the head.
+    fn extend_head(&mut self, diff: &OpaqueDiff, cursor: Cursor, now: Instant) -> bool {
+        let head_index = self.head_index;
+        let head = &self.revisions[head_index];
+        let parent_index = match head.parent {
+            Some(ref parent) if head.children.is_empty() => parent.index,
+            _ => return false,
+        };
+        let child_position = match self.revisions[parent_index]
+            .children
+            .iter()
+            .position(|child| child.index == head_index)
+        {
+            Some(position) => position,
+            None => return false,
+        };
+        let merged = match self.revisions[parent_index].children[child_position]
+            .diff
+            .merge(diff)
+        {
+            Some(merged) => merged,
+            None => return false,
+        };
+
+        self.revisions[parent_index].children[child_position].diff = merged.clone();
+        let head = &mut self.revisions[head_index];
+        head.parent.as_mut().unwrap().diff = merged.reverse();
+        head.text = self.staged.clone();
+        head.cursor = cursor;
+        head.created = now;
+        self.has_staged_changes = false;
+        true
+    }
+
     pub fn create_revision(&mut self, diff: OpaqueDiff, cursor: Cursor) {
+        self.open_group = None;
         let parent_to_child_diff = diff;
         let child_to_parent_diff = parent_to_child_diff.reverse();
         // let child_to_parent_diff = diff;
@@ -82,6 +179,8 @@ impl EditTree {
             }),
             children: SmallVec::new(),
             redo_index: 0,
+            created: Instant::now(),
+            label: None,
         });
         {
             let head = &mut self.revisions[self.head_index];
@@ -104,6 +203,7 @@ impl EditTree {
             let previous_revision = &self.revisions[previous_index];
             self.staged = previous_revision.text.clone();
             self.head_index = previous_index;
+            self.open_group = None;
 
             self.has_staged_changes = false;
             Some((diff.clone(), previous_revision.cursor.clone()))
@@ -118,6 +218,7 @@ impl EditTree {
             head_index,
             staged,
             has_staged_changes,
+            open_group,
             ..
         } = self;
         let Revision {
@@ -136,10 +237,62 @@ impl EditTree {
                 *staged = text.clone();
                 *has_staged_changes = false;
                 *head_index = *index;
+                *open_group = None;
                 (diff.clone(), cursor.clone())
             })
     }
 
+    /// Moves to any revision in the tree, returning a diff replacing the whole
+    /// text and the cursor saved with the revision. Redoing from the revisions
+    /// above it leads back to it. Returns `None` if there's no such revision
+    /// or it's the current one.
+    pub fn jump_to(&mut self, index: usize) -> Option<(OpaqueDiff, Cursor)> {
+        if index >= self.revisions.len() || index == self.head_index {
+            return None;
+        }
+
+        let mut child_index = index;
+        while let Some(parent_index) = self.revisions[child_index]
+            .parent
+            .as_ref()
+            .map(|parent| parent.index)
+        {
+            let parent = &mut self.revisions[parent_index];
+            if let Some(position) = parent
+                .children
+                .iter()
+                .position(|child| child.index == child_index)
+            {
+                parent.redo_index = position;
+            }
+            child_index = parent_index;
+        }
+
+        let Revision {
+            ref text,
+            ref cursor,
+            ..
+        } = self.revisions[index];
+        let diff = OpaqueDiff::new(
+            0,
+            self.staged.len_bytes(),
+            text.len_bytes(),
+            0,
+            self.staged.len_chars(),
+            text.len_chars(),
+        );
+        self.staged = text.clone();
+        self.head_index = index;
+        self.has_staged_changes = false;
+        self.open_group = None;
+        Some((diff, cursor.clone()))
+    }
+
+    /// Names the current revision, or removes its name
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.revisions[self.head_index].label = label;
+    }
+
     pub fn staged(&self) -> &Rope {
         self.deref()
     }
@@ -164,6 +317,12 @@ impl DerefMut for EditTree {
     }
 }
 
+/// How long after the last edit in a group the next one can still be added
+pub const UNDO_GROUP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// The most edits grouped in a single revision
+const MAX_GROUPED_EDITS: usize = 20;
+
 pub struct FormattedRevision {
     pub transform: Vector2D<isize>,
     pub current_branch: bool,
@@ -281,6 +440,88 @@ mod tests {
         assert_eq!("", &tree.to_string());
     }
 
+    fn type_char(tree: &mut EditTree, character: char, now: Instant) {
+        let char_index = tree.len_chars();
+        tree.insert_char(char_index, character);
+        tree.create_grouped_revision(
+            OpaqueDiff::new(char_index, 0, 1, char_index, 0, 1),
+            Cursor::end_of_buffer(tree),
+            now,
+        );
+    }
+
+    #[test]
+    fn group_consecutive_edits() {
+        let now = Instant::now();
+        let mut tree = EditTree::new(Rope::new());
+        type_char(&mut tree, 'a', now);
+        type_char(&mut tree, 'b', now);
+        type_char(&mut tree, 'c', now + Duration::from_millis(100));
+        assert_eq!(tree.revisions.len(), 2);
+
+        assert_eq!(
+            tree.undo().map(|(diff, _)| diff),
+            Some(OpaqueDiff::new(0, 3, 0, 0, 3, 0))
+        );
+        assert_eq!("", &tree.to_string());
+        tree.redo();
+        assert_eq!("abc", &tree.to_string());
+    }
+
+    #[test]
+    fn close_group_after_timeout_or_explicitly() {
+        let now = Instant::now();
+        let mut tree = EditTree::new(Rope::new());
+        type_char(&mut tree, 'a', now);
+        type_char(&mut tree, 'b', now + UNDO_GROUP_TIMEOUT * 2);
+        tree.close_group();
+        type_char(&mut tree, 'c', now + UNDO_GROUP_TIMEOUT * 2);
+        assert_eq!(tree.revisions.len(), 4);
+
+        tree.undo();
+        assert_eq!("ab", &tree.to_string());
+        tree.undo();
+        assert_eq!("a", &tree.to_string());
+    }
+
+    #[test]
+    fn do_not_extend_revisions_with_children() {
+        let now = Instant::now();
+        let mut tree = EditTree::new(Rope::new());
+        type_char(&mut tree, 'a', now);
+        type_char(&mut tree, 'b', now);
+        tree.undo();
+        type_char(&mut tree, 'c', now);
+        assert_eq!("c", &tree.to_string());
+        tree.undo();
+        assert_eq!("", &tree.to_string());
+    }
+
+    #[test]
+    fn jump_to_revision_on_another_branch() {
+        let now = Instant::now();
+        let mut tree = EditTree::new(Rope::new());
+        type_char(&mut tree, 'a', now);
+        tree.close_group();
+        type_char(&mut tree, 'b', now);
+        tree.undo();
+        type_char(&mut tree, 'c', now);
+        assert_eq!("ac", &tree.to_string());
+
+        let (diff, _) = tree.jump_to(2).unwrap();
+        assert_eq!(diff, OpaqueDiff::new(0, 2, 2, 0, 2, 2));
+        assert_eq!("ab", &tree.to_string());
+        assert_eq!(tree.jump_to(2), None);
+        assert_eq!(tree.jump_to(42), None);
+
+        // Redo follows the branch jumped to
+        tree.undo();
+        tree.undo();
+        tree.redo();
+        tree.redo();
+        assert_eq!("ab", &tree.to_string());
+    }
+
     #[test]
     fn render_undo_tree() {}
 }
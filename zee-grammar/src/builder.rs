@@ -23,8 +23,8 @@ const QUERY_DIR: &str = "queries";
 pub(crate) fn load_grammar(grammar_id: String) -> Result<Grammar> {
     let language = load_language(&grammar_id)?;
     let make_query = |name| log_on_error(&grammar_id, load_query(language, &grammar_id, name));
-    let [highlights, indents, injections, locals] =
-        ["highlights", "indents", "injections", "locals"].map(make_query);
+    let [highlights, indents, injections, locals, tags] =
+        ["highlights", "indents", "injections", "locals", "tags"].map(make_query);
 
     Ok(Grammar {
         id: grammar_id,
@@ -33,6 +33,7 @@ pub(crate) fn load_grammar(grammar_id: String) -> Result<Grammar> {
         indents,
         injections,
         locals,
+        tags,
     })
 }
 
@@ -356,7 +357,7 @@ fn copy_tree_sitter_queries(grammar_id: &str, source: &Path, defaults: &Dir) ->
         )
     })?;
 
-    for query_name in ["highlights", "indents", "locals", "injections"] {
+    for query_name in ["highlights", "indents", "locals", "injections", "tags"] {
         let query_filename = PathBuf::from(format!("{}.scm", query_name));
 
         // Query destination path
@@ -17,6 +17,8 @@ pub struct ModeConfig {
     pub comment: Option<CommentConfig>,
     pub indentation: IndentationConfig,
     pub grammar: Option<GrammarConfig>,
+    #[serde(default)]
+    pub closing_keywords: Option<ClosingKeywordsConfig>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -25,11 +27,47 @@ pub struct CommentConfig {
     pub token: String,
 }
 
+/// For languages closing constructs with keywords rather than braces, e.g.
+/// `fi` in shell scripts or `end` in Ruby
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename = "ClosingKeywords")]
+pub struct ClosingKeywordsConfig {
+    /// Each keyword opening a construct with the keyword closing it, e.g.
+    /// `("if", "fi")`
+    pub pairs: Vec<(String, String)>,
+    /// Whether to insert the closing keyword when starting a new line after
+    /// a construct left open
+    #[serde(default)]
+    pub on_new_line: bool,
+}
+
+impl ClosingKeywordsConfig {
+    /// The keyword closing the construct opened by `keyword`
+    pub fn closing(&self, keyword: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(opening, _)| opening == keyword)
+            .map(|(_, closing)| closing.as_str())
+    }
+
+    /// The closing keyword equal to `keyword`, if it is one
+    pub fn as_closing(&self, keyword: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .map(|(_, closing)| closing.as_str())
+            .find(|&closing| closing == keyword)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename = "Indentation")]
 pub struct IndentationConfig {
     pub width: usize,
     pub unit: IndentationUnit,
+    /// How wide tab characters are shown when indenting with spaces. When
+    /// indenting with tabs, they are `width` wide. Defaults to `width`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab_width: Option<usize>,
 }
 
 impl IndentationConfig {
@@ -45,7 +83,10 @@ impl IndentationConfig {
     }
 
     pub fn tab_width(&self) -> usize {
-        self.width
+        match self.unit {
+            IndentationUnit::Space => self.tab_width.unwrap_or(self.width),
+            IndentationUnit::Tab => self.width,
+        }
     }
 }
 
@@ -54,11 +95,12 @@ impl Default for IndentationConfig {
         Self {
             width: 4,
             unit: IndentationUnit::Space,
+            tab_width: None,
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum IndentationUnit {
     Space,
     Tab,
@@ -8,7 +8,9 @@ use once_cell::sync::Lazy;
 use std::path::Path;
 use tree_sitter::{Language, Query};
 
-use self::config::{CommentConfig, FilenamePattern, IndentationConfig, ModeConfig};
+use self::config::{
+    ClosingKeywordsConfig, CommentConfig, FilenamePattern, IndentationConfig, ModeConfig,
+};
 
 #[derive(Debug)]
 pub struct Mode {
@@ -18,6 +20,7 @@ pub struct Mode {
     pub patterns: Vec<FilenamePattern>,
     pub comment: Option<CommentConfig>,
     pub indentation: IndentationConfig,
+    pub closing_keywords: Option<ClosingKeywordsConfig>,
     grammar: LazyGrammar,
 }
 
@@ -31,6 +34,7 @@ impl Mode {
             comment,
             indentation,
             grammar: grammar_config,
+            closing_keywords,
         } = config;
         Self {
             name,
@@ -39,6 +43,7 @@ impl Mode {
             patterns,
             comment,
             indentation,
+            closing_keywords,
             grammar: Lazy::new(Box::new(move || {
                 grammar_config
                     .map(|grammar_config| grammar_config.grammar_id)
@@ -73,6 +78,7 @@ impl Default for Mode {
             patterns: vec![],
             comment: None,
             indentation: Default::default(),
+            closing_keywords: None,
             grammar: Lazy::new(Box::new(|| None)),
         }
     }
@@ -86,6 +92,7 @@ pub struct Grammar {
     pub indents: Option<Query>,
     pub injections: Option<Query>,
     pub locals: Option<Query>,
+    pub tags: Option<Query>,
 }
 
 impl PartialEq for Mode {
@@ -0,0 +1,99 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::BTreeMap;
+use tree_sitter::{Language, Node, Parser};
+
+use zee_highlight::{parse_rules_unwrap, HighlightRules, SelectorNodeId};
+
+const SOURCE: &str = include_str!("../src/lib.rs");
+
+/// A node to highlight: the kinds of the node and its ancestors, innermost
+/// first, each one's index among its siblings and the node's text
+struct NodeContext {
+    node_stack: Vec<SelectorNodeId>,
+    nth_children: Vec<u16>,
+    content: String,
+}
+
+/// Rules with a scope for every named node kind and a more specific one for
+/// every parent and child pair in the source, as a large rules file would have
+fn rules(language: Language, root: Node) -> HighlightRules {
+    let mut scopes = BTreeMap::new();
+    for node_id in 0..language.node_kind_count() as u16 {
+        if language.node_kind_is_named(node_id) && language.node_kind_is_visible(node_id) {
+            let kind = language.node_kind_for_id(node_id).unwrap();
+            scopes.insert(kind.to_owned(), format!("scope.{}", kind));
+        }
+    }
+    visit(root, &mut |node| {
+        if let Some(parent) = node.parent().filter(|_| node.is_named()) {
+            scopes.insert(
+                format!("{} > {}", parent.kind(), node.kind()),
+                format!("scope.{}.{}", parent.kind(), node.kind()),
+            );
+        }
+    });
+    let source = serde_json::json!({ "name": "Rust", "scopes": scopes }).to_string();
+    parse_rules_unwrap(language, &source)
+}
+
+fn node_contexts(rules: &HighlightRules, root: Node) -> Vec<NodeContext> {
+    let mut contexts = Vec::new();
+    visit(root, &mut |node| {
+        let mut node_stack = Vec::new();
+        let mut nth_children = Vec::new();
+        let mut current = Some(node);
+        while let Some(node) = current {
+            node_stack.push(rules.get_selector_node_id(node.kind_id()));
+            nth_children.push(sibling_index(node));
+            current = node.parent();
+        }
+        contexts.push(NodeContext {
+            node_stack,
+            nth_children,
+            content: SOURCE[node.byte_range()].to_owned(),
+        });
+    });
+    contexts
+}
+
+fn sibling_index(node: Node) -> u16 {
+    let mut index = 0;
+    let mut sibling = node.prev_sibling();
+    while let Some(previous) = sibling {
+        index += 1;
+        sibling = previous.prev_sibling();
+    }
+    index
+}
+
+fn visit<'tree>(node: Node<'tree>, on_node: &mut impl FnMut(Node<'tree>)) {
+    on_node(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        visit(child, on_node);
+    }
+}
+
+fn match_all_nodes(criterion: &mut Criterion) {
+    let language = tree_sitter_rust::language();
+    let mut parser = Parser::new();
+    parser.set_language(language).unwrap();
+    let tree = parser.parse(SOURCE, None).unwrap();
+    let rules = rules(language, tree.root_node());
+    let contexts = node_contexts(&rules, tree.root_node());
+
+    criterion.bench_function("match all nodes", |bencher| {
+        bencher.iter(|| {
+            for context in contexts.iter() {
+                black_box(rules.matches(
+                    &context.node_stack,
+                    &context.nth_children,
+                    &context.content,
+                ));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, match_all_nodes);
+criterion_main!(benches);
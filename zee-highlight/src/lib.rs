@@ -3,12 +3,12 @@ mod selector;
 
 use fnv::FnvHashMap;
 use serde_derive::{self, Deserialize, Serialize};
-use std::{cmp, collections::HashMap, convert::TryFrom};
+use std::{collections::HashMap, convert::TryFrom};
 
 use error::Result;
 use tree_sitter::Language;
 
-use crate::selector::{map_node_kind_names, Selector};
+use crate::selector::{map_node_kind_names, SelectorTrie};
 
 pub use crate::selector::SelectorNodeId;
 
@@ -17,14 +17,12 @@ pub struct HighlightRules {
     name: String,
     node_id_to_selector_id: FnvHashMap<u16, SelectorNodeId>,
 
+    /// The scope pattern of each rule
     #[serde(default)]
-    rules: Vec<HighlightRule>,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct HighlightRule {
-    selectors: Vec<Selector>,
-    scope: ScopePattern,
+    scopes: Vec<ScopePattern>,
+    /// The selectors of every rule, compiled for matching node stacks
+    #[serde(default)]
+    selectors: SelectorTrie,
 }
 
 impl HighlightRules {
@@ -38,6 +36,12 @@ impl HighlightRules {
             })
     }
 
+    /// The scope of a node, given the kinds of the node and its ancestors,
+    /// innermost first, and the index of each among its siblings.
+    ///
+    /// Selectors matching closer to the node win, then the more specific ones,
+    /// i.e. the ones with more nodes. A rule only applies if its scope pattern
+    /// matches the node's content.
     #[inline]
     pub fn matches(
         &self,
@@ -45,81 +49,28 @@ impl HighlightRules {
         nth_children: &[u16],
         content: &str,
     ) -> Option<&Scope> {
-        if node_stack.is_empty() {
-            return None;
-        }
-
-        let mut distance_to_match = std::usize::MAX;
-        let mut num_nodes_match = 0;
-        let mut scope_pattern = None;
-        for rule in self.rules.iter() {
-            let rule_scope = match rule.scope.matches(content) {
-                Some(scope) => scope,
-                None => continue,
-            };
-
-            for selector in rule.selectors.iter() {
-                let selector_node_kinds = selector.node_kinds();
-                let selector_nth_children = selector.nth_children();
-
-                // eprintln!("NST {:?} {:?}", node_stack, nth_children);
-                // eprintln!("SEL {:?} {:?}", selector_node_kinds, selector_nth_children);
-
-                assert!(!selector_node_kinds.is_empty());
-                if selector_node_kinds.len() > node_stack.len() {
-                    continue;
-                }
-
-                // TODO: Are for loops over inclusive ranges slow?
-                for start in 0..=cmp::min(
-                    node_stack.len().saturating_sub(selector_node_kinds.len()),
-                    distance_to_match,
-                ) {
-                    let span_range = || start..start + selector_node_kinds.len();
-
-                    // Does the selector match the current node and its ancestors?
-                    if selector_node_kinds
-                        != &node_stack[start..(start + selector_node_kinds.len())]
-                    {
-                        continue;
-                    }
-
-                    // Are the `nth-child` constrains also satisfied?
-                    let nth_child_not_satisfied = selector_nth_children
-                        .iter()
-                        .zip(nth_children[span_range()].iter())
-                        .any(|(&nth_child_selector, &node_sibling_index)| {
-                            nth_child_selector >= 0
-                                && nth_child_selector as u16 != node_sibling_index
-                        });
-                    if nth_child_not_satisfied {
-                        continue;
+        for start in 0..node_stack.len() {
+            // The longest selector matching here, the last rule on ties
+            let mut best_match: Option<(usize, usize, &Scope)> = None;
+            self.selectors.matches(
+                &node_stack[start..],
+                nth_children.get(start..).unwrap_or(&[]),
+                |rule_index, num_nodes| {
+                    if best_match.map_or(false, |(best_num_nodes, best_rule_index, _)| {
+                        (best_num_nodes, best_rule_index) > (num_nodes, rule_index)
+                    }) {
+                        return;
                     }
-
-                    // Is the selector more specific than the most specific
-                    // match we've found so far?
-                    if start == distance_to_match && num_nodes_match > selector_node_kinds.len() {
-                        break;
+                    if let Some(scope) = self.scopes[rule_index].matches(content) {
+                        best_match = Some((num_nodes, rule_index, scope));
                     }
-
-                    assert!(start <= distance_to_match);
-                    // eprintln!(
-                    //     "!!D {} -> {} | N {} -> {}",
-                    //     distance_to_match,
-                    //     start,
-                    //     num_nodes_match,
-                    //     selector_node_kinds.len()
-                    // );
-
-                    distance_to_match = start;
-                    num_nodes_match = selector_node_kinds.len();
-                    scope_pattern = Some(rule_scope);
-                    break;
-                }
+                },
+            );
+            if let Some((_, _, scope)) = best_match {
+                return Some(scope);
             }
         }
-
-        scope_pattern
+        None
     }
 }
 
@@ -137,22 +88,26 @@ impl RawHighlightRules {
             build_node_to_selector_id_maps(language);
         let RawHighlightRules { name, scopes } = self;
 
-        scopes
+        let mut selectors = SelectorTrie::default();
+        let scopes = scopes
             .into_iter()
-            .map(|(selector_str, scope)| {
-                let selectors = selector::parse(&selector_str)?;
-                let selectors = selectors
-                    .into_iter()
-                    .map(|selector| map_node_kind_names(&node_name_to_selector_id, selector))
-                    .collect::<Result<Vec<_>>>()?;
-                Ok(HighlightRule { selectors, scope })
-            })
-            .collect::<Result<Vec<_>>>()
-            .map(|rules| HighlightRules {
-                name,
-                rules,
-                node_id_to_selector_id,
+            .enumerate()
+            .map(|(rule_index, (selector_str, scope))| {
+                for selector in selector::parse(&selector_str)? {
+                    selectors.insert(
+                        rule_index,
+                        map_node_kind_names(&node_name_to_selector_id, selector)?,
+                    );
+                }
+                Ok(scope)
             })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(HighlightRules {
+            name,
+            node_id_to_selector_id,
+            scopes,
+            selectors,
+        })
     }
 }
 
@@ -297,4 +252,94 @@ mod tests {
         assert_eq!(expected.name, actual.name);
         assert_eq!(expected.scopes, actual.scopes);
     }
+
+    fn rust_rules() -> HighlightRules {
+        parse_rules_unwrap(
+            tree_sitter_rust::language(),
+            r#"{
+                "name": "Rust",
+                "scopes": {
+                    "identifier": "variable",
+                    "call_expression > identifier": "function",
+                    "string_literal": "string",
+                    "escape_sequence": "constant.character.escape",
+                    "integer_literal": "constant.numeric",
+                    "tuple_expression > integer_literal:nth-child(1)": "constant.second",
+                    "\"let\"": {"exact": "let", "scopes": "keyword"}
+                }
+            }"#,
+        )
+    }
+
+    fn stack(rules: &HighlightRules, kinds: &[(&str, bool)]) -> Vec<SelectorNodeId> {
+        let language = tree_sitter_rust::language();
+        kinds
+            .iter()
+            .map(|&(kind, named)| {
+                rules.get_selector_node_id(language.id_for_node_kind(kind, named))
+            })
+            .collect()
+    }
+
+    fn scope<'a>(
+        rules: &'a HighlightRules,
+        kinds: &[(&str, bool)],
+        nth_children: &[u16],
+        content: &str,
+    ) -> Option<&'a str> {
+        rules
+            .matches(&stack(rules, kinds), nth_children, content)
+            .map(|scope| scope.0.as_str())
+    }
+
+    #[test]
+    fn more_specific_selectors_win() {
+        let rules = rust_rules();
+        let call = [("identifier", true), ("call_expression", true)];
+        let declaration = [("identifier", true), ("let_declaration", true)];
+        assert_eq!(scope(&rules, &call, &[0, 0], "f"), Some("function"));
+        assert_eq!(scope(&rules, &declaration, &[1, 0], "x"), Some("variable"));
+    }
+
+    #[test]
+    fn closer_selectors_win() {
+        let rules = rust_rules();
+        let escape = [
+            ("escape_sequence", true),
+            ("string_literal", true),
+            ("call_expression", true),
+        ];
+        let quote = [("\"", false), ("string_literal", true)];
+        assert_eq!(
+            scope(&rules, &escape, &[1, 0, 0], "\\n"),
+            Some("constant.character.escape")
+        );
+        assert_eq!(scope(&rules, &quote, &[0, 0], "\""), Some("string"));
+        assert_eq!(scope(&rules, &[("block", true)], &[0], "{}"), None);
+        assert_eq!(scope(&rules, &[], &[], ""), None);
+    }
+
+    #[test]
+    fn nth_child_constraints() {
+        let rules = rust_rules();
+        let element = [("integer_literal", true), ("tuple_expression", true)];
+        assert_eq!(
+            scope(&rules, &element, &[1, 0], "2"),
+            Some("constant.second")
+        );
+        assert_eq!(
+            scope(&rules, &element, &[3, 0], "4"),
+            Some("constant.numeric")
+        );
+    }
+
+    #[test]
+    fn scope_patterns_match_content() {
+        let rules = rust_rules();
+        assert_eq!(
+            scope(&rules, &[("let", false)], &[0], "let"),
+            Some("keyword")
+        );
+        assert_eq!(scope(&rules, &[("let", false)], &[0], "lett"), None);
+    }
 }
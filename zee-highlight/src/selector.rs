@@ -19,7 +19,7 @@ use crate::error::{Error, Result};
 // multiple node kind "ids" to the same node kind string. The highlighting rules
 // refer to strings. To apply rules and avoid string matching, we have to first
 // map the node kind ids of a grammar to a unique set.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub struct SelectorNodeId(pub(crate) u16);
 
 pub type NthChild = i16;
@@ -37,10 +37,79 @@ impl Selector {
     pub(crate) fn node_kinds(&self) -> &[SelectorNodeId] {
         self.node_kinds.as_slice()
     }
+}
+
+/// The selectors of all the highlighting rules, compiled into a trie keyed by
+/// node kind. A selector is stored from its innermost node outwards, so walking
+/// the trie down a node stack finds all the selectors matching at its top in
+/// time proportional to the depth of the stack.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct SelectorTrie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct TrieNode {
+    children: FnvHashMap<SelectorNodeId, usize>,
+    /// The selectors ending at this node, with the index of their rule
+    selectors: Vec<(usize, SmallVec<[NthChild; 4]>)>,
+}
 
+impl SelectorTrie {
+    pub(crate) fn insert(&mut self, rule_index: usize, selector: Selector) {
+        if self.nodes.is_empty() {
+            self.nodes.push(TrieNode::default());
+        }
+        let mut node_index = 0;
+        for node_kind in selector.node_kinds() {
+            node_index = match self.nodes[node_index].children.get(node_kind) {
+                Some(&child_index) => child_index,
+                None => {
+                    let child_index = self.nodes.len();
+                    self.nodes.push(TrieNode::default());
+                    self.nodes[node_index]
+                        .children
+                        .insert(*node_kind, child_index);
+                    child_index
+                }
+            };
+        }
+        self.nodes[node_index]
+            .selectors
+            .push((rule_index, selector.nth_children));
+    }
+
+    /// Calls `on_match` with the rule index and the number of nodes of every
+    /// selector matching the top of the node stack, shortest selectors first.
+    /// The stack starts with the innermost node.
     #[inline]
-    pub(crate) fn nth_children(&self) -> &[NthChild] {
-        self.nth_children.as_slice()
+    pub(crate) fn matches(
+        &self,
+        node_stack: &[SelectorNodeId],
+        nth_children: &[u16],
+        mut on_match: impl FnMut(usize, usize),
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut node_index = 0;
+        for (depth, node_kind) in node_stack.iter().enumerate() {
+            node_index = match self.nodes[node_index].children.get(node_kind) {
+                Some(&child_index) => child_index,
+                None => return,
+            };
+            for (rule_index, selector_nth_children) in self.nodes[node_index].selectors.iter() {
+                let nth_children_satisfied = selector_nth_children
+                    .iter()
+                    .zip(nth_children.iter())
+                    .all(|(&nth_child_selector, &node_sibling_index)| {
+                        nth_child_selector < 0 || nth_child_selector as u16 == node_sibling_index
+                    });
+                if nth_children_satisfied {
+                    on_match(*rule_index, depth + 1);
+                }
+            }
+        }
     }
 }
 
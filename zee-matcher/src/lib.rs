@@ -0,0 +1,420 @@
+//! Matching candidates against a filter as it's typed, in the prompts that
+//! pick a file, buffer, symbol or mode. A `Matcher` keeps the candidates and
+//! their ranking, so typing more of a filter only scores again the candidates
+//! which matched before.
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::{fmt, sync::Arc};
+
+/// How the filter is matched against the candidates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchMode {
+    Fuzzy,
+    Substring,
+}
+
+impl MatchMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Fuzzy => Self::Substring,
+            Self::Substring => Self::Fuzzy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fuzzy => "fuzzy",
+            Self::Substring => "substring",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Candidate {
+    text: String,
+    /// Added to the score when the candidate matches
+    bonus: i64,
+}
+
+/// Ranks a set of candidates by how well they match a filter. Indexing a
+/// `Matcher` with a rank gives the index of the candidate in the order it was
+/// added.
+pub struct Matcher {
+    // Shared as matchers are cloned when drawing the candidates, and the
+    // candidates don't change while a filter is typed
+    candidates: Arc<Vec<Candidate>>,
+    ranked: Vec<(usize, i64)>, // (candidate index, score)
+    filter: String,
+    mode: MatchMode,
+    matcher: Box<SkimMatcherV2>, // Boxed as it's big
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Matcher")
+            .field("num_candidates", &self.candidates.len())
+            .field("ranked", &self.ranked)
+            .field("filter", &self.filter)
+            .field("mode", &self.mode)
+            .field("matcher", &"SkimMatcherV2(...)")
+            .finish()
+    }
+}
+
+impl Clone for Matcher {
+    fn clone(&self) -> Self {
+        Self {
+            candidates: self.candidates.clone(),
+            ranked: self.ranked.clone(),
+            filter: self.filter.clone(),
+            mode: self.mode,
+            matcher: default_matcher().into(),
+        }
+    }
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Matcher {
+    pub fn new() -> Self {
+        Self {
+            candidates: Arc::new(Vec::new()),
+            ranked: Vec::new(),
+            filter: String::new(),
+            mode: MatchMode::Fuzzy,
+            matcher: default_matcher().into(),
+        }
+    }
+
+    pub fn mode(&self) -> MatchMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: MatchMode) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.rank_all();
+        }
+    }
+
+    /// The current filter, without surrounding whitespace
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn num_candidates(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn num_ranked(&self) -> usize {
+        self.ranked.len()
+    }
+
+    /// Replaces the candidates, ranking them with the current filter
+    pub fn set_candidates(&mut self, candidates: impl IntoIterator<Item = impl Into<String>>) {
+        self.set_candidates_with_bonus(
+            candidates
+                .into_iter()
+                .map(|candidate| (candidate.into(), 0)),
+        )
+    }
+
+    /// Like `set_candidates`, but each candidate comes with a bonus added to
+    /// its score when it matches. Used to rank e.g. recently opened files
+    /// higher.
+    pub fn set_candidates_with_bonus(
+        &mut self,
+        candidates: impl IntoIterator<Item = (impl Into<String>, i64)>,
+    ) {
+        self.candidates = Arc::new(
+            candidates
+                .into_iter()
+                .map(|(text, bonus)| Candidate {
+                    text: text.into(),
+                    bonus,
+                })
+                .collect(),
+        );
+        self.rank_all();
+    }
+
+    /// Appends candidates, ranking them with the current filter without
+    /// scoring the others again
+    pub fn extend_candidates_with_bonus(
+        &mut self,
+        candidates: impl IntoIterator<Item = (impl Into<String>, i64)>,
+    ) {
+        let first_index = self.candidates.len();
+        Arc::make_mut(&mut self.candidates).extend(candidates.into_iter().map(|(text, bonus)| {
+            Candidate {
+                text: text.into(),
+                bonus,
+            }
+        }));
+        let mut ranked = std::mem::take(&mut self.ranked);
+        ranked.extend(
+            (first_index..self.candidates.len())
+                .filter_map(|index| self.score_candidate(index).map(|score| (index, score))),
+        );
+        sort_ranked(&mut ranked);
+        self.ranked = ranked;
+    }
+
+    /// Ranks the candidates matching `filter`. When the filter extends the
+    /// previous one, e.g. as it's typed, only the candidates which matched
+    /// before are scored again, as no other candidate can match.
+    pub fn set_filter(&mut self, filter: &str) {
+        let filter = filter.trim();
+        if filter == self.filter {
+            return;
+        }
+        let narrowed = filter.starts_with(self.filter.as_str());
+        self.filter.clear();
+        self.filter.push_str(filter);
+        if !narrowed {
+            self.rank_all();
+            return;
+        }
+        let mut ranked: Vec<_> = std::mem::take(&mut self.ranked)
+            .into_iter()
+            .filter_map(|(index, _)| self.score_candidate(index).map(|score| (index, score)))
+            .collect();
+        sort_ranked(&mut ranked);
+        self.ranked = ranked;
+    }
+
+    /// The score of `entry` matched by `filter`, higher is better, or `None`
+    /// if it doesn't match
+    pub fn score(&self, entry: &str, filter: &str) -> Option<i64> {
+        match self.mode {
+            MatchMode::Fuzzy => self.matcher.fuzzy_match(entry, filter),
+            MatchMode::Substring => substring_match(entry, filter).map(|(start, filter_len)| {
+                // Prefer matches closer to the end, e.g. in a file's name rather than
+                // in the name of one of its parent directories
+                -((entry.chars().count() - start - filter_len) as i64)
+            }),
+        }
+    }
+
+    /// The char indices of `entry` matched by `filter`, used for highlighting
+    pub fn indices(&self, entry: &str, filter: &str) -> Vec<usize> {
+        let filter = filter.trim();
+        match self.mode {
+            MatchMode::Fuzzy => self
+                .matcher
+                .fuzzy_indices(entry, filter)
+                .map(|(_, indices)| indices)
+                .unwrap_or_default(),
+            MatchMode::Substring => substring_match(entry, filter)
+                .map(|(start, filter_len)| (start..start + filter_len).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The char indices of the candidate at `rank` matched by the current
+    /// filter
+    pub fn matched_indices(&self, rank: usize) -> Vec<usize> {
+        let (index, _) = self.ranked[rank];
+        self.indices(&self.candidates[index].text, &self.filter)
+    }
+
+    fn score_candidate(&self, index: usize) -> Option<i64> {
+        let candidate = &self.candidates[index];
+        self.score(&candidate.text, &self.filter)
+            .map(|score| score + candidate.bonus)
+    }
+
+    fn rank_all(&mut self) {
+        let mut ranked = std::mem::take(&mut self.ranked);
+        ranked.clear();
+        ranked.extend(
+            (0..self.candidates.len())
+                .filter_map(|index| self.score_candidate(index).map(|score| (index, score))),
+        );
+        sort_ranked(&mut ranked);
+        self.ranked = ranked;
+    }
+}
+
+impl std::ops::Index<usize> for Matcher {
+    type Output = usize;
+
+    fn index(&self, rank: usize) -> &Self::Output {
+        &self.ranked[rank].0
+    }
+}
+
+/// Splits `content` in consecutive runs of chars, flagging the runs which are
+/// part of a match. `indices` must be sorted, as returned by
+/// `Matcher::indices`.
+pub fn split_matched(content: &str, indices: &[usize]) -> Vec<(String, bool)> {
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    let mut indices = indices.iter().peekable();
+    for (char_index, character) in content.chars().enumerate() {
+        let matched = indices.next_if_eq(&&char_index).is_some();
+        match runs.last_mut() {
+            Some((run, run_matched)) if *run_matched == matched => run.push(character),
+            _ => runs.push((character.to_string(), matched)),
+        }
+    }
+    runs
+}
+
+/// Best score first, candidates with the same score in the order they were
+/// added. Narrowing and extending give the same order as ranking from scratch.
+fn sort_ranked(ranked: &mut [(usize, i64)]) {
+    ranked.sort_unstable_by_key(|&(index, score)| (-score, index));
+}
+
+/// Smart case substring search. Returns the char index where the match starts
+/// and the length of the filter in chars.
+fn substring_match(entry: &str, filter: &str) -> Option<(usize, usize)> {
+    let case_sensitive = filter.chars().any(char::is_uppercase);
+    let normalise = |character: char| {
+        if case_sensitive {
+            character
+        } else {
+            character.to_lowercase().next().unwrap_or(character)
+        }
+    };
+    let entry: Vec<char> = entry.chars().map(normalise).collect();
+    let filter: Vec<char> = filter.chars().map(normalise).collect();
+    if filter.is_empty() {
+        return Some((entry.len(), 0));
+    }
+    entry
+        .windows(filter.len())
+        .position(|window| window == filter.as_slice())
+        .map(|start| (start, filter.len()))
+}
+
+fn default_matcher() -> SkimMatcherV2 {
+    SkimMatcherV2::default().smart_case()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: &[&str] = &[
+        "src/main.rs",
+        "src/editor/mod.rs",
+        "src/editor/buffer.rs",
+        "src/components/prompt/matcher.rs",
+        "README.md",
+        "Cargo.toml",
+    ];
+
+    fn ranked(matcher: &Matcher) -> Vec<usize> {
+        (0..matcher.num_ranked())
+            .map(|rank| matcher[rank])
+            .collect()
+    }
+
+    fn matcher_with(mode: MatchMode, filter: &str) -> Matcher {
+        let mut matcher = Matcher::new();
+        matcher.set_mode(mode);
+        matcher.set_candidates(PATHS.iter().copied());
+        matcher.set_filter(filter);
+        matcher
+    }
+
+    #[test]
+    fn empty_filter_ranks_all_candidates_in_order() {
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            let matcher = matcher_with(mode, "  ");
+            assert_eq!(ranked(&matcher), (0..PATHS.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn fuzzy_matches_subsequences() {
+        let matcher = matcher_with(MatchMode::Fuzzy, "edmod");
+        assert_eq!(ranked(&matcher), vec![1]);
+        assert_eq!(matcher.matched_indices(0), vec![4, 5, 11, 12, 13]);
+    }
+
+    #[test]
+    fn substring_prefers_matches_closer_to_the_end() {
+        let mut matcher = Matcher::new();
+        matcher.set_mode(MatchMode::Substring);
+        matcher.set_candidates(["editor/src/lib.rs", "src/editor.rs"]);
+        matcher.set_filter("editor");
+        assert_eq!(ranked(&matcher), vec![1, 0]);
+        assert_eq!(matcher.matched_indices(0), vec![4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn smart_case() {
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            assert_eq!(ranked(&matcher_with(mode, "readme")), vec![4]);
+            assert_eq!(ranked(&matcher_with(mode, "README")), vec![4]);
+            assert!(ranked(&matcher_with(mode, "Readme")).is_empty());
+        }
+    }
+
+    #[test]
+    fn bonus_breaks_ties() {
+        let mut matcher = Matcher::new();
+        matcher.set_candidates_with_bonus([("a.rs", 0), ("b.rs", 10)]);
+        matcher.set_filter("rs");
+        assert_eq!(ranked(&matcher), vec![1, 0]);
+    }
+
+    #[test]
+    fn narrowing_and_widening_match_ranking_from_scratch() {
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            let mut matcher = matcher_with(mode, "");
+            for filter in ["s", "sr", "src", "src/e", "src/", "", "r", "rs"] {
+                matcher.set_filter(filter);
+                assert_eq!(
+                    ranked(&matcher),
+                    ranked(&matcher_with(mode, filter)),
+                    "{:?} {:?}",
+                    mode,
+                    filter
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extending_ranks_new_candidates_with_the_filter() {
+        let mut matcher = Matcher::new();
+        matcher.set_candidates(PATHS[..3].iter().copied());
+        matcher.set_filter("rs");
+        matcher.extend_candidates_with_bonus(PATHS[3..].iter().map(|path| (*path, 0)));
+        assert_eq!(matcher.num_candidates(), PATHS.len());
+        assert_eq!(
+            ranked(&matcher),
+            ranked(&matcher_with(MatchMode::Fuzzy, "rs"))
+        );
+    }
+
+    #[test]
+    fn changing_mode_ranks_again() {
+        let mut matcher = matcher_with(MatchMode::Fuzzy, "srs");
+        assert!(!ranked(&matcher).is_empty());
+        matcher.set_mode(MatchMode::Substring);
+        assert!(ranked(&matcher).is_empty());
+    }
+
+    #[test]
+    fn split_matched_runs() {
+        assert_eq!(
+            split_matched("mod.rs", &[0, 1, 4]),
+            vec![
+                ("mo".to_owned(), true),
+                ("d.".to_owned(), false),
+                ("r".to_owned(), true),
+                ("s".to_owned(), false),
+            ]
+        );
+        assert_eq!(split_matched("", &[]), vec![]);
+    }
+}
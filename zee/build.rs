@@ -14,6 +14,74 @@ pub struct EditorConfig {
     pub modes: Vec<ModeConfig>,
     #[serde(default)]
     pub trim_trailing_whitespace_on_save: bool,
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    #[serde(default)]
+    pub elevated_save_command: Option<Vec<String>>,
+    #[serde(default)]
+    pub osc52_clipboard: bool,
+    #[serde(default)]
+    pub indent_guides: bool,
+    #[serde(default)]
+    pub case_replace: bool,
+    #[serde(default)]
+    pub file_picker_max_depth: Option<usize>,
+    #[serde(default)]
+    pub startup: Startup,
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    #[serde(default)]
+    pub fill_column: usize,
+    #[serde(default)]
+    pub auto_fill_modes: Vec<String>,
+    #[serde(default)]
+    pub pending_keys_in_status_bar: bool,
+    #[serde(default)]
+    pub crash_recovery_files: bool,
+    #[serde(default)]
+    pub scrollbar: bool,
+    #[serde(default)]
+    pub kill_whole_line_to_indentation: bool,
+    #[serde(default)]
+    pub prompt_history_size: usize,
+    #[serde(default)]
+    pub compose_dead_keys: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename = "Plugin")]
+pub struct PluginConfig {
+    pub name: String,
+    pub command: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename = "Backup")]
+pub struct BackupConfig {
+    pub style: BackupStyle,
+    #[serde(default)]
+    pub directory: Option<std::path::PathBuf>,
+    #[serde(default)]
+    pub keep: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum BackupStyle {
+    Tilde,
+    Numbered,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Startup {
+    Dashboard,
+    Splash,
+    Scratch,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self::Dashboard
+    }
 }
 
 fn main() -> Result<()> {
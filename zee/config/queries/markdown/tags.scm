@@ -0,0 +1,3 @@
+;; Headings are listed as symbols in the outline
+(atx_heading (heading_content) @name) @definition.heading
+(setext_heading (heading_content) @name) @definition.heading
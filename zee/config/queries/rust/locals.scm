@@ -0,0 +1,35 @@
+;; Scopes and local variables, used to rename a variable everywhere it's
+;; referenced but nowhere else
+
+; -------
+; Scopes
+; -------
+
+[
+  (function_item)
+  (closure_expression)
+  (block)
+  (match_arm)
+  (for_expression)
+] @local.scope
+
+; -------
+; Definitions
+; -------
+
+(parameter pattern: (identifier) @local.definition)
+(closure_parameters (identifier) @local.definition)
+(let_declaration pattern: (identifier) @local.definition)
+(for_expression pattern: (identifier) @local.definition)
+(match_arm pattern: (match_pattern (identifier) @local.definition))
+
+; Bindings nested in patterns, e.g. `let (mut a, ref b) = ...`
+(tuple_pattern (identifier) @local.definition)
+(mut_pattern (identifier) @local.definition)
+(ref_pattern (identifier) @local.definition)
+
+; -------
+; References
+; -------
+
+(identifier) @local.reference
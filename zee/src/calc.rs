@@ -0,0 +1,383 @@
+//! Evaluates arithmetic and bitwise expressions typed in the prompt, e.g.
+//! `(0x1f << 2) | 1` or `2 ** 10 / 3`.
+//!
+//! Numbers are integers unless they have a fractional part or an exponent.
+//! Integers can be written in hexadecimal (`0x`), octal (`0o`) or binary
+//! (`0b`) and may contain underscores. Dividing integers gives an integer
+//! when the division is exact. Bitwise operators only take integers.
+//!
+//! From loosest to tightest, the operators are `|`, `^`, `&`, `<<` and `>>`,
+//! `+` and `-`, `*`, `/` and `%`, unary `-`, `+` and `~`, and `**`.
+
+use anyhow::{anyhow, bail};
+use std::fmt;
+
+use crate::error::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Value {
+    /// The value followed by its hexadecimal and binary forms, if it's a
+    /// non-negative integer
+    pub fn describe(&self) -> String {
+        match *self {
+            Value::Integer(value) if value >= 0 => {
+                format!("{} (0x{:x}, 0b{:b})", value, value, value)
+            }
+            value => value.to_string(),
+        }
+    }
+
+    fn as_float(self) -> f64 {
+        match self {
+            Value::Integer(value) => value as f64,
+            Value::Float(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(formatter, "{}", value),
+            Value::Float(value) => write!(formatter, "{}", value),
+        }
+    }
+}
+
+pub fn evaluate(expression: &str) -> Result<Value> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+    };
+    if parser.tokens.is_empty() {
+        bail!("Empty expression");
+    }
+    let value = parser.binary(0)?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(token) => Err(anyhow!("Unexpected `{}`", token)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Number(Value),
+    Operator(&'static str),
+    OpenParen,
+    CloseParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(formatter, "{}", value),
+            Token::Operator(operator) => write!(formatter, "{}", operator),
+            Token::OpenParen => write!(formatter, "("),
+            Token::CloseParen => write!(formatter, ")"),
+        }
+    }
+}
+
+/// Longer operators first, so `**` isn't read as two `*`
+const OPERATORS: &[&str] = &[
+    "**", "<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~",
+];
+
+/// Binary operators by how tightly they bind, loosest first
+const PRECEDENCE: &[&[&str]] = &[
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(character) = rest.chars().next() {
+        let length = if character.is_ascii_digit() || character == '.' {
+            let length = rest
+                .find(|character: char| {
+                    !(character.is_ascii_alphanumeric() || character == '.' || character == '_')
+                })
+                .unwrap_or(rest.len());
+            // Take the sign of an exponent, as in `1e-3`
+            let length = match (
+                rest[..length].ends_with(['e', 'E']),
+                rest[length..].chars().next(),
+            ) {
+                (true, Some('-' | '+')) if !(rest.starts_with("0x") || rest.starts_with("0X")) => {
+                    length
+                        + 1
+                        + rest[length + 1..]
+                            .find(|character: char| !character.is_ascii_digit())
+                            .unwrap_or(rest.len() - length - 1)
+                }
+                _ => length,
+            };
+            tokens.push(Token::Number(parse_number(&rest[..length])?));
+            length
+        } else if character == '(' {
+            tokens.push(Token::OpenParen);
+            1
+        } else if character == ')' {
+            tokens.push(Token::CloseParen);
+            1
+        } else if let Some(operator) = OPERATORS
+            .iter()
+            .find(|operator| rest.starts_with(*operator))
+        {
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        } else {
+            bail!("Unexpected `{}`", character);
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(literal: &str) -> Result<Value> {
+    let digits = literal.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => 10,
+    };
+    let parsed = if radix != 10 {
+        i64::from_str_radix(&digits[2..], radix)
+            .ok()
+            .map(Value::Integer)
+    } else if digits.contains(['.', 'e', 'E']) {
+        digits.parse().ok().map(Value::Float)
+    } else {
+        digits.parse().ok().map(Value::Integer)
+    };
+    parsed.ok_or_else(|| anyhow!("Invalid number `{}`", literal))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .ok_or_else(|| anyhow!("Unexpected end of expression"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Parses operators binding at least as tightly as `PRECEDENCE[level]`
+    fn binary(&mut self, level: usize) -> Result<Value> {
+        let operators = match PRECEDENCE.get(level) {
+            Some(operators) => *operators,
+            None => return self.unary(),
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Operator(operator)) = self.peek() {
+            if !operators.contains(&operator) {
+                break;
+            }
+            self.position += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = apply(operator, lhs, rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some(Token::Operator(operator @ ("-" | "+" | "~"))) => {
+                self.position += 1;
+                let value = self.unary()?;
+                match (operator, value) {
+                    ("-", Value::Integer(value)) => {
+                        value.checked_neg().map(Value::Integer).ok_or_else(overflow)
+                    }
+                    ("-", Value::Float(value)) => Ok(Value::Float(-value)),
+                    ("~", Value::Integer(value)) => Ok(Value::Integer(!value)),
+                    ("~", Value::Float(_)) => bail!("`~` needs an integer"),
+                    _ => Ok(value),
+                }
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// `**` is right associative and binds tighter than a unary operator on
+    /// its left, so `-2 ** 2` is `-4`
+    fn power(&mut self) -> Result<Value> {
+        let base = self.atom()?;
+        if self.peek() == Some(Token::Operator("**")) {
+            self.position += 1;
+            let exponent = self.unary()?;
+            return apply("**", base, exponent);
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Value> {
+        match self.next()? {
+            Token::Number(value) => Ok(value),
+            Token::OpenParen => {
+                let value = self.binary(0)?;
+                match self.next()? {
+                    Token::CloseParen => Ok(value),
+                    token => Err(anyhow!("Expected `)`, found `{}`", token)),
+                }
+            }
+            token => Err(anyhow!("Unexpected `{}`", token)),
+        }
+    }
+}
+
+fn apply(operator: &str, lhs: Value, rhs: Value) -> Result<Value> {
+    match (lhs, rhs) {
+        (Value::Integer(lhs), Value::Integer(rhs)) => apply_integers(operator, lhs, rhs),
+        _ if matches!(operator, "&" | "|" | "^" | "<<" | ">>") => {
+            bail!("`{}` needs integers", operator)
+        }
+        _ => {
+            let (lhs, rhs) = (lhs.as_float(), rhs.as_float());
+            Ok(Value::Float(match operator {
+                "+" => lhs + rhs,
+                "-" => lhs - rhs,
+                "*" => lhs * rhs,
+                "/" => lhs / rhs,
+                "%" => lhs % rhs,
+                "**" => lhs.powf(rhs),
+                _ => unreachable!("unknown operator `{}`", operator),
+            }))
+        }
+    }
+}
+
+fn apply_integers(operator: &str, lhs: i64, rhs: i64) -> Result<Value> {
+    if matches!(operator, "/" | "%") && rhs == 0 {
+        bail!("Division by zero");
+    }
+    let result = match operator {
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "*" => lhs.checked_mul(rhs),
+        // `i64::MIN / -1` overflows, as does its remainder
+        "/" => match lhs.checked_rem(rhs) {
+            Some(0) => lhs.checked_div(rhs),
+            Some(_) => return Ok(Value::Float(lhs as f64 / rhs as f64)),
+            None => None,
+        },
+        "%" => lhs.checked_rem(rhs),
+        "**" if rhs < 0 => return Ok(Value::Float((lhs as f64).powf(rhs as f64))),
+        "**" => u32::try_from(rhs)
+            .ok()
+            .and_then(|exponent| lhs.checked_pow(exponent)),
+        "&" => Some(lhs & rhs),
+        "|" => Some(lhs | rhs),
+        "^" => Some(lhs ^ rhs),
+        // `checked_shl` only checks the shift amount, not the bits shifted
+        // out, so shift back to see if any were lost
+        "<<" => u32::try_from(rhs)
+            .ok()
+            .and_then(|shift| lhs.checked_shl(shift))
+            .filter(|shifted| shifted >> rhs == lhs),
+        ">>" => u32::try_from(rhs)
+            .ok()
+            .and_then(|shift| lhs.checked_shr(shift)),
+        _ => unreachable!("unknown operator `{}`", operator),
+    };
+    result.map(Value::Integer).ok_or_else(overflow)
+}
+
+fn overflow() -> anyhow::Error {
+    anyhow!("Integer overflow")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(expression: &str) -> i64 {
+        match evaluate(expression).unwrap() {
+            Value::Integer(value) => value,
+            value => panic!("`{}` is not an integer: {}", expression, value),
+        }
+    }
+
+    fn float(expression: &str) -> f64 {
+        match evaluate(expression).unwrap() {
+            Value::Float(value) => value,
+            value => panic!("`{}` is not a float: {}", expression, value),
+        }
+    }
+
+    fn error(expression: &str) -> String {
+        evaluate(expression).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(integer("(0x1f << 2) | 1"), 125);
+        assert_eq!(integer("1 + 2 * 3"), 7);
+        assert_eq!(integer("2 ** 3 ** 2"), 512);
+        assert_eq!(integer("-2 ** 2"), -4);
+        assert_eq!(integer("~0"), -1);
+    }
+
+    #[test]
+    fn division_is_exact_or_float() {
+        assert_eq!(integer("6 / 3"), 2);
+        assert_eq!(float("7 / 2"), 3.5);
+        assert_eq!(error("1 / 0"), "Division by zero");
+        assert_eq!(error("1 % 0"), "Division by zero");
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(error("~0x7fffffffffffffff / -1"), "Integer overflow");
+        assert_eq!(error("~0x7fffffffffffffff % -1"), "Integer overflow");
+        assert_eq!(error("0x7fffffffffffffff + 1"), "Integer overflow");
+        assert_eq!(error("2 ** 64"), "Integer overflow");
+        assert_eq!(error("-(~0x7fffffffffffffff)"), "Integer overflow");
+    }
+
+    #[test]
+    fn shifting_out_bits_is_an_overflow() {
+        assert_eq!(integer("1 << 62"), 1 << 62);
+        assert_eq!(integer("-1 << 63"), i64::MIN);
+        assert_eq!(error("3 << 63"), "Integer overflow");
+        assert_eq!(error("1 << 63"), "Integer overflow");
+        assert_eq!(error("1 << 64"), "Integer overflow");
+        assert_eq!(integer("-8 >> 1"), -4);
+    }
+
+    #[test]
+    fn tokenize_radixes_and_exponents() {
+        assert_eq!(integer("0xff"), 255);
+        assert_eq!(integer("0XFF"), 255);
+        assert_eq!(integer("0o17"), 15);
+        assert_eq!(integer("0b1010_1010"), 170);
+        assert_eq!(integer("1_000"), 1000);
+        assert_eq!(float("1e-3"), 0.001);
+        assert_eq!(float("2.5E+2"), 250.0);
+        // The `e` is a hexadecimal digit, not an exponent
+        assert_eq!(integer("0x1e-3"), 27);
+        assert_eq!(integer("0X1e-3"), 27);
+        assert_eq!(error("0x"), "Invalid number `0x`");
+        assert_eq!(error("1 $ 2"), "Unexpected `$`");
+    }
+}
@@ -0,0 +1,97 @@
+//! Emoji and other symbols which are awkward to type, listed with their
+//! Unicode names and the block they are in, so they can be picked by name.
+
+use once_cell::sync::Lazy;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharacterSet {
+    Emoji,
+    /// Emoji and every other block, e.g. arrows, maths and box drawing
+    All,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharacterEntry {
+    pub character: char,
+    pub name: String,
+    pub block: &'static str,
+}
+
+impl CharacterEntry {
+    /// How the entry is shown and matched against in a picker
+    pub fn label(&self) -> String {
+        format!("{}  {}  ({})", self.character, self.name, self.block)
+    }
+}
+
+/// The characters in a set, in code point order
+pub fn characters(set: CharacterSet) -> &'static [CharacterEntry] {
+    match set {
+        CharacterSet::Emoji => &EMOJI,
+        CharacterSet::All => &ALL,
+    }
+}
+
+fn named_characters(blocks: &[(&'static str, RangeInclusive<u32>, bool)]) -> Vec<CharacterEntry> {
+    blocks
+        .iter()
+        .flat_map(|(block, code_points, _)| {
+            code_points.clone().filter_map(move |code_point| {
+                let character = char::from_u32(code_point)?;
+                Some(CharacterEntry {
+                    character,
+                    name: unicode_names2::name(character)?.to_string(),
+                    block,
+                })
+            })
+        })
+        .collect()
+}
+
+static EMOJI: Lazy<Vec<CharacterEntry>> = Lazy::new(|| {
+    let emoji_blocks: Vec<_> = BLOCKS
+        .iter()
+        .filter(|(_, _, is_emoji)| *is_emoji)
+        .cloned()
+        .collect();
+    named_characters(&emoji_blocks)
+});
+
+static ALL: Lazy<Vec<CharacterEntry>> = Lazy::new(|| named_characters(BLOCKS));
+
+/// The Unicode blocks offered, and whether they are mostly emoji
+const BLOCKS: &[(&str, RangeInclusive<u32>, bool)] = &[
+    ("Greek and Coptic", 0x0370..=0x03FF, false),
+    ("General Punctuation", 0x2010..=0x205E, false),
+    ("Superscripts and Subscripts", 0x2070..=0x209C, false),
+    ("Currency Symbols", 0x20A0..=0x20C0, false),
+    ("Letterlike Symbols", 0x2100..=0x214F, false),
+    ("Number Forms", 0x2150..=0x218B, false),
+    ("Arrows", 0x2190..=0x21FF, false),
+    ("Mathematical Operators", 0x2200..=0x22FF, false),
+    ("Miscellaneous Technical", 0x2300..=0x23FF, false),
+    ("Box Drawing", 0x2500..=0x257F, false),
+    ("Block Elements", 0x2580..=0x259F, false),
+    ("Geometric Shapes", 0x25A0..=0x25FF, false),
+    ("Miscellaneous Symbols", 0x2600..=0x26FF, true),
+    ("Dingbats", 0x2700..=0x27BF, true),
+    ("Supplemental Arrows-A", 0x27F0..=0x27FF, false),
+    (
+        "Miscellaneous Symbols and Pictographs",
+        0x1F300..=0x1F5FF,
+        true,
+    ),
+    ("Emoticons", 0x1F600..=0x1F64F, true),
+    ("Transport and Map Symbols", 0x1F680..=0x1F6FF, true),
+    (
+        "Supplemental Symbols and Pictographs",
+        0x1F900..=0x1F9FF,
+        true,
+    ),
+    (
+        "Symbols and Pictographs Extended-A",
+        0x1FA70..=0x1FAFF,
+        true,
+    ),
+];
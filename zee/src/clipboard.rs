@@ -7,17 +7,54 @@ pub trait Clipboard {
     fn set_contents(&self, contents: String) -> Result<()>;
 }
 
-pub fn create() -> Result<Arc<dyn Clipboard>> {
+/// Creates the clipboard, picking the first one which works where zee is
+/// running: the native clipboard if zee was built with the `system-clipboard`
+/// feature, then clipboard programs like `wl-copy`, `xclip` or `pbcopy`, then
+/// OSC 52 escape sequences when running over ssh. Otherwise, text is only
+/// copied within zee.
+///
+/// With `osc52`, OSC 52 escape sequences are always used, as they work over
+/// ssh without native clipboard libraries.
+pub fn create(osc52: bool) -> Arc<dyn Clipboard> {
+    if osc52 {
+        log::info!("Using OSC 52 escape sequences for the clipboard");
+        return Arc::new(osc52::Osc52Clipboard::new());
+    }
+    if let Some(clipboard) = native() {
+        log::info!("Using the native clipboard");
+        return clipboard;
+    }
+    if let Some(clipboard) = command::CommandClipboard::detect() {
+        log::info!("Using `{}` for the clipboard", clipboard.copy_program());
+        return Arc::new(clipboard);
+    }
+    if osc52::over_ssh() {
+        log::info!("Using OSC 52 escape sequences for the clipboard over ssh");
+        return Arc::new(osc52::Osc52Clipboard::new());
+    }
+    log::info!("No system clipboard found, text is only copied within zee");
+    Arc::new(local::LocalClipboard::new())
+}
+
+fn native() -> Option<Arc<dyn Clipboard>> {
     cfg_if::cfg_if! {
-        if #[cfg(feature = "system-clipboard")] {
+        if #[cfg(all(
+            feature = "system-clipboard",
+            any(windows, target_os = "macos", target_os = "linux")
+        ))] {
             system::create()
+                .map_err(|error| log::warn!("Could not open the native clipboard: {}", error))
+                .ok()
         } else {
-            local::create()
+            None
         }
     }
 }
 
-#[cfg(feature = "system-clipboard")]
+#[cfg(all(
+    feature = "system-clipboard",
+    any(windows, target_os = "macos", target_os = "linux")
+))]
 mod system {
     use crossclip::Clipboard;
     use parking_lot::RwLock;
@@ -53,24 +90,18 @@ mod system {
     }
 }
 
-#[cfg(not(feature = "system-clipboard"))]
 mod local {
     use parking_lot::RwLock;
-    use std::sync::Arc;
 
     use super::Clipboard;
     use crate::error::Result;
 
-    pub(crate) fn create() -> Result<Arc<dyn Clipboard>> {
-        Ok(Arc::new(LocalClipboard::new()))
-    }
-
-    struct LocalClipboard {
+    pub(super) struct LocalClipboard {
         contents: RwLock<String>,
     }
 
     impl LocalClipboard {
-        fn new() -> Self {
+        pub(super) fn new() -> Self {
             Self {
                 contents: RwLock::new(String::new()),
             }
@@ -88,3 +119,187 @@ mod local {
         }
     }
 }
+
+mod command {
+    use std::{
+        env,
+        io::Write,
+        path::Path,
+        process::{Command, Stdio},
+    };
+
+    use super::Clipboard;
+    use crate::error::{Context, Result};
+
+    /// Copies and pastes by running clipboard programs, e.g. `xclip`
+    #[derive(Clone, Copy)]
+    pub(super) struct CommandClipboard {
+        copy: &'static [&'static str],
+        paste: &'static [&'static str],
+    }
+
+    /// The programs to try, with the environment variable which must be set
+    /// for them to work, if any
+    const PROGRAMS: &[(Option<&str>, CommandClipboard)] = &[
+        (
+            None,
+            CommandClipboard {
+                copy: &["pbcopy"],
+                paste: &["pbpaste"],
+            },
+        ),
+        (
+            Some("WAYLAND_DISPLAY"),
+            CommandClipboard {
+                copy: &["wl-copy"],
+                paste: &["wl-paste", "--no-newline"],
+            },
+        ),
+        (
+            Some("DISPLAY"),
+            CommandClipboard {
+                copy: &["xclip", "-selection", "clipboard", "-in"],
+                paste: &["xclip", "-selection", "clipboard", "-out"],
+            },
+        ),
+        (
+            Some("DISPLAY"),
+            CommandClipboard {
+                copy: &["xsel", "--clipboard", "--input"],
+                paste: &["xsel", "--clipboard", "--output"],
+            },
+        ),
+    ];
+
+    impl CommandClipboard {
+        /// The first clipboard programs which are installed and usable in the
+        /// current session
+        pub(super) fn detect() -> Option<Self> {
+            PROGRAMS
+                .iter()
+                .find(|(variable, clipboard)| {
+                    variable.map_or(true, |variable| env::var_os(variable).is_some())
+                        && on_path(clipboard.copy[0])
+                        && on_path(clipboard.paste[0])
+                })
+                .map(|(_, clipboard)| *clipboard)
+        }
+
+        pub(super) fn copy_program(&self) -> &'static str {
+            self.copy[0]
+        }
+    }
+
+    impl Clipboard for CommandClipboard {
+        fn get_contents(&self) -> Result<String> {
+            let output = Command::new(self.paste[0])
+                .args(&self.paste[1..])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+                .with_context(|| format!("Could not run `{}`", self.paste[0]))?;
+            if !output.status.success() {
+                anyhow::bail!("`{}` failed ({})", self.paste[0], output.status);
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+
+        fn set_contents(&self, contents: String) -> Result<()> {
+            // The programs keep running in the background to serve the
+            // clipboard, they mustn't write to the terminal zee is drawing to
+            let mut child = Command::new(self.copy[0])
+                .args(&self.copy[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("Could not run `{}`", self.copy[0]))?;
+            child
+                .stdin
+                .take()
+                .expect("stdin is piped")
+                .write_all(contents.as_bytes())?;
+            let status = child.wait()?;
+            if !status.success() {
+                anyhow::bail!("`{}` failed ({})", self.copy[0], status);
+            }
+            Ok(())
+        }
+    }
+
+    fn on_path(program: &str) -> bool {
+        env::var_os("PATH")
+            .map(|paths| {
+                env::split_paths(&paths).any(|directory| is_executable(&directory.join(program)))
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(unix)]
+    fn is_executable(path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(unix))]
+    fn is_executable(path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+mod osc52 {
+    use parking_lot::RwLock;
+    use std::{
+        env,
+        io::{self, Write},
+    };
+
+    use super::Clipboard;
+    use crate::error::Result;
+
+    /// Whether zee is running in a session over ssh, where the clipboard of
+    /// the terminal is on another machine
+    pub(super) fn over_ssh() -> bool {
+        env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+    }
+
+    /// Sets the terminal's clipboard with an OSC 52 escape sequence. Reading
+    /// the terminal's clipboard is rarely allowed, so pasting uses the last
+    /// text copied in zee.
+    pub(super) struct Osc52Clipboard {
+        contents: RwLock<String>,
+        inside_tmux: bool,
+    }
+
+    impl Osc52Clipboard {
+        pub(super) fn new() -> Self {
+            Self {
+                contents: RwLock::new(String::new()),
+                inside_tmux: env::var_os("TMUX").is_some(),
+            }
+        }
+    }
+
+    impl Clipboard for Osc52Clipboard {
+        fn get_contents(&self) -> Result<String> {
+            Ok(self.contents.read().clone())
+        }
+
+        fn set_contents(&self, contents: String) -> Result<()> {
+            let mut sequence = format!("\x1b]52;c;{}\x07", base64::encode(&contents));
+            if self.inside_tmux {
+                // Pass the sequence through to the outer terminal, doubling
+                // the escape characters inside it
+                sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+            }
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            stdout.write_all(sequence.as_bytes())?;
+            stdout.flush()?;
+            *self.contents.write() = contents;
+            Ok(())
+        }
+    }
+}
@@ -1,37 +1,56 @@
 pub mod line_info;
+pub mod scrollbar;
 pub mod status_bar;
 pub mod textarea;
 
-use std::{borrow::Cow, iter, path::PathBuf};
+use ropey::Rope;
+use std::{borrow::Cow, cell::RefCell, iter, ops::Range, path::PathBuf, rc::Rc};
 use zi::{
     components::text::{Text, TextAlign, TextProperties},
     prelude::*,
 };
 
-use zee_edit::{tree::EditTree, Direction};
-use zee_grammar::Mode;
+use zee_edit::{tree::EditTree, Direction, LineIndex};
+use zee_grammar::{config::IndentationConfig, Mode};
 
 use self::{
     line_info::{LineInfo, Properties as LineInfoProperties},
-    status_bar::{Properties as StatusBarProperties, StatusBar, Theme as StatusBarTheme},
+    scrollbar::{Mark, Properties as ScrollbarProperties, Scrollbar, Theme as ScrollbarTheme},
+    status_bar::{
+        Properties as StatusBarProperties, SelectionSize, StatusBar, Theme as StatusBarTheme,
+    },
     textarea::{Properties as TextAreaProperties, TextArea},
 };
-use super::edit_tree_viewer::{
-    EditTreeViewer, Properties as EditTreeViewerProperties, Theme as EditTreeViewerTheme,
+use super::{
+    completion::{CompletionPopup, Theme as CompletionTheme},
+    edit_tree_viewer::{
+        EditTreeViewer, Properties as EditTreeViewerProperties, Theme as EditTreeViewerTheme,
+    },
 };
 use crate::{
     editor::{
-        buffer::{BufferCursor, CursorMessage, ModifiedStatus, RepositoryRc},
-        ContextHandle,
+        annotations::{Annotation, Severity},
+        buffer::{BufferCursor, CursorMessage, LoadingProgress, ModifiedStatus, RepositoryRc},
+        folds::{first_line_for_rows, visual_rows},
+        highlights::Highlight,
+        search::Pattern,
+        ContextHandle, Message as EditorMessage, RecordedBindings,
+    },
+    syntax::{
+        highlight::{HighlightCache, LineScope, Theme as SyntaxTheme},
+        parse::ParseTree,
+        symbols::enclosing_definitions,
     },
-    syntax::{highlight::Theme as SyntaxTheme, parse::ParseTree},
+    utils::LineMetricsCache,
     versioned::WeakHandle,
 };
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Theme {
     pub border: Style,
+    pub completion: CompletionTheme,
     pub edit_tree_viewer: EditTreeViewerTheme,
+    pub scrollbar: ScrollbarTheme,
     pub status_bar: StatusBarTheme,
     pub syntax: SyntaxTheme,
 }
@@ -42,12 +61,40 @@ pub struct Properties {
     pub focused: bool,
     pub frame_id: usize,
     pub mode: &'static Mode,
+    pub indentation: IndentationConfig,
     pub repo: Option<RepositoryRc>,
     pub content: WeakHandle<EditTree>,
     pub file_path: Option<PathBuf>,
+    pub name: Option<String>,
+    /// The number of the indirect view of the buffer shown, if it's one
+    pub indirect_view: Option<usize>,
+    pub read_only: bool,
+    /// Whether the buffer lists the lines found by `occur`, `RET` goes to the
+    /// one on the cursor's line
+    pub occur_results: bool,
     pub cursor: BufferCursor,
     pub parse_tree: Option<ParseTree>,
+    /// Shared by the windows showing the buffer, it doesn't affect what's drawn
+    pub highlight_cache: Rc<RefCell<HighlightCache>>,
+    pub line_metrics: Rc<RefCell<LineMetricsCache>>,
+    pub line_scopes: Rc<[LineScope]>,
+    pub annotations: Rc<[Annotation]>,
+    pub folded_lines: Rc<[Range<LineIndex>]>,
+    pub narrowed_lines: Option<Range<LineIndex>>,
+    /// The completion popup open in the window, if any
+    pub completion: Option<CompletionPopup>,
+    /// A dead key waiting to be composed with the next character
+    pub dead_key: Option<char>,
+    /// The last search, its matches are marked on the scrollbar
+    pub search_pattern: Option<Rc<Pattern>>,
+    /// The patterns highlighted with `highlight-regexp`
+    pub highlights: Rc<[Rc<Highlight>]>,
+    /// The unfinished key sequence typed in the window, if it's shown in the
+    /// status bar
+    pub pending_keys: Option<String>,
     pub modified_status: ModifiedStatus,
+    /// How much of the file has been read, if it's still loading
+    pub loading: Option<LoadingProgress>,
 }
 
 impl PartialEq for Properties {
@@ -56,13 +103,32 @@ impl PartialEq for Properties {
             && self.content.version() == other.content.version()
             && self.parse_tree.as_ref().map(|tree| tree.version)
                 == other.parse_tree.as_ref().map(|tree| tree.version)
+            && Rc::ptr_eq(&self.line_scopes, &other.line_scopes)
+            && Rc::ptr_eq(&self.annotations, &other.annotations)
+            && self.folded_lines == other.folded_lines
+            && self.narrowed_lines == other.narrowed_lines
+            && self.completion == other.completion
+            && self.dead_key == other.dead_key
+            && Rc::ptr_eq(&self.highlights, &other.highlights)
+            && match (&self.search_pattern, &other.search_pattern) {
+                (Some(pattern), Some(other_pattern)) => Rc::ptr_eq(pattern, other_pattern),
+                (None, None) => true,
+                _ => false,
+            }
+            && self.pending_keys == other.pending_keys
             && self.modified_status == other.modified_status
+            && self.loading == other.loading
             && self.focused == other.focused
             && self.frame_id == other.frame_id
             && *self.theme == *other.theme
             && self.mode == other.mode
+            && self.indentation == other.indentation
             && self.repo == other.repo
             && self.file_path == other.file_path
+            && self.name == other.name
+            && self.indirect_view == other.indirect_view
+            && self.read_only == other.read_only
+            && self.occur_results == other.occur_results
     }
 }
 
@@ -78,6 +144,9 @@ pub struct Buffer {
     frame: Rect,
     line_offset: usize,
     viewing_edit_tree: bool,
+    /// The lines matching the search pattern, with the content version and
+    /// pattern they were found for
+    search_lines: Option<(usize, Rc<Pattern>, Rc<[LineIndex]>)>,
 }
 
 impl Buffer {
@@ -85,11 +154,14 @@ impl Buffer {
         let content = self.properties.content.upgrade();
         let current_line = content.char_to_line(self.properties.cursor.inner().range().start);
         let num_lines = self.frame.size.height.saturating_sub(1);
-        if current_line < self.line_offset {
-            self.line_offset = current_line;
+        let folded_lines = &self.properties.folded_lines;
+        let visible_lines = self.visible_lines(&content);
+        if current_line < self.line_offset || self.line_offset < visible_lines.start {
+            self.line_offset = current_line.max(visible_lines.start);
             ShouldRender::Yes
-        } else if current_line - self.line_offset > num_lines.saturating_sub(1) {
-            self.line_offset = current_line + 1 - num_lines;
+        } else if visual_rows(folded_lines, self.line_offset..current_line + 1) > num_lines {
+            self.line_offset =
+                first_line_for_rows(folded_lines, current_line, num_lines).max(visible_lines.start);
             ShouldRender::Yes
         } else {
             ShouldRender::No
@@ -99,19 +171,158 @@ impl Buffer {
     fn center_visual_cursor(&mut self) {
         let content = self.properties.content.upgrade();
         let line_index = content.char_to_line(self.properties.cursor.inner().range().start);
-        if line_index >= self.frame.size.height / 2
-            && self.line_offset != line_index - self.frame.size.height / 2
-        {
-            self.line_offset = line_index - self.frame.size.height / 2;
+        let first_visible_line = self.visible_lines(&content).start;
+        let centred_line_offset = first_line_for_rows(
+            &self.properties.folded_lines,
+            line_index,
+            self.frame.size.height / 2 + 1,
+        )
+        .max(first_visible_line);
+        if line_index >= self.frame.size.height / 2 && self.line_offset != centred_line_offset {
+            self.line_offset = centred_line_offset;
         } else if self.line_offset != line_index {
             self.line_offset = line_index;
         } else {
-            self.line_offset = 0;
+            self.line_offset = first_visible_line;
+        }
+    }
+
+    /// Finds the lines matching the search pattern again if the content or
+    /// the pattern changed since they were last found
+    fn update_search_lines(&mut self) {
+        if !self.properties.context.config.scrollbar {
+            self.search_lines = None;
+            return;
+        }
+        let pattern = match self.properties.search_pattern {
+            Some(ref pattern) => pattern,
+            None => {
+                self.search_lines = None;
+                return;
+            }
+        };
+        let version = self.properties.content.version();
+        if let Some((search_version, ref search_pattern, _)) = self.search_lines {
+            if search_version == version && Rc::ptr_eq(search_pattern, pattern) {
+                return;
+            }
         }
+        let content = self.properties.content.upgrade();
+        let lines = if content.len_bytes() <= MAX_SEARCH_MARKS_BYTES {
+            pattern.matching_lines(content.staged()).into()
+        } else {
+            Rc::from([])
+        };
+        self.search_lines = Some((version, pattern.clone(), lines));
+    }
+
+    /// The lines to mark on the scrollbar: search matches first, then
+    /// annotations from the least to the most severe so the worst one on a
+    /// cell is drawn last
+    fn scrollbar_marks(&self, visible_lines: &Range<LineIndex>, text: &Rope) -> Rc<[Mark]> {
+        let folded_lines = &self.properties.folded_lines;
+        let syntax = &self.properties.theme.syntax;
+        let mark = |line: LineIndex, colour| Mark {
+            row: visual_rows(folded_lines, visible_lines.start..line),
+            colour,
+        };
+
+        let search_lines = self
+            .search_lines
+            .as_ref()
+            .map_or(&[][..], |(_, _, lines)| &lines[..]);
+        let mut marks: Vec<_> = search_lines
+            .iter()
+            .filter(|line| visible_lines.contains(line))
+            .map(|&line| mark(line, self.properties.theme.scrollbar.search_match))
+            .collect();
+
+        let mut annotations: Vec<_> = self
+            .properties
+            .annotations
+            .iter()
+            .filter(|annotation| annotation.range.start <= text.len_chars())
+            .map(|annotation| {
+                (
+                    annotation.severity,
+                    text.char_to_line(annotation.range.start),
+                )
+            })
+            .filter(|(_, line)| visible_lines.contains(line))
+            .collect();
+        annotations.sort_by_key(|&(severity, _)| severity);
+        marks.extend(annotations.into_iter().map(|(severity, line)| {
+            let style = match severity {
+                Severity::Error => syntax.annotation_error,
+                Severity::Warning => syntax.annotation_warning,
+                Severity::Info => syntax.annotation_info,
+                Severity::Hint => syntax.annotation_hint,
+            };
+            mark(line, style.foreground)
+        }));
+        marks.into()
+    }
+
+    /// The lines which can be shown, all of them unless the buffer is narrowed
+    fn visible_lines(&self, text: &Rope) -> Range<LineIndex> {
+        self.properties
+            .narrowed_lines
+            .clone()
+            .unwrap_or(0..text.len_lines())
+    }
+
+    /// The names of the definitions around the cursor, found with the mode's
+    /// `tags` query
+    fn breadcrumbs(&self, text: &Rope) -> Vec<String> {
+        let tags = self
+            .properties
+            .mode
+            .grammar()
+            .and_then(|grammar| grammar.ok())
+            .and_then(|grammar| grammar.tags.as_ref());
+        match (tags, self.properties.parse_tree.as_ref()) {
+            (Some(tags), Some(parse_tree)) => enclosing_definitions(
+                tags,
+                &parse_tree.tree,
+                text,
+                self.properties.cursor.inner().range().start,
+            )
+            .into_iter()
+            .map(|definition| definition.name)
+            .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// How much is selected, if there's a selection. A selection ending at the
+    /// start of a line doesn't count that line.
+    fn selection_size(&self, text: &Rope) -> Option<SelectionSize> {
+        let cursor = self.properties.cursor.inner();
+        let selection = cursor.selection();
+        if selection == cursor.range() {
+            return None;
+        }
+        let start_line = text.char_to_line(selection.start);
+        let end = selection.end.min(text.len_chars());
+        let end_line = text.char_to_line(end);
+        let lines = if end_line > start_line && text.line_to_char(end_line) == end {
+            end_line - start_line
+        } else {
+            end_line - start_line + 1
+        };
+        Some(SelectionSize {
+            chars: selection.len(),
+            lines,
+        })
     }
 
     fn move_up(&self) {
-        if self.viewing_edit_tree {
+        if self.properties.completion.is_some() {
+            self.properties
+                .context
+                .link
+                .send(EditorMessage::SelectCompletion(-1));
+        } else if self.viewing_edit_tree {
             self.properties.cursor.undo();
         } else {
             self.properties.cursor.move_up();
@@ -119,7 +330,12 @@ impl Buffer {
     }
 
     fn move_down(&self) {
-        if self.viewing_edit_tree {
+        if self.properties.completion.is_some() {
+            self.properties
+                .context
+                .link
+                .send(EditorMessage::SelectCompletion(1));
+        } else if self.viewing_edit_tree {
             self.properties.cursor.redo();
         } else {
             self.properties.cursor.move_down();
@@ -158,6 +374,10 @@ impl Buffer {
         self.properties.cursor.move_start_of_line()
     }
 
+    fn move_to_indentation(&self) {
+        self.properties.cursor.move_to_indentation()
+    }
+
     fn move_end_of_line(&self) {
         self.properties.cursor.move_end_of_line()
     }
@@ -178,12 +398,25 @@ impl Buffer {
         self.properties.cursor.delete_backward()
     }
 
-    fn delete_line(&self) {
-        self.properties.cursor.delete_line()
+    fn kill_whole_line(&self) {
+        self.properties.cursor.kill_whole_line()
+    }
+
+    fn kill_to_end_of_line(&self) {
+        self.properties.cursor.kill_to_end_of_line()
     }
 
     fn insert_new_line(&self) {
-        self.properties.cursor.insert_new_line()
+        if self.properties.completion.is_some() {
+            self.properties
+                .context
+                .link
+                .send(EditorMessage::AcceptCompletion);
+        } else if self.properties.occur_results {
+            self.properties.context.link.send(EditorMessage::OccurGoto);
+        } else {
+            self.properties.cursor.insert_new_line()
+        }
     }
 }
 
@@ -195,9 +428,11 @@ impl Component for Buffer {
         let mut buffer = Self {
             line_offset: 0,
             viewing_edit_tree: false,
+            search_lines: None,
             properties,
             frame,
         };
+        buffer.update_search_lines();
         buffer.ensure_cursor_in_view();
         buffer
     }
@@ -205,6 +440,7 @@ impl Component for Buffer {
     fn change(&mut self, properties: Self::Properties) -> ShouldRender {
         let changed_properties = self.properties != properties;
         self.properties = properties;
+        self.update_search_lines();
         self.ensure_cursor_in_view() | changed_properties.into()
     }
 
@@ -242,41 +478,95 @@ impl Component for Buffer {
             text: content.staged().clone(),
             cursor: self.properties.cursor.inner().clone(),
             mode: self.properties.mode,
+            indentation: self.properties.indentation,
             line_offset: self.line_offset,
             parse_tree: self.properties.parse_tree.clone(),
+            highlight_cache: self.properties.highlight_cache.clone(),
+            line_metrics: self.properties.line_metrics.clone(),
+            line_scopes: self.properties.line_scopes.clone(),
+            annotations: self.properties.annotations.clone(),
+            folded_lines: self.properties.folded_lines.clone(),
+            completion: self.properties.completion.clone(),
+            dead_key: self.properties.dead_key,
+            highlights: self.properties.highlights.clone(),
+            indent_guides: self.properties.context.config.indent_guides,
+            visible_lines: self.visible_lines(&content),
         });
 
         // Vertical info bar which shows line specific diagnostics
+        let num_lines = match self.properties.narrowed_lines {
+            Some(ref narrowed_lines) => narrowed_lines.end,
+            None => {
+                content.len_lines()
+                    - if content.line(content.len_lines() - 1).len_chars() > 0 {
+                        0
+                    } else {
+                        1
+                    }
+            }
+        };
         let line_info = LineInfo::with(LineInfoProperties {
             style: self.properties.theme.border,
             line_offset: self.line_offset,
-            num_lines: content.len_lines()
-                - if content.line(content.len_lines() - 1).len_chars() > 0 {
-                    0
-                } else {
-                    1
-                },
+            num_lines: self.line_offset
+                + visual_rows(
+                    &self.properties.folded_lines,
+                    self.line_offset..num_lines.max(self.line_offset),
+                ),
         });
 
-        // The "status bar" which shows information about the file etc.
+        // The "status bar" which shows information about the file etc. Line
+        // numbers are relative to the start of the narrowed region, if any
+        let visible_lines = self.visible_lines(&content);
         let status_bar = StatusBar::with(StatusBarProperties {
-            current_line_index: content.char_to_line(self.properties.cursor.inner().range().start),
+            current_line_index: content.char_to_line(self.properties.cursor.inner().range().start)
+                - visible_lines.start,
             column_offset: self
                 .properties
                 .cursor
                 .inner()
-                .column_offset(self.properties.mode.indentation.tab_width(), &content),
+                .column_offset(self.properties.indentation.tab_width(), &content),
             file_path: self.properties.file_path.clone(),
+            name: self.properties.name.clone(),
+            indirect_view: self.properties.indirect_view,
+            read_only: self.properties.read_only,
             focused: self.properties.focused,
             frame_id: self.properties.frame_id,
             modified_status: self.properties.modified_status,
             mode: self.properties.mode.into(),
-            num_lines: content.len_lines(),
+            indentation: self.properties.indentation,
+            num_lines: visible_lines.len(),
+            narrowed: self.properties.narrowed_lines.is_some(),
             repository: self.properties.repo.clone(),
-            size_bytes: content.len_bytes() as u64,
+            size_bytes: self
+                .properties
+                .loading
+                .map_or(content.len_bytes() as u64, |loading| {
+                    loading.total_bytes.unwrap_or(loading.bytes_read)
+                }),
+            loading: self.properties.loading,
+            breadcrumbs: self.breadcrumbs(content.staged()),
+            pending_keys: self.properties.pending_keys.clone(),
+            selection: self.selection_size(content.staged()),
             theme: self.properties.theme.status_bar.clone(),
         });
 
+        // Thin bar along the right edge showing the part of the buffer in view
+        let scrollbar = if self.properties.context.config.scrollbar {
+            let folded_lines = &self.properties.folded_lines;
+            Some(Item::fixed(1)(Scrollbar::with(ScrollbarProperties {
+                theme: self.properties.theme.scrollbar.clone(),
+                rows_before: visual_rows(
+                    folded_lines,
+                    visible_lines.start..self.line_offset.max(visible_lines.start),
+                ),
+                total_rows: visual_rows(folded_lines, visible_lines.clone()),
+                marks: self.scrollbar_marks(&visible_lines, content.staged()),
+            })))
+        } else {
+            None
+        };
+
         // Edit-tree viewer (aka. undo/redo tree)
         let edit_tree_viewer = if self.viewing_edit_tree {
             Some(Item::fixed(EDIT_TREE_WIDTH)(Container::row([
@@ -305,6 +595,7 @@ impl Component for Buffer {
                 iter::once(edit_tree_viewer)
                     .chain(iter::once(Some(Item::fixed(1)(line_info))))
                     .chain(iter::once(Some(Item::auto(textarea))))
+                    .chain(iter::once(scrollbar))
                     .flatten(),
             )),
             Item::fixed(1)(status_bar),
@@ -314,6 +605,8 @@ impl Component for Buffer {
     fn bindings(&self, bindings: &mut Bindings<Self>) {
         use Key::*;
 
+        let mut bindings = RecordedBindings::new(bindings);
+
         bindings.set_focus(self.properties.focused);
         if !bindings.is_empty() {
             return;
@@ -380,6 +673,14 @@ impl Component for Buffer {
             })
             .with([Alt('n')]);
 
+        // Move by syntax node
+        bindings.add("backward-sexp", [Ctrl('c'), Ctrl('b')], |this: &Self| {
+            this.properties.cursor.move_sexp(Direction::Backward)
+        });
+        bindings.add("forward-sexp", [Ctrl('c'), Ctrl('f')], |this: &Self| {
+            this.properties.cursor.move_sexp(Direction::Forward)
+        });
+
         // Page down
         bindings
             .command("move-page-down", Self::move_page_down)
@@ -397,6 +698,7 @@ impl Component for Buffer {
             .command("move-start-of-line", Self::move_start_of_line)
             .with([Ctrl('a')])
             .with([Home]);
+        bindings.add("move-to-indentation", [Alt('m')], Self::move_to_indentation);
         bindings
             .command("move-end-of-line", Self::move_end_of_line)
             .with([Ctrl('e')])
@@ -421,8 +723,19 @@ impl Component for Buffer {
         // Delete backward
         bindings.add("delete-backward", [Backspace], Self::delete_backward);
 
-        // Delete line
-        bindings.add("delete-line", [Ctrl('k')], Self::delete_line);
+        // Kill line, or the selection
+        bindings.add("kill-whole-line", [Ctrl('k')], Self::kill_whole_line);
+        bindings.add("kill-to-end-of-line", [Alt('k')], Self::kill_to_end_of_line);
+
+        // Kill syntax node
+        bindings.add("kill-sexp", [Ctrl('c'), Ctrl('k')], |this: &Self| {
+            this.properties.cursor.kill_sexp(Direction::Forward)
+        });
+        bindings.add(
+            "backward-kill-sexp",
+            [Ctrl('c'), Backspace],
+            |this: &Self| this.properties.cursor.kill_sexp(Direction::Backward),
+        );
 
         // Insert new line
         bindings.add("insert-new-line", [Char('\n')], Self::insert_new_line);
@@ -430,9 +743,23 @@ impl Component for Buffer {
             this.properties.cursor.insert_char('\n', false)
         });
 
+        // Close the construct left open, e.g. with `fi` or `end`
+        bindings.add(
+            "insert-closing-keyword",
+            [Ctrl('c'), Char(']')],
+            |this: &Self| this.properties.cursor.insert_closing_keyword(),
+        );
+
         // Insert tab
         bindings.add("insert-tab", [Char('\t')], |this: &Self| {
-            this.properties.cursor.insert_tab()
+            if this.properties.completion.is_some() {
+                this.properties
+                    .context
+                    .link
+                    .send(EditorMessage::AcceptCompletion);
+            } else {
+                this.properties.cursor.insert_tab()
+            }
         });
 
         // Insert character
@@ -457,6 +784,21 @@ impl Component for Buffer {
             .with([Null])
             .with([Ctrl(' ')]);
 
+        // Mark ring
+        bindings
+            .command("pop-mark", |this: &Self| {
+                this.properties.cursor.pop_mark();
+            })
+            .with([Ctrl('u'), Null])
+            .with([Ctrl('u'), Ctrl(' ')]);
+        bindings.add(
+            "exchange-point-and-mark",
+            [Ctrl('x'), Ctrl('x')],
+            |this: &Self| {
+                this.properties.cursor.exchange_point_and_mark();
+            },
+        );
+
         // Select all
         bindings.add("select-all", [Ctrl('x'), Char('h')], |this: &Self| {
             this.properties.cursor.select_all();
@@ -490,6 +832,11 @@ impl Component for Buffer {
             this.properties.cursor.redo();
         });
 
+        // End the current group of edits, so the next ones are undone separately
+        bindings.add("undo-boundary", [Ctrl('c'), Char('u')], |this: &Self| {
+            this.properties.cursor.undo_boundary();
+        });
+
         // Save buffer
         bindings
             .command("save-buffer", |this: &Self| {
@@ -498,6 +845,29 @@ impl Component for Buffer {
             .with([Ctrl('x'), Ctrl('s')])
             .with([Ctrl('x'), Char('s')]);
 
+        // Folding
+        bindings.add("toggle-fold", [Ctrl('c'), Char('f')], |this: &Self| {
+            this.properties.cursor.toggle_fold();
+        });
+        bindings.add("unfold-all", [Ctrl('c'), Char('F')], |this: &Self| {
+            this.properties.cursor.unfold_all();
+        });
+
+        // Narrowing
+        bindings.add(
+            "narrow-to-region",
+            [Ctrl('x'), Char('n'), Char('n')],
+            |this: &Self| this.properties.cursor.narrow_to_selection(),
+        );
+        bindings.add(
+            "narrow-to-definition",
+            [Ctrl('x'), Char('n'), Char('d')],
+            |this: &Self| this.properties.cursor.narrow_to_definition(),
+        );
+        bindings.add("widen", [Ctrl('x'), Char('n'), Char('w')], |this: &Self| {
+            this.properties.cursor.widen();
+        });
+
         // Centre cursor visually
         bindings.add("center-cursor-visually", [Ctrl('l')], || {
             Message::CenterCursorVisually
@@ -523,3 +893,7 @@ impl Component for Buffer {
 }
 
 const EDIT_TREE_WIDTH: usize = 36;
+
+/// Files larger than this aren't searched for the scrollbar's marks, it would
+/// slow down every edit
+const MAX_SEARCH_MARKS_BYTES: usize = 8 * 1024 * 1024;
@@ -0,0 +1,98 @@
+use std::rc::Rc;
+use zi::{Canvas, Colour, Component, ComponentLink, Layout, Rect, ShouldRender, Style};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub track: Style,
+    /// The part of the track standing for the lines in view
+    pub thumb: Style,
+    pub search_match: Colour,
+}
+
+/// A line marked on the scrollbar, e.g. a search match or an error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mark {
+    /// The row of the line, counting the rows of all lines before it
+    pub row: usize,
+    pub colour: Colour,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Properties {
+    pub theme: Theme,
+    /// The rows of the lines before the first line in view
+    pub rows_before: usize,
+    /// The rows of all the lines which can be shown, folded lines taking none
+    pub total_rows: usize,
+    /// Drawn in order, a mark on the same cell as an earlier one hides it
+    pub marks: Rc<[Mark]>,
+}
+
+/// A thin vertical bar along a window showing which part of the buffer is in
+/// view, and where the marked lines are
+pub struct Scrollbar {
+    properties: Properties,
+    frame: Rect,
+}
+
+impl Component for Scrollbar {
+    type Message = ();
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, frame: Rect, _link: ComponentLink<Self>) -> Self {
+        Self { properties, frame }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if self.properties != properties {
+            self.properties = properties;
+            ShouldRender::Yes
+        } else {
+            ShouldRender::No
+        }
+    }
+
+    fn resize(&mut self, frame: Rect) -> ShouldRender {
+        self.frame = frame;
+        ShouldRender::Yes
+    }
+
+    fn view(&self) -> Layout {
+        let Properties {
+            ref theme,
+            rows_before,
+            total_rows,
+            ref marks,
+        } = self.properties;
+        let height = self.frame.size.height;
+        let total_rows = total_rows.max(height).max(1);
+        // The cell standing for a row, rounded down
+        let cell = |row: usize| (row * height / total_rows).min(height.saturating_sub(1));
+
+        let thumb_start = cell(rows_before);
+        let thumb_end = (thumb_start + (height * height + total_rows - 1) / total_rows)
+            .max(thumb_start + 1)
+            .min(height);
+        let thumb = thumb_start..thumb_end;
+
+        let mut canvas = Canvas::new(self.frame.size);
+        for y in 0..height {
+            let style = if thumb.contains(&y) {
+                theme.thumb
+            } else {
+                theme.track
+            };
+            canvas.draw_str(0, y, style, " ");
+        }
+        for mark in marks.iter() {
+            let y = cell(mark.row);
+            let background = if thumb.contains(&y) {
+                theme.thumb.background
+            } else {
+                theme.track.background
+            };
+            canvas.draw_str(0, y, Style::normal(background, mark.colour), "━");
+        }
+        canvas.into()
+    }
+}
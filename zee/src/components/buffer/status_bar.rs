@@ -5,10 +5,13 @@ use zi::{
     Size, Style,
 };
 
-use zee_grammar::Mode;
+use zee_grammar::{
+    config::{IndentationConfig, IndentationUnit},
+    Mode,
+};
 
 use crate::{
-    editor::buffer::{ModifiedStatus, RepositoryRc},
+    editor::buffer::{LoadingProgress, ModifiedStatus, RepositoryRc},
     utils::StaticRefEq,
 };
 
@@ -22,6 +25,7 @@ pub struct Theme {
     pub file_name: Style,
     pub file_size: Style,
     pub position_in_file: Style,
+    pub selection: Style,
     pub mode: Style,
 }
 
@@ -31,13 +35,34 @@ pub struct Properties {
     pub current_line_index: usize,
     pub column_offset: usize,
     pub file_path: Option<PathBuf>,
+    pub name: Option<String>,
+    /// Shown after the name, e.g. `main.rs<2>`
+    pub indirect_view: Option<usize>,
+    pub read_only: bool,
     pub focused: bool,
     pub frame_id: usize,
     pub modified_status: ModifiedStatus,
     pub mode: StaticRefEq<Mode>,
+    pub indentation: IndentationConfig,
     pub num_lines: usize,
+    /// Whether the window is narrowed to part of the buffer
+    pub narrowed: bool,
     pub repository: Option<RepositoryRc>,
     pub size_bytes: u64,
+    /// How much of the file has been read, if it's still loading
+    pub loading: Option<LoadingProgress>,
+    /// The names of the definitions around the cursor, outermost first
+    pub breadcrumbs: Vec<String>,
+    /// The unfinished key sequence typed so far, e.g. `C-x-`
+    pub pending_keys: Option<String>,
+    pub selection: Option<SelectionSize>,
+}
+
+/// How much is selected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelectionSize {
+    pub chars: usize,
+    pub lines: usize,
 }
 
 pub struct StatusBar {
@@ -72,16 +97,25 @@ impl Component for StatusBar {
             properties:
                 Properties {
                     ref file_path,
+                    ref name,
+                    indirect_view,
                     ref modified_status,
                     ref mode,
                     ref repository,
+                    indentation,
                     ref theme,
+                    ref breadcrumbs,
+                    ref pending_keys,
+                    selection,
                     current_line_index,
                     focused,
                     frame_id,
                     num_lines,
+                    narrowed,
                     size_bytes,
+                    loading,
                     column_offset,
+                    read_only,
                 },
             frame,
         } = *self;
@@ -107,11 +141,17 @@ impl Component for StatusBar {
                         _ => theme.is_modified,
                     },
                     match modified_status {
+                        _ if read_only => " % ",
                         ModifiedStatus::Unchanged => " - ",
                         ModifiedStatus::Changed | ModifiedStatus::Saving => " + ",
                     },
                 )
             })
+            // The keys typed so far of an unfinished key sequence
+            .and_then(|canvas| match pending_keys {
+                Some(keys) => canvas.append_start(theme.frame_id_focused, &format!(" {} ", keys)),
+                None => Some(canvas),
+            })
             // Visual indicator for current position in the file, right-aligned
             .and_then(|canvas| {
                 if focused {
@@ -135,7 +175,7 @@ impl Component for StatusBar {
                     &format!(" {}", SizeFormatterBinary::new(size_bytes)),
                 )
             })
-            // File name if buffer is backed by a file
+            // File name if buffer is backed by a file, otherwise the buffer's name
             .and_then(|canvas| {
                 canvas.append_start(
                     theme.file_name,
@@ -147,9 +187,22 @@ impl Component for StatusBar {
                                 None => format!(" {}", path.display()),
                             }
                         })
+                        .or_else(|| name.as_ref().map(|name| format!(" {}", name)))
+                        .map(|name| match indirect_view {
+                            Some(number) => format!("{}<{}>", name, number),
+                            None => name,
+                        })
                         .unwrap_or_else(String::new),
                 )
             })
+            // How much of the file has been read so far
+            .and_then(|canvas| match loading.map(|loading| loading.percent()) {
+                Some(Some(percent)) => {
+                    canvas.append_start(theme.mode, &format!(" Loading {}%", percent))
+                }
+                Some(None) => canvas.append_start(theme.mode, " Loading"),
+                None => Some(canvas),
+            })
             // The current position in the file as a percentage, right-aligned
             .and_then(|canvas| {
                 canvas.append_end(
@@ -178,8 +231,33 @@ impl Component for StatusBar {
                 );
                 canvas.append_end(theme.is_not_modified, &line_status)
             })
+            // The size of the selection, right-aligned
+            .and_then(|canvas| match selection {
+                Some(SelectionSize { chars, lines: 1 }) => {
+                    canvas.append_end(theme.selection, &format!(" {} chars ", chars))
+                }
+                Some(SelectionSize { chars, lines }) => canvas.append_end(
+                    theme.selection,
+                    &format!(" {} lines, {} chars ", lines, chars),
+                ),
+                None => Some(canvas),
+            })
             // Name of the current mode
             .and_then(|canvas| canvas.append_start(theme.mode, &format!("  {}", mode.name)))
+            // How the buffer is indented
+            .and_then(|canvas| {
+                canvas.append_start(
+                    theme.mode,
+                    &match indentation.unit {
+                        IndentationUnit::Space => format!(" Spaces:{}", indentation.width),
+                        IndentationUnit::Tab => format!(" Tabs:{}", indentation.width),
+                    },
+                )
+            })
+            // Whether the window is narrowed
+            .and_then(|canvas| {
+                canvas.append_start(theme.mode, if narrowed { " Narrow" } else { "" })
+            })
             // Name of the repo right aligned
             .and_then(|canvas| {
                 canvas.append_end(
@@ -194,6 +272,15 @@ impl Component for StatusBar {
                         None => String::new(),
                     },
                 )
+            })
+            // The definitions around the cursor, dropping the outermost ones
+            // if they don't all fit
+            .and_then(|canvas| {
+                let remaining_space = canvas.remaining_space();
+                (0..breadcrumbs.len())
+                    .map(|start| format!("  {}", breadcrumbs[start..].join(" > ")))
+                    .find(|content| content.width() <= remaining_space)
+                    .and_then(|content| canvas.append_start(theme.position_in_file, &content))
             });
         canvas.into()
     }
@@ -1,18 +1,32 @@
 use euclid::default::SideOffsets2D;
 use ropey::{Rope, RopeSlice};
-use std::{iter, ops::Range};
-use tree_sitter::{Node, Query, QueryCursor, TextProvider};
+use std::{cell::RefCell, cmp::Reverse, iter, ops::Range, rc::Rc};
+use tree_sitter::Query;
 use zi::{
     terminal::GraphemeCluster, Canvas, Component, ComponentLink, Layout, Position, Rect,
-    ShouldRender, Size,
+    ShouldRender, Size, Style,
 };
 
-use zee_edit::{ByteIndex, Cursor, LineIndex, RopeGraphemes};
-use zee_grammar::Mode;
-
-use crate::syntax::{
-    highlight::{text_style_at_char, Theme as SyntaxTheme},
-    parse::ParseTree,
+use zee_edit::{
+    graphemes::{self, grapheme_metrics, is_long_line, GraphemeMetrics},
+    CharIndex, Cursor, LineIndex,
+};
+use zee_grammar::{config::IndentationConfig, Mode};
+
+use crate::{
+    components::completion::CompletionPopup,
+    editor::{
+        annotations::{Annotation, Severity},
+        highlights::{Highlight, TEXT_COLOUR},
+    },
+    syntax::{
+        highlight::{
+            text_style_at_char, HighlightCache, LineScope, ScopeSpan, Theme as SyntaxTheme,
+        },
+        links::find_links,
+        parse::ParseTree,
+    },
+    utils::LineMetricsCache,
 };
 
 #[derive(Clone)]
@@ -22,8 +36,23 @@ pub struct Properties {
     pub text: Rope,
     pub cursor: Cursor,
     pub mode: &'static Mode,
+    pub indentation: IndentationConfig,
     pub line_offset: usize,
     pub parse_tree: Option<ParseTree>,
+    pub highlight_cache: Rc<RefCell<HighlightCache>>,
+    pub line_metrics: Rc<RefCell<LineMetricsCache>>,
+    pub line_scopes: Rc<[LineScope]>,
+    /// Sorted by where they start
+    pub annotations: Rc<[Annotation]>,
+    pub folded_lines: Rc<[Range<LineIndex>]>,
+    pub completion: Option<CompletionPopup>,
+    /// Drawn over the cursor until it's composed with the next character
+    pub dead_key: Option<char>,
+    /// Drawn over the syntax highlighting, the latest on top
+    pub highlights: Rc<[Rc<Highlight>]>,
+    pub indent_guides: bool,
+    /// The lines which can be shown, fewer than all of them when narrowed
+    pub visible_lines: Range<LineIndex>,
 }
 
 pub struct TextArea {
@@ -53,6 +82,8 @@ impl Component for TextArea {
         let mut canvas = Canvas::new(self.frame.size);
         canvas.clear(self.properties.theme.text);
         self.draw_text(&mut canvas);
+        self.draw_dead_key(&mut canvas);
+        self.draw_completion(&mut canvas);
         canvas.into()
     }
 }
@@ -60,81 +91,155 @@ impl Component for TextArea {
 impl TextArea {
     #[inline]
     fn draw_text(&self, canvas: &mut Canvas) {
-        let expanse = self.text_expanse_in_view(canvas);
-
-        let parse_tree = self
-            .properties
-            .parse_tree
-            .as_ref()
-            .map(|parse_tree| &parse_tree.tree);
-
-        if let (Some(tree), Some(query)) = (parse_tree, self.get_highlights_query()) {
-            let mut query_cursor = QueryCursor::new();
-            query_cursor.set_byte_range(expanse.byte_range.clone());
-
-            let mut matches = query_cursor
-                .matches(
-                    query,
-                    tree.root_node(),
-                    RopeProvider(self.properties.text.slice(..)),
-                )
-                .peekable();
-
-            let mut get_scope = |byte_index: usize| loop {
-                let query_match = matches.peek()?;
-                if query_match.captures.is_empty() {
-                    matches.next();
-                    continue;
-                }
-                let capture = query_match.captures[0];
-                let capture_range = capture.node.byte_range();
-                if byte_index < capture_range.start {
-                    return None;
-                } else if byte_index < capture_range.end {
-                    return Some(
-                        query.capture_names()[usize::try_from(capture.index).unwrap()].as_str(),
-                    );
-                } else {
-                    matches.next();
-                    continue;
-                }
-            };
+        let rows = self.rows_in_view(canvas);
+
+        // Highlighting long lines, e.g. in minified code, would be too slow, so
+        // they're drawn as plain text. The rows between them are highlighted
+        // in runs.
+        let text = &self.properties.text;
+        let mut run_start = 0;
+        for (row_index, row) in rows.iter().enumerate() {
+            if matches!(row, Row::Line(line_index) if is_long_line(&text.line(*line_index))) {
+                self.draw_run(canvas, &rows[run_start..row_index], run_start);
+                self.draw_rows(canvas, &rows[row_index..=row_index], row_index, &[]);
+                run_start = row_index + 1;
+            }
+        }
+        self.draw_run(canvas, &rows[run_start..], run_start);
+    }
 
-            self.draw_expanse(expanse, canvas, &mut get_scope);
+    /// Draws the pending dead key, if any, in place of the cursor
+    fn draw_dead_key(&self, canvas: &mut Canvas) {
+        let dead_key = match self.properties.dead_key {
+            Some(dead_key) => dead_key,
+            None => return,
+        };
+        let position = match self.position_in_view(canvas, self.properties.cursor.range().start) {
+            Some(position) => position,
+            None => return,
+        };
+        let style = if self.properties.focused {
+            self.properties.theme.cursor_focused
         } else {
-            self.draw_expanse(expanse, canvas, &mut |_| None)
+            self.properties.theme.cursor_unfocused
+        };
+        canvas.draw_str(
+            position.x,
+            position.y,
+            style,
+            dead_key.encode_utf8(&mut [0; 4]),
+        );
+    }
+
+    /// Draws the completion popup, if any, at the start of the text being
+    /// completed, as long as it's in view
+    fn draw_completion(&self, canvas: &mut Canvas) {
+        let completion = match self.properties.completion.as_ref() {
+            Some(completion) => completion,
+            None => return,
+        };
+        if let Some(position) = self.position_in_view(canvas, completion.anchor) {
+            completion.draw(canvas, Rect::from_size(canvas.size()), position);
         }
     }
 
+    /// Where a char is drawn, if its line is in view
+    fn position_in_view(&self, canvas: &Canvas, char_index: CharIndex) -> Option<Position> {
+        let text = &self.properties.text;
+        let char_index = char_index.min(text.len_chars());
+        let line_index = text.char_to_line(char_index);
+        let row_index = self
+            .rows_in_view(canvas)
+            .iter()
+            .position(|row| matches!(row, Row::Line(row_line) if *row_line == line_index))?;
+        let column = graphemes::line_width(
+            self.properties.indentation.tab_width(),
+            &text.slice(text.line_to_char(line_index)..char_index),
+        );
+        Some(Position::new(column, row_index))
+    }
+
+    /// Draws consecutive rows starting at `first_row_index`, highlighted using
+    /// the syntax tree if there is one
+    #[inline]
+    fn draw_run(&self, canvas: &mut Canvas, rows: &[Row], first_row_index: usize) {
+        let (parse_tree, query) = match (
+            self.properties.parse_tree.as_ref(),
+            self.get_highlights_query(),
+        ) {
+            (Some(parse_tree), Some(query)) => (parse_tree, query),
+            _ => return self.draw_rows(canvas, rows, first_row_index, &[]),
+        };
+        let line_indices: Vec<LineIndex> = rows
+            .iter()
+            .filter_map(|row| match row {
+                Row::Line(line_index) => Some(*line_index),
+                Row::Fold(_) => None,
+            })
+            .collect();
+        let spans = self.properties.highlight_cache.borrow_mut().spans(
+            query,
+            &parse_tree.tree,
+            parse_tree.version,
+            &self.properties.text,
+            &line_indices,
+        );
+        self.draw_rows(canvas, rows, first_row_index, &spans);
+    }
+
+    /// Draws rows starting at `first_row_index`. The highlighting spans are
+    /// given for each line row in order, or none at all for plain text.
     #[inline]
-    fn draw_expanse<'a>(
+    fn draw_rows(
         &self,
-        expanse: TextExpanse,
         canvas: &mut Canvas,
-        get_scope: &mut impl FnMut(ByteIndex) -> Option<&'a str>,
+        rows: &[Row],
+        first_row_index: usize,
+        spans: &[Rc<[ScopeSpan]>],
     ) {
-        for line_index in expanse.line_range {
-            self.draw_line(
-                canvas,
-                Rect::from_size(canvas.size()).inner_rect(SideOffsets2D::new(
-                    line_index - self.properties.line_offset,
-                    0,
-                    0,
-                    0,
-                )),
-                line_index,
-                get_scope,
-            );
+        let mut spans = spans.iter();
+        for (row_index, row) in (first_row_index..).zip(rows) {
+            let frame =
+                Rect::from_size(canvas.size()).inner_rect(SideOffsets2D::new(row_index, 0, 0, 0));
+            match row {
+                Row::Line(line_index) => self.draw_line(
+                    canvas,
+                    frame,
+                    *line_index,
+                    spans.next().map_or(&[], |spans| spans),
+                ),
+                Row::Fold(folded) => self.draw_fold(canvas, frame, folded.start),
+            }
         }
     }
 
+    /// Draws the placeholder for folded lines, indented like the first of them
     #[inline]
-    fn draw_line<'a>(
+    fn draw_fold(&self, canvas: &mut Canvas, frame: Rect, first_line_index: LineIndex) {
+        let indentation = indentation_width(
+            self.properties.text.line(first_line_index),
+            self.properties.indentation.tab_width(),
+        )
+        .unwrap_or(0);
+        let theme = &self.properties.theme;
+        canvas.draw_str(
+            frame.origin.x + indentation.min(frame.size.width.saturating_sub(1)),
+            frame.origin.y,
+            Style {
+                background: theme.text.background,
+                ..theme.code_comment
+            },
+            FOLD_PLACEHOLDER,
+        );
+    }
+
+    #[inline]
+    fn draw_line(
         &self,
         canvas: &mut Canvas,
         frame: Rect,
         line_index: LineIndex,
-        get_scope: &mut impl FnMut(ByteIndex) -> Option<&'a str>,
+        spans: &[ScopeSpan],
     ) {
         // Get references to the relevant bits of context
         let Self {
@@ -144,11 +249,20 @@ impl TextArea {
                     focused,
                     ref text,
                     ref cursor,
+                    ref line_scopes,
+                    ref annotations,
+                    ref highlights,
                     ..
                 },
             ..
         } = *self;
 
+        // Line scopes are sorted and don't overlap
+        let line_scope = line_scopes
+            .get(line_scopes.partition_point(|line_scope| line_scope.lines.end <= line_index))
+            .filter(|line_scope| line_scope.lines.contains(&line_index))
+            .map(|line_scope| line_scope.scope);
+
         // Highlight the currently selected line
         let line = text.line(line_index);
         let line_under_cursor = text.char_to_line(cursor.range().start) == line_index;
@@ -164,13 +278,121 @@ impl TextArea {
 
         let mut visual_x = frame.origin.x;
         let mut char_index = text.line_to_char(line_index);
-        let line_start_byte = text.char_to_byte(char_index);
+        let line_start_char = char_index;
+        let line_end_char = line_start_char + line.len_chars();
+        let links = find_links(line);
+
+        // The matches of the highlighted patterns on the line, as ranges of
+        // chars from its start, with the latest pattern's first
+        let line_highlights: Vec<_> = if highlights.is_empty() {
+            Vec::new()
+        } else {
+            let line_str = String::from(line);
+            highlights
+                .iter()
+                .rev()
+                .flat_map(|highlight| {
+                    highlight.pattern.find_all(&line_str).map(|bytes| {
+                        let chars = line.byte_to_char(bytes.start)..line.byte_to_char(bytes.end);
+                        (chars, highlight.colour)
+                    })
+                })
+                .collect()
+        };
+        let highlight_at = |char_index: CharIndex| {
+            line_highlights
+                .iter()
+                .find(|(chars, _)| chars.contains(&(char_index - line_start_char)))
+                .map(|(_, colour)| *colour)
+        };
+
+        // The annotations overlapping the line, which underline the text they
+        // cover in the colour of their severity
+        let line_annotations: Vec<&Annotation> = annotations
+            [..annotations.partition_point(|annotation| annotation.range.start <= line_end_char)]
+            .iter()
+            .filter(|annotation| {
+                annotation.range.end.max(annotation.range.start + 1) > line_start_char
+            })
+            .collect();
+        let annotation_at = |char_index| {
+            line_annotations
+                .iter()
+                .filter(|annotation| annotation.covers(char_index))
+                .map(|annotation| annotation.severity)
+                .max()
+        };
+
+        // The spans are in the order of the query matches and may overlap. A
+        // grapheme takes the scope of the first span which doesn't end before it.
+        let mut spans = spans.iter().peekable();
+        let mut get_scope = |byte_index| loop {
+            let span = spans.peek()?;
+            if byte_index < span.bytes.start {
+                return None;
+            } else if byte_index < span.bytes.end {
+                return Some(span.scope);
+            } else {
+                spans.next();
+            }
+        };
 
-        for grapheme in RopeGraphemes::new(&line.slice(..)) {
+        // Guides are drawn on the columns of each indentation level, in the
+        // whitespace at the start of the line
+        let guides_end = if self.properties.indent_guides {
+            self.guides_end(line_index)
+        } else {
+            0
+        };
+        let indent_width = self.properties.indentation.width.max(1);
+        let is_guide = |visual_x: usize| {
+            let column = visual_x - frame.origin.x;
+            column < guides_end && column % indent_width == 0
+        };
+        let guide_style = |style: Style| Style {
+            foreground: theme.indent_guide.foreground,
+            ..style
+        };
+
+        // The part of the selection on this line, including the line break
+        let selection = cursor.selection();
+        let selected = selection.start.max(line_start_char)
+            ..selection.end.min(line_start_char + line.len_chars());
+        let on_cursor =
+            |char_index| char_index == cursor.range().start || cursor.range().contains(&char_index);
+
+        // Long lines are only measured as far as they're drawn, other lines
+        // are measured once and cached until they're edited
+        let tab_width = self.properties.indentation.tab_width();
+        let cached_metrics;
+        let graphemes: Box<dyn Iterator<Item = GraphemeMetrics>> = if is_long_line(&line) {
+            Box::new(grapheme_metrics(tab_width, &line))
+        } else {
+            cached_metrics = self
+                .properties
+                .line_metrics
+                .borrow_mut()
+                .metrics(text, tab_width, line_index);
+            Box::new(cached_metrics.iter().copied())
+        };
+
+        let mut line_break = None;
+        for metrics in graphemes {
+            let grapheme = line.slice(metrics.chars());
             let is_error = false;
+            let is_line_break = grapheme == "\n" || grapheme == "\r\n";
+            if is_line_break {
+                line_break = Some((visual_x, char_index));
+            }
 
-            let scope = get_scope(line_start_byte + grapheme.byte_start).unwrap_or("");
-            let style = text_style_at_char(
+            let in_link = links
+                .iter()
+                .any(|link| link.range.contains(&(char_index - line_start_char)));
+            let scope = line_scope
+                .or_else(|| in_link.then(|| "link"))
+                .or_else(|| get_scope(metrics.byte_start))
+                .unwrap_or("");
+            let mut style = text_style_at_char(
                 theme,
                 cursor,
                 char_index,
@@ -179,17 +401,51 @@ impl TextArea {
                 scope,
                 is_error,
             );
-            let grapheme_width =
-                zee_edit::graphemes::width(self.properties.mode.indentation.tab_width(), &grapheme);
+            if let Some(colour) = highlight_at(char_index) {
+                if !on_cursor(char_index) {
+                    style = Style {
+                        background: colour,
+                        foreground: TEXT_COLOUR,
+                        ..style
+                    };
+                }
+            }
+            if let Some(severity) = annotation_at(char_index) {
+                if !on_cursor(char_index) {
+                    style = Style {
+                        foreground: annotation_style(theme, severity).foreground,
+                        underline: true,
+                        ..style
+                    };
+                }
+            }
+            if selected.contains(&char_index) && !on_cursor(char_index) {
+                style = if is_line_break {
+                    theme.selection_end_of_line
+                } else {
+                    Style {
+                        background: theme.selection_background,
+                        ..style
+                    }
+                };
+            }
+            let grapheme_width = metrics.width_at(tab_width, visual_x - frame.origin.x);
             let horizontal_bounds_inclusive = frame.min_x()..=frame.max_x();
             if !horizontal_bounds_inclusive.contains(&(visual_x + grapheme_width)) {
                 break;
             }
 
-            if grapheme.slice == "\t" {
+            if metrics.is_tab() {
                 for offset in 0..grapheme_width {
-                    canvas.draw_str(visual_x + offset, frame.origin.y, style, " ");
+                    let (style, content) = if is_guide(visual_x + offset) {
+                        (guide_style(style), INDENT_GUIDE)
+                    } else {
+                        (style, " ")
+                    };
+                    canvas.draw_str(visual_x + offset, frame.origin.y, style, content);
                 }
+            } else if grapheme == " " && is_guide(visual_x) {
+                canvas.draw_str(visual_x, frame.origin.y, guide_style(style), INDENT_GUIDE);
             } else if grapheme_width == 0 {
                 canvas.draw_str(visual_x, frame.origin.y, style, " ");
             } else {
@@ -201,10 +457,30 @@ impl TextArea {
                 );
             }
 
-            char_index += grapheme.len_chars();
+            char_index += metrics.len_chars;
             visual_x += grapheme_width.max(1);
         }
 
+        // Blank lines continue the guides of the lines around them past their end
+        let line_style = if line_under_cursor && focused {
+            theme.text_current_line
+        } else {
+            theme.text
+        };
+        let (end_of_line_x, end_of_line_char) = line_break.unwrap_or((visual_x, char_index));
+        let end_of_line_x = end_of_line_x + usize::from(cursor.range().start == end_of_line_char);
+        for guide_x in (frame.origin.x..frame.origin.x + guides_end.min(frame.size.width))
+            .step_by(indent_width)
+            .filter(|guide_x| *guide_x >= end_of_line_x)
+        {
+            canvas.draw_str(
+                guide_x,
+                frame.origin.y,
+                guide_style(line_style),
+                INDENT_GUIDE,
+            );
+        }
+
         if line.get_char(line.len_chars().saturating_sub(1)) != Some('\n')
             && cursor.range().start == char_index
         {
@@ -219,33 +495,83 @@ impl TextArea {
                 " ",
             );
         }
+
+        // The messages of the annotations starting on the line are shown
+        // after its end, the most severe first
+        let mut messages: Vec<&Annotation> = line_annotations
+            .iter()
+            .copied()
+            .filter(|annotation| annotation.range.start >= line_start_char)
+            .filter(|annotation| {
+                annotation.range.start < line_end_char || line_index + 1 == text.len_lines()
+            })
+            .collect();
+        messages.sort_by_key(|annotation| Reverse(annotation.severity));
+        let mut message_x = end_of_line_x;
+        for annotation in messages {
+            message_x += ANNOTATION_GAP;
+            if message_x >= frame.max_x() {
+                break;
+            }
+            let message = annotation.message.lines().next().unwrap_or("");
+            let style = Style {
+                background: line_style.background,
+                ..annotation_style(theme, annotation.severity)
+            };
+            message_x += canvas.draw_str(message_x, frame.origin.y, style, message);
+        }
     }
 
-    #[inline]
-    fn text_expanse_in_view(&self, canvas: &Canvas) -> TextExpanse {
-        let line_range = self.properties.line_offset
-            ..(self.properties.line_offset + canvas.size().height)
-                .min(self.properties.text.len_lines());
+    /// The column up to which indentation guides are drawn on a line. This is
+    /// the line's indentation or, for blank lines, the smaller indentation of
+    /// the nearest non-blank lines above and below.
+    fn guides_end(&self, line_index: LineIndex) -> usize {
+        let text = &self.properties.text;
+        let tab_width = self.properties.indentation.tab_width();
+        let indentation = |line_index| indentation_width(text.line(line_index), tab_width);
+        if let Some(width) = indentation(line_index) {
+            return width;
+        }
+        let above = (line_index.saturating_sub(MAX_BLANK_LINES_SCANNED)..line_index)
+            .rev()
+            .find_map(indentation);
+        let below = (line_index + 1..(line_index + MAX_BLANK_LINES_SCANNED).min(text.len_lines()))
+            .find_map(indentation);
+        above.unwrap_or(0).min(below.unwrap_or(0))
+    }
 
-        let start_byte = self
-            .properties
-            .text
-            .try_line_to_byte(self.properties.line_offset)
-            .unwrap_or_else(|_| self.properties.text.len_bytes());
-        let end_byte = self
+    #[inline]
+    fn rows_in_view(&self, canvas: &Canvas) -> Vec<Row> {
+        let num_lines = self
             .properties
-            .text
-            .try_line_to_byte(line_range.end)
-            .unwrap_or_else(|_| self.properties.text.len_bytes());
-
-        TextExpanse {
-            byte_range: start_byte..end_byte,
-            line_range,
+            .visible_lines
+            .end
+            .min(self.properties.text.len_lines());
+        let mut rows = Vec::with_capacity(canvas.size().height);
+        let mut line_index = self.properties.line_offset;
+        while rows.len() < canvas.size().height && line_index < num_lines {
+            match self
+                .properties
+                .folded_lines
+                .iter()
+                .find(|folded| folded.contains(&line_index))
+            {
+                Some(folded) => {
+                    rows.push(Row::Fold(folded.clone()));
+                    line_index = folded.end;
+                }
+                None => {
+                    rows.push(Row::Line(line_index));
+                    line_index += 1;
+                }
+            }
         }
+
+        rows
     }
 
     #[inline]
-    fn get_highlights_query(&self) -> Option<&Query> {
+    fn get_highlights_query(&self) -> Option<&'static Query> {
         self.properties
             .mode
             .grammar()
@@ -255,34 +581,39 @@ impl TextArea {
     }
 }
 
-struct TextExpanse {
-    byte_range: Range<ByteIndex>,
-    line_range: Range<LineIndex>,
+/// What's drawn on a row of the text area
+enum Row {
+    Line(LineIndex),
+    Fold(Range<LineIndex>),
 }
 
-struct ChunksBytes<'a> {
-    chunks: ropey::iter::Chunks<'a>,
-}
-
-impl<'a> Iterator for ChunksBytes<'a> {
-    type Item = &'a [u8];
-
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        self.chunks.next().map(str::as_bytes)
+/// The visual width of the whitespace at the start of a line, or `None` if
+/// the line is blank
+fn annotation_style(theme: &SyntaxTheme, severity: Severity) -> Style {
+    match severity {
+        Severity::Error => theme.annotation_error,
+        Severity::Warning => theme.annotation_warning,
+        Severity::Info => theme.annotation_info,
+        Severity::Hint => theme.annotation_hint,
     }
 }
 
-struct RopeProvider<'a>(RopeSlice<'a>);
-
-impl<'a> TextProvider<'a> for RopeProvider<'a> {
-    type I = ChunksBytes<'a>;
-
-    #[inline]
-    fn text(&mut self, node: Node) -> Self::I {
-        let fragment = self.0.byte_slice(node.start_byte()..node.end_byte());
-        ChunksBytes {
-            chunks: fragment.chunks(),
+fn indentation_width(line: RopeSlice, tab_width: usize) -> Option<usize> {
+    let tab_width = tab_width.max(1);
+    let mut width = 0;
+    for character in line.chars() {
+        match character {
+            ' ' => width += 1,
+            '\t' => width += tab_width - width % tab_width,
+            '\n' | '\r' => return None,
+            _ => return Some(width),
         }
     }
+    None
 }
+
+/// The columns between the end of a line and an annotation's message
+const ANNOTATION_GAP: usize = 2;
+const FOLD_PLACEHOLDER: &str = "…";
+const INDENT_GUIDE: &str = "│";
+const MAX_BLANK_LINES_SCANNED: usize = 64;
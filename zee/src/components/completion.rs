@@ -0,0 +1,197 @@
+//! The popup listing completion candidates at the cursor. Components are laid
+//! out next to each other rather than on top, so the popup is drawn by the
+//! component it covers, e.g. the text area, over its own canvas.
+
+use std::rc::Rc;
+use zi::{
+    unicode_width::{UnicodeWidthChar, UnicodeWidthStr},
+    Canvas, Position, Rect, Size, Style,
+};
+
+use zee_edit::CharIndex;
+
+use crate::editor::completion::CompletionItem;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub item: Style,
+    pub item_selected: Style,
+    pub kind: Style,
+    pub detail: Style,
+    pub documentation: Style,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletionPopup {
+    pub theme: Theme,
+    /// Where the text being completed starts, the labels are lined up with it
+    pub anchor: CharIndex,
+    pub items: Rc<[CompletionItem]>,
+    pub selected: usize,
+}
+
+impl CompletionPopup {
+    /// Draws the candidates below the anchor, which is drawn at `position`,
+    /// or above it if there's more room there. The documentation of the
+    /// selected candidate is shown next to the list.
+    pub fn draw(&self, canvas: &mut Canvas, frame: Rect, position: Position) {
+        let rows_below = frame.max_y().saturating_sub(position.y + 1);
+        let rows_above = position.y.saturating_sub(frame.min_y());
+        let num_rows = self.items.len().min(MAX_VISIBLE_ITEMS);
+        let (num_rows, top) = if rows_below >= num_rows || rows_below >= rows_above {
+            (num_rows.min(rows_below), position.y + 1)
+        } else {
+            (
+                num_rows.min(rows_above),
+                position.y - num_rows.min(rows_above),
+            )
+        };
+        let width = self.list_width().min(frame.size.width);
+        if num_rows == 0 || width <= ICON_WIDTH {
+            return;
+        }
+        let left = position
+            .x
+            .saturating_sub(ICON_WIDTH)
+            .max(frame.min_x())
+            .min(frame.max_x() - width);
+
+        // Scroll just enough to keep the selected candidate in view
+        let first = self.selected.saturating_sub(num_rows - 1);
+        for (offset, item) in self.items[first..first + num_rows].iter().enumerate() {
+            let y = top + offset;
+            let style = if first + offset == self.selected {
+                self.theme.item_selected
+            } else {
+                self.theme.item
+            };
+            canvas.clear_region(
+                Rect::new(Position::new(left, y), Size::new(width, 1)),
+                style,
+            );
+            let kind_style = Style {
+                background: style.background,
+                ..self.theme.kind
+            };
+            canvas.draw_str(left, y, kind_style, item.kind.icon());
+
+            let text_width = width - ICON_WIDTH - 1;
+            let label = truncate(&item.label, text_width);
+            let mut x = left + ICON_WIDTH;
+            x += canvas.draw_str(x, y, style, &label);
+            if let Some(detail) = item.detail.as_deref() {
+                let available = (left + ICON_WIDTH + text_width).saturating_sub(x + DETAIL_GAP);
+                if available > 0 {
+                    let detail_style = Style {
+                        background: style.background,
+                        ..self.theme.detail
+                    };
+                    canvas.draw_str(
+                        x + DETAIL_GAP,
+                        y,
+                        detail_style,
+                        &truncate(detail, available),
+                    );
+                }
+            }
+        }
+
+        let documentation = match self
+            .items
+            .get(self.selected)
+            .and_then(|item| item.documentation.as_deref())
+        {
+            Some(documentation) => documentation,
+            None => return,
+        };
+        // On the right of the list if it fits, otherwise on the left
+        let room_right = frame.max_x() - (left + width);
+        let room_left = left - frame.min_x();
+        let (doc_left, doc_width) =
+            if room_right >= MIN_DOCUMENTATION_WIDTH || room_right >= room_left {
+                (left + width, room_right.min(MAX_DOCUMENTATION_WIDTH))
+            } else {
+                let doc_width = room_left.min(MAX_DOCUMENTATION_WIDTH);
+                (left - doc_width, doc_width)
+            };
+        if doc_width <= 2 {
+            return;
+        }
+        canvas.clear_region(
+            Rect::new(Position::new(doc_left, top), Size::new(doc_width, num_rows)),
+            self.theme.documentation,
+        );
+        for (offset, line) in wrap(documentation, doc_width - 2)
+            .iter()
+            .take(num_rows)
+            .enumerate()
+        {
+            canvas.draw_str(doc_left + 1, top + offset, self.theme.documentation, line);
+        }
+    }
+
+    /// Wide enough for every candidate, so the popup doesn't change size as
+    /// the selection scrolls
+    fn list_width(&self) -> usize {
+        let widest = self
+            .items
+            .iter()
+            .map(|item| {
+                item.label.width()
+                    + item
+                        .detail
+                        .as_deref()
+                        .map_or(0, |detail| DETAIL_GAP + detail.width())
+            })
+            .max()
+            .unwrap_or(0);
+        (ICON_WIDTH + widest + 1).min(MAX_LIST_WIDTH)
+    }
+}
+
+/// The start of a text which fits in a number of columns
+fn truncate(text: &str, width: usize) -> String {
+    let mut columns = 0;
+    text.chars()
+        .take_while(|character| {
+            columns += character.width().unwrap_or(0);
+            columns <= width
+        })
+        .collect()
+}
+
+/// Breaks a text into lines no wider than `width`, at spaces where possible,
+/// keeping its own line breaks
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.width() + 1 + word.width() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            while line.width() > width {
+                let mut head = truncate(&line, width);
+                if head.is_empty() {
+                    head = line.chars().take(1).collect();
+                }
+                line = line[head.len()..].to_owned();
+                lines.push(head);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// The kind's icon and a space
+const ICON_WIDTH: usize = 2;
+const DETAIL_GAP: usize = 2;
+const MAX_VISIBLE_ITEMS: usize = 10;
+const MAX_LIST_WIDTH: usize = 60;
+const MIN_DOCUMENTATION_WIDTH: usize = 24;
+const MAX_DOCUMENTATION_WIDTH: usize = 48;
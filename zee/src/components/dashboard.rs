@@ -0,0 +1,293 @@
+use std::{
+    borrow::Cow,
+    cmp,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use zi::{prelude::*, unicode_width::UnicodeWidthStr};
+
+use super::{
+    prompt::picker::FileSource,
+    splash::{text_block_size, Theme, LOGO, TAGLINE},
+};
+use crate::{
+    editor::{ContextHandle, Message as EditorMessage, RecordedBindings},
+    project::recent_projects,
+    recent::RecentFiles,
+};
+
+#[derive(Clone)]
+pub struct Properties {
+    pub context: ContextHandle,
+    pub theme: Cow<'static, Theme>,
+    pub focused: bool,
+    pub recent_files: Arc<RecentFiles>,
+}
+
+impl PartialEq for Properties {
+    fn eq(&self, other: &Self) -> bool {
+        self.theme == other.theme
+            && self.focused == other.focused
+            && Arc::ptr_eq(&self.recent_files, &other.recent_files)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    SelectNext,
+    SelectPrevious,
+    Open,
+}
+
+/// Shown when no buffers are open, lists recent files, recent projects and
+/// tips, any of which can be selected and opened with the keyboard
+pub struct Dashboard {
+    properties: Properties,
+    frame: Rect,
+    entries: Vec<Entry>,
+    selected_index: usize,
+}
+
+enum Entry {
+    File(PathBuf),
+    /// The root of a project containing recent files
+    Project(PathBuf),
+    Tip(&'static Tip),
+}
+
+struct Tip {
+    keys: &'static str,
+    description: &'static str,
+    message: fn() -> EditorMessage,
+}
+
+impl Entry {
+    fn section(&self) -> &'static str {
+        match self {
+            Self::File(_) => "Recent files",
+            Self::Project(_) => "Recent projects",
+            Self::Tip(_) => "Tips",
+        }
+    }
+}
+
+impl Dashboard {
+    fn entries(recent_files: &RecentFiles) -> Vec<Entry> {
+        let files = recent_files
+            .iter()
+            .filter(|path| path.is_file())
+            .take(MAX_RECENT_FILES_SHOWN)
+            .map(|path| Entry::File(path.to_path_buf()));
+        let projects = recent_projects(recent_files)
+            .into_iter()
+            .take(MAX_RECENT_PROJECTS_SHOWN)
+            .map(Entry::Project);
+        let tips = TIPS.iter().map(Entry::Tip);
+        files.chain(projects).chain(tips).collect()
+    }
+
+    fn open(&self) {
+        let message = match self.entries.get(self.selected_index) {
+            Some(Entry::File(path)) => EditorMessage::OpenFile(path.clone()),
+            Some(Entry::Project(path)) => EditorMessage::OpenProject(path.clone()),
+            Some(Entry::Tip(tip)) => (tip.message)(),
+            None => return,
+        };
+        self.properties.context.link.send(message);
+    }
+
+    /// How many lines the entries take, with a heading and a blank line
+    /// before each section
+    fn entries_height(&self) -> usize {
+        let num_sections = self
+            .entries
+            .windows(2)
+            .filter(|pair| pair[0].section() != pair[1].section())
+            .count()
+            + usize::from(!self.entries.is_empty());
+        self.entries.len() + 2 * num_sections
+    }
+}
+
+impl Component for Dashboard {
+    type Message = Message;
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, frame: Rect, _link: ComponentLink<Self>) -> Self {
+        let entries = Self::entries(&properties.recent_files);
+        Self {
+            properties,
+            frame,
+            entries,
+            selected_index: 0,
+        }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if self.properties == properties {
+            return ShouldRender::No;
+        }
+        if !Arc::ptr_eq(&self.properties.recent_files, &properties.recent_files) {
+            self.entries = Self::entries(&properties.recent_files);
+            self.selected_index = 0;
+        }
+        self.properties = properties;
+        ShouldRender::Yes
+    }
+
+    fn resize(&mut self, frame: Rect) -> ShouldRender {
+        self.frame = frame;
+        ShouldRender::Yes
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        let num_entries = self.entries.len().max(1);
+        match message {
+            Message::SelectNext => {
+                self.selected_index = (self.selected_index + 1) % num_entries;
+            }
+            Message::SelectPrevious => {
+                self.selected_index = (self.selected_index + num_entries - 1) % num_entries;
+            }
+            Message::Open => {
+                self.open();
+                return ShouldRender::No;
+            }
+        }
+        ShouldRender::Yes
+    }
+
+    fn view(&self) -> Layout {
+        let Self {
+            properties: Properties {
+                ref theme, focused, ..
+            },
+            frame,
+            ..
+        } = *self;
+        let mut canvas = Canvas::new(frame.size);
+        canvas.clear(theme.logo);
+
+        // The logo is left out if there isn't room for it
+        let logo_size = text_block_size(LOGO);
+        let header_height = logo_size.height + 2;
+        let mut y = cmp::min(4, frame.size.height.saturating_sub(logo_size.height));
+        if y + header_height + self.entries_height() <= frame.size.height {
+            let x = (frame.size.width / 2).saturating_sub(logo_size.width / 2);
+            for line in LOGO.lines() {
+                canvas.draw_str(x, y, theme.logo, line);
+                y += 1;
+            }
+            let x = (frame.size.width / 2).saturating_sub(TAGLINE.width() / 2);
+            canvas.draw_str(x, y + 1, theme.tagline, TAGLINE);
+            y += header_height;
+        } else {
+            y = 0;
+        }
+
+        let width = cmp::min(DASHBOARD_WIDTH, frame.size.width);
+        let left = (frame.size.width - width) / 2;
+        let mut section = "";
+        for (index, entry) in self.entries.iter().enumerate() {
+            if y >= frame.size.height {
+                break;
+            }
+            if entry.section() != section {
+                section = entry.section();
+                canvas.draw_str(left, y + 1, theme.heading, section);
+                y += 2;
+            }
+
+            let selected = index == self.selected_index;
+            let (style, key_style) = if selected && focused {
+                (theme.item_selected, theme.item_selected)
+            } else {
+                (theme.item, theme.key)
+            };
+            if selected && focused {
+                canvas.clear_region(
+                    Rect::new(Position::new(left, y), Size::new(width, 1)),
+                    style,
+                );
+            }
+            match entry {
+                Entry::File(path) | Entry::Project(path) => {
+                    canvas.draw_str(left + 2, y, style, &display_path(path));
+                }
+                Entry::Tip(tip) => {
+                    canvas.draw_str(left + 2, y, key_style, tip.keys);
+                    canvas.draw_str(left + 2 + TIP_KEYS_WIDTH, y, style, tip.description);
+                }
+            }
+            y += 1;
+        }
+
+        canvas.into()
+    }
+
+    fn bindings(&self, bindings: &mut Bindings<Self>) {
+        use Key::*;
+
+        let mut bindings = RecordedBindings::new(bindings);
+
+        bindings.set_focus(self.properties.focused);
+        if !bindings.is_empty() {
+            return;
+        }
+
+        bindings
+            .command("dashboard-next", || Message::SelectNext)
+            .with([Ctrl('n')])
+            .with([Down]);
+        bindings
+            .command("dashboard-previous", || Message::SelectPrevious)
+            .with([Ctrl('p')])
+            .with([Up]);
+        bindings.add("dashboard-open", [Char('\n')], || Message::Open);
+    }
+}
+
+/// The path with the home directory shortened to `~`
+fn display_path(path: &Path) -> Cow<'_, str> {
+    match dirs::home_dir().and_then(|home_dir| path.strip_prefix(home_dir).ok()) {
+        Some(relative) => Path::new("~")
+            .join(relative)
+            .to_string_lossy()
+            .into_owned()
+            .into(),
+        None => path.to_string_lossy(),
+    }
+}
+
+const TIPS: &[Tip] = &[
+    Tip {
+        keys: "C-x C-f",
+        description: "Open a file",
+        message: || EditorMessage::OpenFilePicker(FileSource::Directory),
+    },
+    Tip {
+        keys: "C-x C-v",
+        description: "Open a file in the repository",
+        message: || EditorMessage::OpenFilePicker(FileSource::Repository),
+    },
+    Tip {
+        keys: "C-x C-r",
+        description: "Open a recent file",
+        message: || EditorMessage::OpenFilePicker(FileSource::Recent),
+    },
+    Tip {
+        keys: "C-h b",
+        description: "List the key bindings",
+        message: || EditorMessage::DescribeBindings,
+    },
+    Tip {
+        keys: "C-x C-c",
+        description: "Quit",
+        message: || EditorMessage::Quit,
+    },
+];
+
+const MAX_RECENT_FILES_SHOWN: usize = 8;
+const MAX_RECENT_PROJECTS_SHOWN: usize = 5;
+const DASHBOARD_WIDTH: usize = 72;
+const TIP_KEYS_WIDTH: usize = 10;
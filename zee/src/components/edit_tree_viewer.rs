@@ -1,7 +1,10 @@
-use std::cmp;
+use std::{
+    cmp,
+    time::{Duration, Instant},
+};
 use zi::{Canvas, Component, ComponentLink, Layout, Rect, ShouldRender, Style};
 
-use zee_edit::tree::{self, EditTree};
+use zee_edit::tree::{self, EditTree, Revision};
 
 use crate::versioned::WeakHandle;
 
@@ -64,6 +67,15 @@ impl Component for EditTreeViewer {
             (middle_x, middle_y)
         };
 
+        // The positions of all revisions sorted by row, to find how much room
+        // there is for details next to each one
+        let mut positions: Vec<_> = formatted_tree
+            .iter()
+            .map(|formatted| (formatted.transform.y, formatted.transform.x))
+            .collect();
+        positions.sort_unstable();
+        let now = Instant::now();
+
         // let mut y = middle_y + 8;
         // let mut revision_index = tree.parent_revision_index;
         for (revision_index, formatted) in formatted_tree.iter().enumerate() {
@@ -82,20 +94,35 @@ impl Component for EditTreeViewer {
                 && x < canvas.size().width as isize
                 && y < canvas.size().height as isize
             {
-                canvas.draw_str(
-                    x as usize,
-                    y as usize,
-                    revision_style,
-                    &format!(
-                        "{:.5}{}",
-                        revision_index,
-                        if revision_index == tree.head_index {
-                            "*"
-                        } else {
-                            ""
-                        },
-                    ),
+                let index = format!(
+                    "{:.5}{}",
+                    revision_index,
+                    if revision_index == tree.head_index {
+                        "*"
+                    } else {
+                        ""
+                    },
                 );
+                canvas.draw_str(x as usize, y as usize, revision_style, &index);
+
+                // Details up to the next revision on the same row
+                let position = (formatted.transform.y, formatted.transform.x);
+                let next_x = positions
+                    .binary_search(&position)
+                    .ok()
+                    .and_then(|position_index| positions.get(position_index + 1))
+                    .filter(|(next_y, _)| *next_y == position.0)
+                    .map(|(_, next_x)| middle_x + next_x);
+                let details_x = x as usize + index.len() + 1;
+                let details_end = next_x.map_or(canvas.size().width, |next_x| {
+                    cmp::min(next_x.max(0) as usize, canvas.size().width)
+                });
+                let details = revision_details(revision, now);
+                if details_x < details_end && !details.is_empty() {
+                    let details: String =
+                        details.chars().take(details_end - details_x - 1).collect();
+                    canvas.draw_str(details_x, y as usize, connector_style, &details);
+                }
             }
 
             let num_children = revision.children.len();
@@ -169,3 +196,30 @@ impl Component for EditTreeViewer {
         canvas.into()
     }
 }
+
+/// How long ago a revision was made, how many characters it inserted and
+/// deleted and its label, e.g. `5m +12 -3 refactor`
+fn revision_details(revision: &Revision, now: Instant) -> String {
+    let mut details = format_age(now.saturating_duration_since(revision.created));
+    if let Some(diff) = revision.diff() {
+        details.push_str(&format!(
+            " +{} -{}",
+            diff.new_char_length, diff.old_char_length
+        ));
+    }
+    if let Some(label) = revision.label.as_ref() {
+        details.push(' ');
+        details.push_str(label);
+    }
+    details
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
@@ -1,4 +1,6 @@
 pub mod buffer;
+pub mod completion;
+pub mod dashboard;
 pub mod edit_tree_viewer;
 pub mod prompt;
 pub mod splash;
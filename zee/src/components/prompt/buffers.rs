@@ -13,14 +13,14 @@ use zi::{
 };
 
 use zee_grammar::Mode;
+use zee_matcher::Matcher;
 
 use super::{
-    matcher::Matcher,
     status::{Status, StatusProperties},
     Theme,
 };
 use crate::{
-    editor::{BufferId, ContextHandle},
+    editor::{BufferId, ContextHandle, RecordedBindings},
     task::TaskId,
 };
 
@@ -32,6 +32,9 @@ pub struct BufferEntry {
     pub len_bytes: usize,
     pub mode: &'static Mode,
     pub name: String,
+    /// The number of the indirect view of the buffer the entry stands for,
+    /// if it's one
+    pub indirect_view: Option<usize>,
 }
 
 impl BufferEntry {
@@ -41,11 +44,15 @@ impl BufferEntry {
         on_screen: bool,
         len_bytes: usize,
         mode: &'static Mode,
+        name: Option<&str>,
     ) -> Self {
-        let name = path
-            .as_ref()
-            .and_then(|path| path.file_name())
-            .map(|path| path.to_string_lossy())
+        let name = name
+            .map(Cow::from)
+            .or_else(|| {
+                path.as_ref()
+                    .and_then(|path| path.file_name())
+                    .map(|path| path.to_string_lossy())
+            })
             .unwrap_or_else(|| "(Unnamed)".into())
             .to_string();
         Self {
@@ -55,8 +62,16 @@ impl BufferEntry {
             len_bytes,
             mode,
             name,
+            indirect_view: None,
         }
     }
+
+    /// An entry for an indirect view of the buffer, named e.g. `main.rs<2>`
+    pub fn indirect_view(mut self, number: usize) -> Self {
+        self.name = format!("{}<{}>", self.name, number);
+        self.indirect_view = Some(number);
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -72,7 +87,7 @@ pub struct Properties {
     pub theme: Cow<'static, Theme>,
     pub message: Cow<'static, str>,
     pub entries: Vec<BufferEntry>,
-    pub on_select: Callback<BufferId>,
+    pub on_select: Callback<BufferEntry>,
     pub on_filter: Callback<usize>,
 }
 
@@ -92,10 +107,7 @@ impl Component for BufferPicker {
 
     fn create(properties: Self::Properties, _frame: Rect, link: ComponentLink<Self>) -> Self {
         let mut matcher = Matcher::new();
-        matcher.set_filter(
-            properties.entries.iter().map(|entry| entry.name.as_str()),
-            "",
-        );
+        matcher.set_candidates(properties.entries.iter().map(|entry| entry.name.as_str()));
         Self {
             properties,
             link,
@@ -108,11 +120,8 @@ impl Component for BufferPicker {
     }
 
     fn change(&mut self, properties: Self::Properties) -> ShouldRender {
-        let filter_str: Cow<str> = self.input.slice(..).into();
-        self.matcher.set_filter(
-            properties.entries.iter().map(|entry| entry.name.as_str()),
-            &filter_str,
-        );
+        self.matcher
+            .set_candidates(properties.entries.iter().map(|entry| entry.name.as_str()));
         self.properties = properties;
         ShouldRender::Yes
     }
@@ -122,7 +131,7 @@ impl Component for BufferPicker {
             Message::Select if self.matcher.num_ranked() > 0 => {
                 self.properties
                     .on_select
-                    .emit(self.properties.entries[self.matcher[self.selected_index]].id);
+                    .emit(self.properties.entries[self.matcher[self.selected_index]].clone());
                 false
             }
             Message::UpdateInput(InputChange { content, cursor }) => {
@@ -144,13 +153,7 @@ impl Component for BufferPicker {
 
         if input_changed {
             let filter_str: Cow<str> = self.input.slice(..).into();
-            self.matcher.set_filter(
-                self.properties
-                    .entries
-                    .iter()
-                    .map(|entry| entry.name.as_str()),
-                &filter_str,
-            );
+            self.matcher.set_filter(&filter_str);
             self.properties.on_filter.emit(self.matcher.num_ranked());
         }
 
@@ -286,6 +289,7 @@ impl Component for BufferPicker {
     }
 
     fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
         if !bindings.is_empty() {
             return;
         }
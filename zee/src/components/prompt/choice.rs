@@ -0,0 +1,100 @@
+use std::{borrow::Cow, fmt::Write};
+use zi::{
+    components::text::{Text, TextProperties},
+    prelude::*,
+    AnyCharacter, Callback,
+};
+
+use super::Theme;
+use crate::editor::RecordedBindings;
+
+/// One of the answers to a `ChoicePrompt`, selected by pressing `key`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Choice {
+    pub key: char,
+    pub description: &'static str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    Choose(char),
+    Cancel,
+}
+
+pub struct Properties {
+    pub theme: Cow<'static, Theme>,
+    pub message: String,
+    pub choices: &'static [Choice],
+    pub on_choice: Callback<Option<char>>,
+}
+
+/// A question with more than two possible answers, each bound to a key.
+/// Cancelling with `Esc` is reported as `None`.
+pub struct ChoicePrompt {
+    properties: Properties,
+}
+
+impl Component for ChoicePrompt {
+    type Message = Message;
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, _frame: Rect, _link: ComponentLink<Self>) -> Self {
+        Self { properties }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        self.properties = properties;
+        ShouldRender::Yes
+    }
+
+    fn view(&self) -> Layout {
+        let mut message = self.properties.message.clone();
+        for (index, choice) in self.properties.choices.iter().enumerate() {
+            let separator = if index == 0 { " " } else { ", " };
+            write!(
+                message,
+                "{}[{}] {}",
+                separator, choice.key, choice.description
+            )
+            .unwrap();
+        }
+        Text::with(
+            TextProperties::new()
+                .style(self.properties.theme.input)
+                .content(message),
+        )
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        self.properties.on_choice.emit(match message {
+            Message::Choose(key) => Some(key),
+            Message::Cancel => None,
+        });
+        ShouldRender::No
+    }
+
+    fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
+        if !bindings.is_empty() {
+            return;
+        }
+
+        // Set focus to `true` in order to react to key presses
+        bindings.set_focus(true);
+
+        bindings.add("cancel", [Key::Esc], || Message::Cancel);
+        bindings.add(
+            "choose",
+            AnyCharacter,
+            |this: &Self, keys: &[Key]| match keys {
+                &[Key::Char(key)] => this
+                    .properties
+                    .choices
+                    .iter()
+                    .any(|choice| choice.key == key)
+                    .then(|| Message::Choose(key)),
+                _ => None,
+            },
+        );
+    }
+}
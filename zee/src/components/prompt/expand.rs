@@ -0,0 +1,174 @@
+use ignore::overrides::{Override, OverrideBuilder};
+use std::{
+    borrow::Cow,
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Expands a leading `~` to the home directory, and `$NAME` or `${NAME}` to
+/// the value of an environment variable. Unset variables are left as typed.
+pub fn expand_path(input: &str) -> Cow<'_, str> {
+    if !input.starts_with('~') && !input.contains('$') {
+        return input.into();
+    }
+
+    let mut expanded = String::with_capacity(input.len());
+    let mut rest = match (input.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home_dir)) if rest.is_empty() || rest.starts_with('/') => {
+            expanded.push_str(&home_dir.to_string_lossy());
+            rest
+        }
+        _ => input,
+    };
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => braced
+                .find('}')
+                .map_or(("", 0), |end| (&braced[..end], end + 2)),
+            None => {
+                let end = after
+                    .find(|character: char| {
+                        !(character.is_ascii_alphanumeric() || character == '_')
+                    })
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let value = if name.is_empty() {
+            None
+        } else {
+            env::var(name).ok()
+        };
+        match value {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = &after[len..];
+            }
+            None => {
+                expanded.push('$');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded.into()
+}
+
+/// A glob pattern typed in the file picker, e.g. `~/src/**/*.toml` once
+/// expanded
+#[derive(Clone, Debug)]
+pub struct Glob {
+    /// The directory to walk, the components of the path before the first
+    /// one with a wildcard
+    pub directory: PathBuf,
+    /// The rest of the path, matched against the paths in the directory
+    pub pattern: String,
+    matcher: Override,
+}
+
+impl Glob {
+    /// The glob pattern in a path, `None` if it has no wildcards or isn't a
+    /// valid pattern
+    pub fn parse(path: &str) -> Option<Self> {
+        let components: Vec<_> = path.split('/').collect();
+        let first_pattern = components
+            .iter()
+            .position(|component| component.contains(['*', '?', '[']))?;
+        let directory = match components[..first_pattern].join("/") {
+            directory if !directory.is_empty() => directory,
+            _ if first_pattern > 0 => "/".into(),
+            _ => ".".into(),
+        };
+        let pattern = components[first_pattern..].join("/");
+        // Anchored at the directory, a pattern without slashes would match
+        // in its subdirectories too
+        let matcher = OverrideBuilder::new(&directory)
+            .add(&format!("/{}", pattern))
+            .and_then(|builder| builder.build())
+            .ok()?;
+        Some(Self {
+            directory: directory.into(),
+            pattern,
+            matcher,
+        })
+    }
+
+    /// How many directories deep matches can be, `None` if `**` matches any
+    /// number of them
+    pub fn max_depth(&self) -> Option<usize> {
+        (!self.pattern.contains("**")).then(|| self.pattern.split('/').count())
+    }
+
+    pub fn is_match(&self, path: &Path, is_dir: bool) -> bool {
+        self.matcher.matched(path, is_dir).is_whitelist()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_home_directory() {
+        let home_dir = dirs::home_dir().unwrap();
+        let home_dir = home_dir.to_string_lossy();
+        assert_eq!(expand_path("~"), home_dir);
+        assert_eq!(expand_path("~/notes.md"), format!("{}/notes.md", home_dir));
+        // Only a leading `~` on its own is the home directory
+        assert_eq!(expand_path("~user/notes.md"), "~user/notes.md");
+        assert_eq!(expand_path("notes~/a.md"), "notes~/a.md");
+    }
+
+    #[test]
+    fn expand_environment_variables() {
+        env::set_var("ZEE_EXPAND_TEST_DIR", "/tmp/zee");
+        assert_eq!(expand_path("$ZEE_EXPAND_TEST_DIR/a.md"), "/tmp/zee/a.md");
+        assert_eq!(expand_path("${ZEE_EXPAND_TEST_DIR}a.md"), "/tmp/zeea.md");
+        // Unset variables, unterminated braces and a lone `$` are left as typed
+        assert_eq!(
+            expand_path("$ZEE_EXPAND_TEST_UNSET/a.md"),
+            "$ZEE_EXPAND_TEST_UNSET/a.md"
+        );
+        assert_eq!(
+            expand_path("${ZEE_EXPAND_TEST_DIR/a.md"),
+            "${ZEE_EXPAND_TEST_DIR/a.md"
+        );
+        assert_eq!(expand_path("price$/a.md"), "price$/a.md");
+    }
+
+    #[test]
+    fn parse_glob_directory_and_pattern() {
+        assert!(Glob::parse("src/main.rs").is_none());
+
+        let glob = Glob::parse("src/*/Cargo.toml").unwrap();
+        assert_eq!(glob.directory, Path::new("src"));
+        assert_eq!(glob.pattern, "*/Cargo.toml");
+        assert_eq!(glob.max_depth(), Some(2));
+
+        let glob = Glob::parse("*.rs").unwrap();
+        assert_eq!(glob.directory, Path::new("."));
+        assert_eq!(glob.max_depth(), Some(1));
+
+        let glob = Glob::parse("/*.conf").unwrap();
+        assert_eq!(glob.directory, Path::new("/"));
+        assert_eq!(glob.pattern, "*.conf");
+
+        let glob = Glob::parse("/src/**/*.toml").unwrap();
+        assert_eq!(glob.directory, Path::new("/src"));
+        assert_eq!(glob.max_depth(), None);
+    }
+
+    #[test]
+    fn match_glob_anchored_at_directory() {
+        let glob = Glob::parse("/src/*.rs").unwrap();
+        assert!(glob.is_match(Path::new("/src/main.rs"), false));
+        assert!(!glob.is_match(Path::new("/src/editor/mod.rs"), false));
+        assert!(!glob.is_match(Path::new("/src/main.toml"), false));
+
+        let glob = Glob::parse("/notes/notes[12].md").unwrap();
+        assert!(glob.is_match(Path::new("/notes/notes1.md"), false));
+        assert!(!glob.is_match(Path::new("/notes/notes3.md"), false));
+    }
+}
@@ -0,0 +1,265 @@
+use ropey::Rope;
+use std::{borrow::Cow, sync::Arc};
+use zi::{
+    components::{
+        input::{Cursor, Input, InputChange, InputProperties, InputStyle},
+        text::{Text, TextAlign, TextProperties},
+    },
+    prelude::*,
+    unicode_width::UnicodeWidthStr,
+    Callback,
+};
+
+use super::{
+    status::{Status, StatusProperties},
+    validate::{Validation, Validator},
+    Theme,
+};
+use crate::{
+    editor::{ContextHandle, RecordedBindings},
+    history::{HistoryKind, PromptHistory},
+    task::TaskId,
+};
+
+#[derive(Debug)]
+pub enum Message {
+    Submit,
+    UpdateInput(InputChange),
+    Validated(TaskId, Validation),
+    PreviousHistory,
+    NextHistory,
+}
+
+/// The earlier inputs of a kind of prompt
+#[derive(Clone)]
+pub struct InputHistory {
+    pub kind: HistoryKind,
+    pub history: Arc<PromptHistory>,
+    /// Called with the input submitted
+    pub on_push: Callback<(HistoryKind, String)>,
+}
+
+impl InputHistory {
+    pub fn entries(&self) -> &[String] {
+        self.history.entries(self.kind)
+    }
+}
+
+#[derive(Clone)]
+pub struct Properties {
+    pub context: ContextHandle,
+    pub theme: Cow<'static, Theme>,
+    pub message: Cow<'static, str>,
+    /// Called with the text whenever it changes, if set
+    pub on_change: Option<Callback<String>>,
+    pub on_submit: Callback<String>,
+    /// Checks the text whenever it changes, if set
+    pub validator: Option<Validator>,
+    /// Earlier inputs, gone through with Up and Down, if kept
+    pub history: Option<InputHistory>,
+}
+
+/// A prompt which reads a single line of text from the user
+pub struct InputPrompt {
+    properties: Properties,
+    link: ComponentLink<Self>,
+    input: Rope,
+    cursor: Cursor,
+    validation: Validation,
+    /// The background check of the latest input, if still running
+    validation_task: Option<TaskId>,
+    /// The index of the history entry shown, `None` when showing what was
+    /// typed
+    history_index: Option<usize>,
+    /// What was typed before going through the history
+    typed: Rope,
+}
+
+impl InputPrompt {
+    fn set_input(&mut self, content: Rope) {
+        self.input = content;
+        let input: String = self.input.slice(..).into();
+        let input = input.trim_end_matches('\n').to_owned();
+        if let Some(on_change) = self.properties.on_change.as_ref() {
+            on_change.emit(input.clone());
+        }
+        self.validate(input);
+    }
+
+    /// Shows the history entry at an index, or what was typed if `None`
+    fn show_history(&mut self, index: Option<usize>) {
+        let content = match (index, self.properties.history.as_ref()) {
+            (Some(index), Some(history)) => format!("{}\n", history.entries()[index]).into(),
+            _ => self.typed.clone(),
+        };
+        self.history_index = index;
+        self.cursor = Cursor::new();
+        self.cursor.move_to_end_of_line(&content);
+        self.set_input(content);
+    }
+
+    fn validate(&mut self, input: String) {
+        self.validation_task = None;
+        let validator = match self.properties.validator {
+            Some(ref validator) if !input.trim().is_empty() => validator,
+            _ => {
+                self.validation = Validation::Valid;
+                return;
+            }
+        };
+        match validator {
+            Validator::Immediate(validate) => self.validation = validate.emit(input),
+            Validator::Background(validate) => {
+                let validate = validate.clone();
+                let link = self.link.clone();
+                self.validation_task =
+                    Some(self.properties.context.task_pool.spawn(move |task_id| {
+                        link.send(Message::Validated(task_id, validate(&input)))
+                    }));
+            }
+        }
+    }
+}
+
+impl Component for InputPrompt {
+    type Message = Message;
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, _frame: Rect, link: ComponentLink<Self>) -> Self {
+        Self {
+            properties,
+            link,
+            input: "\n".into(),
+            cursor: Cursor::new(),
+            validation: Validation::Valid,
+            validation_task: None,
+            history_index: None,
+            typed: "\n".into(),
+        }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        let should_render = (self.properties.theme != properties.theme
+            || self.properties.message != properties.message)
+            .into();
+        self.properties = properties;
+        should_render
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        match message {
+            Message::Submit => {
+                let input: String = self.input.slice(..).into();
+                let input = input.trim_end_matches('\n').to_owned();
+                if let Some(history) = self.properties.history.as_ref() {
+                    history.on_push.emit((history.kind, input.clone()));
+                }
+                self.properties.on_submit.emit(input);
+                ShouldRender::No
+            }
+            Message::UpdateInput(InputChange { content, cursor }) => {
+                self.cursor = cursor;
+                if let Some(content) = content {
+                    self.history_index = None;
+                    self.typed = content.clone();
+                    self.set_input(content);
+                }
+                ShouldRender::Yes
+            }
+            Message::Validated(task_id, validation) => {
+                if self.validation_task != Some(task_id) {
+                    return ShouldRender::No;
+                }
+                self.validation_task = None;
+                self.validation = validation;
+                ShouldRender::Yes
+            }
+            Message::PreviousHistory => {
+                let num_entries = self
+                    .properties
+                    .history
+                    .as_ref()
+                    .map_or(0, |history| history.entries().len());
+                let index = self.history_index.map_or(0, |index| index + 1);
+                if index < num_entries {
+                    self.show_history(Some(index));
+                }
+                ShouldRender::Yes
+            }
+            Message::NextHistory => {
+                if let Some(index) = self.history_index {
+                    self.show_history(index.checked_sub(1));
+                }
+                ShouldRender::Yes
+            }
+        }
+    }
+
+    fn view(&self) -> Layout {
+        let theme = &self.properties.theme;
+        let input = Input::with(InputProperties {
+            style: InputStyle {
+                content: if self.validation.is_invalid() {
+                    Style::normal(theme.input.background, theme.error)
+                } else {
+                    theme.input
+                },
+                cursor: theme.cursor,
+            },
+            content: self.input.clone(),
+            cursor: self.cursor.clone(),
+            on_change: Some(self.link.callback(Message::UpdateInput)),
+            focused: true,
+        });
+
+        let mut items = vec![
+            Status::item_with_key(
+                FlexBasis::Fixed(self.properties.message.width()),
+                "status",
+                StatusProperties {
+                    action_name: self.properties.message.clone(),
+                    pending: self.validation_task.is_some(),
+                    style: theme.action,
+                },
+            ),
+            Text::item_with_key(
+                FlexBasis::Fixed(1),
+                "spacer",
+                TextProperties::new().style(theme.input),
+            ),
+            Item::auto(input),
+        ];
+        if let Some(message) = self.validation.message() {
+            let foreground = if self.validation.is_invalid() {
+                theme.error
+            } else {
+                theme.hint
+            };
+            items.push(Text::item_with_key(
+                FlexBasis::Fixed(message.width() + 1),
+                "validation",
+                TextProperties::new()
+                    .content(format!("{} ", message))
+                    .style(Style::normal(theme.input.background, foreground))
+                    .align(TextAlign::Right),
+            ));
+        }
+        Layout::row(items)
+    }
+
+    fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
+        if !bindings.is_empty() {
+            return;
+        }
+
+        bindings.set_focus(true);
+        bindings.add("submit", [Key::Char('\n')], || Message::Submit);
+        bindings.add("previous-history", [Key::Up], || Message::PreviousHistory);
+        bindings.add("previous-history", [Key::Alt('p')], || {
+            Message::PreviousHistory
+        });
+        bindings.add("next-history", [Key::Down], || Message::NextHistory);
+        bindings.add("next-history", [Key::Alt('n')], || Message::NextHistory);
+    }
+}
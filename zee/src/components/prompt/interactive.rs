@@ -7,6 +7,7 @@ use zi::{
 };
 
 use super::Theme;
+use crate::editor::RecordedBindings;
 
 // Message type handled by the `InteractiveMessage` component
 #[derive(Clone, Copy, PartialEq)]
@@ -49,6 +50,7 @@ impl Component for InteractiveMessage {
     }
 
     fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
         if !bindings.is_empty() {
             return;
         }
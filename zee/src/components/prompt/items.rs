@@ -0,0 +1,296 @@
+use ropey::Rope;
+use std::{borrow::Cow, rc::Rc};
+use zi::{
+    components::{
+        input::{Cursor, Input, InputChange, InputProperties, InputStyle},
+        select::{Select, SelectProperties},
+        text::{Text, TextAlign, TextProperties},
+    },
+    unicode_width::UnicodeWidthStr,
+    Background, Bindings, Callback, Colour, Component, ComponentExt, ComponentLink, Container,
+    FlexBasis, FlexDirection, Item, Key, Layout, Rect, ShouldRender, Style,
+};
+
+use zee_matcher::Matcher;
+
+use super::{
+    status::{Status, StatusProperties},
+    Theme,
+};
+use crate::editor::RecordedBindings;
+
+/// Everything a feature tells the prompt to open a picker of its own: where
+/// the candidates come from, how they're drawn and what to do with the one
+/// picked. The prompt takes care of filtering and selecting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickerDefinition {
+    /// Shown before the filter, e.g. `mode`
+    pub title: Cow<'static, str>,
+    /// Lists the candidates when the picker opens. The text of a candidate is
+    /// what the filter is matched against.
+    pub source: Callback<(), Rc<[String]>>,
+    /// Draws a listed candidate, its text if there's none
+    pub render: Option<Callback<RenderCandidate, Item>>,
+    /// Called with the index of the candidate picked, in the source's list
+    pub on_select: Callback<usize>,
+    /// Shown when no candidate matches the filter
+    pub no_matches: Cow<'static, str>,
+    /// Shown when the source listed no candidates at all
+    pub no_candidates: Cow<'static, str>,
+}
+
+impl PickerDefinition {
+    pub fn new(
+        title: impl Into<Cow<'static, str>>,
+        source: impl Into<Callback<(), Rc<[String]>>>,
+        on_select: impl Into<Callback<usize>>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            source: source.into(),
+            render: None,
+            on_select: on_select.into(),
+            no_matches: "No matching items".into(),
+            no_candidates: "No matching items".into(),
+        }
+    }
+
+    /// A picker of a fixed list of items, e.g. those offered by a plugin
+    pub fn items(
+        title: impl Into<Cow<'static, str>>,
+        items: Rc<[String]>,
+        on_select: impl Into<Callback<usize>>,
+    ) -> Self {
+        Self::new(title, move |()| items.clone(), on_select)
+    }
+
+    pub fn render(mut self, render: impl Into<Callback<RenderCandidate, Item>>) -> Self {
+        self.render = Some(render.into());
+        self
+    }
+
+    /// The messages shown when no candidate matches the filter, and when
+    /// there's no candidate at all
+    pub fn empty_messages(
+        mut self,
+        no_matches: impl Into<Cow<'static, str>>,
+        no_candidates: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.no_matches = no_matches.into();
+        self.no_candidates = no_candidates.into();
+        self
+    }
+}
+
+/// A candidate for a picker's render function to draw, on a single line
+pub struct RenderCandidate {
+    /// The index of the candidate in the source's list
+    pub index: usize,
+    pub background: Background,
+    pub theme: Cow<'static, Theme>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Select,
+    UpdateInput(InputChange),
+    UpdateSelected(usize),
+}
+
+#[derive(Clone)]
+pub struct Properties {
+    pub theme: Cow<'static, Theme>,
+    pub definition: PickerDefinition,
+    /// What the definition's source listed when the picker opened
+    pub candidates: Rc<[String]>,
+    pub on_filter: Callback<usize>,
+}
+
+/// Lists the candidates of a picker definition filtered by text
+pub struct ItemPicker {
+    properties: Properties,
+    link: ComponentLink<Self>,
+    input: Rope,
+    cursor: Cursor,
+    selected_index: usize,
+    matcher: Matcher,
+}
+
+impl ItemPicker {
+    fn filter_items(&mut self) {
+        let filter_str: Cow<str> = self.input.slice(..).into();
+        self.matcher.set_filter(&filter_str);
+    }
+}
+
+impl Component for ItemPicker {
+    type Message = Message;
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, _frame: Rect, link: ComponentLink<Self>) -> Self {
+        let mut matcher = Matcher::new();
+        matcher.set_candidates(properties.candidates.iter().map(String::as_str));
+        Self {
+            properties,
+            link,
+            input: "\n".into(),
+            cursor: Cursor::new(),
+            selected_index: 0,
+            matcher,
+        }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if !Rc::ptr_eq(&self.properties.candidates, &properties.candidates) {
+            self.matcher
+                .set_candidates(properties.candidates.iter().map(String::as_str));
+        }
+        self.properties = properties;
+        ShouldRender::Yes
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        let input_changed = match message {
+            Message::Select if self.matcher.num_ranked() > 0 => {
+                self.properties
+                    .definition
+                    .on_select
+                    .emit(self.matcher[self.selected_index]);
+                false
+            }
+            Message::UpdateInput(InputChange { content, cursor }) => {
+                self.selected_index = 0;
+                self.cursor = cursor;
+                if let Some(content) = content {
+                    self.input = content;
+                    true
+                } else {
+                    false
+                }
+            }
+            Message::UpdateSelected(index) => {
+                self.selected_index = index;
+                false
+            }
+            _ => false,
+        };
+
+        if input_changed {
+            self.filter_items();
+            self.properties.on_filter.emit(self.matcher.num_ranked());
+        }
+
+        ShouldRender::Yes
+    }
+
+    fn view(&self) -> Layout {
+        let input = Input::with(InputProperties {
+            style: InputStyle {
+                content: self.properties.theme.input,
+                cursor: self.properties.theme.cursor,
+            },
+            content: self.input.clone(),
+            cursor: self.cursor.clone(),
+            on_change: Some(self.link.callback(Message::UpdateInput)),
+            focused: true,
+        });
+
+        let candidates = self.properties.candidates.clone();
+        let render = self.properties.definition.render.clone();
+        let matcher = self.matcher.clone();
+        let selected_index = self.selected_index;
+        let theme = self.properties.theme.clone();
+        let item_at = move |index| {
+            let candidate_index = matcher[index];
+            let background = if index == selected_index {
+                theme.item_focused_background
+            } else {
+                theme.item_unfocused_background
+            };
+            match render {
+                Some(ref render) => render.emit(RenderCandidate {
+                    index: candidate_index,
+                    background,
+                    theme: theme.clone(),
+                }),
+                None => Text::item_with_key(
+                    FlexBasis::Fixed(1),
+                    format!("{}", candidate_index).as_str(),
+                    TextProperties::new()
+                        .content(candidates[candidate_index].clone())
+                        .style(Style::normal(background, theme.item_file_foreground)),
+                ),
+            }
+        };
+        Layout::column([
+            if self.matcher.num_ranked() == 0 {
+                Text::item_with(
+                    FlexBasis::Fixed(1),
+                    TextProperties::new()
+                        .content(if self.properties.candidates.is_empty() {
+                            self.properties.definition.no_candidates.clone()
+                        } else {
+                            self.properties.definition.no_matches.clone()
+                        })
+                        .style(Style::normal(
+                            self.properties.theme.item_unfocused_background,
+                            Colour::rgb(251, 73, 52),
+                        )),
+                )
+            } else {
+                Item::auto(Select::with(SelectProperties {
+                    background: Style::normal(
+                        self.properties.theme.item_unfocused_background,
+                        self.properties.theme.item_file_foreground,
+                    ),
+                    direction: FlexDirection::ColumnReverse,
+                    item_at: item_at.into(),
+                    focused: true,
+                    num_items: self.matcher.num_ranked(),
+                    selected: self.selected_index,
+                    on_change: self.link.callback(Message::UpdateSelected).into(),
+                    item_size: 1,
+                }))
+            },
+            Item::fixed(1)(Container::row([
+                Status::item_with_key(
+                    FlexBasis::Fixed(self.properties.definition.title.width()),
+                    "status",
+                    StatusProperties {
+                        action_name: self.properties.definition.title.clone(),
+                        pending: false,
+                        style: self.properties.theme.action,
+                    },
+                ),
+                Text::item_with_key(
+                    FlexBasis::Fixed(1),
+                    "spacer",
+                    TextProperties::new().style(self.properties.theme.input),
+                ),
+                Item::auto(input),
+                Text::item_with_key(
+                    FlexBasis::Fixed(12),
+                    "num-results",
+                    TextProperties::new()
+                        .content(format!(
+                            "{} of {} ",
+                            self.matcher.num_ranked(),
+                            self.properties.candidates.len()
+                        ))
+                        .style(self.properties.theme.action.invert())
+                        .align(TextAlign::Right),
+                ),
+            ])),
+        ])
+    }
+
+    fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
+        if !bindings.is_empty() {
+            return;
+        }
+
+        bindings.set_focus(true);
+        bindings.add("select-item", [Key::Char('\n')], || Message::Select);
+    }
+}
@@ -0,0 +1,82 @@
+use std::borrow::Cow;
+use zi::{
+    components::text::{Text, TextProperties},
+    prelude::*,
+    AnyCharacter, Callback,
+};
+
+use super::Theme;
+
+pub struct Properties {
+    pub theme: Cow<'static, Theme>,
+    pub message: String,
+    pub on_key: Callback<Key>,
+}
+
+/// Reads keys one at a time, telling about each of them instead of running
+/// the command it's bound to. Used by `describe-key`, which decides when the
+/// sequence read is complete.
+pub struct KeyReader {
+    properties: Properties,
+}
+
+impl KeyReader {
+    fn read_key(&self, keys: &[Key]) -> Option<Key> {
+        keys.last().copied()
+    }
+}
+
+impl Component for KeyReader {
+    type Message = Key;
+    type Properties = Properties;
+
+    fn create(properties: Self::Properties, _frame: Rect, _link: ComponentLink<Self>) -> Self {
+        Self { properties }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        self.properties = properties;
+        ShouldRender::Yes
+    }
+
+    fn update(&mut self, key: Self::Message) -> ShouldRender {
+        self.properties.on_key.emit(key);
+        ShouldRender::No
+    }
+
+    fn view(&self) -> Layout {
+        Text::with(
+            TextProperties::new()
+                .style(self.properties.theme.input)
+                .content(self.properties.message.clone()),
+        )
+    }
+
+    fn bindings(&self, bindings: &mut Bindings<Self>) {
+        use Key::*;
+
+        if !bindings.is_empty() {
+            return;
+        }
+
+        // Set focus to `true` in order to react to key presses. Every key is
+        // bound on its own, so none of them is a prefix. The bindings aren't
+        // recorded, there's nothing to learn from them in `describe-bindings`.
+        bindings.set_focus(true);
+
+        bindings.add("read-key", AnyCharacter, Self::read_key);
+        for character in ' '..='~' {
+            bindings.add("read-key", [Ctrl(character)], Self::read_key);
+            bindings.add("read-key", [Alt(character)], Self::read_key);
+        }
+        for number in 1..=12 {
+            bindings.add("read-key", [F(number)], Self::read_key);
+        }
+        for key in [
+            Backspace, Left, Right, Up, Down, Home, End, PageUp, PageDown, BackTab, Delete, Insert,
+            Null, Esc,
+        ] {
+            bindings.add("read-key", [key], Self::read_key);
+        }
+    }
+}
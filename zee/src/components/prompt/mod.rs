@@ -1,24 +1,40 @@
 pub mod buffers;
+pub mod choice;
+pub mod items;
+pub mod keys;
+pub mod modes;
 pub mod picker;
+pub mod symbols;
+pub mod validate;
 
-mod matcher;
+mod expand;
 mod status;
 
+mod input;
 mod interactive;
 
-use std::{borrow::Cow, path::PathBuf};
+use std::{borrow::Cow, path::PathBuf, rc::Rc, sync::Arc};
 use zi::{
     components::text::{Text, TextProperties},
-    Background, Callback, Component, ComponentExt, ComponentLink, Foreground, Layout, Rect,
+    Background, Callback, Component, ComponentExt, ComponentLink, Foreground, Key, Layout, Rect,
     ShouldRender, Style,
 };
 
-use crate::editor::{BufferId, ContextHandle};
+use crate::{
+    editor::ContextHandle,
+    history::{HistoryKind, PromptHistory},
+    recent::RecentFiles,
+};
 
 use self::{
     buffers::{BufferEntry, BufferPicker, Properties as BufferPickerProperties},
+    choice::{Choice, ChoicePrompt, Properties as ChoicePromptProperties},
+    input::{InputHistory, InputPrompt, Properties as InputPromptProperties},
     interactive::{InteractiveMessage, Properties as InteractiveMessageProperties},
+    items::{ItemPicker, PickerDefinition, Properties as ItemPickerProperties},
+    keys::{KeyReader, Properties as KeyReaderProperties},
     picker::{FilePicker, FileSource, Properties as FilePickerProperties},
+    validate::Validator,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -32,6 +48,11 @@ pub struct Theme {
     pub item_unfocused_background: Background,
     pub item_file_foreground: Foreground,
     pub item_directory_foreground: Foreground,
+    pub item_match_foreground: Foreground,
+    /// The input when it's invalid, and what's wrong with it
+    pub error: Foreground,
+    /// A note on valid input
+    pub hint: Foreground,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -43,11 +64,24 @@ pub enum Action {
     PickBuffer {
         message: Cow<'static, str>,
         entries: Vec<BufferEntry>,
-        on_select: Callback<BufferId>,
+        on_select: Callback<BufferEntry>,
+        on_change_height: Callback<usize>,
+    },
+    /// A picker defined by the feature opening it, see `Action::pick`
+    Pick {
+        definition: PickerDefinition,
+        /// What the definition's source listed when the picker opened
+        candidates: Rc<[String]>,
         on_change_height: Callback<usize>,
     },
     OpenFile {
         source: FileSource,
+        message: Option<Cow<'static, str>>,
+        directory: Option<PathBuf>,
+        recent_files: Arc<RecentFiles>,
+        /// Remembers the paths opened, offering them again with `A-p` and
+        /// `A-n`
+        history: Option<HistoryKind>,
         on_open: Callback<PathBuf>,
         on_change_height: Callback<usize>,
     },
@@ -55,9 +89,49 @@ pub enum Action {
         message: Cow<'static, str>,
         on_input: Callback<bool>,
     },
+    Input {
+        message: Cow<'static, str>,
+        on_submit: Callback<String>,
+        validator: Option<Validator>,
+        /// Remembers the inputs submitted, offering them again with Up and
+        /// Down
+        history: Option<HistoryKind>,
+    },
+    /// Reads a line of text like `Input`, telling about every change to it,
+    /// e.g. to preview what it would do
+    IncrementalInput {
+        message: Cow<'static, str>,
+        on_change: Callback<String>,
+        on_submit: Callback<String>,
+        validator: Option<Validator>,
+        history: Option<HistoryKind>,
+    },
+    Choice {
+        message: Cow<'static, str>,
+        choices: &'static [Choice],
+        on_choice: Callback<Option<char>>,
+    },
+    /// Reads the keys pressed, one at a time, without running the commands
+    /// bound to them
+    ReadKeys {
+        message: Cow<'static, str>,
+        /// The keys read so far
+        keys: Vec<Key>,
+        on_key: Callback<Key>,
+    },
 }
 
 impl Action {
+    /// Opens a picker, listing the candidates of its source
+    pub fn pick(definition: PickerDefinition, on_change_height: Callback<usize>) -> Self {
+        let candidates = definition.source.emit(());
+        Self::Pick {
+            definition,
+            candidates,
+            on_change_height,
+        }
+    }
+
     pub fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
@@ -70,11 +144,16 @@ impl Action {
         matches!(self, Self::Log { .. })
     }
 
+    pub fn is_read_keys(&self) -> bool {
+        matches!(self, Self::ReadKeys { .. })
+    }
+
     pub fn initial_height(&self) -> usize {
         match self {
             Self::PickBuffer { ref entries, .. } => {
                 1 + std::cmp::min(std::cmp::max(entries.len(), 1), PROMPT_MAX_HEIGHT)
             }
+            Self::Pick { ref candidates, .. } => 1 + candidates.len().clamp(1, PROMPT_MAX_HEIGHT),
             _ => 1,
         }
     }
@@ -85,12 +164,25 @@ pub struct Properties {
     pub context: ContextHandle,
     pub theme: Cow<'static, Theme>,
     pub action: Action,
+    pub history: Arc<PromptHistory>,
+    /// Called with the inputs submitted to prompts keeping a history
+    pub on_history: Callback<(HistoryKind, String)>,
 }
 
 pub struct Prompt {
     properties: Properties,
 }
 
+impl Prompt {
+    fn input_history(&self, kind: Option<HistoryKind>) -> Option<InputHistory> {
+        kind.map(|kind| InputHistory {
+            kind,
+            history: self.properties.history.clone(),
+            on_push: self.properties.on_history.clone(),
+        })
+    }
+}
+
 impl Component for Prompt {
     type Message = ();
     type Properties = Properties;
@@ -137,14 +229,40 @@ impl Component for Prompt {
                     on_filter,
                 })
             }
+            Action::Pick {
+                definition,
+                candidates,
+                on_change_height,
+            } => {
+                let on_change_height = on_change_height.clone();
+                let on_filter = (move |size: usize| {
+                    on_change_height.emit(1 + size.clamp(1, PROMPT_MAX_HEIGHT));
+                })
+                .into();
+
+                ItemPicker::with(ItemPickerProperties {
+                    theme: self.properties.theme.clone(),
+                    definition: definition.clone(),
+                    candidates: candidates.clone(),
+                    on_filter,
+                })
+            }
             Action::OpenFile {
                 source,
+                message,
+                directory,
+                recent_files,
+                history,
                 on_change_height,
                 on_open,
             } => FilePicker::with(FilePickerProperties {
                 context: self.properties.context.clone(),
                 theme: self.properties.theme.clone(),
                 source: *source,
+                message: message.clone(),
+                directory: directory.clone(),
+                recent_files: recent_files.clone(),
+                history: self.input_history(*history),
                 on_open: on_open.clone(),
                 on_change_height: on_change_height.clone(),
             }),
@@ -155,6 +273,52 @@ impl Component for Prompt {
                     message: message.to_string(),
                 })
             }
+            Action::Input {
+                message,
+                on_submit,
+                validator,
+                history,
+            } => InputPrompt::with(InputPromptProperties {
+                context: self.properties.context.clone(),
+                theme: self.properties.theme.clone(),
+                message: message.clone(),
+                on_change: None,
+                on_submit: on_submit.clone(),
+                validator: validator.clone(),
+                history: self.input_history(*history),
+            }),
+            Action::IncrementalInput {
+                message,
+                on_change,
+                on_submit,
+                validator,
+                history,
+            } => InputPrompt::with(InputPromptProperties {
+                context: self.properties.context.clone(),
+                theme: self.properties.theme.clone(),
+                message: message.clone(),
+                on_change: Some(on_change.clone()),
+                on_submit: on_submit.clone(),
+                validator: validator.clone(),
+                history: self.input_history(*history),
+            }),
+            Action::Choice {
+                message,
+                choices,
+                on_choice,
+            } => ChoicePrompt::with(ChoicePromptProperties {
+                theme: self.properties.theme.clone(),
+                message: message.to_string(),
+                choices,
+                on_choice: on_choice.clone(),
+            }),
+            Action::ReadKeys {
+                message, on_key, ..
+            } => KeyReader::with(KeyReaderProperties {
+                theme: self.properties.theme.clone(),
+                message: message.to_string(),
+                on_key: on_key.clone(),
+            }),
         }
     }
 }
@@ -0,0 +1,83 @@
+use std::{ptr, rc::Rc};
+use zi::{
+    components::text::{Text, TextProperties},
+    Callback, ComponentExt, Container, FlexBasis, Item, Style,
+};
+
+use zee_grammar::{config::FilenamePattern, Mode};
+
+use super::items::{PickerDefinition, RenderCandidate};
+
+/// A mode which can be chosen for a buffer
+#[derive(Clone, Copy, Debug)]
+pub struct ModeEntry {
+    pub mode: &'static Mode,
+    pub current: bool,
+}
+
+impl PartialEq for ModeEntry {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.mode, other.mode) && self.current == other.current
+    }
+}
+
+/// A picker of the configured modes filtered by name, for changing the
+/// language of a buffer
+pub fn mode_picker(entries: Rc<[ModeEntry]>, on_select: Callback<ModeEntry>) -> PickerDefinition {
+    let names: Rc<[String]> = entries
+        .iter()
+        .map(|entry| entry.mode.name.clone())
+        .collect();
+    let render_entries = entries.clone();
+    PickerDefinition::items("mode", names, move |index: usize| {
+        on_select.emit(entries[index])
+    })
+    .render(move |candidate: RenderCandidate| {
+        let ModeEntry { mode, current } = render_entries[candidate.index];
+        let RenderCandidate {
+            background,
+            ref theme,
+            ..
+        } = candidate;
+        let key = mode.scope.as_str();
+        Item::fixed(1)(Container::row([
+            Text::item_with_key(
+                FlexBasis::Fixed(24),
+                format!("{}name", key).as_str(),
+                TextProperties::new()
+                    .content(if current {
+                        format!("{} (current)", mode.name)
+                    } else {
+                        mode.name.clone()
+                    })
+                    .style(Style::normal(background, theme.item_file_foreground)),
+            ),
+            Text::item_with_key(
+                FlexBasis::Fixed(20),
+                format!("{}scope", key).as_str(),
+                TextProperties::new()
+                    .content(mode.scope.clone())
+                    .style(Style::normal(background, theme.mode)),
+            ),
+            Text::item_with_key(
+                FlexBasis::Auto,
+                format!("{}patterns", key).as_str(),
+                TextProperties::new()
+                    .content(format_patterns(&mode.patterns))
+                    .style(Style::normal(background, theme.file_size)),
+            ),
+        ]))
+    })
+    .empty_messages("No matching modes", "No modes")
+}
+
+fn format_patterns(patterns: &[FilenamePattern]) -> String {
+    patterns
+        .iter()
+        .map(|pattern| match pattern {
+            FilenamePattern::Suffix(suffix) => format!("*{}", suffix),
+            FilenamePattern::Name(name) => name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
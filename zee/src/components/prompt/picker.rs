@@ -1,29 +1,39 @@
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-use ignore::WalkBuilder;
+use ignore::{WalkBuilder, WalkState};
 use ropey::Rope;
 use std::{
     borrow::Cow,
-    cmp, fmt, fs,
+    cmp,
+    collections::HashMap,
+    iter, mem,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
+use zee_matcher::{split_matched, MatchMode, Matcher};
 use zi::{
     components::{
         input::{Cursor, Input, InputChange, InputProperties, InputStyle},
         select::{Select, SelectProperties},
-        text::{Text, TextProperties},
+        text::{Text, TextAlign, TextProperties},
     },
     prelude::*,
+    unicode_width::UnicodeWidthStr,
     Callback,
 };
 
 use super::{
+    expand::{expand_path, Glob},
+    input::InputHistory,
     status::{Status, StatusProperties},
     Theme, PROMPT_MAX_HEIGHT,
 };
 use crate::{
-    editor::ContextHandle,
-    error::{Context as _Context, Result},
+    editor::{ContextHandle, RecordedBindings},
+    recent::RecentFiles,
     task::TaskId,
     utils::ensure_trailing_newline_with_content,
 };
@@ -38,6 +48,7 @@ pub struct FileListingDone {
 pub enum FileSource {
     Directory,
     Repository,
+    Recent,
 }
 
 impl FileSource {
@@ -45,6 +56,7 @@ impl FileSource {
         match self {
             Self::Directory => "open",
             Self::Repository => "repo",
+            Self::Recent => "recent",
         }
         .into()
     }
@@ -52,7 +64,10 @@ impl FileSource {
 
 #[derive(Debug)]
 pub enum Message {
-    FileListingDone(Result<FileListingDone>),
+    FileListingDone(FileListingDone),
+    /// A batch of paths found by the walk with the given task id
+    FilesFound(TaskId, Vec<PathBuf>),
+    WalkDone(TaskId),
     OpenFile,
 
     // Path navigation
@@ -60,6 +75,10 @@ pub enum Message {
     ChangePath(InputChange),
     ChangeSelectedFile(usize),
     SelectParentDirectory,
+    ToggleMatchMode,
+    ToggleHiddenFiles,
+    PreviousHistory,
+    NextHistory,
 }
 
 #[derive(Clone)]
@@ -67,6 +86,14 @@ pub struct Properties {
     pub context: ContextHandle,
     pub theme: Cow<'static, Theme>,
     pub source: FileSource,
+    /// Shown instead of the name of the source, e.g. `insert file`
+    pub message: Option<Cow<'static, str>>,
+    /// The directory to list, the current working directory if `None`
+    pub directory: Option<PathBuf>,
+    pub recent_files: Arc<RecentFiles>,
+    /// Paths opened earlier, gone through with `A-p` and `A-n`, if kept. The
+    /// recent files picker doesn't use it, its input is only a filter.
+    pub history: Option<InputHistory>,
     pub on_open: Callback<PathBuf>,
     pub on_change_height: Callback<usize>,
 }
@@ -79,27 +106,144 @@ pub struct FilePicker {
     listing: Rc<FileListing>,
     selected_index: usize,
     current_task_id: Option<TaskId>,
+    walk: Option<Walk>,
+    /// The index of the history entry shown, `None` when showing what was
+    /// typed
+    history_index: Option<usize>,
+    /// What was typed before going through the history
+    typed: Rope,
+}
+
+/// A walk listing the files of a directory in the background
+struct Walk {
+    task_id: TaskId,
+    cancelled: Arc<AtomicBool>,
 }
 
 impl FilePicker {
     fn list_files(&mut self, source: FileSource) {
+        if source == FileSource::Recent {
+            self.cancel_walk();
+            return self.filter_files(source, self.input.to_string());
+        }
+        let path_str = expand_path(&self.input.to_string()).into_owned();
+        // A glob pattern lists the paths matching it instead, unless there's a
+        // file named like it, e.g. `notes[1].md`
+        let glob =
+            Glob::parse(path_str.trim_end()).filter(|_| !Path::new(path_str.trim_end()).exists());
+        if let Some(glob) = glob {
+            if self.listing.prefix() != glob.directory
+                || self.listing.glob() != Some(glob.pattern.as_str())
+            {
+                self.walk_files(source, "", glob.directory.clone(), Some(glob));
+            }
+            return;
+        }
+        let prefix = Path::new(&path_str).parent().unwrap();
+        if self.listing.prefix() != prefix || self.listing.glob().is_some() {
+            let prefix = prefix.to_path_buf();
+            self.walk_files(source, &path_str, prefix, None);
+        } else {
+            self.filter_files(source, path_str);
+        }
+    }
+
+    /// Filters the current listing in the background
+    fn filter_files(&mut self, source: FileSource, path_str: String) {
         let link = self.link.clone();
-        let input = self.input.clone();
         let mut listing = (*self.listing).clone();
         self.current_task_id = Some(self.properties.context.task_pool.spawn(move |task_id| {
-            let path_str = input.to_string();
-            link.send(Message::FileListingDone(match source {
-                FileSource::Directory => pick_from_directory(&mut listing, path_str)
-                    .map(|_| FileListingDone { task_id, listing }),
-                FileSource::Repository => pick_from_repository(&mut listing, path_str)
-                    .map(|_| FileListingDone { task_id, listing }),
+            match source {
+                FileSource::Recent => pick_from_recent(&mut listing, &path_str),
+                FileSource::Directory | FileSource::Repository => listing.set_filter(&path_str),
+            }
+            link.send(Message::FileListingDone(FileListingDone {
+                task_id,
+                listing,
             }))
         }))
     }
 
+    /// Empties the listing and starts walking the directory the input is in,
+    /// or the directory of a glob pattern. The files found are added to the
+    /// listing in batches as they arrive.
+    fn walk_files(
+        &mut self,
+        source: FileSource,
+        path_str: &str,
+        prefix: PathBuf,
+        glob: Option<Glob>,
+    ) {
+        self.cancel_walk();
+        self.current_task_id = None;
+        let listing = Rc::make_mut(&mut self.listing);
+        listing.reset(iter::empty(), path_str, &prefix);
+        listing.set_glob(glob.as_ref().map(|glob| glob.pattern.clone()));
+        self.selected_index = 0;
+
+        let link = self.link.clone();
+        let show_hidden = listing.show_hidden();
+        let max_depth = self.properties.context.config.file_picker_max_depth;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let task_cancelled = cancelled.clone();
+        let task_id = self.properties.context.task_pool.spawn(move |task_id| {
+            walk_directory(
+                &prefix,
+                source,
+                glob.as_ref(),
+                show_hidden,
+                max_depth,
+                &task_cancelled,
+                || Batch::new(link.clone(), task_id),
+            );
+            link.send(Message::WalkDone(task_id))
+        });
+        self.walk = Some(Walk { task_id, cancelled });
+    }
+
+    fn cancel_walk(&mut self) {
+        if let Some(walk) = self.walk.take() {
+            walk.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
     fn height(&self) -> usize {
         1 + cmp::min(self.listing.num_filtered(), PROMPT_MAX_HEIGHT)
     }
+
+    fn history(&self) -> Option<&InputHistory> {
+        self.properties
+            .history
+            .as_ref()
+            .filter(|_| self.properties.source != FileSource::Recent)
+    }
+
+    /// Shows the history entry at an index, or what was typed if `None`
+    fn show_history(&mut self, index: Option<usize>) {
+        self.input = match (index, self.history()) {
+            (Some(index), Some(history)) => format!("{}\n", history.entries()[index]).into(),
+            _ => self.typed.clone(),
+        };
+        self.history_index = index;
+        self.cursor = Cursor::new();
+        self.cursor.move_to_end_of_line(&self.input);
+        self.selected_index = 0;
+    }
+
+    fn open(&self, path: PathBuf) {
+        if let Some(history) = self.history() {
+            history
+                .on_push
+                .emit((history.kind, path.to_string_lossy().into_owned()));
+        }
+        self.properties.on_open.emit(path);
+    }
+}
+
+impl Drop for FilePicker {
+    fn drop(&mut self) {
+        self.cancel_walk();
+    }
 }
 
 impl Component for FilePicker {
@@ -108,24 +252,35 @@ impl Component for FilePicker {
 
     fn create(properties: Self::Properties, _frame: Rect, link: ComponentLink<Self>) -> Self {
         let mut cursor = Cursor::new();
-        let mut current_working_dir: String = properties
-            .context
-            .current_working_dir
-            .to_string_lossy()
-            .into();
-        current_working_dir.push('/');
-        current_working_dir.push('\n');
-        let input = current_working_dir.into();
+        let input = if properties.source == FileSource::Recent {
+            // Recent files can live anywhere, start with an empty filter
+            "\n".into()
+        } else {
+            let mut directory: String = properties
+                .directory
+                .as_deref()
+                .unwrap_or(&properties.context.current_working_dir)
+                .to_string_lossy()
+                .into();
+            directory.push('/');
+            directory.push('\n');
+            directory.into()
+        };
         cursor.move_to_end_of_line(&input);
+        let typed = input.clone();
 
+        let listing = Rc::new(FileListing::new(properties.recent_files.clone()));
         let mut picker = Self {
             properties,
             link,
             input,
             cursor,
-            listing: Rc::new(FileListing::new()),
+            listing,
             selected_index: 0,
             current_task_id: None,
+            walk: None,
+            history_index: None,
+            typed,
         };
         picker.list_files(picker.properties.source);
         picker.properties.on_change_height.emit(picker.height());
@@ -145,9 +300,32 @@ impl Component for FilePicker {
         let initial_height = self.height();
         let input_changed = match message {
             Message::OpenFile => {
+                // The input of the recent files picker is only a filter, open the selected file
+                let selected = match self.properties.source {
+                    FileSource::Recent => self.listing.selected(self.selected_index),
+                    FileSource::Directory | FileSource::Repository => None,
+                };
                 let path_str: Cow<str> = self.input.slice(..).into();
-                let path = PathBuf::from(path_str.trim());
-                self.properties.on_open.emit(path);
+                let typed_path = PathBuf::from(expand_path(path_str.trim()).as_ref());
+                if self.listing.glob().is_some() {
+                    // A glob pattern picks among the paths matching it. If
+                    // none do, the path typed is a new file, e.g. `notes[1].md`
+                    match self.listing.selected(self.selected_index) {
+                        Some(path) => self.open(path.to_path_buf()),
+                        None if self.walk.is_none() => {
+                            self.open(typed_path);
+                            self.properties.context.log(format!(
+                                "No files match `{}`, opened as a new file",
+                                path_str.trim()
+                            ));
+                        }
+                        None => {}
+                    }
+                } else if let Some(path) = selected {
+                    self.open(path.to_path_buf());
+                } else {
+                    self.open(typed_path);
+                }
                 false
             }
             Message::SelectParentDirectory => {
@@ -181,6 +359,8 @@ impl Component for FilePicker {
             Message::ChangePath(InputChange { content, cursor }) => {
                 self.cursor = cursor;
                 if let Some(content) = content {
+                    self.history_index = None;
+                    self.typed = content.clone();
                     self.input = content;
                     true
                 } else {
@@ -191,19 +371,68 @@ impl Component for FilePicker {
                 self.selected_index = index;
                 false
             }
-            Message::FileListingDone(Ok(FileListingDone { task_id, listing }))
-                if self
-                    .current_task_id
-                    .as_ref()
-                    .map(|&expected_task_id| expected_task_id == task_id)
-                    .unwrap_or(false) =>
+            Message::ToggleMatchMode => {
+                let listing = Rc::make_mut(&mut self.listing);
+                listing.set_match_mode(listing.match_mode().toggle());
+                self.selected_index = 0;
+                true
+            }
+            Message::ToggleHiddenFiles => {
+                let listing = Rc::make_mut(&mut self.listing);
+                listing.set_show_hidden(!listing.show_hidden());
+                self.selected_index = 0;
+                true
+            }
+            Message::PreviousHistory => {
+                let num_entries = self.history().map_or(0, |history| history.entries().len());
+                let index = self.history_index.map_or(0, |index| index + 1);
+                if index < num_entries {
+                    self.show_history(Some(index));
+                }
+                index < num_entries
+            }
+            Message::NextHistory => match self.history_index {
+                Some(index) => {
+                    self.show_history(index.checked_sub(1));
+                    true
+                }
+                None => false,
+            },
+            Message::FileListingDone(FileListingDone {
+                task_id,
+                mut listing,
+            }) if self
+                .current_task_id
+                .as_ref()
+                .map(|&expected_task_id| expected_task_id == task_id)
+                .unwrap_or(false) =>
             {
+                // Files found by the walk while filtering were only added to
+                // the current listing
+                if listing.prefix() == self.listing.prefix() {
+                    listing.extend(self.listing.paths[listing.paths.len()..].to_vec());
+                }
                 self.listing = Rc::new(listing);
                 self.current_task_id = None;
                 self.selected_index = 0;
 
                 false
             }
+            Message::FilesFound(task_id, paths)
+                if self.walk.as_ref().map(|walk| walk.task_id) == Some(task_id) =>
+            {
+                Rc::make_mut(&mut self.listing).extend(paths);
+                self.selected_index = self
+                    .selected_index
+                    .min(self.listing.num_filtered().saturating_sub(1));
+                false
+            }
+            Message::WalkDone(task_id)
+                if self.walk.as_ref().map(|walk| walk.task_id) == Some(task_id) =>
+            {
+                self.walk = None;
+                false
+            }
             _ => {
                 return ShouldRender::No;
             }
@@ -231,6 +460,11 @@ impl Component for FilePicker {
             on_change: Some(self.link.callback(Message::ChangePath)),
             focused: true,
         });
+        let action_name = self
+            .properties
+            .message
+            .clone()
+            .unwrap_or_else(|| self.properties.source.status_name());
 
         let listing = self.listing.clone();
         let selected_index = self.selected_index;
@@ -247,14 +481,44 @@ impl Component for FilePicker {
             } else {
                 Style::normal(background, theme.item_file_foreground)
             };
-            let content = &path.to_string_lossy()[listing
+            let match_style = Style::bold(background, theme.item_match_foreground);
+
+            let path_str = path.to_string_lossy();
+            let prefix_len = listing
                 .prefix()
                 .to_str()
+                .filter(|prefix| !prefix.is_empty())
                 .map(|prefix| prefix.len() + 1)
-                .unwrap_or(0)..];
-            Item::fixed(1)(Text::with_key(
-                content,
-                TextProperties::new().content(content).style(style),
+                .unwrap_or(0);
+            let content = &path_str[prefix_len..];
+
+            // Highlight the parts of the path matched by the filter
+            let num_prefix_chars = path_str[..prefix_len].chars().count();
+            let indices: Vec<_> = listing
+                .matched_indices(index)
+                .into_iter()
+                .filter_map(|index| index.checked_sub(num_prefix_chars))
+                .collect();
+            let runs = split_matched(content, &indices);
+            Item::fixed(1)(Container::row(
+                runs.into_iter()
+                    .enumerate()
+                    .map(|(run_index, (run, matched))| {
+                        Text::item_with_key(
+                            FlexBasis::Fixed(run.width()),
+                            format!("{}.{}", content, run_index).as_str(),
+                            TextProperties::new().content(run).style(if matched {
+                                match_style
+                            } else {
+                                style
+                            }),
+                        )
+                    })
+                    .chain(std::iter::once(Text::item_with_key(
+                        FlexBasis::Auto,
+                        format!("{}.end", content).as_str(),
+                        TextProperties::new().style(style),
+                    ))),
             ))
         };
         Layout::column([
@@ -272,20 +536,37 @@ impl Component for FilePicker {
                 item_size: 1,
             })),
             Item::fixed(1)(Container::row([
-                Item::fixed(4)(Status::with(StatusProperties {
-                    action_name: self.properties.source.status_name(),
-                    pending: self.current_task_id.is_some(),
+                Item::fixed(action_name.width())(Status::with(StatusProperties {
+                    action_name,
+                    pending: self.current_task_id.is_some() || self.walk.is_some(),
                     style: self.properties.theme.action,
                 })),
                 Item::fixed(1)(Text::with(
                     TextProperties::new().style(self.properties.theme.input),
                 )),
                 Item::auto(input),
+                Text::item_with_key(
+                    FlexBasis::Fixed(FILTER_STATUS_WIDTH),
+                    "filter-status",
+                    TextProperties::new()
+                        .content(format!(
+                            "{}{} ",
+                            self.listing.match_mode().name(),
+                            if self.listing.show_hidden() {
+                                " +hidden"
+                            } else {
+                                ""
+                            }
+                        ))
+                        .style(self.properties.theme.action.invert())
+                        .align(TextAlign::Right),
+                ),
             ])),
         ])
     }
 
     fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
         if !bindings.is_empty() {
             return;
         }
@@ -299,46 +580,41 @@ impl Component for FilePicker {
         bindings.add("autocomplete-path", [Key::Char('\t')], || {
             Message::AutocompletePath
         });
+        bindings.add("toggle-match-mode", [Key::Alt('m')], || {
+            Message::ToggleMatchMode
+        });
+        bindings.add("toggle-hidden-files", [Key::Alt('.')], || {
+            Message::ToggleHiddenFiles
+        });
+        // Up and Down move through the listed files
+        bindings.add("previous-history", [Key::Alt('p')], || {
+            Message::PreviousHistory
+        });
+        bindings.add("next-history", [Key::Alt('n')], || Message::NextHistory);
     }
 }
 
+#[derive(Clone, Debug)]
 struct FileListing {
     paths: Vec<PathBuf>,
-    filtered: Vec<(usize, i64)>, // (index, score)
-    matcher: Box<SkimMatcherV2>, // Boxed as it's big and we store a FileListing in an enum variant
+    matcher: Matcher,
     prefix: PathBuf,
-}
-
-impl fmt::Debug for FileListing {
-    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter
-            .debug_struct("FileListing")
-            .field("paths", &self.paths)
-            .field("filtered", &self.filtered)
-            .field("matcher", &"SkimMatcherV2(...)")
-            .field("prefix", &self.prefix)
-            .finish()
-    }
-}
-
-impl Clone for FileListing {
-    fn clone(&self) -> Self {
-        Self {
-            paths: self.paths.clone(),
-            filtered: self.filtered.clone(),
-            matcher: Default::default(),
-            prefix: self.prefix.clone(),
-        }
-    }
+    /// The glob pattern the paths were listed with, matched relative to the
+    /// prefix
+    glob: Option<String>,
+    show_hidden: bool,
+    recent_files: Arc<RecentFiles>,
 }
 
 impl FileListing {
-    pub fn new() -> Self {
+    pub fn new(recent_files: Arc<RecentFiles>) -> Self {
         Self {
             paths: Vec::new(),
-            filtered: Vec::new(),
-            matcher: Default::default(),
+            matcher: Matcher::new(),
             prefix: PathBuf::new(),
+            glob: None,
+            show_hidden: false,
+            recent_files,
         }
     }
 
@@ -346,24 +622,55 @@ impl FileListing {
         self.prefix.as_path()
     }
 
+    pub fn glob(&self) -> Option<&str> {
+        self.glob.as_deref()
+    }
+
+    pub fn set_glob(&mut self, glob: Option<String>) {
+        self.glob = glob;
+    }
+
     pub fn num_filtered(&self) -> usize {
-        self.filtered.len()
+        self.matcher.num_ranked()
+    }
+
+    pub fn match_mode(&self) -> MatchMode {
+        self.matcher.mode()
+    }
+
+    pub fn set_match_mode(&mut self, mode: MatchMode) {
+        self.matcher.set_mode(mode);
+    }
+
+    /// Whether hidden files and files excluded by `.gitignore` are listed
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+        // Force listing the files again
+        self.prefix.clear();
     }
 
     pub fn set_filter(&mut self, filter: &str) {
-        let Self {
-            ref mut paths,
-            ref mut filtered,
-            ref mut matcher,
-            ..
-        } = *self;
-        filtered.clear();
-        filtered.extend(paths.iter().enumerate().filter_map(|(index, file)| {
-            matcher
-                .fuzzy_match(&file.to_string_lossy(), filter.trim())
-                .map(|score| (index, score))
-        }));
-        filtered.sort_unstable_by_key(|(_, score)| -score);
+        self.matcher.set_filter(filter);
+    }
+
+    /// Appends paths to the listing, ranking them with the current filter
+    pub fn extend(&mut self, new_paths: Vec<PathBuf>) {
+        let first_index = self.paths.len();
+        let num_new = new_paths
+            .len()
+            .min(MAX_FILES_IN_PICKER.saturating_sub(first_index));
+        self.paths.extend(new_paths.into_iter().take(num_new));
+
+        let bonus = recency_bonus(&self.recent_files);
+        self.matcher.extend_candidates_with_bonus(
+            self.paths[first_index..]
+                .iter()
+                .map(|path| (path.to_string_lossy(), bonus(path))),
+        );
     }
 
     pub fn reset(
@@ -374,86 +681,176 @@ impl FileListing {
     ) {
         let Self {
             ref mut paths,
+            ref mut matcher,
             ref mut prefix,
+            ref mut glob,
+            ref recent_files,
             ..
         } = *self;
         paths.clear();
         paths.extend(paths_iter);
+        let bonus = recency_bonus(recent_files);
+        matcher.set_candidates_with_bonus(
+            paths
+                .iter()
+                .map(|path| (path.to_string_lossy(), bonus(path))),
+        );
+        matcher.set_filter(filter);
         prefix.clear();
         prefix.push(prefix_path);
-        self.set_filter(filter);
+        *glob = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn recent_files(&self) -> &Arc<RecentFiles> {
+        &self.recent_files
     }
 
     pub fn selected(&self, filtered_index: usize) -> Option<&Path> {
-        self.filtered
-            .get(filtered_index)
-            .map(|(index, _)| self.paths[*index].as_path())
+        (filtered_index < self.matcher.num_ranked())
+            .then(|| self.paths[self.matcher[filtered_index]].as_path())
+    }
+
+    /// The char indices of the path at `filtered_index` matched by the
+    /// current filter
+    pub fn matched_indices(&self, filtered_index: usize) -> Vec<usize> {
+        self.matcher.matched_indices(filtered_index)
     }
 }
 
-fn update_listing<FilesIterT>(
-    listing: &mut FileListing,
-    path_str: String,
-    files_iter: impl FnOnce(String) -> Result<FilesIterT>,
-) -> Result<()>
-where
-    FilesIterT: Iterator<Item = PathBuf>,
-{
-    let prefix = Path::new(&path_str).parent().unwrap();
-    if listing.prefix() != prefix {
+/// Recently opened files get a bonus which decreases with how long ago they
+/// were opened
+fn recency_bonus(recent_files: &RecentFiles) -> impl Fn(&Path) -> i64 + '_ {
+    let num_recent = recent_files.len() as i64;
+    let recent_ranks: HashMap<&Path, i64> = recent_files
+        .iter()
+        .enumerate()
+        .map(|(rank, path)| (path, rank as i64))
+        .collect();
+    move |path| {
+        recent_ranks
+            .get(path)
+            .map(|rank| RECENCY_BONUS * (num_recent - rank) / num_recent)
+            .unwrap_or(0)
+    }
+}
+
+fn pick_from_recent(listing: &mut FileListing, path_str: &str) {
+    if listing.is_empty() {
+        let recent_files = listing.recent_files().clone();
         listing.reset(
-            files_iter(path_str.clone())?.take(MAX_FILES_IN_PICKER),
-            &path_str,
-            &prefix,
+            recent_files
+                .iter()
+                .filter(|path| path.exists())
+                .map(Path::to_path_buf),
+            path_str,
+            "",
         );
     } else {
-        listing.set_filter(&path_str)
+        listing.set_filter(path_str);
     }
-    Ok(())
 }
 
-fn pick_from_directory(listing: &mut FileListing, path_str: String) -> Result<()> {
-    update_listing(listing, path_str, |path| {
-        Ok(directory_files_iter(path)?.filter_map(|result_path| result_path.ok()))
-    })
+/// Walks the directory in parallel, skipping hidden files and files excluded by
+/// `.gitignore` unless `show_hidden` is set. With a glob pattern, only the
+/// paths matching it are kept. Each thread sends the paths it finds in
+/// batches, the walk stops early once the picker is full or when it is
+/// cancelled.
+fn walk_directory(
+    prefix: &Path,
+    source: FileSource,
+    glob: Option<&Glob>,
+    show_hidden: bool,
+    max_depth: Option<usize>,
+    cancelled: &AtomicBool,
+    make_batch: impl Fn() -> Batch,
+) {
+    let (max_depth, include_dirs) = match (glob, source) {
+        (Some(glob), _) => (glob.max_depth().or(max_depth), true),
+        (None, FileSource::Directory) => (Some(1), true),
+        (None, FileSource::Repository | FileSource::Recent) => (max_depth, false),
+    };
+    let num_found = AtomicUsize::new(0);
+    WalkBuilder::new(prefix)
+        .standard_filters(!show_hidden)
+        .max_depth(max_depth)
+        .build_parallel()
+        .run(|| {
+            let mut batch = make_batch();
+            let num_found = &num_found;
+            Box::new(move |entry| {
+                if cancelled.load(Ordering::Relaxed) {
+                    return WalkState::Quit;
+                }
+                let entry = match entry {
+                    // The first entry is the directory itself
+                    Ok(entry) if entry.depth() > 0 => entry,
+                    _ => return WalkState::Continue,
+                };
+                let is_dir = entry.path().is_dir();
+                if !include_dirs && is_dir {
+                    return WalkState::Continue;
+                }
+                if glob.map_or(false, |glob| !glob.is_match(entry.path(), is_dir)) {
+                    return WalkState::Continue;
+                }
+                if num_found.fetch_add(1, Ordering::Relaxed) >= MAX_FILES_IN_PICKER {
+                    return WalkState::Quit;
+                }
+                batch.push(entry.into_path());
+                WalkState::Continue
+            })
+        });
 }
 
-fn pick_from_repository(listing: &mut FileListing, path_str: String) -> Result<()> {
-    update_listing(listing, path_str, |path| {
-        Ok(repository_files_iter(path).filter_map(|result_path| result_path.ok()))
-    })
+/// Paths found by one thread of a walk, sent to the picker when enough of
+/// them have accumulated or enough time has passed since the last batch
+struct Batch {
+    paths: Vec<PathBuf>,
+    last_sent: Instant,
+    link: ComponentLink<FilePicker>,
+    task_id: TaskId,
 }
 
-fn directory_files_iter(path: impl AsRef<Path>) -> Result<impl Iterator<Item = Result<PathBuf>>> {
-    Ok(
-        fs::read_dir(path.as_ref().parent().unwrap_or_else(|| path.as_ref())).map(|walk| {
-            walk.map(|entry| {
-                entry
-                    .map(|entry| entry.path())
-                    .context("Cannot read entry while walking directory")
-            })
-        })?,
-    )
+impl Batch {
+    fn new(link: ComponentLink<FilePicker>, task_id: TaskId) -> Self {
+        Self {
+            paths: Vec::new(),
+            last_sent: Instant::now(),
+            link,
+            task_id,
+        }
+    }
+
+    fn push(&mut self, path: PathBuf) {
+        self.paths.push(path);
+        if self.paths.len() >= FILE_BATCH_SIZE || self.last_sent.elapsed() >= FILE_BATCH_INTERVAL {
+            self.send();
+        }
+    }
+
+    fn send(&mut self) {
+        if !self.paths.is_empty() {
+            self.link.send(Message::FilesFound(
+                self.task_id,
+                mem::take(&mut self.paths),
+            ));
+        }
+        self.last_sent = Instant::now();
+    }
 }
 
-fn repository_files_iter(path: impl AsRef<Path>) -> impl Iterator<Item = Result<PathBuf>> {
-    WalkBuilder::new(path.as_ref().parent().unwrap_or_else(|| path.as_ref()))
-        .build()
-        .filter_map(|entry| {
-            let is_dir = entry
-                .as_ref()
-                .map(|entry| entry.path().is_dir())
-                .unwrap_or(false);
-            if entry.is_ok() && !is_dir {
-                Some(
-                    entry
-                        .map(|entry| entry.path().to_path_buf())
-                        .context("Cannot read entry while walking directory"),
-                )
-            } else {
-                None
-            }
-        })
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.send();
+    }
 }
 
 const MAX_FILES_IN_PICKER: usize = 16384;
+const FILTER_STATUS_WIDTH: usize = 18;
+const RECENCY_BONUS: i64 = 100;
+const FILE_BATCH_SIZE: usize = 1024;
+const FILE_BATCH_INTERVAL: Duration = Duration::from_millis(50);
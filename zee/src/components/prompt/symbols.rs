@@ -0,0 +1,93 @@
+use std::{path::PathBuf, rc::Rc};
+use zi::{
+    components::text::{Text, TextAlign, TextProperties},
+    Callback, ComponentExt, Container, FlexBasis, Item, Style,
+};
+
+use super::items::{PickerDefinition, RenderCandidate};
+use crate::syntax::symbols::Symbol;
+
+/// A symbol, optionally defined in a file other than the current buffer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolEntry {
+    pub symbol: Symbol,
+    pub path: Option<PathBuf>,
+}
+
+/// A picker of symbols, either from a buffer or from a whole project,
+/// filtered by name. Paths are shown relative to `current_working_dir`.
+pub fn symbol_picker(
+    entries: Rc<[SymbolEntry]>,
+    current_working_dir: PathBuf,
+    on_select: Callback<SymbolEntry>,
+) -> PickerDefinition {
+    let names: Rc<[String]> = entries
+        .iter()
+        .map(|entry| entry.symbol.name.clone())
+        .collect();
+    let render_entries = entries.clone();
+    PickerDefinition::items("symbol", names, move |index: usize| {
+        on_select.emit(entries[index].clone())
+    })
+    .render(move |candidate: RenderCandidate| {
+        let SymbolEntry {
+            ref symbol,
+            ref path,
+        } = render_entries[candidate.index];
+        let RenderCandidate {
+            background,
+            ref theme,
+            ..
+        } = candidate;
+        let key = format!(
+            "{}:{}",
+            path.as_ref()
+                .map(|path| path.to_string_lossy())
+                .unwrap_or_default(),
+            symbol.char_index
+        );
+        Item::fixed(1)(Container::row([
+            Text::item_with_key(
+                FlexBasis::Fixed(8),
+                format!("{}line", key).as_str(),
+                TextProperties::new()
+                    .content(format!("{} ", symbol.line_index + 1))
+                    .style(Style::normal(background, theme.file_size))
+                    .align(TextAlign::Right),
+            ),
+            Text::item_with_key(
+                FlexBasis::Fixed(12),
+                format!("{}kind", key).as_str(),
+                TextProperties::new()
+                    .content(format!(" {}", symbol.kind))
+                    .style(Style::normal(background, theme.mode)),
+            ),
+            Text::item_with_key(
+                FlexBasis::Fixed(40),
+                format!("{}name", key).as_str(),
+                TextProperties::new()
+                    .content(symbol.name.clone())
+                    .style(Style::normal(background, theme.item_file_foreground)),
+            ),
+            Text::item_with_key(
+                FlexBasis::Auto,
+                format!("{}path", key).as_str(),
+                TextProperties::new()
+                    .content(
+                        path.as_ref()
+                            .map(|path| {
+                                format!(
+                                    "    {}",
+                                    path.strip_prefix(&current_working_dir)
+                                        .unwrap_or(path)
+                                        .display()
+                                )
+                            })
+                            .unwrap_or_default(),
+                    )
+                    .style(Style::normal(background, theme.file_size)),
+            ),
+        ]))
+    })
+    .empty_messages("No matching symbols", "No symbols found")
+}
@@ -0,0 +1,101 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use zi::Callback;
+
+/// What a validator makes of the text typed in an input prompt so far
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Validation {
+    Valid,
+    /// Valid, with a note shown after the input, e.g. the character a code
+    /// point stands for
+    Hint(Cow<'static, str>),
+    /// Shown after the input, which is coloured as an error. The input can
+    /// still be submitted, whatever reads it reports the error.
+    Invalid(Cow<'static, str>),
+}
+
+impl Validation {
+    /// Invalid if parsing the input failed
+    pub fn from_result<T, E: fmt::Display>(result: Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::Valid,
+            Err(error) => Self::Invalid(error.to_string().into()),
+        }
+    }
+
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, Self::Invalid(_))
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Valid => None,
+            Self::Hint(message) | Self::Invalid(message) => Some(message),
+        }
+    }
+}
+
+/// Checks the text of an input prompt as it's typed. Empty inputs are never
+/// checked.
+#[derive(Clone)]
+pub enum Validator {
+    /// A cheap check, run on every change, e.g. parsing a number
+    Immediate(Callback<String, Validation>),
+    /// An expensive check, run on the task pool, e.g. one touching the file
+    /// system. Only the result for the latest input is shown.
+    Background(Arc<dyn Fn(&str) -> Validation + Send + Sync>),
+}
+
+impl Validator {
+    pub fn immediate(validate: impl Fn(&str) -> Validation + 'static) -> Self {
+        Self::Immediate((move |input: String| validate(&input)).into())
+    }
+
+    pub fn background(validate: impl Fn(&str) -> Validation + Send + Sync + 'static) -> Self {
+        Self::Background(Arc::new(validate))
+    }
+
+    /// Checks a path a file is about to be written to, relative to a
+    /// directory: its parent directory has to exist, and an existing file is
+    /// pointed out as it would be overwritten
+    pub fn path_to_write(base_dir: PathBuf) -> Self {
+        Self::background(move |input| {
+            let path = base_dir.join(input.trim());
+            if path.is_dir() {
+                Validation::Invalid("is a directory".into())
+            } else if path.exists() {
+                Validation::Hint("exists, will be overwritten".into())
+            } else if !path.parent().map_or(true, Path::is_dir) {
+                Validation::Invalid("directory does not exist".into())
+            } else {
+                Validation::Valid
+            }
+        })
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Immediate(validate) => formatter
+                .debug_tuple("Validator::Immediate")
+                .field(validate)
+                .finish(),
+            Self::Background(_) => formatter.write_str("Validator::Background"),
+        }
+    }
+}
+
+impl PartialEq for Validator {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Immediate(left), Self::Immediate(right)) => left == right,
+            (Self::Background(left), Self::Background(right)) => Arc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
@@ -11,6 +11,12 @@ pub struct Theme {
     pub logo: Style,
     pub tagline: Style,
     pub credits: Style,
+    /// The names of the dashboard's sections
+    pub heading: Style,
+    pub item: Style,
+    pub item_selected: Style,
+    /// The keys of the dashboard's tips
+    pub key: Style,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -87,13 +93,40 @@ impl Component for Splash {
     }
 }
 
-fn text_block_size(text: &str) -> Size {
+/// Shown instead of the editor while the terminal is smaller than
+/// `min_size`, e.g. `Terminal too small (12x4, needs 20x5)`, wrapped to fit
+pub fn too_small(size: Size, min_size: Size, theme: &Theme) -> Layout {
+    let mut canvas = Canvas::new(size);
+    canvas.clear(theme.tagline);
+    let message = format!(
+        "Terminal too small ({}x{}, needs {}x{})",
+        size.width, size.height, min_size.width, min_size.height
+    );
+    let mut lines: Vec<String> = Vec::new();
+    for word in message.split(' ') {
+        match lines.last_mut() {
+            Some(line) if line.width() + 1 + word.width() <= size.width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_owned()),
+        }
+    }
+    let top = size.height.saturating_sub(lines.len()) / 2;
+    for (offset, line) in lines.iter().enumerate() {
+        let left = size.width.saturating_sub(line.width()) / 2;
+        canvas.draw_str(left, top + offset, theme.tagline, line);
+    }
+    canvas.into()
+}
+
+pub(super) fn text_block_size(text: &str) -> Size {
     let width = text.lines().map(UnicodeWidthStr::width).max().unwrap_or(0);
     let height = text.lines().count();
     Size::new(width, height)
 }
 
-const LOGO: &str = r#"
+pub(super) const LOGO: &str = r#"
 zzzzzzzzzzzzzzzzz     eeeeeeeeeeee         eeeeeeeeeeee
 z:::::::::::::::z   ee::::::::::::ee     ee::::::::::::ee
 z::::::::::::::z   e::::::eeeee:::::ee  e::::::eeeee:::::ee
@@ -107,7 +140,7 @@ zzzzzzzz::::::z   e::::::e     e:::::e e::::::e     e:::::e
 z:::::::::::::::z   ee:::::::::::::e     ee:::::::::::::e
 zzzzzzzzzzzzzzzzz     eeeeeeeeeeeeee       eeeeeeeeeeeeee
 "#;
-const TAGLINE: &str = "a modern editor for the terminal";
+pub(super) const TAGLINE: &str = "a modern editor for the terminal";
 
 static CREDITS: Lazy<String> = Lazy::new(|| {
     format!(
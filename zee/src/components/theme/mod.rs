@@ -4,14 +4,18 @@ pub use self::base16::Base16Theme;
 use zi::terminal::{Colour, Style};
 
 use super::{
-    buffer::{status_bar::Theme as StatusBarTheme, Theme as BufferTheme},
+    buffer::{
+        scrollbar::Theme as ScrollbarTheme, status_bar::Theme as StatusBarTheme,
+        Theme as BufferTheme,
+    },
+    completion::Theme as CompletionTheme,
     edit_tree_viewer::Theme as EditTreeViewerTheme,
     prompt::Theme as PromptTheme,
     splash::Theme as SplashTheme,
 };
 use crate::syntax::highlight::Theme as SyntaxTheme;
 
-pub const THEMES: [(Theme, &str); 31] = [
+pub static THEMES: [(Theme, &str); 31] = [
     (Theme::gruvbox(), "zee-gruvbox"),
     (
         Theme::from_base16(&base16::SOLARIZED_DARK),
@@ -123,6 +127,7 @@ impl Theme {
                     cursor_focused: normal(LIGHT0, DARK0),
                     cursor_unfocused: normal(GRAY_245, DARK0_HARD),
                     selection_background: DARK0_HARD,
+                    selection_end_of_line: normal(DARK1, LIGHT1),
                     text: normal(DARK0, LIGHT1),
                     text_current_line: normal(DARK0_HARD, LIGHT1),
                     code_char: normal(DARK0_SOFT, BRIGHT_GREEN),
@@ -139,6 +144,14 @@ impl Theme {
                     code_string: normal(DARK0_SOFT, BRIGHT_GREEN),
                     code_type: normal(DARK0_SOFT, BRIGHT_YELLOW),
                     code_variant: normal(DARK0_SOFT, BRIGHT_PURPLE),
+                    diff_added: normal(DARK0_SOFT, BRIGHT_GREEN),
+                    diff_removed: normal(DARK0_SOFT, BRIGHT_RED),
+                    diff_changed: normal(DARK0_SOFT, BRIGHT_AQUA),
+                    indent_guide: normal(DARK0, DARK1),
+                    annotation_error: normal(DARK0_SOFT, BRIGHT_RED),
+                    annotation_warning: normal(DARK0_SOFT, BRIGHT_YELLOW),
+                    annotation_info: normal(DARK0_SOFT, BRIGHT_BLUE),
+                    annotation_hint: normal(DARK0_SOFT, GRAY_245),
                 },
                 edit_tree_viewer: EditTreeViewerTheme {
                     current_revision: bold(DARK0, BRIGHT_RED),
@@ -148,6 +161,18 @@ impl Theme {
                     alternate_connector: normal(DARK0, DARK4),
                 },
                 border: normal(DARK0_HARD, GRAY_245),
+                scrollbar: ScrollbarTheme {
+                    track: normal(DARK0_HARD, GRAY_245),
+                    thumb: normal(DARK2, LIGHT1),
+                    search_match: BRIGHT_ORANGE,
+                },
+                completion: CompletionTheme {
+                    item: normal(DARK1, LIGHT1),
+                    item_selected: normal(DARK3, LIGHT0),
+                    kind: normal(DARK1, BRIGHT_AQUA),
+                    detail: normal(DARK1, GRAY_245),
+                    documentation: normal(DARK0_HARD, LIGHT2),
+                },
                 status_bar: StatusBarTheme {
                     base: normal(DARK0_SOFT, DARK0),
                     frame_id_focused: normal(BRIGHT_BLUE, DARK0_HARD),
@@ -157,6 +182,7 @@ impl Theme {
                     file_name: normal(DARK0_SOFT, BRIGHT_BLUE),
                     file_size: normal(DARK0_SOFT, GRAY_245),
                     position_in_file: normal(DARK0_SOFT, GRAY_245),
+                    selection: normal(DARK0_SOFT, BRIGHT_YELLOW),
                     mode: bold(DARK0_SOFT, BRIGHT_AQUA),
                 },
             },
@@ -164,6 +190,10 @@ impl Theme {
                 logo: normal(DARK0_SOFT, LIGHT2),
                 tagline: normal(DARK0_SOFT, BRIGHT_BLUE),
                 credits: normal(DARK0_SOFT, GRAY_245),
+                heading: bold(DARK0_SOFT, BRIGHT_YELLOW),
+                item: normal(DARK0_SOFT, LIGHT2),
+                item_selected: normal(DARK0_HARD, BRIGHT_BLUE),
+                key: normal(DARK0_SOFT, BRIGHT_AQUA),
             },
             prompt: PromptTheme {
                 input: normal(DARK0_HARD, NEUTRAL_YELLOW),
@@ -175,6 +205,9 @@ impl Theme {
                 item_unfocused_background: DARK0,
                 item_file_foreground: LIGHT1,
                 item_directory_foreground: BRIGHT_RED,
+                item_match_foreground: BRIGHT_ORANGE,
+                error: BRIGHT_RED,
+                hint: GRAY_245,
             },
         }
     }
@@ -221,6 +254,7 @@ impl Theme {
                     cursor_focused: normal(light_foreground, default_background),
                     cursor_unfocused: normal(comments, default_background),
                     selection_background,
+                    selection_end_of_line: normal(comments, default_foreground),
                     text: normal(default_background, default_foreground),
                     text_current_line: normal(lighter_background, default_foreground),
                     code_char: normal(default_background, support),
@@ -237,6 +271,14 @@ impl Theme {
                     code_string: normal(default_background, strings),
                     code_type: normal(default_background, classes),
                     code_variant: normal(default_background, classes),
+                    diff_added: normal(default_background, strings),
+                    diff_removed: normal(default_background, variables),
+                    diff_changed: normal(default_background, keywords),
+                    indent_guide: normal(default_background, selection_background),
+                    annotation_error: normal(default_background, variables),
+                    annotation_warning: normal(default_background, classes),
+                    annotation_info: normal(default_background, functions),
+                    annotation_hint: normal(default_background, comments),
                 },
                 edit_tree_viewer: EditTreeViewerTheme {
                     current_revision: bold(default_background, embedded),
@@ -246,6 +288,18 @@ impl Theme {
                     alternate_connector: normal(default_background, comments),
                 },
                 border: normal(lighter_background, dark_foreground),
+                scrollbar: ScrollbarTheme {
+                    track: normal(lighter_background, dark_foreground),
+                    thumb: normal(selection_background, light_foreground),
+                    search_match: classes,
+                },
+                completion: CompletionTheme {
+                    item: normal(lighter_background, default_foreground),
+                    item_selected: normal(selection_background, light_foreground),
+                    kind: normal(lighter_background, support),
+                    detail: normal(lighter_background, comments),
+                    documentation: normal(lighter_background, dark_foreground),
+                },
                 status_bar: StatusBarTheme {
                     base: normal(lighter_background, default_background),
                     frame_id_focused: normal(functions, default_background),
@@ -255,6 +309,7 @@ impl Theme {
                     file_name: bold(lighter_background, strings),
                     file_size: normal(lighter_background, dark_foreground),
                     position_in_file: normal(lighter_background, dark_foreground),
+                    selection: normal(lighter_background, classes),
                     mode: normal(lighter_background, strings),
                 },
             },
@@ -262,6 +317,10 @@ impl Theme {
                 logo: normal(lighter_background, dark_foreground),
                 tagline: normal(lighter_background, support),
                 credits: normal(lighter_background, comments),
+                heading: bold(lighter_background, functions),
+                item: normal(lighter_background, default_foreground),
+                item_selected: normal(default_background, functions),
+                key: normal(lighter_background, support),
             },
             prompt: PromptTheme {
                 input: normal(default_background, classes),
@@ -273,6 +332,9 @@ impl Theme {
                 item_unfocused_background: lighter_background,
                 item_file_foreground: default_foreground,
                 item_directory_foreground: keywords,
+                item_match_foreground: constants,
+                error: variables,
+                hint: comments,
             },
         }
     }
@@ -17,6 +17,130 @@ pub struct EditorConfig {
     /// impact performance. Default: `true`.
     #[serde(default)]
     pub trim_trailing_whitespace_on_save: bool,
+    /// Back up files before saving over them. Default: `None`, no backups.
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    /// A command for writing files we don't have permission to write, e.g.
+    /// `sudo -n tee`. The file path is appended to the arguments and the text
+    /// is written to its standard input. Default: `None`.
+    #[serde(default)]
+    pub elevated_save_command: Option<Vec<String>>,
+    /// Copy to the terminal's clipboard using OSC 52 escape sequences, which
+    /// works over ssh if the terminal supports it. Otherwise they're only used
+    /// over ssh when no other clipboard is found. Default: `false`.
+    #[serde(default)]
+    pub osc52_clipboard: bool,
+    /// Draw faint vertical lines at each level of indentation. Default:
+    /// `true`.
+    #[serde(default)]
+    pub indent_guides: bool,
+    /// When replacing with a pattern without uppercase letters, match text in
+    /// any case and give each replacement the case of the text it replaces,
+    /// i.e. all caps or capitalized. Default: `true`.
+    #[serde(default)]
+    pub case_replace: bool,
+    /// How many directories deep the file picker lists files when searching
+    /// a repository, `None` for no limit. Default: `None`.
+    #[serde(default)]
+    pub file_picker_max_depth: Option<usize>,
+    /// What to show when zee starts without any files to open. Default:
+    /// `Dashboard`.
+    #[serde(default)]
+    pub startup: Startup,
+    /// External programs started with the editor, which talk to it using the
+    /// plugin protocol. Default: `[]`.
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    /// The column `fill-paragraph` and auto fill wrap text at. Default: `80`.
+    #[serde(default = "default_fill_column")]
+    pub fill_column: usize,
+    /// The names of the modes in which typing past the fill column breaks
+    /// the line. Default: `["Markdown", "Plain"]`.
+    #[serde(default)]
+    pub auto_fill_modes: Vec<String>,
+    /// Show the keys typed so far of an unfinished key sequence, e.g. `C-x`,
+    /// in the status bar of the focused window instead of the prompt.
+    /// Default: `false`.
+    #[serde(default)]
+    pub pending_keys_in_status_bar: bool,
+    /// When zee crashes, write the unsaved changes of each buffer next to its
+    /// file as `#name#`. Default: `true`.
+    #[serde(default)]
+    pub crash_recovery_files: bool,
+    /// Draw a scrollbar on the right edge of each window, marking the lines
+    /// with search matches and annotations. Default: `false`.
+    #[serde(default)]
+    pub scrollbar: bool,
+    /// After `kill-whole-line`, leave the cursor on the indentation of the
+    /// line taking the killed line's place rather than at its start.
+    /// Default: `false`.
+    #[serde(default)]
+    pub kill_whole_line_to_indentation: bool,
+    /// How many inputs each kind of prompt remembers, e.g. search patterns
+    /// or file paths, `0` to keep no history. Default: `100`.
+    #[serde(default = "default_prompt_history_size")]
+    pub prompt_history_size: usize,
+    /// Compose spacing accents, e.g. `´`, with the letter typed after them,
+    /// for keyboard layouts whose dead keys the terminal doesn't compose.
+    /// Default: `false`.
+    #[serde(default)]
+    pub compose_dead_keys: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename = "Plugin")]
+pub struct PluginConfig {
+    pub name: String,
+    /// The program to run followed by its arguments
+    pub command: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename = "Backup")]
+pub struct BackupConfig {
+    pub style: BackupStyle,
+    /// Where to store backups. When `None`, they're created next to the file.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// How many numbered backups to keep for each file, older ones are deleted
+    #[serde(default = "default_backups_to_keep")]
+    pub keep: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BackupStyle {
+    /// A single backup, `file.txt~`, replaced on every save
+    Tilde,
+    /// `file.txt.~1~`, `file.txt.~2~`, etc., the highest number is the latest
+    Numbered,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Startup {
+    /// Recent files, recent projects and tips, which can be selected
+    Dashboard,
+    /// Only the logo
+    Splash,
+    /// An empty `*scratch*` buffer, ready to type in
+    Scratch,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self::Dashboard
+    }
+}
+
+fn default_backups_to_keep() -> usize {
+    DEFAULT_BACKUPS_TO_KEEP
+}
+
+fn default_fill_column() -> usize {
+    DEFAULT_FILL_COLUMN
+}
+
+fn default_prompt_history_size() -> usize {
+    DEFAULT_PROMPT_HISTORY_SIZE
 }
 
 impl Default for EditorConfig {
@@ -70,6 +194,10 @@ pub fn default_config_str() -> &'static str {
         .expect("mode configuration file is not valid utf-8")
 }
 
+const DEFAULT_BACKUPS_TO_KEEP: usize = 5;
+const DEFAULT_FILL_COLUMN: usize = 80;
+const DEFAULT_PROMPT_HISTORY_SIZE: usize = 100;
+
 pub static PLAIN_TEXT_MODE: Lazy<Mode> = Lazy::new(Default::default);
 
 pub static DEFAULT_CONFIG_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/config");
@@ -0,0 +1,86 @@
+use regex::Regex;
+use ropey::{Rope, RopeSlice};
+use std::ops::Range;
+
+use zee_edit::{graphemes, CharIndex, LineIndex};
+
+use crate::error::{Context, Result};
+
+/// Parses what to align on: a regex between slashes, e.g. `/=>?/`, or
+/// otherwise a literal delimiter such as `=` or `:`
+pub fn parse_delimiter(input: &str) -> Result<Regex> {
+    let input = input.trim();
+    let pattern = match input
+        .strip_prefix('/')
+        .and_then(|rest| rest.strip_suffix('/'))
+    {
+        Some(pattern) => pattern.to_owned(),
+        None => regex::escape(input),
+    };
+    if pattern.is_empty() {
+        anyhow::bail!("Nothing to align on");
+    }
+    Regex::new(&pattern).with_context(|| format!("Invalid regex `{}`", pattern))
+}
+
+/// The text of `lines` with the first match of `delimiter` on each line
+/// moved to the same column, and the range of text it replaces.
+///
+/// The whitespace before each delimiter is replaced by padding. Lines keep at
+/// least as much space before the delimiter as the tightest line had, so
+/// `a=1` stays tight while `a = 1` keeps a space. Lines without a match are
+/// left alone.
+pub fn align(
+    text: &Rope,
+    lines: Range<LineIndex>,
+    delimiter: &Regex,
+    tab_width: usize,
+) -> (Range<CharIndex>, Rope) {
+    let range = text.line_to_char(lines.start)..text.line_to_char(lines.end);
+    let lines: Vec<String> = lines
+        .map(|line_index| text.line(line_index).into())
+        .collect();
+
+    // For each line, where the prefix before the delimiter ends (without its
+    // trailing whitespace) and where the delimiter starts, as byte offsets
+    let splits: Vec<Option<(usize, usize)>> = lines
+        .iter()
+        .map(|line| {
+            let start = delimiter.find(line)?.start();
+            Some((line[..start].trim_end().len(), start))
+        })
+        .collect();
+    let prefix_width =
+        |line: &str, end: usize| graphemes::line_width(tab_width, &RopeSlice::from(&line[..end]));
+    let column = lines
+        .iter()
+        .zip(splits.iter())
+        .filter_map(|(line, split)| Some(prefix_width(line, split.as_ref()?.0)))
+        .max();
+    let gap = lines
+        .iter()
+        .zip(splits.iter())
+        .filter_map(|(line, split)| {
+            let (prefix_end, start) = split.as_ref()?;
+            Some(prefix_width(line, *start) - prefix_width(line, *prefix_end))
+        })
+        .min();
+
+    let mut aligned = Rope::new();
+    for (line, split) in lines.iter().zip(splits.iter()) {
+        let new_line = match (split, column, gap) {
+            (Some((prefix_end, start)), Some(column), Some(gap)) => {
+                let padding = column - prefix_width(line, *prefix_end) + gap;
+                format!(
+                    "{}{}{}",
+                    &line[..*prefix_end],
+                    " ".repeat(padding),
+                    &line[*start..]
+                )
+            }
+            _ => line.clone(),
+        };
+        aligned.insert(aligned.len_chars(), &new_line);
+    }
+    (range, aligned)
+}
@@ -0,0 +1,117 @@
+//! Notes attached to ranges of a buffer's text by other subsystems, e.g. the
+//! errors reported by a compiler or the words flagged by a spell checker. The
+//! text area underlines each range and shows its message after the end of the
+//! line it starts on.
+//!
+//! Each subsystem, the annotations' source, replaces or clears its own
+//! annotations independently of the others. Annotations are attached to a
+//! version of the buffer, and follow the text as it's edited afterwards.
+//! Those on text which was changed are dropped, until their source looks at
+//! the new version.
+
+use serde_derive::Deserialize;
+use std::{ops::Range, rc::Rc};
+
+use zee_edit::{CharIndex, OpaqueDiff};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub range: Range<CharIndex>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Annotation {
+    /// Whether the annotation covers a char. Empty ranges, e.g. for a missing
+    /// semicolon, cover the char they point at.
+    pub fn covers(&self, char_index: CharIndex) -> bool {
+        self.range.contains(&char_index)
+            || (self.range.is_empty() && self.range.start == char_index)
+    }
+}
+
+/// The annotations of a buffer from all sources
+#[derive(Clone, Debug, Default)]
+pub struct Annotations {
+    sources: Vec<(String, Vec<Annotation>)>,
+    /// The annotations from every source sorted by where they start, shared
+    /// with the windows drawing them
+    merged: Rc<[Annotation]>,
+}
+
+impl Annotations {
+    /// Replaces the annotations from a source
+    pub fn set(&mut self, source: &str, annotations: Vec<Annotation>) {
+        match self.sources.iter_mut().find(|(name, _)| name == source) {
+            Some((_, previous)) => *previous = annotations,
+            None => self.sources.push((source.to_owned(), annotations)),
+        }
+        self.merge();
+    }
+
+    /// Removes the annotations from a source
+    pub fn clear(&mut self, source: &str) {
+        self.sources.retain(|(name, _)| name != source);
+        self.merge();
+    }
+
+    pub fn clear_all(&mut self) {
+        self.sources.clear();
+        self.merge();
+    }
+
+    pub fn merged(&self) -> &Rc<[Annotation]> {
+        &self.merged
+    }
+
+    /// Moves the annotations after an edit along with the text, and drops
+    /// those overlapping the edited text
+    pub fn reconcile(&mut self, diff: &OpaqueDiff) {
+        if self.merged.is_empty() {
+            return;
+        }
+        let edit_start = diff.char_index;
+        let old_edit_end = diff.char_index + diff.old_char_length;
+        let shift =
+            |char_index: CharIndex| char_index - diff.old_char_length + diff.new_char_length;
+        for (_, annotations) in self.sources.iter_mut() {
+            *annotations = annotations
+                .drain(..)
+                .filter_map(|annotation| {
+                    let Range { start, end } = annotation.range.clone();
+                    // An empty range covers the char after it
+                    if end.max(start + 1) <= edit_start {
+                        Some(annotation)
+                    } else if start >= old_edit_end {
+                        Some(Annotation {
+                            range: shift(start)..shift(end),
+                            ..annotation
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        }
+        self.merge();
+    }
+
+    fn merge(&mut self) {
+        let mut merged: Vec<Annotation> = self
+            .sources
+            .iter()
+            .flat_map(|(_, annotations)| annotations.iter().cloned())
+            .collect();
+        merged.sort_by_key(|annotation| annotation.range.start);
+        self.merged = merged.into();
+    }
+}
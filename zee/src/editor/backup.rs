@@ -0,0 +1,86 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+};
+
+use crate::config::{BackupConfig, BackupStyle};
+
+/// Copies a file before a save overwrites it, following the configured naming
+/// style. Files which don't exist yet have nothing to back up.
+pub fn backup_file(file_path: &Path, config: &BackupConfig) -> io::Result<()> {
+    if !file_path.exists() {
+        return Ok(());
+    }
+
+    let (directory, name) = backup_location(file_path, config)?;
+    fs::create_dir_all(&directory)?;
+    match config.style {
+        BackupStyle::Tilde => {
+            fs::copy(file_path, directory.join(format!("{}~", name)))?;
+        }
+        BackupStyle::Numbered => {
+            let mut versions = numbered_versions(&directory, &name)?;
+            let latest = versions.iter().max().map_or(1, |version| version + 1);
+            fs::copy(file_path, directory.join(numbered_name(&name, latest)))?;
+
+            versions.push(latest);
+            versions.sort_unstable();
+            let num_expired = versions.len().saturating_sub(config.keep.max(1));
+            for version in &versions[..num_expired] {
+                fs::remove_file(directory.join(numbered_name(&name, *version)))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The directory to store a file's backups in and the name they start with.
+/// In a shared backup directory, the name encodes the file's full path so
+/// files with the same name in different directories don't clash.
+fn backup_location(file_path: &Path, config: &BackupConfig) -> io::Result<(PathBuf, String)> {
+    match config.directory.as_ref() {
+        Some(directory) => {
+            let directory = match (directory.strip_prefix("~"), dirs::home_dir()) {
+                (Ok(relative), Some(home_dir)) => home_dir.join(relative),
+                _ => directory.clone(),
+            };
+            let name = file_path
+                .canonicalize()?
+                .to_string_lossy()
+                .replace(MAIN_SEPARATOR, "!");
+            Ok((directory, name))
+        }
+        None => {
+            let name = file_path
+                .file_name()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+                .to_string_lossy()
+                .into_owned();
+            let directory = file_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            Ok((directory, name))
+        }
+    }
+}
+
+fn numbered_versions(directory: &Path, name: &str) -> io::Result<Vec<usize>> {
+    let prefix = format!("{}.~", name);
+    Ok(fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .strip_suffix('~')?
+                .parse()
+                .ok()
+        })
+        .collect())
+}
+
+fn numbered_name(name: &str, version: usize) -> String {
+    format!("{}.~{}~", name, version)
+}
@@ -1,6 +1,9 @@
-use zi::{terminal::Key, Bindings, EndsWith, FlexDirection};
+use zi::{terminal::Key, EndsWith, FlexDirection};
 
-use super::{Editor, FileSource, Message};
+use zee_edit::Direction;
+
+use super::{windows::FocusDirection, Editor, FileSource, Message, RecordedBindings};
+use crate::characters::CharacterSet;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(super) struct KeySequenceSlice<'a> {
@@ -22,6 +25,7 @@ impl<'a> std::fmt::Display for KeySequenceSlice<'a> {
                 Key::Char('\n') => write!(formatter, "RET")?,
                 Key::Char('\t') => write!(formatter, "TAB")?,
                 Key::Char(char) => write!(formatter, "{}", char)?,
+                Key::Ctrl(' ') => write!(formatter, "C-SPC")?,
                 Key::Ctrl(char) => write!(formatter, "C-{}", char)?,
                 Key::Alt(char) => write!(formatter, "A-{}", char)?,
                 Key::F(number) => write!(formatter, "F{}", number)?,
@@ -38,7 +42,39 @@ impl<'a> std::fmt::Display for KeySequenceSlice<'a> {
     }
 }
 
-pub(super) fn initialize(bindings: &mut Bindings<Editor>) {
+/// Parses a key sequence written like `KeySequenceSlice` displays it, e.g.
+/// `C-x C-s` or `A-g RET`
+pub(super) fn parse_key_sequence(sequence: &str) -> Option<Vec<Key>> {
+    let parse_char = |text: &str| match text {
+        "SPC" => Some(' '),
+        "RET" => Some('\n'),
+        "TAB" => Some('\t'),
+        text => {
+            let mut chars = text.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        }
+    };
+    let keys = sequence
+        .split_whitespace()
+        .map(|key| match key {
+            "ESC" => Some(Key::Esc),
+            key => {
+                if let Some(char) = key.strip_prefix("C-") {
+                    parse_char(char).map(Key::Ctrl)
+                } else if let Some(char) = key.strip_prefix("A-") {
+                    parse_char(char).map(Key::Alt)
+                } else if let Some(number) = key.strip_prefix('F').filter(|n| !n.is_empty()) {
+                    number.parse().ok().map(Key::F)
+                } else {
+                    parse_char(key).map(Key::Char)
+                }
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!keys.is_empty()).then(|| keys)
+}
+
+pub(super) fn initialize(bindings: &mut RecordedBindings<Editor>) {
     bindings.set_focus(true);
     bindings.set_notify(true);
 
@@ -54,6 +90,49 @@ pub(super) fn initialize(bindings: &mut Bindings<Editor>) {
         [Key::Ctrl('x'), Key::Ctrl('v')],
         || Message::OpenFilePicker(FileSource::Repository),
     );
+    bindings.add("find-recent-file", [Key::Ctrl('x'), Key::Ctrl('r')], || {
+        Message::OpenFilePicker(FileSource::Recent)
+    });
+
+    // Projects
+    bindings.add(
+        "switch-project",
+        [Key::Ctrl('x'), Key::Char('p'), Key::Char('p')],
+        || Message::ProjectPicker,
+    );
+    bindings.add(
+        "find-file-in-project",
+        [Key::Ctrl('x'), Key::Char('p'), Key::Char('f')],
+        || Message::FindFileInProject,
+    );
+
+    // Jump to a symbol defined in the current buffer
+    bindings.add("goto-symbol", [Key::Alt('g'), Key::Char('i')], || {
+        Message::GotoSymbolPicker
+    });
+    bindings.add("goto-definition", [Key::Alt('g'), Key::Char('d')], || {
+        Message::GotoDefinition
+    });
+    bindings.add("jump-back", [Key::Alt('g'), Key::Char('b')], || {
+        Message::JumpBack
+    });
+    bindings.add("complete-word", [Key::Alt('/')], || Message::CompleteWord);
+    bindings.add("complete-path", [Key::Ctrl('c'), Key::Char('/')], || {
+        Message::CompletePath
+    });
+    bindings.add("rename-symbol", [Key::Ctrl('c'), Key::Char('R')], || {
+        Message::RenameSymbolPrompt
+    });
+    bindings.add(
+        "find-file-at-point",
+        [Key::Alt('g'), Key::Char('f')],
+        || Message::FindFileAtPoint,
+    );
+    bindings.add(
+        "goto-project-symbol",
+        [Key::Alt('g'), Key::Char('p')],
+        || Message::GotoProjectSymbolPicker,
+    );
 
     // Buffer management
     bindings.add("switch-buffer", [Key::Ctrl('x'), Key::Char('b')], || {
@@ -62,6 +141,16 @@ pub(super) fn initialize(bindings: &mut Bindings<Editor>) {
     bindings.add("kill-buffer", [Key::Ctrl('x'), Key::Char('k')], || {
         Message::KillBufferPicker
     });
+    bindings.add(
+        "reopen-killed-buffer",
+        [Key::Ctrl('x'), Key::Char('K')],
+        || Message::ReopenKilledBuffer,
+    );
+    bindings.add(
+        "clone-indirect-view",
+        [Key::Ctrl('x'), Key::Char('4'), Key::Char('c')],
+        || Message::CloneIndirectView,
+    );
 
     // Window management
     //
@@ -74,6 +163,38 @@ pub(super) fn initialize(bindings: &mut Bindings<Editor>) {
         .command("focus-previous-window", || Message::FocusPreviousWindow)
         .with([Key::Ctrl('x'), Key::Char('i')])
         .with([Key::Ctrl('x'), Key::Ctrl('i')]);
+    for (number, name) in (1..).zip(FOCUS_WINDOW_COMMANDS) {
+        let digit = std::char::from_digit(number, 10).unwrap();
+        bindings.add(
+            name,
+            [Key::Ctrl('x'), Key::Char('w'), Key::Char(digit)],
+            move || Message::FocusWindow(number as usize),
+        );
+    }
+    bindings
+        .command("focus-window-left", || {
+            Message::FocusWindowInDirection(FocusDirection::Left)
+        })
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Left])
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Char('b')]);
+    bindings
+        .command("focus-window-right", || {
+            Message::FocusWindowInDirection(FocusDirection::Right)
+        })
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Right])
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Char('f')]);
+    bindings
+        .command("focus-window-up", || {
+            Message::FocusWindowInDirection(FocusDirection::Up)
+        })
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Up])
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Char('p')]);
+    bindings
+        .command("focus-window-down", || {
+            Message::FocusWindowInDirection(FocusDirection::Down)
+        })
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Down])
+        .with([Key::Ctrl('x'), Key::Char('w'), Key::Char('n')]);
 
     // Make current window fullscreen
     bindings
@@ -108,6 +229,201 @@ pub(super) fn initialize(bindings: &mut Bindings<Editor>) {
         Message::ChangeTheme
     });
 
+    // Revert
+    bindings.add("revert-buffer", [Key::Ctrl('c'), Key::Char('r')], || {
+        Message::RevertBuffer
+    });
+
+    // Scripting
+    bindings.add("run-script-command", [Key::Alt('x')], || {
+        Message::RunScriptCommandPrompt
+    });
+
+    // Modes
+    bindings.add("set-mode", [Key::Ctrl('c'), Key::Char('m')], || {
+        Message::SetModePicker
+    });
+    bindings.add("set-indentation", [Key::Ctrl('c'), Key::Char('t')], || {
+        Message::SetIndentationPrompt
+    });
+
+    // Edit tree
+    bindings.add(
+        "jump-to-revision",
+        [Key::Ctrl('c'), Key::Char('v'), Key::Char('j')],
+        || Message::JumpToRevisionPrompt,
+    );
+    bindings.add(
+        "label-revision",
+        [Key::Ctrl('c'), Key::Char('v'), Key::Char('l')],
+        || Message::LabelRevisionPrompt,
+    );
+
+    bindings.add("insert-file", [Key::Ctrl('c'), Key::Char('i')], || {
+        Message::InsertFilePicker
+    });
+    bindings.add("export", [Key::Ctrl('c'), Key::Char('e')], || {
+        Message::ExportPrompt
+    });
+    bindings.add("write-region", [Key::Ctrl('c'), Key::Char('w')], || {
+        Message::WriteRegionPrompt
+    });
+
+    // Links
+    bindings.add(
+        "open-link-at-point",
+        [Key::Ctrl('c'), Key::Char('o')],
+        || Message::OpenLinkAtPoint,
+    );
+    bindings.add("list-links", [Key::Ctrl('c'), Key::Char('l')], || {
+        Message::LinksPicker
+    });
+
+    // Diffs
+    bindings.add(
+        "diff-buffer-with-file",
+        [Key::Ctrl('x'), Key::Ctrl('d')],
+        || Message::DiffBufferWithFile,
+    );
+
+    bindings.add("compare-buffers", [Key::Ctrl('c'), Key::Char('=')], || {
+        Message::CompareBuffersPicker
+    });
+    bindings.add(
+        "compare-two-buffers",
+        [Key::Ctrl('c'), Key::Char('+')],
+        || Message::CompareTwoBuffersPicker,
+    );
+    bindings.add(
+        "compare-next-hunk",
+        [Key::Ctrl('c'), Key::Char('n')],
+        || Message::CompareMoveToHunk(Direction::Forward),
+    );
+    bindings.add(
+        "compare-previous-hunk",
+        [Key::Ctrl('c'), Key::Char('p')],
+        || Message::CompareMoveToHunk(Direction::Backward),
+    );
+    bindings.add(
+        "compare-copy-hunk-right",
+        [Key::Ctrl('c'), Key::Char('a')],
+        || Message::CompareCopyHunk(Direction::Forward),
+    );
+    bindings.add(
+        "compare-copy-hunk-left",
+        [Key::Ctrl('c'), Key::Char('b')],
+        || Message::CompareCopyHunk(Direction::Backward),
+    );
+    bindings.add("compare-quit", [Key::Ctrl('c'), Key::Char('q')], || {
+        Message::CompareQuit
+    });
+
+    // Git
+    bindings.add(
+        "git-next-hunk",
+        [Key::Ctrl('x'), Key::Char('v'), Key::Char(']')],
+        || Message::GitMoveToHunk(Direction::Forward),
+    );
+    bindings.add(
+        "git-previous-hunk",
+        [Key::Ctrl('x'), Key::Char('v'), Key::Char('[')],
+        || Message::GitMoveToHunk(Direction::Backward),
+    );
+    bindings.add(
+        "git-hunks",
+        [Key::Ctrl('x'), Key::Char('v'), Key::Char('h')],
+        || Message::GitHunksPicker,
+    );
+    bindings.add(
+        "git-revert-hunk",
+        [Key::Ctrl('x'), Key::Char('v'), Key::Char('n')],
+        || Message::GitRevertHunk,
+    );
+
+    // Help
+    bindings.add(
+        "describe-bindings",
+        [Key::Ctrl('h'), Key::Char('b')],
+        || Message::DescribeBindings,
+    );
+    bindings.add("describe-key", [Key::Ctrl('h'), Key::Char('k')], || {
+        Message::DescribeKeyPrompt
+    });
+    bindings.add("view-messages", [Key::Ctrl('h'), Key::Char('e')], || {
+        Message::ViewMessages
+    });
+    bindings.add("describe-char", [Key::Ctrl('x'), Key::Char('=')], || {
+        Message::DescribeChar
+    });
+    bindings.add(
+        "insert-char",
+        [Key::Ctrl('x'), Key::Char('8'), Key::Char('\n')],
+        || Message::InsertCharPrompt,
+    );
+    bindings.add("align", [Key::Ctrl('x'), Key::Char('a')], || {
+        Message::AlignPrompt
+    });
+    bindings.add("toggle-read-only", [Key::Ctrl('x'), Key::Ctrl('q')], || {
+        Message::ToggleReadOnly
+    });
+    bindings.add("fill-paragraph", [Key::Alt('q')], || Message::FillParagraph);
+    bindings.add("auto-fill-mode", [Key::Ctrl('x'), Key::Char('f')], || {
+        Message::ToggleAutoFill
+    });
+    bindings.add("replace", [Key::Alt('%')], || Message::ReplacePrompt);
+    bindings.add("stats", [Key::Alt('=')], || Message::Stats);
+    bindings.add("occur", [Key::Alt('s'), Key::Char('o')], || {
+        Message::OccurPrompt
+    });
+    bindings.add(
+        "highlight-regexp",
+        [Key::Alt('s'), Key::Char('h'), Key::Char('r')],
+        || Message::HighlightPrompt,
+    );
+    bindings.add(
+        "unhighlight-regexp",
+        [Key::Alt('s'), Key::Char('h'), Key::Char('u')],
+        || Message::UnhighlightPicker,
+    );
+    bindings.add(
+        "number-lines",
+        [Key::Ctrl('x'), Key::Char('r'), Key::Char('N')],
+        || Message::NumberLinesPrompt,
+    );
+    bindings.add(
+        "calc",
+        [Key::Ctrl('x'), Key::Char('*'), Key::Char('e')],
+        || Message::CalcPrompt,
+    );
+    bindings.add(
+        "calc-insert",
+        [Key::Ctrl('x'), Key::Char('*'), Key::Char('i')],
+        || Message::CalcInsertPrompt,
+    );
+    bindings.add(
+        "insert-emoji",
+        [Key::Ctrl('x'), Key::Char('8'), Key::Char('e')],
+        || Message::InsertCharacterPicker(CharacterSet::Emoji),
+    );
+    bindings.add(
+        "insert-symbol",
+        [Key::Ctrl('x'), Key::Char('8'), Key::Char('s')],
+        || Message::InsertCharacterPicker(CharacterSet::All),
+    );
+
     // Quit
     bindings.add("quit", [Key::Ctrl('x'), Key::Ctrl('c')], || Message::Quit);
 }
+
+/// The commands focusing a window by number, bound to `C-x w 1` to `C-x w 9`
+const FOCUS_WINDOW_COMMANDS: [&str; 9] = [
+    "focus-window-1",
+    "focus-window-2",
+    "focus-window-3",
+    "focus-window-4",
+    "focus-window-5",
+    "focus-window-6",
+    "focus-window-7",
+    "focus-window-8",
+    "focus-window-9",
+];
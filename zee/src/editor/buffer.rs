@@ -1,28 +1,55 @@
 use git2::Repository;
 use ropey::Rope;
+use serde_derive::{Deserialize, Serialize};
 use std::{
+    cell::RefCell,
     fmt::Display,
-    fs::File,
-    io::{self, BufWriter},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    ops::Range,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
     rc::Rc,
+    time::{Instant, SystemTime},
 };
 use zi::ComponentLink;
 
 use zee_edit::{
-    graphemes::strip_trailing_whitespace, movement, tree::EditTree, Cursor, Direction, OpaqueDiff,
+    batch::{self, TextEdit},
+    graphemes::strip_trailing_whitespace,
+    indentation::{self, DetectedIndentation},
+    movement,
+    tree::EditTree,
+    CharIndex, Cursor, DeleteOperation, Direction, LineIndex, OpaqueDiff,
+};
+use zee_grammar::{
+    config::{IndentationConfig, IndentationUnit},
+    Mode,
 };
-use zee_grammar::Mode;
 
-use super::{ContextHandle, Editor};
+use super::{
+    annotations::{Annotation, Annotations},
+    backup::backup_file,
+    compose, fill,
+    folds::Folds,
+    lock::{self, FileLock, LockOwner},
+    narrow::Narrowed,
+    remote::RemotePath,
+    ContextHandle, Editor, Message,
+};
 use crate::{
-    config::PLAIN_TEXT_MODE,
-    error::Result,
-    syntax::parse::{ParseTree, ParserPool, ParserStatus},
+    error::{Context, Result},
+    syntax::{
+        closing::{self, Closing},
+        highlight::{HighlightCache, LineScope},
+        parse::{ParseTree, ParserPool, ParserStatus},
+        sexp,
+    },
+    utils::LineMetricsCache,
     versioned::{Versioned, WeakHandle},
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct BufferId(usize);
 
 impl Display for BufferId {
@@ -84,11 +111,68 @@ impl Buffers {
             buffer_id,
             text,
             file_path,
+            None,
             repo,
         ));
         buffer_id
     }
 
+    /// Adds a buffer for a file on another machine, which is uploaded to it
+    /// when saving
+    pub fn add_remote(&mut self, text: Rope, remote: RemotePath) -> BufferId {
+        let buffer_id = BufferId(self.next_buffer_id);
+        self.next_buffer_id += 1;
+        let mut buffer = Buffer::new(
+            self.context.clone(),
+            buffer_id,
+            text,
+            None,
+            Some(remote.clone()),
+            None,
+        );
+        buffer.name = Some(remote.to_string());
+        self.buffers.push(buffer);
+        buffer_id
+    }
+
+    /// Adds an empty buffer for a file which is being read in the background.
+    /// The text is filled in by [`Buffer::finish_loading`].
+    pub fn add_loading(
+        &mut self,
+        file_path: PathBuf,
+        repo: Option<RepositoryRc>,
+        total_bytes: u64,
+    ) -> BufferId {
+        let buffer_id = self.add(Rope::new(), Some(file_path), repo);
+        self.get_mut(buffer_id)
+            .unwrap()
+            .start_loading(Some(total_bytes));
+        buffer_id
+    }
+
+    /// Adds a read-only buffer for a stream of unknown length, e.g. standard
+    /// input, which is read in the background like a file
+    pub fn add_loading_read_only(&mut self, name: impl Into<String>) -> BufferId {
+        let buffer_id = self.add_read_only(name, Rope::new());
+        self.get_mut(buffer_id).unwrap().start_loading(None);
+        buffer_id
+    }
+
+    /// Adds an empty buffer which isn't backed by a file, e.g. `*scratch*`
+    pub fn add_scratch(&mut self, name: impl Into<String>) -> BufferId {
+        let buffer_id = self.add(Rope::new(), None, None);
+        self.get_mut(buffer_id).unwrap().name = Some(name.into());
+        buffer_id
+    }
+
+    pub fn add_read_only(&mut self, name: impl Into<String>, text: Rope) -> BufferId {
+        let buffer_id = self.add(text, None, None);
+        let buffer = self.get_mut(buffer_id).unwrap();
+        buffer.name = Some(name.into());
+        buffer.read_only = true;
+        buffer_id
+    }
+
     pub fn remove(&mut self, id: BufferId) -> Option<Buffer> {
         self.buffers
             .iter()
@@ -104,6 +188,13 @@ impl Buffers {
         self.buffers.iter_mut().find(|buffer| buffer.id == id)
     }
 
+    pub fn find_by_remote(&self, remote: &RemotePath) -> Option<BufferId> {
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.remote.as_ref() == Some(remote))
+            .map(|buffer| buffer.id)
+    }
+
     pub fn find_by_path(&self, path: impl AsRef<Path>) -> Option<BufferId> {
         self.buffers
             .iter()
@@ -117,6 +208,13 @@ impl Buffers {
             .map(|buffer| buffer.id)
     }
 
+    pub fn find_by_name(&self, name: &str) -> Option<BufferId> {
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.name.as_deref() == Some(name))
+            .map(|buffer| buffer.id)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Buffer> {
         self.buffers.iter()
     }
@@ -133,6 +231,7 @@ impl Buffers {
         match self.get_mut(message.buffer_id) {
             Some(buffer) => {
                 buffer.handle_message(message.inner);
+                buffer.update_lock();
             }
             None => {
                 log::warn!(
@@ -145,6 +244,106 @@ impl Buffers {
     }
 }
 
+/// How much of a file has been read into a buffer which is still loading
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub bytes_read: u64,
+    /// `None` when reading a stream whose length isn't known in advance
+    pub total_bytes: Option<u64>,
+}
+
+impl LoadingProgress {
+    pub fn percent(&self) -> Option<u64> {
+        let total_bytes = self.total_bytes?;
+        Some(
+            (self.bytes_read.min(total_bytes) * 100)
+                .checked_div(total_bytes)
+                .unwrap_or(100),
+        )
+    }
+}
+
+struct Loading {
+    progress: LoadingProgress,
+    /// Where cursors were moved to before the text arrived, e.g. the line
+    /// given on the command line
+    cursor_targets: Vec<(CursorId, CursorTarget)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum CursorTarget {
+    Char(CharIndex),
+    /// A line and a column in chars, clamped to the text once it's known
+    Line(LineIndex, usize),
+}
+
+impl CursorTarget {
+    fn char_index(self, text: &Rope) -> CharIndex {
+        match self {
+            Self::Char(char_index) => char_index,
+            Self::Line(line_index, column) => {
+                let line_index = line_index.min(text.len_lines().saturating_sub(1));
+                let line = text.line(line_index);
+                let mut line_length = line.len_chars();
+                for line_break in ['\n', '\r'] {
+                    if line_length > 0 && line.char(line_length - 1) == line_break {
+                        line_length -= 1;
+                    }
+                }
+                text.line_to_char(line_index) + column.min(line_length)
+            }
+        }
+    }
+}
+
+/// The lock on a buffer's file, taken while the buffer has unsaved changes
+#[derive(Debug)]
+enum LockState {
+    Unlocked,
+    Held(FileLock),
+    /// We couldn't lock the file, or someone else has and we left them be.
+    /// We don't try again until the changes are saved or discarded.
+    NotHeld(PathBuf),
+}
+
+/// What the window showing a cursor keeps besides the cursor itself
+#[derive(Clone, Debug, Default)]
+struct CursorState {
+    folds: Folds,
+    /// The lines the window is narrowed to, if any
+    narrowed: Option<Narrowed>,
+    /// The dead key typed at the cursor, waiting for the character it's
+    /// composed with
+    dead_key: Option<char>,
+    /// Whether the last thing done at the cursor was killing text, so the
+    /// next kill is added to it in the clipboard rather than replacing it
+    killing: bool,
+}
+
+impl CursorState {
+    /// A new window showing the same lines, with nothing typed or killed yet
+    fn duplicate(&self) -> Self {
+        Self {
+            folds: self.folds.clone(),
+            narrowed: self.narrowed.clone(),
+            ..Self::default()
+        }
+    }
+
+    fn reconcile(&mut self, text: &Rope, num_lines_before: usize, diff: &OpaqueDiff) {
+        self.folds.reconcile(text, num_lines_before, diff);
+        if let Some(narrowed) = self.narrowed.as_mut() {
+            narrowed.reconcile(text, num_lines_before, diff);
+        }
+    }
+
+    /// Unfolds and widens the window, when its lines no longer apply
+    fn reset_lines(&mut self) {
+        self.folds.clear();
+        self.narrowed = None;
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ModifiedStatus {
     Changed,
@@ -156,12 +355,41 @@ pub struct Buffer {
     context: ContextHandle,
     id: BufferId,
     mode: &'static Mode,
+    /// How the text is indented, guessed from the text when it's opened or
+    /// set by the user, otherwise the mode's default
+    indentation: IndentationConfig,
+    /// Whether typing a space past the fill column breaks the line
+    auto_fill: bool,
     repo: Option<RepositoryRc>,
     content: Versioned<EditTree>,
     file_path: Option<PathBuf>,
+    remote: Option<RemotePath>,
+    name: Option<String>,
+    read_only: bool,
     modified_status: ModifiedStatus,
+    lock: LockState,
+    /// Whether to edit the file even though someone else locked it
+    steal_lock: bool,
     cursors: Vec<Cursor>,
+    /// The state of the window showing each cursor, indexed like `cursors`
+    cursor_states: Vec<CursorState>,
+    /// Cursors given back with `release_cursor`, reused by the next new ones
+    free_cursors: Vec<CursorId>,
+    /// The number each indirect view of the buffer is named with, e.g.
+    /// `main.rs<2>`, and the cursor it shows
+    indirect_views: Vec<(usize, CursorId)>,
     parser: Option<ParserPool>,
+    line_scopes: Rc<[LineScope]>,
+    annotations: Annotations,
+    /// The syntax highlighting of the lines drawn so far, shared by the
+    /// windows showing the buffer
+    highlight_cache: Rc<RefCell<HighlightCache>>,
+    /// The graphemes of the lines drawn so far, also shared by the windows
+    line_metrics: Rc<RefCell<LineMetricsCache>>,
+    /// When the file was last modified on disk, as of loading or saving it
+    modified_on_disk: Option<SystemTime>,
+    /// Set while the file is being read in the background
+    loading: Option<Loading>,
 }
 
 impl Buffer {
@@ -170,41 +398,48 @@ impl Buffer {
         id: BufferId,
         text: Rope,
         file_path: Option<PathBuf>,
+        remote: Option<RemotePath>,
         repo: Option<RepositoryRc>,
     ) -> Self {
-        let mode = file_path
-            .as_ref()
-            .map(|path| context.0.mode_by_filename(path))
-            .unwrap_or(&PLAIN_TEXT_MODE);
-
-        let mut parser = mode
-            .language()
-            .and_then(|result| result.ok())
-            .map(ParserPool::new);
-        if let Some(parser) = parser.as_mut() {
-            let link = context.link.clone();
-            parser.ensure_tree(
-                &context.task_pool,
-                || text.clone(),
-                move |status| {
-                    link.send(
-                        BuffersMessage::new(id, BufferMessage::ParseSyntax { version: 0, status })
-                            .into(),
-                    )
-                },
-            );
-        };
+        let mode = context.0.detect_mode(
+            file_path
+                .as_deref()
+                .or_else(|| remote.as_ref().map(|remote| Path::new(&remote.path))),
+            &text,
+        );
+        let parser = start_parser(&context, id, mode, &text, 0);
+        let indentation = detect_indentation(mode, &text);
+        let auto_fill = context.config.auto_fill_modes.contains(&mode.name);
+        let highlight_cache = Rc::new(RefCell::new(HighlightCache::new(&text)));
+        let line_metrics = Rc::new(RefCell::new(LineMetricsCache::new(&text)));
 
+        let modified_on_disk = file_path.as_ref().and_then(|path| file_modified_time(path));
         Self {
             context,
             id,
             mode,
+            indentation,
+            auto_fill,
             repo,
             content: Versioned::new(EditTree::new(text)),
             file_path,
+            remote,
+            name: None,
+            read_only: false,
             modified_status: ModifiedStatus::Unchanged,
+            lock: LockState::Unlocked,
+            steal_lock: false,
             cursors: vec![Cursor::new()],
+            cursor_states: vec![CursorState::default()],
+            free_cursors: Vec::new(),
+            indirect_views: Vec::new(),
             parser,
+            line_scopes: Vec::new().into(),
+            annotations: Annotations::default(),
+            highlight_cache,
+            line_metrics,
+            modified_on_disk,
+            loading: None,
         }
     }
 
@@ -218,11 +453,140 @@ impl Buffer {
         self.file_path.as_ref()
     }
 
+    /// The file on another machine the buffer is editing, if any
+    #[inline]
+    pub fn remote(&self) -> Option<&RemotePath> {
+        self.remote.as_ref()
+    }
+
+    /// The name of a buffer which isn't backed by a file, e.g. `*Bindings*`
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Who else is editing the file, unless we hold the lock or chose to
+    /// take it over
+    pub fn lock_owner(&self) -> Option<LockOwner> {
+        if self.steal_lock || matches!(self.lock, LockState::Held(_)) {
+            return None;
+        }
+        self.file_path.as_deref().and_then(lock::other_owner)
+    }
+
+    /// Edit the file even though someone else locked it, taking over their
+    /// lock once the buffer is modified
+    pub fn steal_lock(&mut self) {
+        self.steal_lock = true;
+        if matches!(self.lock, LockState::NotHeld(_)) {
+            self.lock = LockState::Unlocked;
+        }
+        self.update_lock();
+    }
+
+    /// Holds a lock on the file while the buffer has unsaved changes
+    fn update_lock(&mut self) {
+        let file_path = match self.file_path.as_deref() {
+            Some(file_path) if self.modified_status != ModifiedStatus::Unchanged => file_path,
+            _ => {
+                self.lock = LockState::Unlocked;
+                return;
+            }
+        };
+        match &self.lock {
+            LockState::Held(lock) if lock.file_path() == file_path => return,
+            LockState::NotHeld(path) if path == file_path => return,
+            _ => {}
+        }
+        self.lock = if !self.steal_lock && lock::other_owner(file_path).is_some() {
+            LockState::NotHeld(file_path.to_path_buf())
+        } else {
+            match FileLock::acquire(file_path) {
+                Ok(lock) => LockState::Held(lock),
+                Err(error) => {
+                    log::warn!("{:#}", error);
+                    LockState::NotHeld(file_path.to_path_buf())
+                }
+            }
+        };
+    }
+
+    fn start_loading(&mut self, total_bytes: Option<u64>) {
+        self.loading = Some(Loading {
+            progress: LoadingProgress {
+                bytes_read: 0,
+                total_bytes,
+            },
+            cursor_targets: Vec::new(),
+        });
+    }
+
+    /// How far along reading the file is, if the buffer is still loading
+    #[inline]
+    pub fn loading(&self) -> Option<LoadingProgress> {
+        self.loading.as_ref().map(|loading| loading.progress)
+    }
+
+    pub fn set_loading_progress(&mut self, bytes_read: u64) {
+        if let Some(loading) = self.loading.as_mut() {
+            loading.progress.bytes_read = bytes_read;
+        }
+    }
+
+    /// Fills in the text of a buffer added with [`Buffers::add_loading`]. The
+    /// mode and indentation are detected again now that there's text to go by.
+    pub fn finish_loading(&mut self, text: Rope) {
+        let loading = match self.loading.take() {
+            Some(loading) => loading,
+            None => return,
+        };
+        let mode = self.context.0.detect_mode(self.file_path.as_deref(), &text);
+        *self.content = EditTree::new(text);
+        self.line_metrics.borrow_mut().clear(self.content.staged());
+        self.modified_on_disk = self
+            .file_path
+            .as_ref()
+            .and_then(|path| file_modified_time(path));
+        for (cursor_id, target) in loading.cursor_targets {
+            let char_index = target.char_index(&self.content);
+            movement::move_to_char(&self.content, &mut self.cursors[cursor_id.0], char_index);
+        }
+        self.set_mode(mode);
+    }
+
     #[inline]
     pub fn mode(&self) -> &'static Mode {
         self.mode
     }
 
+    #[inline]
+    pub fn indentation(&self) -> IndentationConfig {
+        self.indentation
+    }
+
+    /// Overrides the indentation guessed when the buffer was opened
+    pub fn set_indentation(&mut self, indentation: IndentationConfig) {
+        self.indentation = indentation;
+    }
+
+    #[inline]
+    pub fn auto_fill(&self) -> bool {
+        self.auto_fill
+    }
+
+    pub fn set_auto_fill(&mut self, auto_fill: bool) {
+        self.auto_fill = auto_fill;
+    }
+
     #[inline]
     pub fn repository(&self) -> Option<&RepositoryRc> {
         self.repo.as_ref()
@@ -250,17 +614,112 @@ impl Buffer {
 
     #[inline]
     pub fn new_cursor(&mut self) -> CursorId {
-        let new_cursor_id = CursorId(self.cursors.len());
-        self.cursors
-            .push(self.cursors.get(0).cloned().unwrap_or_else(Cursor::new));
-        new_cursor_id
+        let cursor = self.cursors.get(0).cloned().unwrap_or_else(Cursor::new);
+        self.add_cursor(cursor, CursorState::default())
     }
 
     #[inline]
     pub fn duplicate_cursor(&mut self, cursor_id: CursorId) -> CursorId {
-        let new_cursor_id = CursorId(self.cursors.len());
-        self.cursors.push(self.cursors[cursor_id.0].clone());
-        new_cursor_id
+        let cursor = self.cursors[cursor_id.0].clone();
+        let state = self.cursor_states[cursor_id.0].duplicate();
+        self.add_cursor(cursor, state)
+    }
+
+    /// Gives back a cursor nothing shows anymore, e.g. a closed indirect
+    /// view's, so its slot is reused by the next new cursor. The first cursor
+    /// is never released.
+    pub fn release_cursor(&mut self, cursor_id: CursorId) {
+        if cursor_id == CursorId::default() || self.free_cursors.contains(&cursor_id) {
+            return;
+        }
+        self.cursors[cursor_id.0] = Cursor::new();
+        self.cursor_states[cursor_id.0] = CursorState::default();
+        self.free_cursors.push(cursor_id);
+    }
+
+    fn add_cursor(&mut self, cursor: Cursor, state: CursorState) -> CursorId {
+        match self.free_cursors.pop() {
+            Some(cursor_id) => {
+                self.cursors[cursor_id.0] = cursor;
+                self.cursor_states[cursor_id.0] = state;
+                cursor_id
+            }
+            None => {
+                self.cursors.push(cursor);
+                self.cursor_states.push(state);
+                CursorId(self.cursors.len() - 1)
+            }
+        }
+    }
+
+    /// Clones a cursor into an indirect view of the buffer. The view shares
+    /// the text but has its own cursor, narrowing and folds, and can be
+    /// switched to by name like a buffer. Returns the view's cursor and the
+    /// number it's named with.
+    pub fn clone_indirect_view(&mut self, cursor_id: CursorId) -> (CursorId, usize) {
+        let number = self
+            .indirect_views
+            .last()
+            .map_or(2, |(number, _)| number + 1);
+        let view_cursor_id = self.duplicate_cursor(cursor_id);
+        self.indirect_views.push((number, view_cursor_id));
+        (view_cursor_id, number)
+    }
+
+    /// The indirect views of the buffer, oldest first
+    pub fn indirect_views(&self) -> &[(usize, CursorId)] {
+        &self.indirect_views
+    }
+
+    /// The number the indirect view showing a cursor is named with, if any
+    pub fn indirect_view_number(&self, cursor_id: CursorId) -> Option<usize> {
+        self.indirect_views
+            .iter()
+            .find(|(_, view_cursor_id)| *view_cursor_id == cursor_id)
+            .map(|(number, _)| *number)
+    }
+
+    /// The cursor shown by the indirect view named with `number`, if any
+    pub fn indirect_view_cursor(&self, number: usize) -> Option<CursorId> {
+        self.indirect_views
+            .iter()
+            .find(|(view_number, _)| *view_number == number)
+            .map(|(_, cursor_id)| *cursor_id)
+    }
+
+    /// Forgets the indirect view showing a cursor. Returns `false` if there's
+    /// none.
+    pub fn remove_indirect_view(&mut self, cursor_id: CursorId) -> bool {
+        let num_views = self.indirect_views.len();
+        self.indirect_views
+            .retain(|(_, view_cursor_id)| *view_cursor_id != cursor_id);
+        self.indirect_views.len() < num_views
+    }
+
+    #[inline]
+    pub fn move_cursor_to(&mut self, cursor_id: CursorId, char_index: CharIndex) {
+        self.move_cursor_to_target(cursor_id, CursorTarget::Char(char_index));
+    }
+
+    /// Moves a cursor to a column of a line, counted in chars. Both are
+    /// clamped to the text.
+    pub fn move_cursor_to_line(
+        &mut self,
+        cursor_id: CursorId,
+        line_index: LineIndex,
+        column: usize,
+    ) {
+        self.move_cursor_to_target(cursor_id, CursorTarget::Line(line_index, column));
+    }
+
+    fn move_cursor_to_target(&mut self, cursor_id: CursorId, target: CursorTarget) {
+        if let Some(loading) = self.loading.as_mut() {
+            loading.cursor_targets.retain(|(id, _)| *id != cursor_id);
+            loading.cursor_targets.push((cursor_id, target));
+            return;
+        }
+        let char_index = target.char_index(&self.content);
+        movement::move_to_char(&self.content, &mut self.cursors[cursor_id.0], char_index);
     }
 
     #[inline]
@@ -268,43 +727,290 @@ impl Buffer {
         self.parser.as_ref().and_then(|parser| parser.tree.as_ref())
     }
 
+    /// The dead key typed at a cursor which hasn't been composed yet, shown
+    /// at the cursor until the next character is typed
+    pub fn pending_dead_key(&self, cursor_id: CursorId) -> Option<char> {
+        self.cursor_states[cursor_id.0].dead_key
+    }
+
+    /// The lines folded away in the window showing a cursor
+    pub fn folded_lines(&self, cursor_id: CursorId) -> Rc<[Range<LineIndex>]> {
+        self.cursor_states[cursor_id.0]
+            .folds
+            .hidden_lines(self.content.staged())
+            .into()
+    }
+
+    /// The lines the window showing a cursor is narrowed to, if any
+    pub fn narrowed_lines(&self, cursor_id: CursorId) -> Option<Range<LineIndex>> {
+        self.cursor_states[cursor_id.0]
+            .narrowed
+            .as_ref()
+            .map(Narrowed::lines)
+    }
+
+    #[inline]
+    pub fn line_scopes(&self) -> &Rc<[LineScope]> {
+        &self.line_scopes
+    }
+
+    #[inline]
+    pub fn highlight_cache(&self) -> &Rc<RefCell<HighlightCache>> {
+        &self.highlight_cache
+    }
+
+    #[inline]
+    pub fn line_metrics(&self) -> &Rc<RefCell<LineMetricsCache>> {
+        &self.line_metrics
+    }
+
+    /// Sets highlighting scopes for whole lines, which take precedence over
+    /// the syntax tree. The scopes must be sorted and not overlap.
+    pub fn set_line_scopes(&mut self, line_scopes: Vec<LineScope>) {
+        self.line_scopes = line_scopes.into();
+    }
+
+    /// The annotations from every source, sorted by where they start
+    #[inline]
+    pub fn annotations(&self) -> &Rc<[Annotation]> {
+        self.annotations.merged()
+    }
+
+    /// Replaces the annotations from a source, e.g. `"spell"`, with ones
+    /// computed for a version of the text. Annotations for an older version
+    /// are rejected, as the text they refer to may have moved since.
+    pub fn set_annotations(
+        &mut self,
+        source: &str,
+        version: usize,
+        annotations: Vec<Annotation>,
+    ) -> Result<()> {
+        if version != self.content.version() {
+            anyhow::bail!(
+                "Annotations are for version {} of the buffer, which is now at version {}",
+                version,
+                self.content.version()
+            );
+        }
+        let len_chars = self.content.len_chars();
+        if let Some(annotation) = annotations.iter().find(|annotation| {
+            annotation.range.start > annotation.range.end || annotation.range.end > len_chars
+        }) {
+            anyhow::bail!(
+                "Annotation range {:?} is outside of the buffer",
+                annotation.range
+            );
+        }
+        self.annotations.set(source, annotations);
+        Ok(())
+    }
+
+    /// Removes the annotations from a source
+    pub fn clear_annotations(&mut self, source: &str) {
+        self.annotations.clear(source);
+    }
+
+    /// Replaces a range of text in a single edit, which can be undone
+    pub fn replace_range(&mut self, range: Range<CharIndex>, text: Rope) {
+        // A single edit inside the text can't fail
+        let _ = self.apply_edits(vec![TextEdit { range, text }]);
+    }
+
+    /// Applies a batch of edits, e.g. from a formatter, as a single revision
+    /// which can be undone. The ranges refer to the text before the edits and
+    /// must not overlap, otherwise nothing is changed.
+    pub fn apply_edits(&mut self, edits: Vec<TextEdit>) -> Result<()> {
+        self.apply_edits_recording(edits, true)
+    }
+
+    /// Applies a batch of edits to a buffer generated by the editor, e.g.
+    /// `*Messages*`, like `apply_edits` but without recording a revision or
+    /// marking the buffer as modified. Its history is dropped, there is
+    /// nothing to undo.
+    pub fn apply_generated_edits(&mut self, edits: Vec<TextEdit>) -> Result<()> {
+        self.apply_edits_recording(edits, false)
+    }
+
+    fn apply_edits_recording(&mut self, edits: Vec<TextEdit>, record: bool) -> Result<()> {
+        if self.loading.is_some() {
+            anyhow::bail!("Buffer is still loading");
+        }
+        let num_lines_before = self.content.len_lines();
+        let diff = batch::apply_edits(self.content.staged_mut(), edits, self.cursors.iter_mut())?;
+        if diff.is_empty() {
+            return Ok(());
+        }
+        for state in self.cursor_states.iter_mut() {
+            state.reconcile(self.content.staged(), num_lines_before, &diff);
+        }
+        if record {
+            self.modified_status = ModifiedStatus::Changed;
+            self.content
+                .create_revision(diff.clone(), self.cursors[0].clone());
+            self.update_lock();
+        } else {
+            let text = self.content.staged().clone();
+            *self.content = EditTree::new(text);
+        }
+        self.update_parse_tree(&diff, false);
+        Ok(())
+    }
+
+    /// Replaces the whole content of the buffer. The replacement is a new
+    /// revision, so it can be undone.
+    pub fn replace_text(&mut self, text: Rope) {
+        let diff = OpaqueDiff::between(self.content.staged(), &text);
+        for cursor in self.cursors.iter_mut() {
+            cursor.sync(&self.content, &text);
+        }
+        self.cursor_states
+            .iter_mut()
+            .for_each(CursorState::reset_lines);
+        *self.content.staged_mut() = text;
+        self.content
+            .create_revision(diff.clone(), self.cursors[0].clone());
+        self.update_parse_tree(&diff, false);
+    }
+
+    /// Replaces the whole content of a buffer generated by the editor, e.g.
+    /// `*Occur*`, without recording a revision. Its history is dropped, there
+    /// is nothing to undo.
+    pub fn replace_generated_text(&mut self, text: Rope) {
+        let diff = OpaqueDiff::between(self.content.staged(), &text);
+        for cursor in self.cursors.iter_mut() {
+            cursor.sync(&self.content, &text);
+        }
+        self.cursor_states
+            .iter_mut()
+            .for_each(CursorState::reset_lines);
+        *self.content = EditTree::new(text);
+        self.update_parse_tree(&diff, false);
+    }
+
+    /// Whether the file was changed by someone else since we loaded or last
+    /// saved it. A file which was deleted doesn't count, saving recreates it.
+    pub fn changed_on_disk(&self) -> bool {
+        match self
+            .file_path
+            .as_ref()
+            .and_then(|path| file_modified_time(path))
+        {
+            Some(modified) => self.modified_on_disk != Some(modified),
+            None => false,
+        }
+    }
+
+    /// Writes the buffer to disk even if the file changed since we loaded it
+    pub fn save_overwriting(&mut self) {
+        self.spawn_save_file(false);
+    }
+
+    /// Writes the buffer to disk using the configured `elevated_save_command`,
+    /// for files we don't have permission to write
+    pub fn save_elevated(&mut self) {
+        self.spawn_save_file(true);
+    }
+
+    /// Makes the buffer visit a different file and writes it there
+    pub fn save_as(&mut self, file_path: PathBuf) {
+        self.file_path = Some(file_path);
+        self.spawn_save_file(false);
+    }
+
+    /// Reads the file the buffer is visiting again, discarding any unsaved
+    /// changes. Like any other edit, reverting can be undone.
+    pub fn revert(&mut self) -> Result<()> {
+        let file_path = self
+            .file_path
+            .clone()
+            .context("Buffer is not visiting a file")?;
+        if self.loading.is_some() {
+            anyhow::bail!("Buffer is still loading");
+        }
+        let text = File::open(&file_path)
+            .and_then(|file| Rope::from_reader(BufReader::new(file)))
+            .with_context(|| format!("Could not read `{}`", file_path.display()))?;
+        self.replace_text(text);
+        self.modified_status = ModifiedStatus::Unchanged;
+        self.modified_on_disk = file_modified_time(&file_path);
+        self.update_lock();
+        Ok(())
+    }
+
+    /// Changes the language of the buffer, overriding the mode detected when
+    /// it was opened, and parses the text again
+    pub fn set_mode(&mut self, mode: &'static Mode) {
+        self.mode = mode;
+        self.indentation = detect_indentation(mode, self.content.staged());
+        self.auto_fill = self.context.config.auto_fill_modes.contains(&mode.name);
+        self.highlight_cache
+            .borrow_mut()
+            .clear(self.content.staged());
+        self.parser = start_parser(
+            &self.context,
+            self.id,
+            mode,
+            self.content.staged(),
+            self.content.version(),
+        );
+    }
+
     #[inline]
     pub fn handle_message(&mut self, message: BufferMessage) {
         match message {
             // Start writing the buffer to disk asynchronously
             BufferMessage::SaveBufferStart => {
-                self.spawn_save_file();
+                if self.changed_on_disk() {
+                    self.context.link.send(Message::SaveConflict(self.id));
+                } else {
+                    self.spawn_save_file(false);
+                }
             }
             // Saved the buffer successfully
             BufferMessage::SaveBufferEnd(Ok(new_content)) => {
                 self.modified_status = ModifiedStatus::Unchanged;
+                self.modified_on_disk = self
+                    .file_path
+                    .as_ref()
+                    .and_then(|path| file_modified_time(path));
 
-                // For now, we just assume the content may have changed
-                //
-                // Sync the cursors
+                // Saving may have changed the content, e.g. by stripping
+                // trailing whitespace. If so, the change is a new revision.
+                let diff = OpaqueDiff::between(self.content.staged(), &new_content);
+                if diff.is_empty() {
+                    return;
+                }
+                let num_lines_before = self.content.len_lines();
+                *self.content.staged_mut() = new_content;
                 for cursor in self.cursors.iter_mut() {
-                    cursor.sync(&self.content, &new_content);
+                    cursor.reconcile(&self.content, &diff);
+                }
+                for state in self.cursor_states.iter_mut() {
+                    state.reconcile(self.content.staged(), num_lines_before, &diff);
                 }
-
-                // Create a new revision, update the content.
                 self.content
-                    .create_revision(OpaqueDiff::empty(), self.cursors[0].clone());
-                *self.content.staged_mut() = new_content;
-
-                // We don't know the diff, so we just use OpaqueDiff::Empty.
-                // This is ok as we pass in fresh=true, so the previous parser
-                // tree won't be used.
-                self.update_parse_tree(&OpaqueDiff::empty(), true);
+                    .create_revision(diff.clone(), self.cursors[0].clone());
+                self.update_parse_tree(&diff, false);
             }
             // Failed to save the buffer
+            BufferMessage::SaveBufferEnd(Err(error))
+                if error.kind() == io::ErrorKind::PermissionDenied
+                    && self.context.config.elevated_save_command.is_some() =>
+            {
+                self.modified_status = ModifiedStatus::Changed;
+                self.context
+                    .link
+                    .send(Message::SavePermissionDenied(self.id));
+            }
             BufferMessage::SaveBufferEnd(Err(error)) => {
+                self.modified_status = ModifiedStatus::Changed;
                 self.context.log(error.to_string());
             }
             // The syntax parser finished parsing the code (tree-sitter)
             BufferMessage::ParseSyntax { version, status } => {
                 let parsed = status.unwrap();
                 if let Some(parser) = self.parser.as_mut() {
-                    parser.handle_parse_syntax_done(version, parsed);
+                    parser.handle_parse_syntax_done(&self.context.task_pool, version, parsed);
                 }
             }
             BufferMessage::CursorMessage { cursor_id, message } => {
@@ -317,6 +1023,72 @@ impl Buffer {
 
     #[inline]
     fn handle_cursor_message(&mut self, cursor_id: CursorId, message: CursorMessage) {
+        if self.read_only && message.modifies_text() {
+            self.context.log("Buffer is read-only");
+            return;
+        }
+        if self.loading.is_some() && message.modifies_text() {
+            self.context.log("Buffer is still loading");
+            return;
+        }
+        if message.modifies_text() && self.lock_owner().is_some() {
+            self.context.link.send(Message::FileLocked(self.id));
+            return;
+        }
+        // Anything but typing commits a pending dead key, inserting its accent
+        // on its own as typing the dead key again does
+        if let Some(dead_key) = self.cursor_states[cursor_id.0].dead_key.filter(|_| {
+            !matches!(
+                message,
+                CursorMessage::InsertChar {
+                    move_forward: true,
+                    ..
+                }
+            )
+        }) {
+            self.handle_cursor_message(
+                cursor_id,
+                CursorMessage::InsertChar {
+                    character: dead_key,
+                    move_forward: true,
+                },
+            );
+        }
+        let appending_kill = std::mem::replace(&mut self.cursor_states[cursor_id.0].killing, false);
+
+        // Typing and deleting characters are undone in groups, which end when
+        // anything else happens, e.g. moving the cursor
+        let grouped = message.is_grouped_edit();
+        if !grouped && self.content.has_open_group() {
+            self.content.close_group();
+        }
+
+        if let Some(narrowed) = self.cursor_states[cursor_id.0].narrowed.as_ref() {
+            let char_index = self.cursors[cursor_id.0].range().start;
+            let at_end = char_index == narrowed.last_char(&self.content)
+                && char_index < self.content.len_chars();
+            let selecting = self.cursors[cursor_id.0].has_selection();
+            match message {
+                CursorMessage::DeleteForward
+                | CursorMessage::KillWholeLine
+                | CursorMessage::KillToEndOfLine
+                    if at_end && !selecting =>
+                {
+                    self.context.log("End of narrowed region");
+                    return;
+                }
+                CursorMessage::DeleteBackward
+                    if char_index == narrowed.first_char(&self.content) && char_index > 0 =>
+                {
+                    self.context.log("Beginning of narrowed region");
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let previous_char_index = self.cursors[cursor_id.0].range().start;
+        let num_lines_before = self.content.len_lines();
         {
             let content = &self.content;
             let cursor = &mut self.cursors[cursor_id.0];
@@ -325,14 +1097,14 @@ impl Buffer {
                 CursorMessage::Up(n) => movement::move_vertically(
                     content,
                     cursor,
-                    self.mode.indentation.tab_width(),
+                    self.indentation.tab_width(),
                     Direction::Backward,
                     n,
                 ),
                 CursorMessage::Down(n) => movement::move_vertically(
                     content,
                     cursor,
-                    self.mode.indentation.tab_width(),
+                    self.indentation.tab_width(),
                     Direction::Forward,
                     n,
                 ),
@@ -342,7 +1114,10 @@ impl Buffer {
                 CursorMessage::Right => {
                     movement::move_horizontally(content, cursor, Direction::Forward, 1)
                 }
-                CursorMessage::StartOfLine => movement::move_to_start_of_line(content, cursor),
+                CursorMessage::StartOfLine => {
+                    movement::move_to_indentation_or_start_of_line(content, cursor)
+                }
+                CursorMessage::Indentation => movement::move_to_indentation(content, cursor),
                 CursorMessage::EndOfLine => movement::move_to_end_of_line(content, cursor),
                 CursorMessage::StartOfBuffer => movement::move_to_start_of_buffer(content, cursor),
                 CursorMessage::EndOfBuffer => movement::move_to_end_of_buffer(content, cursor),
@@ -352,17 +1127,86 @@ impl Buffer {
                 CursorMessage::MoveParagraph(direction, count) => {
                     movement::move_paragraph(content, cursor, direction, count)
                 }
+                CursorMessage::MoveSexp(direction) => {
+                    let tree = self.parser.as_ref().and_then(|parser| parser.tree.as_ref());
+                    match tree {
+                        Some(tree) => match sexp::node_boundary(
+                            tree,
+                            content,
+                            cursor.range().start,
+                            direction,
+                        ) {
+                            Some(char_index) => movement::move_to_char(content, cursor, char_index),
+                            None => self.context.log(no_sexp_message(direction)),
+                        },
+                        None => self.context.log("Buffer has not been parsed yet"),
+                    }
+                }
 
                 CursorMessage::BeginSelection => cursor.begin_selection(),
                 CursorMessage::ClearSelection => {
                     cursor.clear_selection();
                 }
                 CursorMessage::SelectAll => cursor.select_all(content),
+                CursorMessage::PopMark if !cursor.pop_mark(content) => {
+                    self.context.log("No mark set in this buffer");
+                }
+                CursorMessage::ExchangePointAndMark if !cursor.exchange_point_and_mark(content) => {
+                    self.context.log("No mark set in this buffer");
+                }
+
+                CursorMessage::ToggleFold => {
+                    let char_index = cursor.range().start;
+                    let folds = &mut self.cursor_states[cursor_id.0].folds;
+                    let tree = self.parser.as_ref().and_then(|parser| parser.tree.as_ref());
+                    match tree {
+                        Some(tree) if folds.toggle(content, tree, char_index) => {
+                            // Keep the cursor on the first line of the fold
+                            if let Some(char_index) =
+                                folds.skip_hidden(content, char_index + 1, char_index)
+                            {
+                                movement::move_to_char(content, cursor, char_index);
+                            }
+                        }
+                        Some(_) => self.context.log("Nothing to fold"),
+                        None => self.context.log("Buffer has not been parsed yet"),
+                    }
+                }
+                CursorMessage::UnfoldAll => self.cursor_states[cursor_id.0].folds.clear(),
+
+                CursorMessage::NarrowToSelection => {
+                    if cursor.selection().len() > 1 {
+                        self.cursor_states[cursor_id.0].narrowed =
+                            Some(Narrowed::new(content, cursor.selection()));
+                        cursor.clear_selection();
+                    } else {
+                        self.context.log("No selection to narrow to");
+                    }
+                }
+                CursorMessage::NarrowToDefinition => {
+                    let tree = self.parser.as_ref().and_then(|parser| parser.tree.as_ref());
+                    match tree {
+                        Some(tree) => match Narrowed::to_definition(
+                            self.mode,
+                            tree,
+                            content,
+                            cursor.range().start,
+                        ) {
+                            Some(narrowed) => {
+                                self.cursor_states[cursor_id.0].narrowed = Some(narrowed)
+                            }
+                            None => self.context.log("No definition around the cursor"),
+                        },
+                        None => self.context.log("Buffer has not been parsed yet"),
+                    }
+                }
+                CursorMessage::Widen => self.cursor_states[cursor_id.0].narrowed = None,
 
                 _ => {}
             }
         }
 
+        let typed_space = matches!(message, CursorMessage::InsertChar { character: ' ', .. });
         let mut undoing = false;
         let diff = {
             match message {
@@ -380,21 +1224,41 @@ impl Buffer {
                     }
                     operation.diff
                 }
-                CursorMessage::DeleteLine => {
-                    let diff = self.delete_line(cursor_id);
-                    if diff.is_empty() {
-                        self.context.log("End of buffer");
+                CursorMessage::KillWholeLine | CursorMessage::KillToEndOfLine
+                    if self.cursors[cursor_id.0].has_selection() =>
+                {
+                    self.cursor_states[cursor_id.0].killing = true;
+                    self.cut_selection_to_clipboard(cursor_id)
+                }
+                CursorMessage::KillWholeLine => {
+                    let operation = self.cursors[cursor_id.0].delete_line(&mut self.content);
+                    if self.context.config.kill_whole_line_to_indentation {
+                        movement::move_to_indentation(
+                            &self.content,
+                            &mut self.cursors[cursor_id.0],
+                        );
                     }
-                    diff
+                    self.kill(cursor_id, operation, appending_kill, Direction::Forward)
+                }
+                CursorMessage::KillToEndOfLine => {
+                    let operation =
+                        self.cursors[cursor_id.0].delete_to_end_of_line(&mut self.content);
+                    self.kill(cursor_id, operation, appending_kill, Direction::Forward)
                 }
+                CursorMessage::KillSexp(direction) => match self.sexp_range(cursor_id, direction) {
+                    Some(range) => {
+                        let operation =
+                            self.cursors[cursor_id.0].delete_range(&mut self.content, range);
+                        self.kill(cursor_id, operation, appending_kill, direction)
+                    }
+                    None => OpaqueDiff::empty(),
+                },
                 CursorMessage::Yank => self.paste_from_clipboard(cursor_id),
                 CursorMessage::CopySelection => self.copy_selection_to_clipboard(cursor_id),
                 CursorMessage::CutSelection => self.cut_selection_to_clipboard(cursor_id),
                 CursorMessage::InsertTab => {
-                    let (indentation_unit, indentation_count) = (
-                        self.mode.indentation.to_char(),
-                        self.mode.indentation.char_count(),
-                    );
+                    let (indentation_unit, indentation_count) =
+                        (self.indentation.to_char(), self.indentation.char_count());
                     let diff = self.cursors[cursor_id.0].insert_chars(
                         &mut self.content,
                         std::iter::repeat(indentation_unit).take(indentation_count),
@@ -407,34 +1271,46 @@ impl Buffer {
                     );
                     diff
                 }
-                CursorMessage::InsertNewLine => {
-                    let diff = self.cursors[cursor_id.0].insert_char(&mut self.content, '\n');
-                    let cursor = &mut self.cursors[cursor_id.0];
-                    movement::move_vertically(
-                        &self.content,
-                        cursor,
-                        self.mode.indentation.tab_width(),
-                        Direction::Forward,
-                        1,
-                    );
-                    movement::move_to_start_of_line(&self.content, cursor);
-                    diff
-                }
-                CursorMessage::InsertChar {
-                    character,
-                    move_forward,
-                } => {
-                    let diff = self.cursors[cursor_id.0].insert_char(&mut self.content, character);
-                    if move_forward {
-                        movement::move_horizontally(
+                CursorMessage::InsertNewLine => match self.closing_on_new_line(cursor_id) {
+                    Some(closing) => {
+                        let char_index = self.cursors[cursor_id.0].range().start;
+                        let line_end = line_content_end(&self.content, char_index);
+                        let inserted = format!(
+                            "\n\n{}{}",
+                            line_indentation(&self.content, closing.line),
+                            closing.keyword
+                        );
+                        self.splice(cursor_id, char_index..line_end, &inserted, 1)
+                    }
+                    None => {
+                        let diff = self.cursors[cursor_id.0].insert_char(&mut self.content, '\n');
+                        let cursor = &mut self.cursors[cursor_id.0];
+                        movement::move_vertically(
                             &self.content,
-                            &mut self.cursors[cursor_id.0],
+                            cursor,
+                            self.indentation.tab_width(),
                             Direction::Forward,
                             1,
                         );
+                        movement::move_to_start_of_line(&self.content, cursor);
+                        diff
                     }
-                    diff
-                }
+                },
+                CursorMessage::InsertClosingKeyword => match self.expected_closing(cursor_id) {
+                    Some(closing) => self.insert_closing_keyword(cursor_id, closing),
+                    None => {
+                        self.context.log("No construct left open here");
+                        OpaqueDiff::empty()
+                    }
+                },
+                CursorMessage::InsertChar {
+                    character,
+                    move_forward: true,
+                } => self.insert_typed_char(cursor_id, character),
+                CursorMessage::InsertChar {
+                    character,
+                    move_forward: false,
+                } => self.cursors[cursor_id.0].insert_char(&mut self.content, character),
                 CursorMessage::Undo => {
                     undoing = true;
                     self.undo(cursor_id)
@@ -443,6 +1319,10 @@ impl Buffer {
                     undoing = true;
                     self.redo(cursor_id)
                 }
+                CursorMessage::JumpToRevision(index) => {
+                    undoing = true;
+                    self.jump_to_revision(cursor_id, index)
+                }
 
                 _ => OpaqueDiff::empty(),
             }
@@ -453,20 +1333,235 @@ impl Buffer {
             for (id, cursor) in self.cursors.iter_mut().enumerate() {
                 if id != cursor_id.0 {
                     cursor.reconcile(&self.content, &diff);
+                } else if !undoing {
+                    cursor.reconcile_marks(&self.content, &diff);
                 }
             }
             if !undoing {
-                self.content
-                    .create_revision(diff.clone(), self.cursors[cursor_id.0].clone());
+                let cursor = self.cursors[cursor_id.0].clone();
+                if grouped {
+                    self.content
+                        .create_grouped_revision(diff.clone(), cursor, Instant::now());
+                } else {
+                    self.content.create_revision(diff.clone(), cursor);
+                }
                 self.update_parse_tree(&diff, false);
             }
+            for state in self.cursor_states.iter_mut() {
+                if undoing {
+                    state.folds.clear();
+                    if let Some(narrowed) = state.narrowed.as_mut() {
+                        narrowed.fit(self.content.staged());
+                    }
+                } else {
+                    state.reconcile(self.content.staged(), num_lines_before, &diff);
+                }
+            }
+        }
+
+        if typed_space && self.auto_fill {
+            self.auto_fill_line(cursor_id);
+        }
+
+        // Moving the cursor skips over folded lines
+        let cursor = &mut self.cursors[cursor_id.0];
+        if let Some(char_index) = self.cursor_states[cursor_id.0].folds.skip_hidden(
+            self.content.staged(),
+            previous_char_index,
+            cursor.range().start,
+        ) {
+            movement::move_to_char(&self.content, cursor, char_index);
+        }
+        if let Some(narrowed) = self.cursor_states[cursor_id.0].narrowed.as_ref() {
+            narrowed.restrict(&self.content, cursor);
         }
     }
 
-    fn delete_line(&mut self, cursor_id: CursorId) -> OpaqueDiff {
-        self.cursors[cursor_id.0]
-            .delete_line(&mut self.content)
-            .diff
+    /// The text from a cursor to the end of the syntax node after it, or from
+    /// the start of the node before it. Logs why if there's none, or if the
+    /// node reaches out of the narrowed region.
+    fn sexp_range(&self, cursor_id: CursorId, direction: Direction) -> Option<Range<CharIndex>> {
+        let tree = match self.parser.as_ref().and_then(|parser| parser.tree.as_ref()) {
+            Some(tree) => tree,
+            None => {
+                self.context.log("Buffer has not been parsed yet");
+                return None;
+            }
+        };
+        let char_index = self.cursors[cursor_id.0].range().start;
+        let range = match sexp::node_boundary(tree, &self.content, char_index, direction) {
+            Some(boundary) if boundary > char_index => char_index..boundary,
+            Some(boundary) => boundary..char_index,
+            None => {
+                self.context.log(no_sexp_message(direction));
+                return None;
+            }
+        };
+        if let Some(narrowed) = self.cursor_states[cursor_id.0].narrowed.as_ref() {
+            if range.start < narrowed.first_char(&self.content)
+                || range.end > narrowed.last_char(&self.content)
+            {
+                self.context
+                    .log("Expression reaches out of the narrowed region");
+                return None;
+            }
+        }
+        Some(range)
+    }
+
+    /// The keyword expected to close the construct left open before a
+    /// cursor, in modes configured with closing keywords
+    fn expected_closing(&self, cursor_id: CursorId) -> Option<Closing<'static>> {
+        let config = self.mode.closing_keywords.as_ref()?;
+        let tree = self.parser.as_ref()?.tree.as_ref()?;
+        closing::expected_closing(
+            config,
+            tree,
+            &self.content,
+            self.cursors[cursor_id.0].range().start,
+        )
+    }
+
+    /// The closing keyword to insert after the new line started at a cursor,
+    /// if the mode asks for it. Only when the rest of the line is blank, so a
+    /// line isn't split away from the construct.
+    fn closing_on_new_line(&self, cursor_id: CursorId) -> Option<Closing<'static>> {
+        let on_new_line = self
+            .mode
+            .closing_keywords
+            .as_ref()
+            .map_or(false, |config| config.on_new_line);
+        let char_index = self.cursors[cursor_id.0].range().start;
+        let rest_is_blank = self
+            .content
+            .slice(char_index..line_content_end(&self.content, char_index))
+            .chars()
+            .all(|character| character == ' ' || character == '\t');
+        if on_new_line && rest_is_blank {
+            self.expected_closing(cursor_id)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts a closing keyword on its own line, indented like the opening
+    /// keyword, after the line of the cursor or in place of it if it's blank
+    fn insert_closing_keyword(&mut self, cursor_id: CursorId, closing: Closing) -> OpaqueDiff {
+        let char_index = self.cursors[cursor_id.0].range().start;
+        let line_index = self.content.char_to_line(char_index);
+        let line_start = self.content.line_to_char(line_index);
+        let line_end = line_content_end(&self.content, char_index);
+        let indentation = line_indentation(&self.content, closing.line);
+        let blank_line = self
+            .content
+            .slice(line_start..line_end)
+            .chars()
+            .all(|character| character == ' ' || character == '\t');
+        let (range, inserted) = if blank_line {
+            (
+                line_start..line_end,
+                format!("{}{}", indentation, closing.keyword),
+            )
+        } else {
+            (
+                line_end..line_end,
+                format!("\n{}{}", indentation, closing.keyword),
+            )
+        };
+        let cursor_offset = inserted.chars().count();
+        self.splice(cursor_id, range, &inserted, cursor_offset)
+    }
+
+    /// Replaces a range of text with a string, leaving the cursor
+    /// `cursor_offset` characters into it
+    fn splice(
+        &mut self,
+        cursor_id: CursorId,
+        range: Range<CharIndex>,
+        inserted: &str,
+        cursor_offset: usize,
+    ) -> OpaqueDiff {
+        let byte_range =
+            self.content.char_to_byte(range.start)..self.content.char_to_byte(range.end);
+        self.content.remove(range.clone());
+        self.content.insert(range.start, inserted);
+        let cursor = &mut self.cursors[cursor_id.0];
+        cursor.clear_selection();
+        movement::move_to_char(&self.content, cursor, range.start + cursor_offset);
+        OpaqueDiff::new(
+            byte_range.start,
+            byte_range.end - byte_range.start,
+            inserted.len(),
+            range.start,
+            range.end - range.start,
+            inserted.chars().count(),
+        )
+    }
+
+    /// Inserts a character typed at a cursor and moves past it. With
+    /// `compose_dead_keys` on, accents typed with dead keys are composed with
+    /// the letter typed next where there's a precomposed character.
+    fn insert_typed_char(&mut self, cursor_id: CursorId, character: char) -> OpaqueDiff {
+        let characters = match self.cursor_states[cursor_id.0].dead_key.take() {
+            Some(dead_key) => compose::commit_dead_key(dead_key, character),
+            None if self.context.config.compose_dead_keys
+                && compose::dead_key_mark(character).is_some() =>
+            {
+                self.cursor_states[cursor_id.0].dead_key = Some(character);
+                return OpaqueDiff::empty();
+            }
+            None => vec![character],
+        };
+
+        let cursor = &mut self.cursors[cursor_id.0];
+        let diff = cursor.insert_chars(&mut self.content, characters.iter().copied());
+        movement::move_horizontally(&self.content, cursor, Direction::Forward, characters.len());
+        diff
+    }
+
+    /// Breaks the line before the cursor if it's gone past the fill column
+    fn auto_fill_line(&mut self, cursor_id: CursorId) {
+        let line_break = fill::break_line(
+            &self.content,
+            self.cursors[cursor_id.0].range().start,
+            self.context.config.fill_column,
+            self.mode
+                .comment
+                .as_ref()
+                .map(|comment| comment.token.as_str()),
+            self.indentation.tab_width(),
+        );
+        if let Some((range, text)) = line_break {
+            self.replace_range(range, text);
+        }
+    }
+
+    /// Puts the text a kill deleted in the clipboard. Kills made one after the
+    /// other are put together, so they're yanked back as one, the text killed
+    /// backwards going before the text killed earlier.
+    fn kill(
+        &mut self,
+        cursor_id: CursorId,
+        operation: DeleteOperation,
+        appending: bool,
+        direction: Direction,
+    ) -> OpaqueDiff {
+        if operation.diff.is_empty() {
+            self.context.log("End of buffer");
+            return operation.diff;
+        }
+        let mut killed = String::from(operation.deleted);
+        if appending {
+            if let Ok(previous) = self.context.clipboard.get_contents() {
+                match direction {
+                    Direction::Forward => killed.insert_str(0, &previous),
+                    Direction::Backward => killed.push_str(&previous),
+                }
+            }
+        }
+        self.context.clipboard.set_contents(killed).unwrap();
+        self.cursor_states[cursor_id.0].killing = true;
+        operation.diff
     }
 
     fn copy_selection_to_clipboard(&mut self, cursor_id: CursorId) -> OpaqueDiff {
@@ -519,7 +1614,50 @@ impl Buffer {
             .unwrap_or_else(OpaqueDiff::empty)
     }
 
+    fn jump_to_revision(&mut self, cursor_id: CursorId, index: usize) -> OpaqueDiff {
+        self.content
+            .jump_to(index)
+            .map(|(diff, cursor)| {
+                self.cursors[cursor_id.0] = cursor;
+                self.update_parse_tree(&diff, true);
+                diff
+            })
+            .unwrap_or_else(OpaqueDiff::empty)
+    }
+
+    /// The index of a revision given by its index or label
+    pub fn find_revision(&self, index_or_label: &str) -> Option<usize> {
+        let revisions = &self.content.revisions;
+        index_or_label
+            .parse()
+            .ok()
+            .filter(|index| *index < revisions.len())
+            .or_else(|| {
+                revisions
+                    .iter()
+                    .rposition(|revision| revision.label.as_deref() == Some(index_or_label))
+            })
+    }
+
+    /// Names the current revision in the edit tree, or removes its name
+    pub fn label_revision(&mut self, label: Option<String>) {
+        self.content.set_label(label);
+    }
+
     fn update_parse_tree(&mut self, diff: &OpaqueDiff, fresh: bool) {
+        let mut highlight_cache = self.highlight_cache.borrow_mut();
+        let mut line_metrics = self.line_metrics.borrow_mut();
+        if fresh {
+            highlight_cache.clear(self.content.staged());
+            line_metrics.clear(self.content.staged());
+            self.annotations.clear_all();
+        } else {
+            highlight_cache.reconcile(self.content.staged(), diff);
+            line_metrics.reconcile(self.content.staged(), diff);
+            self.annotations.reconcile(diff);
+        }
+        drop((highlight_cache, line_metrics));
+
         if let Some(parser) = self.parser.as_mut() {
             if fresh {
                 parser.tree = None;
@@ -540,10 +1678,23 @@ impl Buffer {
         }
     }
 
-    fn spawn_save_file(&mut self) {
-        let file_path = match self.file_path.clone() {
-            Some(file_path) => file_path,
-            None => return,
+    fn spawn_save_file(&mut self, elevated: bool) {
+        // Saving now would overwrite the file with the part read so far
+        if self.loading.is_some() {
+            self.context.log("Buffer is still loading");
+            return;
+        }
+        let file_path = match (self.file_path.clone(), self.remote.clone()) {
+            (Some(file_path), _) => file_path,
+            (None, Some(remote)) => return self.spawn_upload(remote),
+            (None, None) => return,
+        };
+        let elevated_save_command = match elevated {
+            true => match self.context.config.elevated_save_command.clone() {
+                Some(command) => Some(command),
+                None => return,
+            },
+            false => None,
         };
 
         self.modified_status = ModifiedStatus::Saving;
@@ -551,20 +1702,46 @@ impl Buffer {
         let text = self.content.staged().clone();
         let link = self.context.link.clone();
         let trim_trailing_whitespace = self.context.config.trim_trailing_whitespace_on_save;
+        let backup = self.context.config.backup.clone();
         self.context.task_pool.spawn(move |_| {
             let text = match trim_trailing_whitespace {
                 true => strip_trailing_whitespace(text),
                 false => text,
             };
 
-            let buffer_message = BufferMessage::SaveBufferEnd(
-                File::create(&file_path)
+            let buffer_message = BufferMessage::SaveBufferEnd(match elevated_save_command {
+                Some(command) => write_file_elevated(&command, &file_path, &text).map(|()| text),
+                None => backup
+                    .map_or(Ok(()), |backup| backup_file(&file_path, &backup))
+                    .map_err(|error| {
+                        io::Error::new(
+                            error.kind(),
+                            format!("Could not back up {} ({})", file_path.display(), error),
+                        )
+                    })
+                    .and_then(|()| File::create(&file_path))
                     .map(BufWriter::new)
                     .and_then(|writer| {
                         text.write_to(writer)?;
                         Ok(text)
                     }),
-            );
+            });
+            link.send(BuffersMessage::new(buffer_id, buffer_message).into())
+        });
+    }
+
+    fn spawn_upload(&mut self, remote: RemotePath) {
+        self.modified_status = ModifiedStatus::Saving;
+        let buffer_id = self.id;
+        let text = self.content.staged().clone();
+        let link = self.context.link.clone();
+        let trim_trailing_whitespace = self.context.config.trim_trailing_whitespace_on_save;
+        self.context.task_pool.spawn(move |_| {
+            let text = match trim_trailing_whitespace {
+                true => strip_trailing_whitespace(text),
+                false => text,
+            };
+            let buffer_message = BufferMessage::SaveBufferEnd(remote.upload(&text).map(|()| text));
             link.send(BuffersMessage::new(buffer_id, buffer_message).into())
         });
     }
@@ -664,6 +1841,11 @@ impl BufferCursor {
         self.send_cursor(CursorMessage::StartOfLine);
     }
 
+    #[inline]
+    pub fn move_to_indentation(&self) {
+        self.send_cursor(CursorMessage::Indentation);
+    }
+
     #[inline]
     pub fn move_end_of_line(&self) {
         self.send_cursor(CursorMessage::EndOfLine);
@@ -679,6 +1861,31 @@ impl BufferCursor {
         self.send_cursor(CursorMessage::EndOfBuffer);
     }
 
+    #[inline]
+    pub fn toggle_fold(&self) {
+        self.send_cursor(CursorMessage::ToggleFold);
+    }
+
+    #[inline]
+    pub fn unfold_all(&self) {
+        self.send_cursor(CursorMessage::UnfoldAll);
+    }
+
+    #[inline]
+    pub fn narrow_to_selection(&self) {
+        self.send_cursor(CursorMessage::NarrowToSelection);
+    }
+
+    #[inline]
+    pub fn narrow_to_definition(&self) {
+        self.send_cursor(CursorMessage::NarrowToDefinition);
+    }
+
+    #[inline]
+    pub fn widen(&self) {
+        self.send_cursor(CursorMessage::Widen);
+    }
+
     #[inline]
     pub fn begin_selection(&self) {
         self.send_cursor(CursorMessage::BeginSelection);
@@ -694,6 +1901,16 @@ impl BufferCursor {
         self.send_cursor(CursorMessage::SelectAll);
     }
 
+    #[inline]
+    pub fn pop_mark(&self) {
+        self.send_cursor(CursorMessage::PopMark);
+    }
+
+    #[inline]
+    pub fn exchange_point_and_mark(&self) {
+        self.send_cursor(CursorMessage::ExchangePointAndMark);
+    }
+
     #[inline]
     pub fn paste_from_clipboard(&self) {
         self.send_cursor(CursorMessage::Yank);
@@ -719,6 +1936,16 @@ impl BufferCursor {
         self.send_cursor(CursorMessage::Redo);
     }
 
+    #[inline]
+    pub fn jump_to_revision(&self, index: usize) {
+        self.send_cursor(CursorMessage::JumpToRevision(index));
+    }
+
+    #[inline]
+    pub fn undo_boundary(&self) {
+        self.send_cursor(CursorMessage::UndoBoundary);
+    }
+
     #[inline]
     pub fn delete_forward(&self) {
         self.send_cursor(CursorMessage::DeleteForward);
@@ -730,8 +1957,13 @@ impl BufferCursor {
     }
 
     #[inline]
-    pub fn delete_line(&self) {
-        self.send_cursor(CursorMessage::DeleteLine);
+    pub fn kill_whole_line(&self) {
+        self.send_cursor(CursorMessage::KillWholeLine);
+    }
+
+    #[inline]
+    pub fn kill_to_end_of_line(&self) {
+        self.send_cursor(CursorMessage::KillToEndOfLine);
     }
 
     #[inline]
@@ -739,6 +1971,21 @@ impl BufferCursor {
         self.send_cursor(CursorMessage::InsertNewLine);
     }
 
+    #[inline]
+    pub fn move_sexp(&self, direction: Direction) {
+        self.send_cursor(CursorMessage::MoveSexp(direction));
+    }
+
+    #[inline]
+    pub fn kill_sexp(&self, direction: Direction) {
+        self.send_cursor(CursorMessage::KillSexp(direction));
+    }
+
+    #[inline]
+    pub fn insert_closing_keyword(&self) {
+        self.send_cursor(CursorMessage::InsertClosingKeyword);
+    }
+
     #[inline]
     pub fn insert_tab(&self) {
         self.send_cursor(CursorMessage::InsertTab);
@@ -776,31 +2023,97 @@ pub enum CursorMessage {
     Down(usize),
     Left,
     Right,
+    /// The indentation of the line, or its start if the cursor is there
+    /// already
     StartOfLine,
+    Indentation,
     EndOfLine,
     StartOfBuffer,
     EndOfBuffer,
     MoveWord(Direction, usize),
     MoveParagraph(Direction, usize),
+    /// Over the syntax node after or before the cursor
+    MoveSexp(Direction),
+
+    // Folding
+    ToggleFold,
+    UnfoldAll,
+
+    // Narrowing
+    NarrowToSelection,
+    NarrowToDefinition,
+    Widen,
 
     // Editing
     BeginSelection,
     ClearSelection,
     SelectAll,
+    /// Jumps to the mark, cycling through the mark ring when repeated
+    PopMark,
+    /// Swaps the cursor and the mark, selecting the text in between
+    ExchangePointAndMark,
     Yank,
     CopySelection,
     CutSelection,
 
     DeleteForward,
     DeleteBackward,
-    DeleteLine,
+    /// Kills the selection if there's one, otherwise the line with its
+    /// newline
+    KillWholeLine,
+    /// Kills the selection if there's one, otherwise the rest of the line, or
+    /// its newline at the end of the line
+    KillToEndOfLine,
+    /// Kills from the cursor to the end of the syntax node after it, or from
+    /// the start of the node before it
+    KillSexp(Direction),
     InsertTab,
     InsertNewLine,
-    InsertChar { character: char, move_forward: bool },
+    /// Closes the construct left open before the cursor with its keyword,
+    /// e.g. `fi`
+    InsertClosingKeyword,
+    InsertChar {
+        character: char,
+        move_forward: bool,
+    },
 
     // Undo / Redo
     Undo,
     Redo,
+    UndoBoundary,
+    JumpToRevision(usize),
+}
+
+impl CursorMessage {
+    /// Whether handling the message could change the contents of the buffer
+    fn modifies_text(&self) -> bool {
+        matches!(
+            self,
+            Self::Yank
+                | Self::CutSelection
+                | Self::DeleteForward
+                | Self::DeleteBackward
+                | Self::KillWholeLine
+                | Self::KillToEndOfLine
+                | Self::KillSexp(_)
+                | Self::InsertTab
+                | Self::InsertNewLine
+                | Self::InsertClosingKeyword
+                | Self::InsertChar { .. }
+                | Self::Undo
+                | Self::Redo
+                | Self::JumpToRevision(_)
+        )
+    }
+
+    /// Whether the edit is undone together with similar edits right before
+    /// and after it
+    fn is_grouped_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::DeleteForward | Self::DeleteBackward | Self::InsertChar { .. }
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -825,3 +2138,150 @@ impl std::ops::Deref for RepositoryRc {
         &self.0
     }
 }
+
+/// The indentation guessed from a text, or the mode's default if there's too
+/// little indented text to tell. Tabs keep the mode's tab width.
+fn detect_indentation(mode: &Mode, text: &Rope) -> IndentationConfig {
+    match indentation::detect(text) {
+        Some(DetectedIndentation::Tabs) => IndentationConfig {
+            width: mode.indentation.tab_width(),
+            unit: IndentationUnit::Tab,
+            tab_width: None,
+        },
+        Some(DetectedIndentation::Spaces(width)) => IndentationConfig {
+            width,
+            unit: IndentationUnit::Space,
+            tab_width: Some(mode.indentation.tab_width()),
+        },
+        None => mode.indentation,
+    }
+}
+
+fn no_sexp_message(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Forward => "No expression after the cursor",
+        Direction::Backward => "No expression before the cursor",
+    }
+}
+
+/// Where the text of the line of a character ends, before its newline
+fn line_content_end(text: &Rope, char_index: CharIndex) -> CharIndex {
+    let line_index = text.char_to_line(char_index);
+    let line = text.line(line_index);
+    let line_end = text.line_to_char(line_index) + line.len_chars();
+    if line.len_chars() > 0 && line.char(line.len_chars() - 1) == '\n' {
+        line_end - 1
+    } else {
+        line_end
+    }
+}
+
+/// The spaces and tabs a line starts with
+fn line_indentation(text: &Rope, line_index: LineIndex) -> String {
+    text.line(line_index)
+        .chars()
+        .take_while(|&character| character == ' ' || character == '\t')
+        .collect()
+}
+
+/// Parses indentation typed in the prompt, e.g. `tabs`, `spaces 2` or just
+/// `2` for two spaces. A missing width keeps the current one, or the current
+/// tab width when switching to tabs.
+pub fn parse_indentation(input: &str, current: IndentationConfig) -> Result<IndentationConfig> {
+    let mut fields = input.split_whitespace();
+    let (unit, width) = match (fields.next(), fields.next(), fields.next()) {
+        (Some("tabs" | "tab"), width, None) => (IndentationUnit::Tab, width),
+        (Some("spaces" | "space"), width, None) => (IndentationUnit::Space, width),
+        (Some(width), None, None) => (IndentationUnit::Space, Some(width)),
+        _ => anyhow::bail!("Expected `tabs`, `spaces` or a width, e.g. `spaces 2`"),
+    };
+    let width = match width {
+        Some(width) => width
+            .parse()
+            .ok()
+            .filter(|width| *width > 0)
+            .with_context(|| format!("Invalid width `{}`", width))?,
+        None => match unit {
+            IndentationUnit::Space => current.width,
+            IndentationUnit::Tab => current.tab_width(),
+        },
+    };
+    Ok(IndentationConfig {
+        width,
+        unit,
+        tab_width: Some(current.tab_width()),
+    })
+}
+
+/// Creates a parser for the mode's language, if it has one, and starts parsing
+/// the text in the background
+fn start_parser(
+    context: &ContextHandle,
+    buffer_id: BufferId,
+    mode: &Mode,
+    text: &Rope,
+    version: usize,
+) -> Option<ParserPool> {
+    let mut parser = mode
+        .language()
+        .and_then(|result| result.ok())
+        .map(ParserPool::new)?;
+    let link = context.link.clone();
+    parser.ensure_tree(
+        &context.task_pool,
+        || text.clone(),
+        move |status| {
+            link.send(
+                BuffersMessage::new(buffer_id, BufferMessage::ParseSyntax { version, status })
+                    .into(),
+            )
+        },
+    );
+    Some(parser)
+}
+
+/// Writes a file by piping the text to a privileged command like `sudo -n tee`.
+/// The command has no access to the terminal, which the editor is drawing to.
+fn write_file_elevated(command: &[String], file_path: &Path, text: &Rope) -> io::Result<()> {
+    let (program, arguments) = command.split_first().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The elevated save command is empty",
+        )
+    })?;
+    let mut child = Command::new(program)
+        .args(arguments)
+        .arg(file_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Could not run `{}` ({})", program, error),
+            )
+        })?;
+
+    // Closing stdin, by dropping the writer, lets the command finish
+    let written = text.write_to(BufWriter::new(child.stdin.take().unwrap()));
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "`{}` failed ({}): {}",
+                command.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    written
+}
+
+fn file_modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,154 @@
+use zee_edit::{
+    line_diff::{diff_lines, Hunk},
+    Direction,
+};
+
+use super::{buffer::Buffers, BufferViewId};
+use crate::{error::Result, syntax::highlight::LineScope};
+
+/// Two buffers shown side by side, with the lines which differ highlighted.
+/// The hunks are kept up to date as either buffer is edited.
+pub struct Comparison {
+    pub left: BufferViewId,
+    pub right: BufferViewId,
+    hunks: Vec<Hunk>,
+    current_hunk: Option<usize>,
+    versions: Option<(usize, usize)>,
+}
+
+impl Comparison {
+    pub fn new(left: BufferViewId, right: BufferViewId) -> Self {
+        Self {
+            left,
+            right,
+            hunks: Vec::new(),
+            current_hunk: None,
+            versions: None,
+        }
+    }
+
+    pub fn num_hunks(&self) -> usize {
+        self.hunks.len()
+    }
+
+    pub fn current_hunk(&self) -> Option<(usize, &Hunk)> {
+        self.current_hunk
+            .and_then(|index| Some((index, self.hunks.get(index)?)))
+    }
+
+    /// Diffs the buffers again if either changed since the last refresh and
+    /// updates their highlighting
+    pub fn refresh(&mut self, buffers: &mut Buffers) {
+        let (left, right) = match (
+            buffers.get(self.left.buffer_id),
+            buffers.get(self.right.buffer_id),
+        ) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return,
+        };
+        let versions = (
+            left.edit_tree_handle().version(),
+            right.edit_tree_handle().version(),
+        );
+        if self.versions == Some(versions) {
+            return;
+        }
+        self.versions = Some(versions);
+        self.hunks = diff_lines(left.edit_tree(), right.edit_tree());
+        self.current_hunk = match self.hunks.len() {
+            0 => None,
+            num_hunks => self.current_hunk.map(|index| index.min(num_hunks - 1)),
+        };
+        self.highlight(buffers);
+    }
+
+    /// Makes the next or previous hunk current and moves the cursors of both
+    /// windows to its start
+    pub fn move_to_hunk(&mut self, buffers: &mut Buffers, direction: Direction) -> bool {
+        let num_hunks = self.hunks.len();
+        let index = match (self.current_hunk, direction) {
+            _ if num_hunks == 0 => return false,
+            (None, Direction::Forward) => 0,
+            (None, Direction::Backward) => num_hunks - 1,
+            (Some(index), Direction::Forward) if index + 1 < num_hunks => index + 1,
+            (Some(index), Direction::Backward) if index > 0 => index - 1,
+            _ => return false,
+        };
+        self.current_hunk = Some(index);
+
+        let hunk = &self.hunks[index];
+        for (view_id, line_index) in [(self.left, hunk.old.start), (self.right, hunk.new.start)] {
+            if let Some(buffer) = buffers.get_mut(view_id.buffer_id) {
+                let char_index = buffer.edit_tree().line_to_char(line_index);
+                buffer.move_cursor_to(view_id.cursor_id, char_index);
+            }
+        }
+        self.highlight(buffers);
+        true
+    }
+
+    /// Replaces the lines of the current hunk in one buffer with the lines
+    /// from the other. `Direction::Forward` copies from left to right.
+    pub fn copy_hunk(&mut self, buffers: &mut Buffers, direction: Direction) -> Result<()> {
+        let hunk = match self.current_hunk() {
+            Some((_, hunk)) => hunk.clone(),
+            None => anyhow::bail!("No hunk selected"),
+        };
+        let ((source, source_lines), (target, target_lines)) = match direction {
+            Direction::Forward => ((self.left, hunk.old), (self.right, hunk.new)),
+            Direction::Backward => ((self.right, hunk.new), (self.left, hunk.old)),
+        };
+
+        let source_text = buffers.get(source.buffer_id).unwrap().edit_tree();
+        let lines = source_text
+            .slice(
+                source_text.line_to_char(source_lines.start)
+                    ..source_text.line_to_char(source_lines.end),
+            )
+            .into();
+
+        let target = buffers.get_mut(target.buffer_id).unwrap();
+        if target.is_read_only() {
+            anyhow::bail!("Buffer is read-only");
+        }
+        let target_text = target.edit_tree();
+        let range = target_text.line_to_char(target_lines.start)
+            ..target_text.line_to_char(target_lines.end);
+        target.replace_range(range, lines);
+        self.refresh(buffers);
+        Ok(())
+    }
+
+    /// Removes the highlighting from the buffers, when done comparing
+    pub fn clear(&self, buffers: &mut Buffers) {
+        for view_id in [self.left, self.right] {
+            if let Some(buffer) = buffers.get_mut(view_id.buffer_id) {
+                buffer.set_line_scopes(Vec::new());
+            }
+        }
+    }
+
+    fn highlight(&self, buffers: &mut Buffers) {
+        let scopes = |lines: fn(&Hunk) -> &std::ops::Range<usize>, scope| {
+            self.hunks
+                .iter()
+                .enumerate()
+                .filter(|(_, hunk)| !lines(hunk).is_empty())
+                .map(|(index, hunk)| LineScope {
+                    lines: lines(hunk).clone(),
+                    scope: if Some(index) == self.current_hunk {
+                        "diff.delta"
+                    } else {
+                        scope
+                    },
+                })
+                .collect()
+        };
+        if let Some(buffer) = buffers.get_mut(self.left.buffer_id) {
+            buffer.set_line_scopes(scopes(|hunk| &hunk.old, "diff.minus"));
+        }
+        if let Some(buffer) = buffers.get_mut(self.right.buffer_id) {
+            buffer.set_line_scopes(scopes(|hunk| &hunk.new, "diff.plus"));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Completing the word before the cursor from a list of candidates, shown in a
+//! popup at the cursor. The candidates come from a source, e.g. the words of
+//! the open buffers or the files in a directory, and are narrowed down as more
+//! of the word is typed.
+
+use ropey::Rope;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    rc::Rc,
+};
+
+use zee_edit::CharIndex;
+
+use super::{buffer::Buffers, BufferViewId};
+use crate::{
+    components::completion::{CompletionPopup, Theme},
+    syntax::symbols::Symbol,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompletionKind {
+    Word,
+    Function,
+    Type,
+    Module,
+    Constant,
+    /// Any other definition
+    Symbol,
+    File,
+    Directory,
+}
+
+impl CompletionKind {
+    /// The kind of a definition captured by a `tags` query as
+    /// `@definition.<kind>`
+    pub fn from_tag(kind: &str) -> Self {
+        match kind {
+            "function" | "method" | "macro" => Self::Function,
+            "class" | "interface" | "type" | "struct" | "enum" | "implementation" => Self::Type,
+            "module" => Self::Module,
+            "constant" => Self::Constant,
+            _ => Self::Symbol,
+        }
+    }
+
+    /// A single column symbol shown before each candidate
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Word => "w",
+            Self::Function => "λ",
+            Self::Type => "T",
+            Self::Module => "M",
+            Self::Constant => "c",
+            Self::Symbol => "s",
+            Self::File => "f",
+            Self::Directory => "d",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// Shown after the label, e.g. a function's signature
+    pub detail: Option<String>,
+    /// Shown next to the list while the candidate is selected
+    pub documentation: Option<String>,
+    /// The text replacing what was typed, if it isn't the label
+    pub insert_text: Option<String>,
+}
+
+impl CompletionItem {
+    pub fn new(label: impl Into<String>, kind: CompletionKind) -> Self {
+        Self {
+            label: label.into(),
+            kind,
+            detail: None,
+            documentation: None,
+            insert_text: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        self.insert_text.as_deref().unwrap_or(&self.label)
+    }
+}
+
+/// The completion popup open in a window
+pub struct Completion {
+    pub(super) view_id: BufferViewId,
+    /// Where the text being completed starts
+    start: CharIndex,
+    /// Which chars can be typed without closing the popup
+    is_part: fn(char) -> bool,
+    /// Ordered by relevance, as given by the source
+    candidates: Vec<CompletionItem>,
+    /// The text typed since the start, and the candidates starting with it
+    typed: Option<String>,
+    matches: Rc<[CompletionItem]>,
+    selected: usize,
+}
+
+impl Completion {
+    pub(super) fn new(
+        view_id: BufferViewId,
+        start: CharIndex,
+        is_part: fn(char) -> bool,
+        candidates: Vec<CompletionItem>,
+    ) -> Self {
+        Self {
+            view_id,
+            start,
+            is_part,
+            candidates,
+            typed: None,
+            matches: Vec::new().into(),
+            selected: 0,
+        }
+    }
+
+    pub fn start(&self) -> CharIndex {
+        self.start
+    }
+
+    /// Whether the text typed since the start can still be completed
+    pub fn continues(&self, typed: &str) -> bool {
+        typed.chars().all(self.is_part)
+    }
+
+    pub fn num_matches(&self) -> usize {
+        self.matches.len()
+    }
+
+    /// Narrows the candidates down to those starting with the typed text.
+    /// Returns whether any are left.
+    pub fn set_filter(&mut self, typed: &str) -> bool {
+        if self.typed.as_deref() == Some(typed) {
+            return !self.matches.is_empty();
+        }
+        self.typed = Some(typed.to_owned());
+        self.matches = self
+            .candidates
+            .iter()
+            .filter(|candidate| has_prefix(&candidate.label, typed))
+            .cloned()
+            .collect();
+        self.selected = 0;
+        !self.matches.is_empty()
+    }
+
+    /// Moves the selection by a number of candidates, wrapping around at
+    /// either end of the list
+    pub fn select(&mut self, offset: isize) {
+        let num_matches = self.matches.len() as isize;
+        if num_matches > 0 {
+            self.selected = (self.selected as isize + offset).rem_euclid(num_matches) as usize;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.selected)
+    }
+
+    pub fn popup(&self, theme: Theme) -> CompletionPopup {
+        CompletionPopup {
+            theme,
+            anchor: self.start,
+            items: self.matches.clone(),
+            selected: self.selected,
+        }
+    }
+}
+
+/// Whether a candidate starts with the typed text, ignoring case unless the
+/// typed text has capitals
+pub fn has_prefix(candidate: &str, typed: &str) -> bool {
+    if typed.chars().any(char::is_uppercase) {
+        candidate.starts_with(typed)
+    } else {
+        candidate.to_lowercase().starts_with(typed)
+    }
+}
+
+pub fn is_word_char(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// Where the word ending at `char_index` starts
+pub fn word_start(text: &Rope, char_index: CharIndex) -> CharIndex {
+    char_index
+        - text
+            .chars_at(char_index)
+            .reversed()
+            .take_while(|character| is_word_char(*character))
+            .count()
+}
+
+/// Dynamic abbreviations, as in Emacs' `dabbrev`: the words starting with a
+/// prefix, from the buffer being edited, nearest to the cursor first, then
+/// from the other buffers. Words defined in the buffer being edited, as found
+/// by its `tags` query, show the kind and line of their definition.
+pub(super) fn word_candidates(
+    buffers: &Buffers,
+    view_id: BufferViewId,
+    prefix: &str,
+    cursor: CharIndex,
+    symbols: &[Symbol],
+) -> Vec<CompletionItem> {
+    let text = buffers
+        .get(view_id.buffer_id)
+        .map(|buffer| buffer.edit_tree());
+    let definitions: HashMap<&str, &Symbol> = symbols
+        .iter()
+        .map(|symbol| (symbol.name.as_str(), symbol))
+        .collect();
+    let mut seen = HashSet::new();
+    seen.insert(prefix.to_owned());
+    let mut candidates = Vec::new();
+    let mut add = |word: String, origin: Option<&str>| {
+        if candidates.len() >= MAX_WORD_CANDIDATES || !seen.insert(word.clone()) {
+            return;
+        }
+        let candidate = match (definitions.get(word.as_str()), text) {
+            (Some(symbol), Some(text)) => CompletionItem {
+                detail: Some(symbol.kind.clone()),
+                documentation: Some(text.line(symbol.line_index).to_string().trim().to_owned()),
+                ..CompletionItem::new(word, CompletionKind::from_tag(&symbol.kind))
+            },
+            _ => CompletionItem {
+                detail: origin.map(str::to_owned),
+                ..CompletionItem::new(word, CompletionKind::Word)
+            },
+        };
+        candidates.push(candidate);
+    };
+
+    if let Some(text) = text {
+        let mut before = words_with_prefix(text, 0, cursor, prefix);
+        let after = words_with_prefix(text, cursor, text.len_chars(), prefix);
+        let mut after = after.into_iter();
+        // Alternate between the words before and after the cursor, so the
+        // nearest come first
+        loop {
+            match (before.pop(), after.next()) {
+                (None, None) => break,
+                (before, after) => {
+                    before
+                        .into_iter()
+                        .chain(after)
+                        .for_each(|word| add(word, None));
+                }
+            }
+        }
+    }
+    for buffer in buffers.iter() {
+        if buffer.id() == view_id.buffer_id {
+            continue;
+        }
+        let text = buffer.edit_tree();
+        let name = buffer
+            .file_path()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .or_else(|| buffer.name().map(str::to_owned));
+        for word in words_with_prefix(text, 0, text.len_chars(), prefix) {
+            add(word, name.as_deref());
+        }
+    }
+    candidates
+}
+
+/// The words between two chars starting with a prefix, in order
+fn words_with_prefix(text: &Rope, start: CharIndex, end: CharIndex, prefix: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut after_word_char = start > 0 && is_word_char(text.char(start - 1));
+    for character in text.slice(start..end).chars() {
+        if is_word_char(character) {
+            word.push(character);
+        } else {
+            if !after_word_char && has_prefix(&word, prefix) {
+                words.push(std::mem::take(&mut word));
+            }
+            word.clear();
+            after_word_char = false;
+        }
+    }
+    if !after_word_char && has_prefix(&word, prefix) {
+        words.push(word);
+    }
+    words
+}
+
+/// Whether a char can be part of a file name typed in a buffer, e.g. in a
+/// shell script or an include
+pub fn is_file_name_char(character: char) -> bool {
+    character.is_alphanumeric() || "_-.~+@%".contains(character)
+}
+
+/// The path ending at `char_index`, split into where its last component
+/// starts and the directory before it, e.g. `src/` for `src/ma`
+pub fn path_before(text: &Rope, char_index: CharIndex) -> (CharIndex, String) {
+    let path_length = text
+        .chars_at(char_index)
+        .reversed()
+        .take_while(|character| is_file_name_char(*character) || *character == '/')
+        .count();
+    let path = text.slice(char_index - path_length..char_index).to_string();
+    let directory = match path.rfind('/') {
+        Some(slash) => &path[..=slash],
+        None => "",
+    };
+    (
+        char_index - path_length + directory.chars().count(),
+        directory.to_owned(),
+    )
+}
+
+/// The entries of the directory a typed path names, relative to `base` unless
+/// it's absolute or starts with `~/`. Directories end with a slash, so their
+/// own entries can be completed next. Hidden files are only listed when the
+/// typed name starts with a dot.
+pub fn path_candidates(directory: &str, base: &Path, show_hidden: bool) -> Vec<CompletionItem> {
+    let directory = if directory == "~/" || directory.starts_with("~/") {
+        match dirs::home_dir() {
+            Some(home_dir) => home_dir.join(&directory[2..]),
+            None => return Vec::new(),
+        }
+    } else {
+        base.join(directory)
+    };
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut candidates: Vec<CompletionItem> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !show_hidden {
+                return None;
+            }
+            // Follows symlinks, unlike the entry's own file type
+            let candidate = if entry.path().is_dir() {
+                CompletionItem::new(name + "/", CompletionKind::Directory)
+            } else {
+                CompletionItem::new(name, CompletionKind::File)
+            };
+            Some(candidate)
+        })
+        .collect();
+    candidates.sort_by(|left, right| left.label.cmp(&right.label));
+    candidates
+}
+
+const MAX_WORD_CANDIDATES: usize = 100;
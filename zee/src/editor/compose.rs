@@ -0,0 +1,41 @@
+//! Composing accented characters typed with dead keys. Terminals compose most
+//! dead keys and input methods themselves and send the finished characters,
+//! but some keyboard layouts send the accent on its own, e.g. `´`, followed by
+//! the letter it goes on. With `compose_dead_keys` on, the accent is held back
+//! as a pre-edit shown at the cursor until the next character commits it.
+
+use unicode_normalization::char::compose;
+
+/// The combining mark a dead key adds to the letter typed after it, if the
+/// character is a spacing accent sent by a dead key
+pub fn dead_key_mark(character: char) -> Option<char> {
+    let mark = match character {
+        '´' => '\u{0301}',
+        '¨' => '\u{0308}',
+        '¸' => '\u{0327}',
+        '¯' => '\u{0304}',
+        '˜' => '\u{0303}',
+        'ˆ' => '\u{0302}',
+        'ˇ' => '\u{030C}',
+        '˘' => '\u{0306}',
+        '˙' => '\u{0307}',
+        '˚' => '\u{030A}',
+        '˝' => '\u{030B}',
+        '˛' => '\u{0328}',
+        _ => return None,
+    };
+    Some(mark)
+}
+
+/// What to insert after a dead key followed by another character. Typing a
+/// space or the dead key again inserts the accent itself, and characters which
+/// don't take the accent are inserted after it.
+pub fn commit_dead_key(dead_key: char, character: char) -> Vec<char> {
+    if character == ' ' || character == dead_key {
+        return vec![dead_key];
+    }
+    match dead_key_mark(dead_key).and_then(|mark| compose(character, mark)) {
+        Some(composed) => vec![composed],
+        None => vec![dead_key, character],
+    }
+}
@@ -0,0 +1,60 @@
+use ropey::Rope;
+use std::{fs::File, io::BufReader, path::Path};
+
+use zee_edit::line_diff::{diff_lines, unified_diff};
+
+use crate::{
+    error::{Context, Result},
+    syntax::highlight::LineScope,
+};
+
+/// Formats the unsaved changes of a buffer as a unified diff against the file
+/// it's visiting. A file which doesn't exist yet is treated as empty. Returns
+/// `None` if the buffer is unchanged.
+pub fn diff_with_file(file_path: &Path, display_path: &Path, text: &Rope) -> Result<Option<Rope>> {
+    let saved = if file_path.exists() {
+        Rope::from_reader(BufReader::new(File::open(file_path)?))
+            .with_context(|| format!("Could not read `{}`", file_path.display()))?
+    } else {
+        Rope::new()
+    };
+    let hunks = diff_lines(&saved, text);
+    if hunks.is_empty() {
+        return Ok(None);
+    }
+
+    let mut diff = format!(
+        "--- {path} (on disk)\n+++ {path} (buffer)\n",
+        path = display_path.display()
+    );
+    diff.push_str(&unified_diff(&saved, text, &hunks, CONTEXT_LINES));
+    Ok(Some(diff.into()))
+}
+
+/// Colours the lines of a diff returned by `diff_with_file` according to
+/// their prefix
+pub fn unified_diff_scopes(text: &Rope) -> Vec<LineScope> {
+    let mut line_scopes: Vec<LineScope> = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let scope = match line.get_char(0) {
+            // The `---` and `+++` file header
+            _ if line_index < 2 => "diff.delta",
+            Some('@') => "diff.delta",
+            Some('+') => "diff.plus",
+            Some('-') => "diff.minus",
+            _ => continue,
+        };
+        match line_scopes.last_mut() {
+            Some(last) if last.scope == scope && last.lines.end == line_index => {
+                last.lines.end += 1;
+            }
+            _ => line_scopes.push(LineScope {
+                lines: line_index..line_index + 1,
+                scope,
+            }),
+        }
+    }
+    line_scopes
+}
+
+const CONTEXT_LINES: usize = 3;
@@ -0,0 +1,173 @@
+//! Renders a buffer with its syntax highlighting, to share it outside of the
+//! editor as text with ANSI escape codes or as a standalone HTML page.
+
+use ropey::Rope;
+use std::{fmt::Write, ops::Range, path::Path};
+use zi::terminal::{Colour, Style};
+
+use zee_edit::LineIndex;
+
+use crate::syntax::highlight::{scope_style, LineScope, ScopeSpan, Theme};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Ansi,
+    Html,
+}
+
+impl Format {
+    /// HTML for `.html` and `.htm` files, text with ANSI escape codes for any
+    /// other file
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("html")
+                    || extension.eq_ignore_ascii_case("htm") =>
+            {
+                Self::Html
+            }
+            _ => Self::Ansi,
+        }
+    }
+}
+
+/// The text to export and how to highlight it
+pub struct Highlighted<'a> {
+    pub text: &'a Rope,
+    pub lines: Range<LineIndex>,
+    /// The highlighting spans of each line, if the buffer was parsed
+    pub spans: Option<Vec<Vec<ScopeSpan>>>,
+    pub line_scopes: &'a [LineScope],
+}
+
+pub fn export(format: Format, theme: &Theme, title: &str, highlighted: &Highlighted) -> String {
+    let mut output = String::new();
+    if format == Format::Html {
+        write!(
+            output,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             </head>\n<body style=\"margin: 0\">\n<pre style=\"{}; margin: 0; padding: 1em\">",
+            escape_html(title),
+            css(theme.text, true),
+        )
+        .unwrap();
+    }
+
+    for (offset, line_index) in highlighted.lines.clone().enumerate() {
+        let spans = highlighted
+            .spans
+            .as_ref()
+            .and_then(|spans| spans.get(offset))
+            .map_or(&[][..], Vec::as_slice);
+        for (style, run) in styled_runs(theme, highlighted, line_index, spans) {
+            let run = run.trim_end_matches(['\n', '\r']);
+            if run.is_empty() {
+                continue;
+            }
+            match format {
+                Format::Ansi if style == theme.text => output.push_str(run),
+                Format::Ansi => write!(output, "{}{}\x1b[0m", ansi(style), run).unwrap(),
+                Format::Html if style == theme.text => output.push_str(&escape_html(run)),
+                Format::Html => write!(
+                    output,
+                    "<span style=\"{}\">{}</span>",
+                    css(style, false),
+                    escape_html(run)
+                )
+                .unwrap(),
+            }
+        }
+        if line_index + 1 < highlighted.text.len_lines() {
+            output.push('\n');
+        }
+    }
+
+    if format == Format::Html {
+        output.push_str("</pre>\n</body>\n</html>\n");
+    }
+    output
+}
+
+/// Splits a line into runs of text with the same style. As when drawing, a
+/// char takes the scope of the first span which doesn't end before it.
+fn styled_runs(
+    theme: &Theme,
+    highlighted: &Highlighted,
+    line_index: LineIndex,
+    spans: &[ScopeSpan],
+) -> Vec<(Style, String)> {
+    let line_scope = highlighted
+        .line_scopes
+        .iter()
+        .find(|line_scope| line_scope.lines.contains(&line_index))
+        .map(|line_scope| line_scope.scope);
+    let mut spans = spans.iter().peekable();
+    let mut get_scope = |byte_index| loop {
+        let span = spans.peek()?;
+        if byte_index < span.bytes.start {
+            return None;
+        } else if byte_index < span.bytes.end {
+            return Some(span.scope);
+        } else {
+            spans.next();
+        }
+    };
+
+    let mut runs: Vec<(Style, String)> = Vec::new();
+    let mut byte_index = 0;
+    for character in highlighted.text.line(line_index).chars() {
+        let scope = line_scope.or_else(|| get_scope(byte_index)).unwrap_or("");
+        let style = scope_style(theme, scope);
+        match runs.last_mut() {
+            Some((run_style, run)) if *run_style == style => run.push(character),
+            _ => runs.push((style, character.into())),
+        }
+        byte_index += character.len_utf8();
+    }
+    runs
+}
+
+fn ansi(style: Style) -> String {
+    let Colour { red, green, blue } = style.foreground;
+    let mut code = format!("\x1b[38;2;{};{};{}", red, green, blue);
+    if style.bold {
+        code.push_str(";1");
+    }
+    if style.underline {
+        code.push_str(";4");
+    }
+    code.push('m');
+    code
+}
+
+fn css(style: Style, with_background: bool) -> String {
+    let mut css = format!("color: {}", hex(style.foreground));
+    if with_background {
+        write!(css, "; background-color: {}", hex(style.background)).unwrap();
+    }
+    if style.bold {
+        css.push_str("; font-weight: bold");
+    }
+    if style.underline {
+        css.push_str("; text-decoration: underline");
+    }
+    css
+}
+
+fn hex(Colour { red, green, blue }: Colour) -> String {
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
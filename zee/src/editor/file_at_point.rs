@@ -0,0 +1,103 @@
+//! Finding the file referenced by the text under the cursor, e.g. a path in an
+//! error log followed by a line and column, or a module in an import.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use ropey::RopeSlice;
+use std::path::{Path, PathBuf};
+
+use zee_edit::LineIndex;
+
+/// A file referenced in the text and where to go in it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReference {
+    pub text: String,
+    /// Zero based, like the rest of the editor, the text counts from one
+    pub line: Option<LineIndex>,
+    pub column: Option<usize>,
+}
+
+/// The file reference which contains `column` on a line, if any
+pub fn reference_at(line: RopeSlice, column: usize) -> Option<FileReference> {
+    let chars: Vec<char> = line.chars().collect();
+    if column >= chars.len() || !is_reference_char(chars[column]) {
+        return None;
+    }
+    let start = chars[..column]
+        .iter()
+        .rposition(|character| !is_reference_char(*character))
+        .map_or(0, |index| index + 1);
+    let end = chars[column..]
+        .iter()
+        .position(|character| !is_reference_char(*character))
+        .map_or(chars.len(), |index| column + index);
+    let token: String = chars[start..end].iter().collect();
+
+    let captures = LINE_COLUMN.captures(&token)?;
+    let text = captures[1].trim_end_matches(['.', ',', ';', '!', '?']);
+    let number = |index| {
+        captures
+            .get(index)
+            .and_then(|number| number.as_str().parse::<usize>().ok())
+            .map(|number| number.saturating_sub(1))
+    };
+    (!text.is_empty()).then(|| FileReference {
+        text: text.to_owned(),
+        line: number(2),
+        column: number(3),
+    })
+}
+
+/// The first existing file the reference can be resolved to, looking in each
+/// directory of `roots` in order. Module references like `editor::search` or
+/// `editor.search` are tried with the extension of the current file,
+/// including the module's `mod` or `__init__` file.
+pub fn resolve(reference: &str, roots: &[PathBuf], extension: Option<&str>) -> Option<PathBuf> {
+    if let Some(relative) = reference.strip_prefix("~/") {
+        return dirs::home_dir()
+            .map(|home_dir| home_dir.join(relative))
+            .filter(|path| path.is_file());
+    }
+    if Path::new(reference).is_absolute() {
+        return Some(PathBuf::from(reference)).filter(|path| path.is_file());
+    }
+
+    let candidates = candidates(reference, extension);
+    roots.iter().find_map(|root| {
+        candidates
+            .iter()
+            .map(|candidate| root.join(candidate))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The relative paths a reference may stand for, most likely first
+fn candidates(reference: &str, extension: Option<&str>) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from(reference)];
+    let extension = match extension {
+        Some(extension) if !reference.contains('/') => extension,
+        _ => return candidates,
+    };
+    let separator = if reference.contains("::") { "::" } else { "." };
+    let segments: Vec<&str> = reference
+        .split(separator)
+        .skip_while(|segment| matches!(*segment, "crate" | "self" | "super" | ""))
+        .collect();
+
+    // The last segments may name an item inside the module rather than a file
+    for num_segments in (1..=segments.len()).rev() {
+        let module: PathBuf = segments[..num_segments].iter().collect();
+        candidates.push(module.with_extension(extension));
+        candidates.push(module.join("mod").with_extension(extension));
+        candidates.push(module.join("__init__").with_extension(extension));
+    }
+    candidates
+}
+
+fn is_reference_char(character: char) -> bool {
+    character.is_alphanumeric() || "_-./~:+@%".contains(character)
+}
+
+/// A reference followed by an optional line and column, e.g. `src/main.rs:12:5:`
+static LINE_COLUMN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(.*?)(?::(\d+))?(?::(\d+))?:?$").unwrap());
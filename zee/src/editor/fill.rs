@@ -0,0 +1,206 @@
+//! Wrapping prose to a fill column, either a whole paragraph at once or a line
+//! at a time while typing. In modes with a comment token only comments are
+//! filled, and each line keeps the comment prefix.
+
+use ropey::{Rope, RopeSlice};
+use std::ops::Range;
+
+use zee_edit::{graphemes, CharIndex, LineIndex};
+
+/// The paragraph around `line_index` re-wrapped so that no line is wider than
+/// `fill_column`, and the range of text it replaces, or `None` if the line
+/// isn't part of a paragraph.
+///
+/// Paragraphs are separated by blank lines, or in code by lines which aren't
+/// comments, are empty comments or start with a different comment marker. The first line keeps its prefix, the
+/// indentation and comment token, and the other lines take the prefix of the
+/// paragraph's second line, so hanging indentation is preserved.
+pub fn fill_paragraph(
+    text: &Rope,
+    line_index: LineIndex,
+    fill_column: usize,
+    comment_token: Option<&str>,
+    tab_width: usize,
+) -> Option<(Range<CharIndex>, Rope)> {
+    let comment_token = trim_token(comment_token);
+    let line = Line::parse(text.line(line_index), comment_token)?;
+    if comment_token.is_some() && !line.is_comment {
+        return None;
+    }
+    // Comments only continue with the same marker, `///` doesn't follow `//`
+    let in_paragraph = |line_index: LineIndex| {
+        Line::parse(text.line(line_index), comment_token)
+            .filter(|other| other.prefix.trim() == line.prefix.trim())
+    };
+    let start = (0..line_index)
+        .rev()
+        .find(|line_index| in_paragraph(*line_index).is_none())
+        .map_or(0, |line_index| line_index + 1);
+    let end = (line_index + 1..text.len_lines())
+        .find(|line_index| in_paragraph(*line_index).is_none())
+        .unwrap_or_else(|| text.len_lines());
+    let lines: Vec<Line> = (start..end).filter_map(in_paragraph).collect();
+
+    let first_prefix = lines[0].prefix.as_str();
+    let prefix = lines
+        .get(1)
+        .map_or(first_prefix, |line| line.prefix.as_str());
+    let words: Vec<&str> = lines
+        .iter()
+        .flat_map(|line| line.body.split_whitespace())
+        .collect();
+    let line_ending = line_ending(&lines[0].ending);
+    let width = |text: &str| graphemes::line_width(tab_width, &RopeSlice::from(text));
+
+    let mut filled = String::from(first_prefix);
+    let mut column = width(first_prefix);
+    let mut line_start = true;
+    for word in words {
+        let word_width = width(word);
+        if !line_start && column + 1 + word_width > fill_column {
+            filled.push_str(line_ending);
+            filled.push_str(prefix);
+            column = width(prefix);
+            line_start = true;
+        }
+        if !line_start {
+            filled.push(' ');
+            column += 1;
+        }
+        filled.push_str(word);
+        column += word_width;
+        line_start = false;
+    }
+    filled.push_str(&lines[lines.len() - 1].ending);
+
+    let range = text.line_to_char(start)..text.line_to_char(end);
+    Some((range, Rope::from(filled)))
+}
+
+/// Where to break the line being typed on, if the text before `char_index`
+/// is wider than `fill_column`. The whitespace before the last word which
+/// fits, or the first word if none does, is replaced by a new line with the
+/// same prefix.
+pub fn break_line(
+    text: &Rope,
+    char_index: CharIndex,
+    fill_column: usize,
+    comment_token: Option<&str>,
+    tab_width: usize,
+) -> Option<(Range<CharIndex>, Rope)> {
+    let comment_token = trim_token(comment_token);
+    let line_index = text.char_to_line(char_index);
+    let line_start = text.line_to_char(line_index);
+    let typed = text.slice(line_start..char_index);
+    let line = Line::parse(typed, comment_token)?;
+    if comment_token.is_some() && !line.is_comment {
+        return None;
+    }
+    let width = |text: &str| graphemes::line_width(tab_width, &RopeSlice::from(text));
+    if width(&format!("{}{}", line.prefix, line.body.trim_end())) <= fill_column {
+        return None;
+    }
+
+    // The whitespace runs between words, as char offsets into the body
+    let body: Vec<char> = line.body.chars().collect();
+    let mut gaps = Vec::new();
+    let mut index = 0;
+    while index < body.len() {
+        if body[index].is_whitespace() {
+            let gap_start = index;
+            while index < body.len() && body[index].is_whitespace() {
+                index += 1;
+            }
+            if gap_start > 0 && index < body.len() {
+                gaps.push(gap_start..index);
+            }
+        } else {
+            index += 1;
+        }
+    }
+    let prefix_width = width(&line.prefix);
+    let fits = |gap: &Range<usize>| {
+        let before: String = body[..gap.start].iter().collect();
+        prefix_width + width(&before) <= fill_column
+    };
+    let gap = gaps
+        .iter()
+        .rev()
+        .find(|gap| fits(gap))
+        .or_else(|| gaps.first())?;
+
+    let body_start = line_start + line.prefix.chars().count();
+    let range = body_start + gap.start..body_start + gap.end;
+    let ending: String = text
+        .line(line_index)
+        .chars()
+        .skip_while(|character| *character != '\n' && *character != '\r')
+        .collect();
+    let new_line = format!("{}{}", line_ending(&ending), line.prefix);
+    Some((range, Rope::from(new_line)))
+}
+
+/// The comment token without the space which follows it when commenting
+fn trim_token(comment_token: Option<&str>) -> Option<&str> {
+    comment_token
+        .map(str::trim_end)
+        .filter(|token| !token.is_empty())
+}
+
+/// The line ending to break lines with, as used by the text being filled
+fn line_ending(ending: &str) -> &str {
+    if ending.is_empty() {
+        "\n"
+    } else {
+        ending
+    }
+}
+
+/// A line of a paragraph split into its prefix, text and line ending
+struct Line {
+    /// The indentation, followed by the comment token and the whitespace
+    /// after it if the line is a comment
+    prefix: String,
+    body: String,
+    ending: String,
+    is_comment: bool,
+}
+
+impl Line {
+    /// `None` for blank lines and empty comments, which separate paragraphs
+    fn parse(line: RopeSlice, comment_token: Option<&str>) -> Option<Self> {
+        let line = String::from(line);
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = line[content.len()..].to_owned();
+        let indentation_end = content.len() - content.trim_start().len();
+
+        let mut prefix_end = indentation_end;
+        let mut is_comment = false;
+        if let Some(token) = comment_token {
+            if let Some(after_token) = content[indentation_end..].strip_prefix(token) {
+                // Doc comments like `///` or `//!` extend the token
+                let marks = after_token.len()
+                    - after_token
+                        .trim_start_matches(|character| {
+                            token.contains(character) || character == '!'
+                        })
+                        .len();
+                let after_marks = &after_token[marks..];
+                let spaces = after_marks.len() - after_marks.trim_start().len();
+                prefix_end += token.len() + marks + spaces;
+                is_comment = true;
+            }
+        }
+
+        let body = &content[prefix_end..];
+        if body.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            prefix: content[..prefix_end].to_owned(),
+            body: body.to_owned(),
+            ending,
+            is_comment,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+use ropey::Rope;
+use std::ops::Range;
+use tree_sitter::{Node, Tree};
+
+use zee_edit::{CharIndex, LineIndex, OpaqueDiff};
+
+/// The regions of a buffer folded away in a window. Each fold hides whole
+/// lines, those between the first and last line of a syntax node, so the
+/// lines opening and closing the node stay visible.
+#[derive(Clone, Debug, Default)]
+pub struct Folds {
+    /// The lines hidden by each fold, sorted and not overlapping
+    hidden: Vec<Range<LineIndex>>,
+}
+
+impl Folds {
+    /// Unfolds the fold right below the cursor's line, or the one around the
+    /// cursor. Otherwise, folds the innermost syntax node around the cursor
+    /// which spans at least three lines. Returns whether anything changed.
+    pub fn toggle(&mut self, text: &Rope, tree: &Tree, char_index: CharIndex) -> bool {
+        let line_index = text.char_to_line(char_index);
+        if let Some(index) = self
+            .hidden
+            .iter()
+            .position(|hidden| hidden.start == line_index + 1)
+        {
+            self.hidden.remove(index);
+            return true;
+        }
+
+        let byte_index = text.char_to_byte(char_index);
+        let mut node = tree
+            .root_node()
+            .descendant_for_byte_range(byte_index, byte_index);
+        while let Some(current) = node {
+            if let Some(lines) = hidden_lines(&current) {
+                if let Some(index) = self.hidden.iter().position(|hidden| *hidden == lines) {
+                    self.hidden.remove(index);
+                } else {
+                    // Folds nested in the new one are forgotten
+                    self.hidden
+                        .retain(|hidden| lines.end <= hidden.start || hidden.end <= lines.start);
+                    let index = self
+                        .hidden
+                        .partition_point(|hidden| hidden.start < lines.start);
+                    self.hidden.insert(index, lines);
+                }
+                return true;
+            }
+            node = current.parent();
+        }
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.hidden.clear();
+    }
+
+    /// Updates the folds after an edit, given the text after the edit and
+    /// how many lines it had before. Folds touched by the edit are opened.
+    pub fn reconcile(&mut self, text: &Rope, num_lines_before: usize, diff: &OpaqueDiff) {
+        if self.hidden.is_empty() {
+            return;
+        }
+        let num_lines = text.len_lines();
+        let edit_start = text.char_to_line(diff.char_index.min(text.len_chars()));
+        let new_edit_end =
+            text.char_to_line((diff.char_index + diff.new_char_length).min(text.len_chars()));
+        let old_edit_end = (new_edit_end + num_lines_before).saturating_sub(num_lines);
+        let shift = |line_index: LineIndex| (line_index + num_lines) - num_lines_before;
+        self.hidden = self
+            .hidden
+            .iter()
+            .filter_map(|hidden| {
+                if old_edit_end < hidden.start {
+                    Some(shift(hidden.start)..shift(hidden.end))
+                } else {
+                    (hidden.end <= edit_start).then(|| hidden.clone())
+                }
+            })
+            .collect();
+    }
+
+    /// The lines hidden by each fold
+    pub fn hidden_lines(&self, text: &Rope) -> Vec<Range<LineIndex>> {
+        self.hidden
+            .iter()
+            .filter(|hidden| hidden.end <= text.len_lines())
+            .cloned()
+            .collect()
+    }
+
+    /// Where a cursor which moved from `previous` to `char_index` should go
+    /// instead, if it landed on a hidden line: past the fold when moving
+    /// forward, or to the end of the line before it when moving backward
+    pub fn skip_hidden(
+        &self,
+        text: &Rope,
+        previous: CharIndex,
+        char_index: CharIndex,
+    ) -> Option<CharIndex> {
+        let line_index = text.char_to_line(char_index);
+        let hidden = self
+            .hidden
+            .iter()
+            .find(|hidden| hidden.contains(&line_index))?;
+        Some(if char_index >= previous {
+            text.line_to_char(hidden.end.min(text.len_lines()))
+        } else {
+            text.line_to_char(hidden.start).saturating_sub(1)
+        })
+    }
+}
+
+/// The number of rows it takes to show `lines`, given the sorted ranges of
+/// lines folded away. Each fold is shown as a single placeholder row.
+pub fn visual_rows(folded: &[Range<LineIndex>], lines: Range<LineIndex>) -> usize {
+    folded
+        .iter()
+        .map(|hidden| hidden.start.max(lines.start)..hidden.end.min(lines.end))
+        .filter(|overlap| !overlap.is_empty())
+        .fold(lines.len(), |rows, overlap| rows + 1 - overlap.len())
+}
+
+/// The first line to show such that `line` is on the last of `rows` rows
+pub fn first_line_for_rows(folded: &[Range<LineIndex>], line: LineIndex, rows: usize) -> LineIndex {
+    let mut first_line = line;
+    for _ in 1..rows {
+        if first_line == 0 {
+            break;
+        }
+        first_line -= 1;
+        if let Some(hidden) = folded.iter().find(|hidden| hidden.contains(&first_line)) {
+            first_line = hidden.start;
+        }
+    }
+    first_line
+}
+
+/// The lines a fold of a syntax node would hide, all but its first and last,
+/// if it spans at least three lines
+fn hidden_lines(node: &Node) -> Option<Range<LineIndex>> {
+    let start_line = node.start_position().row;
+    let end = node.end_position();
+    let end_line = if end.column == 0 {
+        // Nodes including a trailing newline end at the start of the next line
+        end.row.checked_sub(1)?
+    } else {
+        end.row
+    };
+    (start_line + 1 < end_line).then(|| start_line + 1..end_line)
+}
@@ -0,0 +1,320 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use ropey::Rope;
+use std::{any, borrow::Cow, fmt::Write, ops::Range};
+use zi::{terminal::Key, AnyCharacter, Bindings, CommandFn, Component, EndsWith, IntoKeyPattern};
+
+use zee_edit::CharIndex;
+
+use super::bindings::KeySequenceSlice;
+
+/// What a command is bound to, as added to a component's `Bindings`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyPattern {
+    Keys(Vec<Key>),
+    /// Any sequence ending with the key, e.g. `C-g` cancelling a prefix
+    EndsWith(Key),
+    AnyCharacter,
+}
+
+impl KeyPattern {
+    fn matches(&self, keys: &[Key]) -> bool {
+        match self {
+            Self::Keys(pattern) => pattern == keys,
+            Self::EndsWith(key) => keys.last() == Some(key),
+            Self::AnyCharacter => matches!(keys, [Key::Char(_)]),
+        }
+    }
+
+    fn is_prefix(&self, keys: &[Key]) -> bool {
+        matches!(self, Self::Keys(pattern) if pattern.len() > keys.len() && pattern.starts_with(keys))
+    }
+}
+
+impl std::fmt::Display for KeyPattern {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Keys(keys) => write!(formatter, "{}", KeySequenceSlice::new(keys, false)),
+            Self::EndsWith(key) => write!(
+                formatter,
+                "... {}",
+                KeySequenceSlice::new(std::slice::from_ref(key), false)
+            ),
+            Self::AnyCharacter => write!(formatter, "any character"),
+        }
+    }
+}
+
+/// The key patterns zi accepts, described for the help commands
+pub trait DescribePattern: IntoKeyPattern {
+    fn pattern(&self) -> KeyPattern;
+}
+
+impl<const N: usize> DescribePattern for [Key; N] {
+    fn pattern(&self) -> KeyPattern {
+        KeyPattern::Keys(self.to_vec())
+    }
+}
+
+impl DescribePattern for Vec<Key> {
+    fn pattern(&self) -> KeyPattern {
+        KeyPattern::Keys(self.clone())
+    }
+}
+
+impl DescribePattern for EndsWith {
+    fn pattern(&self) -> KeyPattern {
+        KeyPattern::EndsWith(self.0)
+    }
+}
+
+impl DescribePattern for AnyCharacter {
+    fn pattern(&self) -> KeyPattern {
+        KeyPattern::AnyCharacter
+    }
+}
+
+/// A named command together with all the key patterns bound to it
+struct CommandBindings {
+    name: Cow<'static, str>,
+    patterns: Vec<KeyPattern>,
+}
+
+/// The commands a component binds while it has focus
+struct ComponentBindings {
+    component: &'static str,
+    commands: Vec<CommandBindings>,
+}
+
+/// The bindings of every kind of component shown so far, in the order they
+/// were first registered. All instances of a component bind the same keys,
+/// so only the first one is recorded.
+static REGISTERED: Lazy<Mutex<Vec<ComponentBindings>>> = Lazy::new(Default::default);
+
+/// Wraps a component's `Bindings`, recording the commands added to them for
+/// `describe_bindings` and `describe_key`. Use it in place of the bindings
+/// passed to `Component::bindings`.
+pub struct RecordedBindings<'a, C: Component> {
+    bindings: &'a mut Bindings<C>,
+    recorded: Option<Vec<CommandBindings>>,
+}
+
+impl<'a, C: Component> RecordedBindings<'a, C> {
+    pub fn new(bindings: &'a mut Bindings<C>) -> Self {
+        let component = component_name::<C>();
+        let recorded = (bindings.is_empty()
+            && !REGISTERED
+                .lock()
+                .iter()
+                .any(|registered| registered.component == component))
+        .then(Vec::new);
+        Self { bindings, recorded }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    pub fn set_focus(&mut self, focused: bool) {
+        self.bindings.set_focus(focused);
+    }
+
+    pub fn set_notify(&mut self, notify: bool) {
+        self.bindings.set_notify(notify);
+    }
+
+    pub fn add<Marker>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        keys: impl DescribePattern,
+        command: impl CommandFn<C, Marker>,
+    ) {
+        let name = name.into();
+        self.record(name.clone(), keys.pattern());
+        self.bindings.add(name, keys, command);
+    }
+
+    /// Starts binding a command to one or more key patterns, each added with
+    /// `RecordedCommand::with`
+    pub fn command<Marker, F>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        command: F,
+    ) -> RecordedCommand<'_, 'a, C, F, Marker>
+    where
+        F: CommandFn<C, Marker> + Clone,
+    {
+        RecordedCommand {
+            bindings: self,
+            name: name.into(),
+            command,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn record(&mut self, name: Cow<'static, str>, pattern: KeyPattern) {
+        let recorded = match self.recorded.as_mut() {
+            Some(recorded) => recorded,
+            None => return,
+        };
+        match recorded.iter_mut().find(|command| command.name == name) {
+            Some(command) => command.patterns.push(pattern),
+            None => recorded.push(CommandBindings {
+                name,
+                patterns: vec![pattern],
+            }),
+        }
+    }
+}
+
+impl<'a, C: Component> Drop for RecordedBindings<'a, C> {
+    fn drop(&mut self) {
+        match self.recorded.take() {
+            Some(commands) if !commands.is_empty() => {
+                REGISTERED.lock().push(ComponentBindings {
+                    component: component_name::<C>(),
+                    commands,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A command being bound by `RecordedBindings::command`
+pub struct RecordedCommand<'b, 'a, C: Component, F, Marker> {
+    bindings: &'b mut RecordedBindings<'a, C>,
+    name: Cow<'static, str>,
+    command: F,
+    _marker: std::marker::PhantomData<Marker>,
+}
+
+impl<'b, 'a, C, F, Marker> RecordedCommand<'b, 'a, C, F, Marker>
+where
+    C: Component,
+    F: CommandFn<C, Marker> + Clone,
+{
+    pub fn with(self, keys: impl DescribePattern) -> Self {
+        self.bindings
+            .add(self.name.clone(), keys, self.command.clone());
+        self
+    }
+}
+
+/// The name of a component's type without its path, e.g. `InputPrompt`
+fn component_name<C>() -> &'static str {
+    let name = any::type_name::<C>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// The components whose bindings are active while editing a buffer, what
+/// `describe_key` resolves key sequences against
+const EDITING_COMPONENTS: [&str; 2] = ["Editor", "Buffer"];
+
+/// Renders every named command and its key bindings, grouped by the
+/// components shown so far
+pub fn describe_bindings() -> Rope {
+    let registered = REGISTERED.lock();
+    let mut text = String::new();
+    for (index, component) in registered.iter().enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        writeln!(text, "{} bindings", component.component).unwrap();
+        writeln!(text, "{}", "-".repeat(component.component.len() + 9)).unwrap();
+        for command in &component.commands {
+            let patterns = command
+                .patterns
+                .iter()
+                .map(KeyPattern::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(text, "{:<28}{}", patterns, command.name).unwrap();
+        }
+    }
+    text.into()
+}
+
+/// Describes what the keys pressed so far are bound to while editing a
+/// buffer, `None` if they are the prefix of a longer binding and more keys
+/// should be read
+pub fn describe_key(keys: &[Key]) -> Option<String> {
+    let registered = REGISTERED.lock();
+    let components = registered
+        .iter()
+        .filter(|registered| EDITING_COMPONENTS.contains(&registered.component));
+    let mut matches = Vec::new();
+    let mut is_prefix = false;
+    for component in components {
+        for command in &component.commands {
+            if command.patterns.iter().any(|pattern| pattern.matches(keys)) {
+                matches.push(format!("{} ({})", command.name, component.component));
+            }
+            is_prefix |= command
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_prefix(keys));
+        }
+    }
+
+    let sequence = KeySequenceSlice::new(keys, false);
+    if !matches.is_empty() {
+        Some(format!("{} runs {}", sequence, matches.join(", ")))
+    } else if is_prefix {
+        None
+    } else {
+        Some(format!("{} is undefined", sequence))
+    }
+}
+
+/// Describes the grapheme under the cursor: its code points, how it's
+/// encoded in UTF-8, the highlighting scopes around it and where it is, e.g.
+/// `"é" U+0065 U+0301 (UTF-8 65 CC 81), scope string, char 10 of 200`
+pub fn describe_char(text: &Rope, range: Range<CharIndex>, scopes: &[&str]) -> String {
+    if range.is_empty() {
+        return format!("End of buffer, char {}", range.start);
+    }
+    let grapheme = text.slice(range.clone()).to_string();
+    let displayed = if grapheme.chars().any(char::is_control) {
+        grapheme.escape_debug().to_string()
+    } else {
+        grapheme.clone()
+    };
+    let code_points = grapheme
+        .chars()
+        .map(|character| format!("U+{:04X}", character as u32))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let bytes = grapheme
+        .bytes()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let scope = if scopes.is_empty() {
+        "none".into()
+    } else {
+        scopes.join(" > ")
+    };
+    format!(
+        "\"{}\" {} (UTF-8 {}), scope {}, char {} of {}",
+        displayed,
+        code_points,
+        bytes,
+        scope,
+        range.start,
+        text.len_chars()
+    )
+}
+
+/// Parses a code point written in hexadecimal, optionally prefixed with `U+`
+/// or `0x`, e.g. `U+00E9`
+pub fn parse_code_point(input: &str) -> Option<char> {
+    let input = input.trim();
+    let digits = ["U+", "u+", "0x", "0X"]
+        .iter()
+        .find_map(|prefix| input.strip_prefix(prefix))
+        .unwrap_or(input);
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
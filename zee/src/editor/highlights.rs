@@ -0,0 +1,152 @@
+//! Patterns highlighted with `highlight-regexp`, drawn in every buffer on top
+//! of the syntax highlighting until they're removed or zee exits. Only the
+//! lines in view are searched, so the highlights follow edits as they're
+//! made, and a pattern is previewed while it's typed.
+
+use std::rc::Rc;
+use zi::{
+    components::text::{Text, TextProperties},
+    Callback, Colour, ComponentExt, Container, FlexBasis, Item, Style,
+};
+
+use super::search::Pattern;
+use crate::components::prompt::items::{PickerDefinition, RenderCandidate};
+
+/// The colours a pattern can be highlighted in, drawn behind `TEXT_COLOUR`
+pub const HIGHLIGHT_COLOURS: [(&str, Colour); 6] = [
+    ("yellow", Colour::rgb(250, 189, 47)),
+    ("green", Colour::rgb(184, 187, 38)),
+    ("aqua", Colour::rgb(142, 192, 124)),
+    ("blue", Colour::rgb(131, 165, 152)),
+    ("pink", Colour::rgb(211, 134, 155)),
+    ("orange", Colour::rgb(254, 128, 25)),
+];
+
+/// The colour of highlighted text, dark enough on any of the highlight colours
+pub const TEXT_COLOUR: Colour = Colour::rgb(29, 32, 33);
+
+pub struct Highlight {
+    /// What was typed, a string or a `/regex/`
+    pub input: String,
+    pub pattern: Pattern,
+    pub colour: Colour,
+}
+
+#[derive(Default)]
+pub struct Highlights {
+    highlights: Vec<Rc<Highlight>>,
+    /// The pattern being typed, in the colour it would get by default
+    preview: Option<Rc<Highlight>>,
+    /// The highlights and the preview, shared with the windows drawing them
+    drawn: Rc<[Rc<Highlight>]>,
+}
+
+impl Highlights {
+    /// The highlights to draw, latest last. Where two overlap, the latest is
+    /// drawn.
+    pub fn drawn(&self) -> Rc<[Rc<Highlight>]> {
+        self.drawn.clone()
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &str> {
+        self.highlights
+            .iter()
+            .map(|highlight| highlight.input.as_str())
+    }
+
+    /// The colour a new pattern gets unless another is picked, cycling
+    /// through `HIGHLIGHT_COLOURS`
+    pub fn next_colour_index(&self) -> usize {
+        self.highlights.len() % HIGHLIGHT_COLOURS.len()
+    }
+
+    /// Highlights a pattern, replacing the highlight of the same input if
+    /// there's one
+    pub fn add(&mut self, input: String, pattern: Pattern, colour: Colour) {
+        self.highlights.retain(|highlight| highlight.input != input);
+        self.highlights.push(Rc::new(Highlight {
+            input,
+            pattern,
+            colour,
+        }));
+        self.preview = None;
+        self.update_drawn();
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<String> {
+        if index >= self.highlights.len() {
+            return None;
+        }
+        let removed = self.highlights.remove(index);
+        self.update_drawn();
+        Some(removed.input.clone())
+    }
+
+    /// Shows what a pattern being typed would highlight, or nothing if it
+    /// doesn't parse
+    pub fn set_preview(&mut self, input: String, pattern: Option<Pattern>) {
+        let colour = HIGHLIGHT_COLOURS[self.next_colour_index()].1;
+        self.preview = pattern.map(|pattern| {
+            Rc::new(Highlight {
+                input,
+                pattern,
+                colour,
+            })
+        });
+        self.update_drawn();
+    }
+
+    pub fn clear_preview(&mut self) {
+        if self.preview.take().is_some() {
+            self.update_drawn();
+        }
+    }
+
+    fn update_drawn(&mut self) {
+        self.drawn = self
+            .highlights
+            .iter()
+            .chain(self.preview.iter())
+            .cloned()
+            .collect();
+    }
+}
+
+/// A picker of the highlight colours, showing a sample of each. The colour at
+/// `first` in `HIGHLIGHT_COLOURS` is listed first, and the index of the colour
+/// picked in `HIGHLIGHT_COLOURS` is passed to `on_select`.
+pub fn colour_picker(first: usize, on_select: Callback<usize>) -> PickerDefinition {
+    let colour_indices: Rc<[usize]> = (0..HIGHLIGHT_COLOURS.len())
+        .map(|offset| (first + offset) % HIGHLIGHT_COLOURS.len())
+        .collect();
+    let names: Rc<[String]> = colour_indices
+        .iter()
+        .map(|&index| HIGHLIGHT_COLOURS[index].0.to_owned())
+        .collect();
+    let render_indices = colour_indices.clone();
+    PickerDefinition::items("colour", names, move |index: usize| {
+        on_select.emit(colour_indices[index])
+    })
+    .render(move |candidate: RenderCandidate| {
+        let (name, colour) = HIGHLIGHT_COLOURS[render_indices[candidate.index]];
+        Item::fixed(1)(Container::row([
+            Text::item_with_key(
+                FlexBasis::Fixed(4),
+                format!("{}sample", name).as_str(),
+                TextProperties::new()
+                    .content(" ab ")
+                    .style(Style::normal(colour, TEXT_COLOUR)),
+            ),
+            Text::item_with_key(
+                FlexBasis::Auto,
+                format!("{}name", name).as_str(),
+                TextProperties::new()
+                    .content(format!(" {}", name))
+                    .style(Style::normal(
+                        candidate.background,
+                        candidate.theme.item_file_foreground,
+                    )),
+            ),
+        ]))
+    })
+}
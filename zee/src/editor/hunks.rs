@@ -0,0 +1,111 @@
+//! The changes of a buffer against the version of its file at `HEAD` in the
+//! file's git repository, found by diffing the buffer with the committed
+//! text. Unsaved changes are included.
+
+use git2::Repository;
+use ropey::Rope;
+use std::{ops::Range, path::Path};
+
+use zee_edit::{
+    line_diff::{diff_lines, Hunk},
+    CharIndex, Direction, LineIndex,
+};
+
+use crate::error::{Context, Result};
+
+pub struct HeadDiff {
+    head: Rope,
+    hunks: Vec<Hunk>,
+}
+
+impl HeadDiff {
+    /// Diffs a text with the version of its file at `HEAD`
+    pub fn new(repo: &Repository, file_path: &Path, text: &Rope) -> Result<Self> {
+        let head = head_text(repo, file_path)?;
+        let hunks = diff_lines(&head, text);
+        Ok(Self { head, hunks })
+    }
+
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// The index of the hunk on a line of the text. A hunk deleting lines is
+    /// on the line after them.
+    pub fn hunk_at_line(&self, line_index: LineIndex) -> Option<usize> {
+        self.hunks.iter().position(|hunk| {
+            hunk.new.contains(&line_index) || (hunk.new.is_empty() && hunk.new.start == line_index)
+        })
+    }
+
+    /// The index of the first hunk starting after a line, or the last one
+    /// starting before it
+    pub fn next_hunk(&self, line_index: LineIndex, direction: Direction) -> Option<usize> {
+        match direction {
+            Direction::Forward => self
+                .hunks
+                .iter()
+                .position(|hunk| hunk.new.start > line_index),
+            Direction::Backward => self
+                .hunks
+                .iter()
+                .rposition(|hunk| hunk.new.start < line_index),
+        }
+    }
+
+    /// The edit restoring the committed lines of a hunk: the range of the
+    /// text to replace and the lines to replace it with
+    pub fn revert_edit(&self, text: &Rope, index: usize) -> (Range<CharIndex>, Rope) {
+        let Hunk { ref old, ref new } = self.hunks[index];
+        let range = text.line_to_char(new.start)..text.line_to_char(new.end);
+        let lines = self
+            .head
+            .slice(self.head.line_to_char(old.start)..self.head.line_to_char(old.end))
+            .into();
+        (range, lines)
+    }
+
+    /// A line describing a hunk in a picker: where it starts, how many lines
+    /// it adds and removes, and its first line, e.g. `12  +2 -1  let x = 1;`
+    pub fn describe(&self, text: &Rope, index: usize) -> String {
+        let Hunk { ref old, ref new } = self.hunks[index];
+        let first_line = if new.is_empty() {
+            self.head.line(old.start)
+        } else {
+            text.line(new.start)
+        };
+        format!(
+            "{:>5}  {:<9} {}",
+            new.start + 1,
+            format!("+{} -{}", new.len(), old.len()),
+            first_line.to_string().trim()
+        )
+    }
+}
+
+/// The text of a file as committed at `HEAD`
+fn head_text(repo: &Repository, file_path: &Path) -> Result<Rope> {
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?;
+    let file_path = file_path
+        .canonicalize()
+        .unwrap_or_else(|_| file_path.into());
+    let workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.into());
+    let relative_path = file_path
+        .strip_prefix(&workdir)
+        .context("File is outside of the repository")?;
+
+    let tree = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .context("Could not read HEAD")?;
+    let blob = tree
+        .get_path(relative_path)
+        .and_then(|entry| entry.to_object(repo))
+        .and_then(|object| object.peel_to_blob())
+        .with_context(|| format!("`{}` is not in HEAD", relative_path.display()))?;
+    let text = std::str::from_utf8(blob.content())
+        .with_context(|| format!("`{}` is not a text file", relative_path.display()))?;
+    Ok(text.into())
+}
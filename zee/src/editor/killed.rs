@@ -0,0 +1,44 @@
+//! The files of the buffers killed lately, remembered with where their cursor
+//! was so they can be reopened like a closed browser tab.
+
+use std::path::{Path, PathBuf};
+use zee_edit::LineIndex;
+
+pub struct KilledBuffer {
+    pub file_path: PathBuf,
+    pub line: LineIndex,
+    /// The column of the cursor, counted in chars
+    pub column: usize,
+}
+
+#[derive(Default)]
+pub struct KilledBuffers {
+    /// Latest last
+    entries: Vec<KilledBuffer>,
+}
+
+impl KilledBuffers {
+    /// Remembers a killed buffer. Killing a file's buffer again replaces its
+    /// older entry, and the oldest entry is forgotten past `MAX_KILLED`.
+    pub fn push(&mut self, killed: KilledBuffer) {
+        self.entries
+            .retain(|entry| entry.file_path != killed.file_path);
+        if self.entries.len() >= MAX_KILLED {
+            self.entries.remove(0);
+        }
+        self.entries.push(killed);
+    }
+
+    /// Takes the latest killed buffer whose file isn't open again. The ones
+    /// opened since are dropped on the way.
+    pub fn pop(&mut self, is_open: impl Fn(&Path) -> bool) -> Option<KilledBuffer> {
+        while let Some(killed) = self.entries.pop() {
+            if !is_open(&killed.file_path) {
+                return Some(killed);
+            }
+        }
+        None
+    }
+}
+
+const MAX_KILLED: usize = 16;
@@ -0,0 +1,180 @@
+//! Emacs style lock files. While a buffer has unsaved changes, `.#name` next
+//! to the file says who is editing it, so that other editors, zee or Emacs,
+//! can warn before making conflicting changes.
+//!
+//! Like Emacs, the lock is a symbolic link whose target is `user@host.pid`,
+//! on platforms without symbolic links it's a file with the same contents.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::error::{Context, Result};
+
+/// The editor holding a lock
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            user: current_user(),
+            host: current_host(),
+            pid: process::id(),
+        }
+    }
+
+    /// Parses a lock's target, ignoring the boot time Emacs appends after a
+    /// colon
+    fn parse(target: &str) -> Option<Self> {
+        let target = target.split(':').next()?;
+        let (user, host_pid) = target.split_once('@')?;
+        let (host, pid) = host_pid.rsplit_once('.')?;
+        Some(Self {
+            user: user.to_owned(),
+            host: host.to_owned(),
+            pid: pid.parse().ok()?,
+        })
+    }
+
+    /// A lock is stale if its owner exited without removing it. We can only
+    /// tell for processes on this machine, and only where `/proc` lists them.
+    fn is_alive(&self) -> bool {
+        let proc = Path::new("/proc");
+        self.host != current_host() || !proc.is_dir() || proc.join(self.pid.to_string()).exists()
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}@{}.{}", self.user, self.host, self.pid)
+    }
+}
+
+/// Who else is editing a file, if anyone. Stale locks and our own don't
+/// count.
+pub fn other_owner(file_path: &Path) -> Option<LockOwner> {
+    let owner = read_lock(&lock_path(file_path)?)?;
+    (owner != LockOwner::current() && owner.is_alive()).then(|| owner)
+}
+
+/// A lock we hold on a file, removed when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    file_path: PathBuf,
+    lock_path: PathBuf,
+}
+
+impl FileLock {
+    /// Locks a file, replacing any lock held by someone else
+    pub fn acquire(file_path: &Path) -> Result<Self> {
+        let lock_path = lock_path(file_path).context("Cannot lock a file without a name")?;
+        let target = LockOwner::current().to_string();
+        let mut result = create_lock(&lock_path, &target);
+        if matches!(&result, Err(error) if error.kind() == io::ErrorKind::AlreadyExists) {
+            result = fs::remove_file(&lock_path).and_then(|()| create_lock(&lock_path, &target));
+        }
+        result.with_context(|| format!("Could not create lock file `{}`", lock_path.display()))?;
+        HELD_LOCKS.lock().push(lock_path.clone());
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            lock_path,
+        })
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        HELD_LOCKS.lock().retain(|path| *path != self.lock_path);
+        remove_if_ours(&self.lock_path);
+    }
+}
+
+/// Removes the locks still held when dropped, even while unwinding from a
+/// panic, when the buffers holding them may never be dropped
+pub struct ReleaseLocksOnExit;
+
+impl Drop for ReleaseLocksOnExit {
+    fn drop(&mut self) {
+        for lock_path in HELD_LOCKS.lock().drain(..) {
+            remove_if_ours(&lock_path);
+        }
+    }
+}
+
+fn lock_path(file_path: &Path) -> Option<PathBuf> {
+    let file_name = file_path.file_name()?.to_string_lossy();
+    Some(file_path.with_file_name(format!("{}{}", LOCK_PREFIX, file_name)))
+}
+
+fn read_lock(lock_path: &Path) -> Option<LockOwner> {
+    let target = match fs::read_link(lock_path) {
+        Ok(target) => target.to_string_lossy().into_owned(),
+        Err(_) => fs::read_to_string(lock_path).ok()?,
+    };
+    LockOwner::parse(target.trim())
+}
+
+/// Someone else may have stolen the lock in the meantime, theirs is left alone
+fn remove_if_ours(lock_path: &Path) {
+    if read_lock(lock_path) == Some(LockOwner::current()) {
+        if let Err(error) = fs::remove_file(lock_path) {
+            log::warn!(
+                "Could not remove lock file `{}` ({})",
+                lock_path.display(),
+                error
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_lock(lock_path: &Path, target: &str) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, lock_path)
+}
+
+#[cfg(not(unix))]
+fn create_lock(lock_path: &Path, target: &str) -> io::Result<()> {
+    use std::io::Write;
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path)?
+        .write_all(target.as_bytes())
+}
+
+fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+fn current_host() -> String {
+    static HOST: Lazy<String> = Lazy::new(|| {
+        env::var("HOSTNAME")
+            .or_else(|_| env::var("COMPUTERNAME"))
+            .ok()
+            .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+            .or_else(|| fs::read_to_string("/etc/hostname").ok())
+            .map(|host| host.trim().to_owned())
+            .filter(|host| !host.is_empty())
+            .unwrap_or_else(|| "localhost".into())
+    });
+    HOST.clone()
+}
+
+static HELD_LOCKS: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(Default::default);
+
+const LOCK_PREFIX: &str = ".#";
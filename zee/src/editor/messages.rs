@@ -0,0 +1,108 @@
+//! Every message shown in the prompt, kept with the time it was shown so it
+//! can be read again in the `*Messages*` buffer after the prompt moved on.
+
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+struct Entry {
+    /// When the message was last shown
+    time: SystemTime,
+    message: String,
+    /// How many times in a row it was shown
+    count: usize,
+}
+
+impl Entry {
+    /// The entry's line in the `*Messages*` buffer, e.g.
+    /// `14:02:31 UTC Could not save file (3 times)`. Messages spanning several
+    /// lines are joined into one.
+    fn line(&self) -> String {
+        let mut line = time_of_day(self.time);
+        line.push_str(" UTC");
+        for message_line in self.message.lines() {
+            line.push(' ');
+            line.push_str(message_line);
+        }
+        if self.count > 1 {
+            line.push_str(&format!(" ({} times)", self.count));
+        }
+        line.push('\n');
+        line
+    }
+}
+
+/// How the log changed after a message was recorded
+pub struct Recorded {
+    /// The line of the new or updated entry
+    pub line: String,
+    /// Whether the message repeated the latest one, updating its entry rather
+    /// than adding one
+    pub repeated: bool,
+    /// Whether the oldest entry was dropped to make room
+    pub dropped_oldest: bool,
+}
+
+#[derive(Default)]
+pub struct MessageLog {
+    entries: VecDeque<Entry>,
+}
+
+impl MessageLog {
+    /// Records a message. A message repeating the latest one is counted
+    /// rather than listed again, so a failure happening over and over, e.g.
+    /// when saving, doesn't push everything else out.
+    pub fn record(&mut self, message: &str) -> Recorded {
+        let time = SystemTime::now();
+        if let Some(latest) = self.entries.back_mut() {
+            if latest.message == message {
+                latest.time = time;
+                latest.count += 1;
+                return Recorded {
+                    line: latest.line(),
+                    repeated: true,
+                    dropped_oldest: false,
+                };
+            }
+        }
+        let dropped_oldest = self.entries.len() >= MAX_MESSAGES;
+        if dropped_oldest {
+            self.entries.pop_front();
+        }
+        let entry = Entry {
+            time,
+            message: message.to_owned(),
+            count: 1,
+        };
+        let line = entry.line();
+        self.entries.push_back(entry);
+        Recorded {
+            line,
+            repeated: false,
+            dropped_oldest,
+        }
+    }
+
+    /// The contents of the `*Messages*` buffer, one line per entry, oldest
+    /// first
+    pub fn text(&self) -> String {
+        self.entries.iter().map(Entry::line).collect()
+    }
+}
+
+/// The time of day in UTC, e.g. `14:02:31`
+fn time_of_day(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+        % 86400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+const MAX_MESSAGES: usize = 1000;
@@ -1,46 +1,129 @@
+mod align;
+pub mod annotations;
+mod backup;
 mod bindings;
 pub mod buffer;
+mod compare;
+pub mod completion;
+mod compose;
+mod diff;
+mod export;
+mod file_at_point;
+mod fill;
+pub mod folds;
+mod help;
+pub mod highlights;
+mod hunks;
+mod killed;
+pub mod lock;
+mod messages;
+pub mod narrow;
+mod occur;
+mod plugins;
+pub mod recovery;
+mod remote;
+pub mod search;
+mod sequence;
+mod stats;
 mod windows;
 
-pub use self::buffer::{BufferId, ModifiedStatus};
+pub use self::{
+    buffer::{BufferId, ModifiedStatus},
+    help::RecordedBindings,
+    windows::WindowLayout,
+};
 
 use git2::Repository;
 use ropey::Rope;
+use serde_json::{json, Value};
 use std::{
     borrow::Cow,
+    collections::HashSet,
     fmt::Display,
-    fs::File,
-    io::{self, BufReader},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read},
+    iter,
+    ops::Range,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
+    ptr,
+    rc::Rc,
     sync::Arc,
 };
 use zi::{
     Bindings, Callback, Component, ComponentExt, ComponentLink, FlexBasis, FlexDirection, Item,
-    Key, Layout, NamedBindingQuery, Rect, ShouldRender,
+    Key, Layout, NamedBindingQuery, Rect, ShouldRender, Size,
 };
 
+use zee_edit::{batch::TextEdit, CharIndex, Direction, LineIndex};
 use zee_grammar::Mode;
 
 use crate::{
+    calc,
+    characters::{characters, CharacterEntry, CharacterSet},
     clipboard::Clipboard,
     components::{
         buffer::{Buffer as BufferView, Properties as BufferViewProperties},
+        dashboard::{Dashboard, Properties as DashboardProperties},
         prompt::{
-            buffers::BufferEntry, picker::FileSource, Action as PromptAction, Prompt,
-            Properties as PromptProperties, PROMPT_INACTIVE_HEIGHT,
+            buffers::BufferEntry,
+            choice::Choice,
+            items::PickerDefinition,
+            modes::{mode_picker, ModeEntry},
+            picker::FileSource,
+            symbols::{symbol_picker, SymbolEntry},
+            validate::{Validation, Validator},
+            Action as PromptAction, Prompt, Properties as PromptProperties, PROMPT_INACTIVE_HEIGHT,
         },
-        splash::{Properties as SplashProperties, Splash},
+        splash::{self, Properties as SplashProperties, Splash},
         theme::{Theme, THEMES},
     },
-    config::{EditorConfig, PLAIN_TEXT_MODE},
-    error::Result,
-    task::TaskPool,
+    config::{EditorConfig, Startup, PLAIN_TEXT_MODE},
+    error::{Context as _, Result},
+    history::{HistoryKind, PromptHistory},
+    local_config::{self, LocalConfig, TrustedConfigs},
+    project::{self, Project},
+    recent::RecentFiles,
+    scripting::{ScriptOutput, ScriptState, Scripts},
+    syntax::{
+        detect,
+        highlight::{query_spans, scopes_at},
+        index::SymbolIndex,
+        links::{find_links, LinkTarget},
+        locals::local_occurrences,
+        symbols::{extract_symbols, identifier_at},
+    },
+    task::{TaskId, TaskPool},
+    terminal,
+    utils::ProgressReader,
 };
 
 use self::{
+    annotations::Annotation,
     bindings::KeySequenceSlice,
-    buffer::{BufferCursor, Buffers, BuffersMessage, CursorId, RepositoryRc},
-    windows::{CycleFocus, Window, WindowTree},
+    buffer::{
+        parse_indentation, Buffer, BufferCursor, BufferMessage, Buffers, BuffersMessage, CursorId,
+        RepositoryRc,
+    },
+    compare::Comparison,
+    completion::{
+        is_file_name_char, is_word_char, path_before, path_candidates, word_candidates, word_start,
+        Completion, CompletionItem,
+    },
+    highlights::{Highlights, HIGHLIGHT_COLOURS},
+    hunks::HeadDiff,
+    killed::{KilledBuffer, KilledBuffers},
+    messages::MessageLog,
+    occur::Occur,
+    plugins::{
+        parse_params as parse_plugin_params, AnnotationParams, ClearAnnotationsParams,
+        GetTextParams, InsertTextParams, OpenPickerParams, PluginHost, PluginId, PluginRequest,
+        SetAnnotationsParams, SetStatusParams,
+    },
+    remote::RemotePath,
+    sequence::NumberSequence,
+    stats::TextStats,
+    windows::{CycleFocus, FocusDirection, Window, WindowTree},
 };
 
 #[derive(Debug)]
@@ -49,20 +132,151 @@ pub enum Message {
     DeleteWindow,
     FocusNextWindow,
     FocusPreviousWindow,
+    /// Focuses the window with the one-based number shown in its status bar
+    FocusWindow(usize),
+    FocusWindowInDirection(FocusDirection),
     SplitWindow(FlexDirection),
+    ArrangeWindows(WindowLayout),
     FullscreenWindow,
 
     // Prompt
     SelectBufferPicker,
-    SelectBuffer(BufferId),
+    /// Shows a buffer in the focused window, or one of its indirect views by
+    /// number
+    SelectBuffer(BufferId, Option<usize>),
     KillBufferPicker,
     KillBuffer(BufferId),
+    /// Opens the file of the latest killed buffer again, where it was left
+    ReopenKilledBuffer,
+    KillIndirectView(BufferId, usize),
+    CloneIndirectView,
     OpenFilePicker(FileSource),
+    /// Makes a project active and opens the repository file picker in its
+    /// directory
+    OpenProject(PathBuf),
+    ProjectPicker,
+    ProjectSelected(PathBuf),
+    FindFileInProject,
+    AskTrustLocalConfig(PathBuf),
+    TrustLocalConfig(PathBuf, bool),
     OpenFile(PathBuf),
+    FileLoading(BufferId, u64),
+    FileLoaded(BufferId, io::Result<Rope>),
+    OpenRemoteFile(RemotePath),
+    /// Reads standard input into a read-only buffer, from `zee -`
+    OpenStdin,
+    RemoteFileDownloaded(RemotePath, io::Result<Rope>),
+    CreateParentDirectories(PathBuf, bool),
+    GotoSymbolPicker,
+    GotoDefinition,
+    JumpBack,
+    CompleteWord,
+    CompletePath,
+    SelectCompletion(isize),
+    AcceptCompletion,
+    RenameSymbolPrompt,
+    RenameSymbol(BufferId, CursorId, String),
+    GotoProjectSymbolPicker,
+    GotoSymbol(SymbolEntry),
+    SymbolIndexDone(TaskId, SymbolIndex),
     ChangePromptHeight(usize),
+    PushPromptHistory(HistoryKind, String),
     Buffer(BuffersMessage),
     Log(Option<String>),
-    PostInteractionQuit(bool),
+    PendingKeys(Option<String>),
+    QuitChoice(Option<char>),
+    QuitSaveBuffer(BufferId, bool),
+    RevertBuffer,
+    SetModePicker,
+    RunScriptCommandPrompt,
+    RunScriptCommand(String),
+    SetMode(ModeEntry),
+    SetIndentationPrompt,
+    SetIndentation(String),
+    JumpToRevisionPrompt,
+    JumpToRevision(String),
+    LabelRevisionPrompt,
+    LabelRevision(String),
+    OpenLinkAtPoint,
+    LinksPicker,
+    FindFileAtPoint,
+    OpenLink(LinkTarget),
+    PluginRequest(PluginId, PluginRequest),
+    PluginExited(PluginId),
+    PluginItemSelected(usize),
+    SaveConflict(BufferId),
+    SaveConflictChoice(BufferId, Option<char>),
+    FileLocked(BufferId),
+    FileLockedChoice(BufferId, Option<char>),
+    ToggleReadOnly,
+    SaveBufferAs(BufferId, String),
+    SavePermissionDenied(BufferId),
+    PostInteractionSaveElevated(BufferId, bool),
+    PostInteractionRevertBuffer(BufferId, bool),
+    InsertFilePicker,
+    InsertFile(PathBuf),
+    FileReadForInsert(BufferId, CursorId, PathBuf, io::Result<Rope>),
+    WriteRegionPrompt,
+    WriteRegion(Rope, String),
+    ExportPrompt,
+    Export(BufferId, CursorId, String),
+    PostInteractionWriteRegion(PathBuf, Rope, bool),
+    RegionWritten(PathBuf, io::Result<()>),
+
+    // Diffs
+    DiffBufferWithFile,
+    CompareBuffersPicker,
+    CompareBuffers(BufferId),
+    CompareTwoBuffersPicker,
+    CompareLeftBufferSelected(BufferId),
+    CompareTwoBuffers(BufferId, BufferId),
+    /// Opens two files and compares them, e.g. from `zee --diff old new`
+    CompareFiles(PathBuf, PathBuf),
+    CompareMoveToHunk(Direction),
+    CompareCopyHunk(Direction),
+    CompareQuit,
+    GitMoveToHunk(Direction),
+    GitHunksPicker,
+    GitGotoHunk(LineIndex),
+    GitRevertHunk,
+
+    // Help
+    DescribeBindings,
+    DescribeKeyPrompt,
+    DescribeKey(Key),
+    ViewMessages,
+    DescribeChar,
+    InsertCharPrompt,
+    InsertChar(String),
+    InsertCharacterPicker(CharacterSet),
+    InsertCharacter(char),
+    CalcPrompt,
+    Calc(String),
+    CalcInsertPrompt,
+    CalcInsert(String),
+    NumberLinesPrompt,
+    NumberLines(String),
+    AlignPrompt,
+    Align(String),
+    FillParagraph,
+    ToggleAutoFill,
+    ReplacePrompt,
+    ReplaceWithPrompt(String),
+    Replace(String, String),
+    Stats,
+    OccurPrompt,
+    Occur(String),
+    /// Enter in a read-only buffer, which visits the match under the cursor
+    /// in `*Occur*`
+    OccurGoto,
+    HighlightPrompt,
+    /// The pattern typed so far in the highlight prompt
+    HighlightPreview(String),
+    HighlightColourPicker(String),
+    /// Highlights a pattern in the colour at an index of `HIGHLIGHT_COLOURS`
+    Highlight(String, usize),
+    UnhighlightPicker,
+    Unhighlight(usize),
 
     // Global
     ChangeTheme,
@@ -78,15 +292,25 @@ impl From<BuffersMessage> for Message {
 
 pub struct Properties {
     pub args_files: Vec<PathBuf>,
+    /// How the files opened at startup are arranged in windows
+    pub window_layout: WindowLayout,
+    /// Files to compare side by side at startup
+    pub diff_files: Option<(PathBuf, PathBuf)>,
     pub current_working_dir: PathBuf,
+    pub config_dir: Option<PathBuf>,
     pub config: EditorConfig,
     pub task_pool: TaskPool,
     pub clipboard: Arc<dyn Clipboard>,
+    pub recent_files: RecentFiles,
+    pub prompt_history: PromptHistory,
+    pub trusted_configs: TrustedConfigs,
+    pub scripts: Rc<dyn Scripts>,
 }
 
 pub struct Context {
     pub args_files: Vec<PathBuf>,
     pub current_working_dir: PathBuf,
+    pub config_dir: Option<PathBuf>,
     pub config: EditorConfig,
     pub modes: Vec<Mode>,
     pub task_pool: TaskPool,
@@ -95,11 +319,43 @@ pub struct Context {
 }
 
 impl Context {
-    pub fn mode_by_filename(&self, filename: impl AsRef<Path>) -> &Mode {
+    /// Picks the mode for a text. A modeline takes precedence over the file
+    /// name, which takes precedence over a shebang or the start of the text.
+    pub fn detect_mode(&self, filename: Option<&Path>, text: &Rope) -> &Mode {
+        detect::modeline(text)
+            .and_then(|name| self.mode_by_name(&name))
+            .or_else(|| {
+                let filename = filename?;
+                self.modes
+                    .iter()
+                    .find(|&mode| mode.matches_by_filename(filename))
+            })
+            .or_else(|| detect::shebang(text).and_then(|name| self.mode_by_name(&name)))
+            .or_else(|| detect::sniff(text).and_then(|name| self.mode_by_name(name)))
+            .unwrap_or(&PLAIN_TEXT_MODE)
+    }
+
+    /// Finds a mode by a name like those used in modelines and shebangs,
+    /// ignoring case: the mode's name, the last part of its scope, e.g. `bash`
+    /// for `source.bash`, or a file extension, e.g. `rs`
+    pub fn mode_by_name(&self, name: &str) -> Option<&Mode> {
+        let name = name.trim().to_lowercase().replace(['-', '_'], " ");
+        let name = match name.as_str() {
+            "node" | "nodejs" => "js",
+            "plain" | "text" | "fundamental" => return Some(&PLAIN_TEXT_MODE),
+            name => name,
+        };
         self.modes
             .iter()
-            .find(|&mode| mode.matches_by_filename(filename.as_ref()))
-            .unwrap_or(&PLAIN_TEXT_MODE)
+            .find(|mode| {
+                mode.name.to_lowercase() == name || mode.scope.rsplit('.').next() == Some(name)
+            })
+            .or_else(|| {
+                let filename = format!("file.{}", name);
+                self.modes
+                    .iter()
+                    .find(|mode| mode.matches_by_filename(&filename))
+            })
     }
 }
 
@@ -122,6 +378,8 @@ impl Context {
 
 pub struct Editor {
     context: ContextHandle,
+    /// Where the editor is drawn, the whole terminal
+    frame: Rect,
     themes: &'static [(Theme, &'static str)],
     theme_index: usize,
 
@@ -130,6 +388,74 @@ pub struct Editor {
 
     buffers: Buffers,
     windows: WindowTree<BufferViewId>,
+    recent_files: Arc<RecentFiles>,
+    prompt_history: Arc<PromptHistory>,
+    /// The project of the file opened last, or of the working directory
+    project: Option<Project>,
+    trusted_configs: TrustedConfigs,
+    /// The untrusted local configuration files we asked about this session
+    asked_local_configs: HashSet<PathBuf>,
+    scripts: Rc<dyn Scripts>,
+    script_bindings: Vec<(Vec<Key>, String)>,
+    plugins: PluginHost,
+    /// The `openPicker` request waiting for the user to pick an item
+    pending_plugin_picker: Option<PendingPluginPicker>,
+
+    symbol_index: Option<SymbolIndex>,
+    symbol_index_task: Option<TaskId>,
+    pick_symbol_when_indexed: bool,
+    /// The identifier to find the definition of once the project is indexed
+    definition_when_indexed: Option<String>,
+    /// Where the cursor was before each jump to a symbol, latest last
+    jump_back: Vec<JumpLocation>,
+
+    comparison: Option<Comparison>,
+    /// The files of the buffers killed lately, to reopen them
+    killed_buffers: KilledBuffers,
+
+    /// The pattern last searched for, counted by `stats`
+    last_search: Option<String>,
+    /// The last search parsed, its matches are marked on the scrollbars
+    last_search_pattern: Option<Rc<search::Pattern>>,
+    occur: Option<Occur>,
+    /// The patterns highlighted in every buffer
+    highlights: Highlights,
+    /// Every message shown in the prompt, listed in the `*Messages*` buffer
+    message_log: MessageLog,
+    /// The completion popup open in the focused window, if any
+    completion: Option<Completion>,
+    /// The unfinished key sequence typed so far, when shown in the status bar
+    pending_keys: Option<String>,
+
+    pending_quit: Option<PendingQuit>,
+
+    /// The title last set on the terminal
+    terminal_title: String,
+}
+
+/// Buffers with unsaved changes while exiting, see `Message::Quit`
+#[derive(Default)]
+struct PendingQuit {
+    /// Buffers still to ask about in reverse order, including the one we're
+    /// currently asking about
+    unasked: Vec<BufferId>,
+    /// Buffers being saved, we exit once all of them are written
+    saving: Vec<BufferId>,
+}
+
+/// Where the cursor was before jumping to a symbol, see `Message::JumpBack`.
+/// The file is opened again if the buffer was killed in the meantime.
+struct JumpLocation {
+    buffer_id: BufferId,
+    file_path: Option<PathBuf>,
+    char_index: CharIndex,
+}
+
+/// A plugin's `openPicker` request, answered once an item is picked
+struct PendingPluginPicker {
+    plugin_id: PluginId,
+    request_id: Value,
+    items: Rc<[String]>,
 }
 
 impl Editor {
@@ -139,9 +465,188 @@ impl Editor {
             self.windows
                 .add(BufferViewId::new(buffer_id, CursorId::default()));
         } else {
+            let replaced = self.windows.get_focused();
             self.windows
                 .set_focused(BufferViewId::new(buffer_id, CursorId::default()));
+            self.release_unused_cursors(replaced);
+        }
+    }
+
+    /// Lists the lines of a buffer matching a pattern in the `*Occur*` buffer,
+    /// shown in a window below unless it's already visible
+    fn occur(&mut self, source: BufferViewId, pattern: search::Pattern, input: &str) {
+        // Searching the results again searches the buffer they came from
+        let source = match self.occur.as_ref() {
+            Some(occur) if occur.results == source.buffer_id => occur.source,
+            _ => source,
+        };
+        let description = format!(
+            "`{}` in {}",
+            input.trim(),
+            self.buffer_display_name(source.buffer_id)
+        );
+        let results = match self.buffers.find_by_name(OCCUR_BUFFER_NAME) {
+            Some(results) => results,
+            None => self.buffers.add_read_only(OCCUR_BUFFER_NAME, Rope::new()),
+        };
+        let occur = Occur::new(source, results, pattern, description, &mut self.buffers);
+        if occur.num_lines() == 0 {
+            self.context.log("No matches");
+        }
+
+        let results_view = BufferViewId::new(results, CursorId::default());
+        if !self.windows.focus(results_view) {
+            self.windows
+                .insert_at_focused(results_view, FlexDirection::Column);
+            self.windows.cycle_focus(CycleFocus::Next);
+        }
+        let results = self.buffers.get_mut(results).unwrap();
+        let first_match = results.edit_tree().line_to_char(1.min(occur.num_lines()));
+        results.move_cursor_to(CursorId::default(), first_match);
+        self.occur = Some(occur);
+    }
+
+    /// Opens the completion popup for the text between `start` and the cursor,
+    /// with candidates from any source. The popup stays open while the chars
+    /// typed are `is_part` of the text. A single match is inserted right away.
+    fn open_completion(
+        &mut self,
+        view_id: BufferViewId,
+        start: CharIndex,
+        is_part: fn(char) -> bool,
+        candidates: Vec<CompletionItem>,
+    ) {
+        self.completion = Some(Completion::new(view_id, start, is_part, candidates));
+        self.refresh_completion();
+        match self.completion.as_ref().map(Completion::num_matches) {
+            None => self.context.log("No completions"),
+            Some(1) => self.accept_completion(),
+            Some(_) => {}
+        }
+    }
+
+    /// Narrows the candidates down as the text is typed. The popup is closed
+    /// when the cursor leaves the text being completed or nothing matches.
+    fn refresh_completion(&mut self) {
+        let completion = match self.completion.as_mut() {
+            Some(completion) => completion,
+            None => return,
+        };
+        let view_id = completion.view_id;
+        let is_open = self.windows.get_focused() == Some(view_id)
+            && self.buffers.get(view_id.buffer_id).map_or(false, |buffer| {
+                let text = buffer.edit_tree();
+                let cursor = buffer.cursor(view_id.cursor_id).range().start;
+                let start = completion.start();
+                if cursor < start || cursor > text.len_chars() {
+                    return false;
+                }
+                let typed = text.slice(start..cursor).to_string();
+                completion.continues(&typed) && completion.set_filter(&typed)
+            });
+        if !is_open {
+            self.completion = None;
+        }
+    }
+
+    /// Replaces the text being completed with the selected candidate
+    fn accept_completion(&mut self) {
+        let completion = match self.completion.take() {
+            Some(completion) => completion,
+            None => return,
+        };
+        let (view_id, item) = match completion.selected() {
+            Some(item) => (completion.view_id, item),
+            None => return,
+        };
+        let buffer = match self.buffers.get_mut(view_id.buffer_id) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if buffer.is_read_only() {
+            self.context.log("Buffer is read-only");
+            return;
         }
+        let cursor = buffer.cursor(view_id.cursor_id).range().start;
+        buffer.replace_range(completion.start()..cursor, item.text().into());
+    }
+
+    /// Keeps a message shown in the prompt in the message log, and in the
+    /// `*Messages*` buffer if it's open
+    fn record_message(&mut self, message: &str) {
+        let recorded = self.message_log.record(message);
+        let buffer = match self
+            .buffers
+            .find_by_name(MESSAGES_BUFFER_NAME)
+            .and_then(|buffer_id| self.buffers.get_mut(buffer_id))
+        {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let text = buffer.edit_tree();
+        let end = text.len_chars();
+        let mut edits = Vec::new();
+        if recorded.dropped_oldest {
+            edits.push(TextEdit {
+                range: 0..text.line_to_char(1),
+                text: Rope::new(),
+            });
+        }
+        let start = if recorded.repeated {
+            text.line_to_char(text.len_lines().saturating_sub(2))
+        } else {
+            end
+        };
+        edits.push(TextEdit {
+            range: start..end,
+            text: recorded.line.as_str().into(),
+        });
+        // The edits don't overlap and are inside the text
+        let _ = buffer.apply_generated_edits(edits);
+    }
+
+    /// Shows the unsaved changes of a buffer in the `*Diff*` buffer
+    fn diff_buffer_with_file(&mut self, buffer_id: BufferId) -> Result<()> {
+        let buffer = self.buffers.get(buffer_id).unwrap();
+        let file_path = match buffer.file_path() {
+            Some(file_path) => file_path,
+            None => {
+                self.context.log("Buffer is not visiting a file");
+                return Ok(());
+            }
+        };
+        let diff = diff::diff_with_file(
+            file_path,
+            file_path
+                .strip_prefix(&self.context.current_working_dir)
+                .unwrap_or(file_path),
+            buffer.edit_tree(),
+        )?;
+        let diff = match diff {
+            Some(diff) => diff,
+            None => {
+                self.context.log("No unsaved changes");
+                return Ok(());
+            }
+        };
+
+        let line_scopes = diff::unified_diff_scopes(&diff);
+        let diff_buffer_id = match self.buffers.find_by_name(DIFF_BUFFER_NAME) {
+            Some(diff_buffer_id) => {
+                self.buffers
+                    .get_mut(diff_buffer_id)
+                    .unwrap()
+                    .replace_text(diff);
+                diff_buffer_id
+            }
+            None => self.buffers.add_read_only(DIFF_BUFFER_NAME, diff),
+        };
+        self.buffers
+            .get_mut(diff_buffer_id)
+            .unwrap()
+            .set_line_scopes(line_scopes);
+        self.focus_on_buffer(diff_buffer_id);
+        Ok(())
     }
 
     fn open_file(&mut self, file_path: PathBuf) -> Result<bool> {
@@ -151,14 +656,23 @@ impl Editor {
             return Ok(false);
         }
 
-        let (is_new_file, text) = if file_path.exists() {
-            (
-                false,
-                Rope::from_reader(BufReader::new(File::open(&file_path)?))?,
-            )
-        } else {
-            // Optimistically check if we can create it
-            let is_new_file = File::open(&file_path)
+        if file_path.exists() {
+            // Fail early if the file can't be read, otherwise read it in the
+            // background, big files or slow disks would block the UI
+            let file = File::open(&file_path)?;
+            let total_bytes = file.metadata()?.len();
+            let repo = Repository::discover(&file_path).ok().map(RepositoryRc::new);
+            self.remember_recent_file(&file_path);
+            self.activate_project_of(&file_path);
+            let buffer_id = self.buffers.add_loading(file_path, repo, total_bytes);
+            self.focus_on_buffer(buffer_id);
+            self.spawn_load_file(buffer_id, file);
+            return Ok(false);
+        }
+
+        // Optimistically check if we can create it
+        let is_new_file =
+            File::open(&file_path)
                 .map(|_| false)
                 .or_else(|error| match error.kind() {
                     io::ErrorKind::NotFound => {
@@ -181,13 +695,15 @@ impl Editor {
                         Err(error)
                     }
                 })?;
-            (is_new_file, Rope::new())
-        };
 
         let repo = Repository::discover(&file_path).ok().map(RepositoryRc::new);
 
+        self.remember_recent_file(&file_path);
+        self.activate_project_of(&file_path);
+
         // Store the new buffer
-        let buffer_id = self.buffers.add(text, Some(file_path), repo);
+        let buffer_id = self.buffers.add(Rope::new(), Some(file_path), repo);
+        self.apply_buffer_settings(buffer_id);
 
         // Focus on the new buffer
         self.focus_on_buffer(buffer_id);
@@ -195,161 +711,2890 @@ impl Editor {
         Ok(is_new_file)
     }
 
-    fn open_buffer_picker(&mut self, message: Cow<'static, str>, on_select: Callback<BufferId>) {
-        self.prompt_action = PromptAction::PickBuffer {
-            message,
-            entries: self
-                .buffers
-                .iter()
-                .map(|buffer| {
-                    BufferEntry::new(
-                        buffer.id(),
-                        buffer.file_path().cloned(),
-                        false,
-                        buffer.edit_tree().len_bytes(),
-                        buffer.mode(),
-                    )
-                })
-                .collect(),
-            on_select,
-            on_change_height: self.context.link.callback(Message::ChangePromptHeight),
-        };
-        self.prompt_height = self.prompt_action.initial_height();
+    /// Reads a file, or standard input, into a buffer added with
+    /// `Buffers::add_loading`, reporting progress along the way
+    fn spawn_load_file(&mut self, buffer_id: BufferId, file: impl Read + Send + 'static) {
+        let link = self.context.link.clone();
+        self.context.task_pool.spawn(move |_| {
+            let progress_link = link.clone();
+            let reader = ProgressReader::new(file, move |bytes_read| {
+                progress_link.send(Message::FileLoading(buffer_id, bytes_read))
+            });
+            let text = Rope::from_reader(BufReader::new(reader));
+            link.send(Message::FileLoaded(buffer_id, text))
+        });
     }
-}
 
-impl Component for Editor {
-    type Message = Message;
-    type Properties = Properties;
+    /// Whether the terminal is too small to lay out the windows in
+    fn is_too_small(&self) -> bool {
+        self.frame.size.width < MIN_WIDTH || self.frame.size.height < MIN_HEIGHT
+    }
 
-    fn create(properties: Properties, _frame: Rect, link: ComponentLink<Self>) -> Self {
-        for (index, file_path) in properties.args_files.iter().cloned().enumerate() {
-            link.send(Message::OpenFile(file_path));
-            if index < properties.args_files.len().saturating_sub(1) {
-                link.send(Message::SplitWindow(FlexDirection::Row));
+    /// Makes the project containing a file active, unless it's in the active
+    /// project already. Files outside of any project leave it as it is.
+    fn activate_project_of(&mut self, file_path: &Path) {
+        let file_path = self.context.current_working_dir.join(file_path);
+        if self
+            .project
+            .as_ref()
+            .map_or(false, |project| project.contains(&file_path))
+        {
+            return;
+        }
+        if let Some(project) = Project::find(&file_path, &self.trusted_configs) {
+            self.set_project(project);
+        }
+    }
+
+    /// Switches to another project, applying its theme, or going back to the
+    /// configured one if only the previous project had a theme. The symbol
+    /// index is rebuilt for the new root when it's next needed.
+    fn set_project(&mut self, project: Project) {
+        if self.project.as_ref() == Some(&project) {
+            return;
+        }
+        let previous_theme = self
+            .project
+            .as_ref()
+            .and_then(|previous| previous.config.theme.as_deref());
+        let theme_name = match (project.config.theme.as_deref(), previous_theme) {
+            (Some(theme_name), _) => Some(theme_name),
+            (None, Some(_)) => Some(self.context.0.config.theme.as_str()),
+            (None, None) => None,
+        };
+        if let Some(theme_name) = theme_name {
+            match self.themes.iter().position(|(_, name)| *name == theme_name) {
+                Some(theme_index) => self.theme_index = theme_index,
+                None => self.context.log(format!("Unknown theme `{}`", theme_name)),
             }
         }
+        if self.project.as_ref().map(|active| &active.root) != Some(&project.root) {
+            self.symbol_index = None;
+            self.symbol_index_task = None;
+            self.pick_symbol_when_indexed = false;
+            self.definition_when_indexed = None;
+        }
+        self.project = Some(project);
+    }
 
-        let theme_name = properties.config.theme.clone();
-        let context = ContextHandle(Box::leak(
-            Context {
-                args_files: properties.args_files,
-                current_working_dir: properties.current_working_dir,
-                modes: properties
-                    .config
-                    .modes
-                    .iter()
-                    .cloned()
-                    .map(Mode::new)
-                    .collect(),
-                config: properties.config,
-                task_pool: properties.task_pool,
-                clipboard: properties.clipboard,
-                link,
+    /// Applies the settings of the active project and of the trusted local
+    /// configuration files to a buffer visiting a file. We ask whether to
+    /// trust the other local configuration files, once per session.
+    fn apply_buffer_settings(&mut self, buffer_id: BufferId) {
+        let file_path = match self
+            .buffers
+            .get(buffer_id)
+            .and_then(|buffer| buffer.file_path())
+        {
+            Some(file_path) => self.context.current_working_dir.join(file_path),
+            None => return,
+        };
+
+        let project = self
+            .project
+            .as_ref()
+            .filter(|project| project.contains(&file_path));
+        if let Some(config_path) = project
+            .and_then(|project| project.untrusted_config.clone())
+            .filter(|config_path| !self.asked_local_configs.contains(config_path))
+        {
+            self.context
+                .link
+                .send(Message::AskTrustLocalConfig(config_path));
+        }
+        let mut indentation = project.and_then(|project| project.config.indentation);
+        let mut local_config = LocalConfig::default();
+        for config_path in local_config::find(&file_path, self.context.config_dir.as_deref()) {
+            let contents = match fs::read_to_string(&config_path) {
+                Ok(contents) => contents,
+                Err(error) => {
+                    log::warn!("Could not read `{}` ({})", config_path.display(), error);
+                    continue;
+                }
+            };
+            if !self.trusted_configs.is_trusted(&config_path, &contents) {
+                if !self.asked_local_configs.contains(&config_path) {
+                    self.context
+                        .link
+                        .send(Message::AskTrustLocalConfig(config_path));
+                }
+                continue;
             }
-            .into(),
-        ));
+            match LocalConfig::read(&config_path, &contents) {
+                Ok(nearer) => local_config.merge(nearer),
+                Err(error) => self.context.log(format!("{:#}", error)),
+            }
+        }
+        indentation = local_config.indentation.or(indentation);
 
-        let theme_index = {
-            let theme = THEMES.iter().position(|(_, name)| *name == theme_name);
-            if theme.is_none() {
-                context.log(format!("Unknown theme `{}`", theme_name));
+        let mode = local_config
+            .mode_name_for(&file_path)
+            .and_then(|mode_name| self.context.0.mode_by_name(mode_name));
+        let buffer = self.buffers.get_mut(buffer_id).unwrap();
+        if let Some(mode) = mode.filter(|mode| !ptr::eq(*mode, buffer.mode())) {
+            buffer.set_mode(mode);
+        }
+        if let Some(indentation) = indentation {
+            buffer.set_indentation(indentation);
+        }
+    }
+
+    fn push_prompt_history(&mut self, kind: HistoryKind, input: String) {
+        let prompt_history = Arc::make_mut(&mut self.prompt_history);
+        prompt_history.push(kind, input);
+        if let Err(error) = prompt_history.save() {
+            log::warn!("{}", error);
+        }
+    }
+
+    fn remember_recent_file(&mut self, file_path: &Path) {
+        let file_path = file_path
+            .canonicalize()
+            .unwrap_or_else(|_| self.context.current_working_dir.join(file_path));
+        let recent_files = Arc::make_mut(&mut self.recent_files);
+        recent_files.push(file_path);
+        if let Err(error) = recent_files.save() {
+            log::warn!("{}", error);
+        }
+    }
+
+    /// The changes of the focused buffer since `HEAD`, and the window it's
+    /// in
+    fn focused_head_diff(&self) -> Result<(BufferViewId, HeadDiff)> {
+        let view_id = self.windows.get_focused().context("No buffer to diff")?;
+        let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+        let repo = buffer
+            .repository()
+            .context("Buffer is not in a git repository")?;
+        let file_path = buffer
+            .file_path()
+            .context("Buffer is not visiting a file")?;
+        let head_diff = HeadDiff::new(repo, file_path, buffer.edit_tree())?;
+        Ok((view_id, head_diff))
+    }
+
+    fn open_symbol_picker(&mut self, entries: Rc<[SymbolEntry]>) {
+        self.prompt_action = PromptAction::pick(
+            symbol_picker(
+                entries,
+                self.context.current_working_dir.clone(),
+                self.context.link.callback(Message::GotoSymbol),
+            ),
+            self.context.link.callback(Message::ChangePromptHeight),
+        );
+        self.prompt_height = self.prompt_action.initial_height();
+    }
+
+    /// Jumps to where an identifier is defined, looking in the current buffer
+    /// and the project's symbol index, or lets the user pick if it's defined
+    /// in more than one place
+    fn goto_definition(&mut self, name: String) {
+        let index = match self.symbol_index.as_ref() {
+            Some(index) => index,
+            None => {
+                self.definition_when_indexed = Some(name);
+                self.context.log("Indexing project symbols...");
+                self.refresh_symbol_index();
+                return;
             }
-            theme
+        };
+
+        // The current buffer's symbols are up to date with unsaved changes,
+        // the index's are as of the file on disk
+        let current = self.windows.get_focused().map(|view_id| {
+            let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+            let tags = buffer
+                .mode()
+                .grammar()
+                .and_then(|grammar| grammar.ok())
+                .and_then(|grammar| grammar.tags.as_ref());
+            let symbols = match (tags, buffer.parse_tree()) {
+                (Some(tags), Some(parse_tree)) => {
+                    extract_symbols(tags, parse_tree, buffer.edit_tree())
+                }
+                _ => Vec::new(),
+            };
+            (buffer.file_path().cloned(), symbols)
+        });
+        let current_path = current.as_ref().and_then(|(path, _)| path.clone());
+        let mut entries: Vec<SymbolEntry> = current
+            .into_iter()
+            .flat_map(|(path, symbols)| {
+                symbols.into_iter().map(move |symbol| SymbolEntry {
+                    symbol,
+                    path: path.clone(),
+                })
+            })
+            .chain(
+                index
+                    .symbols()
+                    .filter(|(path, _)| Some(*path) != current_path.as_deref())
+                    .map(|(path, symbol)| SymbolEntry {
+                        symbol: symbol.clone(),
+                        path: Some(path.to_path_buf()),
+                    }),
+            )
+            .filter(|entry| entry.symbol.name == name)
+            .collect();
+        entries.sort_by(|lhs, rhs| {
+            (&lhs.path, lhs.symbol.char_index).cmp(&(&rhs.path, rhs.symbol.char_index))
+        });
+
+        match entries.len() {
+            0 => self
+                .context
+                .log(format!("No definition found for `{}`", name)),
+            1 => self
+                .context
+                .link
+                .send(Message::GotoSymbol(entries.pop().unwrap())),
+            _ => self.open_symbol_picker(entries.into()),
         }
-        .unwrap_or(0);
+        // Pick up any changes made since the index was last refreshed
+        self.refresh_symbol_index();
+    }
 
-        Self {
-            themes: &THEMES,
-            theme_index,
-            prompt_action: PromptAction::None,
-            prompt_height: PROMPT_INACTIVE_HEIGHT,
-            buffers: Buffers::new(context.clone()),
-            context,
-            windows: WindowTree::new(),
+    /// Starts indexing the symbols of the project in the task pool, unless
+    /// we're already indexing. Files that haven't changed since they were
+    /// last indexed are not parsed again.
+    fn refresh_symbol_index(&mut self) {
+        if self.symbol_index_task.is_some() {
+            return;
         }
+        let index = self.symbol_index.clone().unwrap_or_else(|| {
+            let current_working_dir = &self.context.current_working_dir;
+            SymbolIndex::new(match self.project.as_ref() {
+                Some(project) => project.root.clone(),
+                None => Repository::discover(current_working_dir)
+                    .ok()
+                    .and_then(|repo| repo.workdir().map(Path::to_path_buf))
+                    .unwrap_or_else(|| current_working_dir.clone()),
+            })
+        });
+        let modes: &'static [Mode] = &self.context.0.modes;
+        let link = self.context.link.clone();
+        self.symbol_index_task = Some(self.context.task_pool.spawn(move |task_id| {
+            link.send(Message::SymbolIndexDone(task_id, index.refresh(modes)))
+        }));
     }
 
-    fn update(&mut self, message: Self::Message) -> ShouldRender {
-        match message {
-            Message::Cancel => {
-                self.prompt_action = PromptAction::None;
-                self.prompt_height = self.prompt_action.initial_height();
-                self.context.log("Cancel");
+    /// Runs a command defined by the init script on the focused buffer,
+    /// replacing the selection, or the whole buffer, with the text it returns
+    fn run_script_command(&mut self, command: &str) -> Result<()> {
+        let view_id = match self.windows.get_focused() {
+            Some(view_id) => view_id,
+            None => return Ok(()),
+        };
+        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+        let cursor = buffer.cursor(view_id.cursor_id);
+        let text = buffer.edit_tree();
+        let selection = cursor.selection() != cursor.range();
+        let range = if selection {
+            cursor.selection()
+        } else {
+            0..text.len_chars()
+        };
+        let line = text.char_to_line(cursor.range().start);
+        let state = ScriptState {
+            text: text.slice(range.clone()).into(),
+            selection,
+            file_path: buffer
+                .file_path()
+                .map(|file_path| file_path.to_string_lossy().into_owned()),
+            mode: buffer.mode().name.clone(),
+            line: line + 1,
+            column: cursor.range().start - text.line_to_char(line) + 1,
+        };
+
+        let ScriptOutput {
+            replacement,
+            messages,
+        } = self.scripts.run(command, state)?;
+        if let Some(replacement) = replacement {
+            if buffer.is_read_only() {
+                anyhow::bail!("Buffer is read-only");
             }
-            Message::ChangeTheme => {
-                self.theme_index = (self.theme_index + 1) % self.themes.len();
-                if !self.prompt_action.is_interactive() {
-                    self.context.log(format!(
-                        "Theme changed to {}",
-                        self.themes[self.theme_index].1
-                    ));
+            buffer.replace_range(range, replacement.into());
+        }
+        if let Some(message) = messages.last() {
+            self.context.log(message.clone());
+        }
+        Ok(())
+    }
+
+    fn handle_plugin_request(&mut self, plugin_id: PluginId, request: PluginRequest) {
+        let PluginRequest { id, method, params } = request;
+        let result = match method.as_str() {
+            "subscribe" => self
+                .plugins
+                .subscribe(plugin_id, params)
+                .map(|()| Value::Null),
+            "getText" => self.plugin_get_text(params),
+            "insertText" => self.plugin_insert_text(params),
+            "setAnnotations" => self.plugin_set_annotations(plugin_id, params),
+            "clearAnnotations" => self.plugin_clear_annotations(plugin_id, params),
+            "setStatus" => parse_plugin_params(params).map(|SetStatusParams { message }| {
+                self.context.log(message);
+                Value::Null
+            }),
+            "openPicker" => match (id.clone(), parse_plugin_params(params)) {
+                (Some(request_id), Ok(params)) => {
+                    return self.open_plugin_picker(plugin_id, request_id, params)
+                }
+                (_, Ok(_)) => Err("`openPicker` must be sent as a request".into()),
+                (_, Err(error)) => Err(error),
+            },
+            _ => {
+                match id {
+                    Some(id) => self
+                        .plugins
+                        .respond_method_not_found(plugin_id, id, &method),
+                    None => log::warn!(
+                        "Unknown notification `{}` from plugin `{}`",
+                        method,
+                        self.plugins.name(plugin_id)
+                    ),
                 }
+                return;
             }
-            Message::OpenFilePicker(source) if !self.prompt_action.is_interactive() => {
-                self.prompt_action = PromptAction::OpenFile {
-                    source,
-                    on_open: self.context.link.callback(Message::OpenFile),
-                    on_change_height: self.context.link.callback(Message::ChangePromptHeight),
-                };
-                self.prompt_height = self.prompt_action.initial_height();
+        };
+        match (id, result) {
+            (Some(id), result) => self.plugins.respond(plugin_id, id, result),
+            (None, Err(error)) => log::warn!(
+                "Invalid `{}` notification from plugin `{}`: {}",
+                method,
+                self.plugins.name(plugin_id),
+                error
+            ),
+            (None, Ok(_)) => {}
+        }
+    }
+
+    fn plugin_get_text(&self, params: Value) -> std::result::Result<Value, String> {
+        let GetTextParams { buffer } = parse_plugin_params(params)?;
+        let buffer = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| format!("Unknown buffer {}", buffer))?;
+        Ok(json!({ "text": buffer.edit_tree().to_string() }))
+    }
+
+    fn plugin_insert_text(&mut self, params: Value) -> std::result::Result<Value, String> {
+        let InsertTextParams { text } = parse_plugin_params(params)?;
+        let view_id = self.windows.get_focused().ok_or("No buffer is focused")?;
+        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+        if buffer.is_read_only() {
+            return Err("Buffer is read-only".into());
+        }
+        let start = buffer.cursor(view_id.cursor_id).range().start;
+        buffer.replace_range(start..start, text.into());
+        Ok(Value::Null)
+    }
+
+    fn plugin_set_annotations(
+        &mut self,
+        plugin_id: PluginId,
+        params: Value,
+    ) -> std::result::Result<Value, String> {
+        let SetAnnotationsParams {
+            buffer,
+            version,
+            annotations,
+        } = parse_plugin_params(params)?;
+        let annotations = annotations
+            .into_iter()
+            .map(
+                |AnnotationParams {
+                     start,
+                     end,
+                     severity,
+                     message,
+                 }| Annotation {
+                    range: start..end,
+                    severity,
+                    message,
+                },
+            )
+            .collect();
+        let source = self.plugins.name(plugin_id);
+        self.buffers
+            .get_mut(buffer)
+            .ok_or_else(|| format!("Unknown buffer {}", buffer))?
+            .set_annotations(source, version, annotations)
+            .map(|()| Value::Null)
+            .map_err(|error| error.to_string())
+    }
+
+    fn plugin_clear_annotations(
+        &mut self,
+        plugin_id: PluginId,
+        params: Value,
+    ) -> std::result::Result<Value, String> {
+        let ClearAnnotationsParams { buffer } = parse_plugin_params(params)?;
+        let source = self.plugins.name(plugin_id);
+        self.buffers
+            .get_mut(buffer)
+            .ok_or_else(|| format!("Unknown buffer {}", buffer))?
+            .clear_annotations(source);
+        Ok(Value::Null)
+    }
+
+    fn open_plugin_picker(
+        &mut self,
+        plugin_id: PluginId,
+        request_id: Value,
+        params: OpenPickerParams,
+    ) {
+        if self.prompt_action.is_interactive() {
+            self.plugins
+                .respond(plugin_id, request_id, Err("The prompt is busy".into()));
+            return;
+        }
+        let items: Rc<[String]> = params.items.into();
+        self.prompt_action = PromptAction::pick(
+            PickerDefinition::items(
+                params.title,
+                items.clone(),
+                self.context.link.callback(Message::PluginItemSelected),
+            ),
+            self.context.link.callback(Message::ChangePromptHeight),
+        );
+        self.prompt_height = self.prompt_action.initial_height();
+        self.pending_plugin_picker = Some(PendingPluginPicker {
+            plugin_id,
+            request_id,
+            items,
+        });
+    }
+
+    /// Opens a URL in the browser, or a path in a new buffer. Relative paths
+    /// are relative to the directory of the focused buffer's file.
+    fn open_link(&mut self, target: LinkTarget) {
+        match target {
+            LinkTarget::Url(url) => {
+                let link = self.context.link.clone();
+                self.context.task_pool.spawn(move |_| {
+                    let status = open_url_command(&url)
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::null())
+                        .stderr(Stdio::null())
+                        .status();
+                    let message = match status {
+                        Ok(status) if status.success() => format!("Opened {}", url),
+                        Ok(status) => format!("Could not open {} ({})", url, status),
+                        Err(error) => format!("Could not open {} ({})", url, error),
+                    };
+                    link.send(Message::Log(Some(message)));
+                });
             }
-            Message::OpenFile(path) => {
-                self.prompt_action = self.open_file(path).map_or_else(
-                    |error| PromptAction::Log {
-                        message: format!("Could not open file: {}", error),
-                    },
-                    |new_file| {
-                        if new_file {
-                            PromptAction::Log {
-                                message: "[New file]".into(),
-                            }
-                        } else {
-                            PromptAction::None
-                        }
+            LinkTarget::Path(path) => {
+                let path = match path.strip_prefix("~/") {
+                    Some(relative) => match dirs::home_dir() {
+                        Some(home_dir) => home_dir.join(relative),
+                        None => PathBuf::from(path),
                     },
-                );
-                self.prompt_height = self.prompt_action.initial_height();
-            }
-            Message::SelectBufferPicker if !self.prompt_action.is_interactive() => {
-                self.open_buffer_picker(
-                    "buffer".into(),
-                    self.context.link.callback(Message::SelectBuffer),
-                );
-            }
-            Message::SelectBuffer(buffer_id) => {
-                self.prompt_action = PromptAction::None;
-                self.prompt_height = self.prompt_action.initial_height();
-                self.focus_on_buffer(buffer_id);
-            }
-            Message::KillBufferPicker if !self.prompt_action.is_interactive() => {
-                self.open_buffer_picker(
-                    "kill buffer".into(),
-                    self.context.link.callback(Message::KillBuffer),
-                );
+                    None => {
+                        let buffer_dir = self
+                            .windows
+                            .get_focused()
+                            .and_then(|view_id| self.buffers.get(view_id.buffer_id))
+                            .and_then(|buffer| buffer.file_path())
+                            .and_then(|file_path| file_path.parent())
+                            .unwrap_or(&self.context.current_working_dir);
+                        buffer_dir.join(path)
+                    }
+                };
+                self.context.link.send(Message::OpenFile(path));
             }
-            Message::KillBuffer(buffer_id) => {
-                self.prompt_action = PromptAction::None;
-                self.prompt_height = self.prompt_action.initial_height();
+        }
+    }
+
+    /// Writes part of a buffer to a file on the task pool, without the buffer
+    /// visiting it
+    fn spawn_write_region(&self, path: PathBuf, text: Rope) {
+        let link = self.context.link.clone();
+        self.context.task_pool.spawn(move |_| {
+            let result = File::create(&path)
+                .map(BufWriter::new)
+                .and_then(|writer| text.write_to(writer));
+            link.send(Message::RegionWritten(path, result))
+        });
+    }
+
+    /// Types a character at the cursor of the focused window
+    fn insert_char_at_cursor(&self, character: char) {
+        if let Some(view_id) = self.windows.get_focused() {
+            let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+            BufferCursor::new(
+                view_id.buffer_id,
+                view_id.cursor_id,
+                buffer.cursor(view_id.cursor_id).clone(),
+                self.context.link.clone(),
+            )
+            .insert_char(character, true);
+        }
+    }
+
+    /// Where the local variable under a cursor is defined and referenced
+    fn local_variable_at(&self, view_id: BufferViewId) -> Result<Vec<Range<CharIndex>>> {
+        let buffer = self
+            .buffers
+            .get(view_id.buffer_id)
+            .context("Buffer was closed")?;
+        let locals = buffer
+            .mode()
+            .grammar()
+            .and_then(|grammar| grammar.ok())
+            .and_then(|grammar| grammar.locals.as_ref())
+            .with_context(|| {
+                format!("No locals query available for {} mode", buffer.mode().name)
+            })?;
+        let text = buffer.edit_tree();
+        let parse_tree = buffer
+            .parse_tree()
+            .filter(|parse_tree| parse_tree.version == buffer.edit_tree_handle().version())
+            .context("Buffer has not been parsed yet")?;
+        let char_index = buffer.cursor(view_id.cursor_id).range().start;
+        let occurrences = local_occurrences(locals, parse_tree, text, char_index)
+            .context("No local variable under the cursor")?;
+        Ok(occurrences
+            .into_iter()
+            .map(|range| text.byte_to_char(range.start)..text.byte_to_char(range.end))
+            .collect())
+    }
+
+    /// Renames a local variable everywhere it's referenced in its scope, as a
+    /// single edit which can be undone at once
+    fn rename_local_variable(&mut self, view_id: BufferViewId, new_name: &str) -> Result<usize> {
+        if new_name.is_empty() || new_name.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid name `{}`", new_name);
+        }
+        let occurrences = self.local_variable_at(view_id)?;
+        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+        if buffer.is_read_only() {
+            anyhow::bail!("Buffer is read-only");
+        }
+        let num_occurrences = occurrences.len();
+        let new_name = Rope::from(new_name);
+        buffer.apply_edits(
+            occurrences
+                .into_iter()
+                .map(|range| TextEdit {
+                    range,
+                    text: new_name.clone(),
+                })
+                .collect(),
+        )?;
+        Ok(num_occurrences)
+    }
+
+    /// Opens the file referenced under the cursor, looking for it in the
+    /// directory of the buffer and each parent up to the root of the
+    /// repository, then in the current working directory
+    fn find_file_at_point(&mut self, view_id: BufferViewId) {
+        let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+        let text = buffer.edit_tree();
+        let char_index = buffer.cursor(view_id.cursor_id).range().start;
+        let line_index = text.char_to_line(char_index);
+        let column = char_index - text.line_to_char(line_index);
+        let reference = match file_at_point::reference_at(text.line(line_index), column) {
+            Some(reference) => reference,
+            None => return self.context.log("No file name at point"),
+        };
+
+        let repo_root = buffer.repository().and_then(|repo| repo.workdir());
+        let mut roots: Vec<PathBuf> = Vec::new();
+        if let Some(buffer_dir) = buffer.file_path().and_then(|path| path.parent()) {
+            for directory in buffer_dir.ancestors() {
+                roots.push(directory.to_path_buf());
+                if repo_root.map_or(true, |repo_root| {
+                    directory == repo_root || !directory.starts_with(repo_root)
+                }) {
+                    break;
+                }
+            }
+        }
+        roots.push(self.context.current_working_dir.clone());
+        let extension = buffer
+            .file_path()
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str());
+
+        let path = match file_at_point::resolve(&reference.text, &roots, extension) {
+            Some(path) => path,
+            None => {
+                return self
+                    .context
+                    .log(format!("No file found for `{}`", reference.text))
+            }
+        };
+        if let Err(error) = self.open_file(path) {
+            return self.context.log(format!("Could not open file: {}", error));
+        }
+        if let (Some(line_index), Some(view_id)) = (reference.line, self.windows.get_focused()) {
+            self.buffers
+                .get_mut(view_id.buffer_id)
+                .unwrap()
+                .move_cursor_to_line(view_id.cursor_id, line_index, reference.column.unwrap_or(0));
+        }
+    }
+
+    /// Shows the focused buffer's name in the terminal's title, marking it
+    /// with `*` if it has unsaved changes
+    fn update_terminal_title(&mut self) {
+        let title = match self.windows.get_focused() {
+            Some(view_id) => {
+                let modified = self
+                    .buffers
+                    .get(view_id.buffer_id)
+                    .unwrap()
+                    .modified_status()
+                    != ModifiedStatus::Unchanged;
+                format!(
+                    "{}{} - zee",
+                    self.buffer_display_name(view_id.buffer_id),
+                    if modified { "*" } else { "" }
+                )
+            }
+            None => "zee".into(),
+        };
+        if title != self.terminal_title {
+            terminal::set_title(&title);
+            self.terminal_title = title;
+        }
+    }
+
+    fn modified_buffers(&self) -> Vec<BufferId> {
+        self.buffers
+            .iter()
+            .filter(|buffer| {
+                (buffer.file_path().is_some() || buffer.remote().is_some())
+                    && buffer.modified_status() != ModifiedStatus::Unchanged
+            })
+            .map(|buffer| buffer.id())
+            .collect()
+    }
+
+    fn set_last_search(&mut self, input: String, fold_case: bool) {
+        self.last_search_pattern = search::Pattern::parse(&input, fold_case).ok().map(Rc::new);
+        self.last_search = Some(input);
+    }
+
+    /// Releases the cursors of views which no window, indirect view,
+    /// comparison or `occur` refers to anymore, so new views reuse them
+    fn release_unused_cursors(&mut self, view_ids: impl IntoIterator<Item = BufferViewId>) {
+        for view_id in view_ids {
+            let in_use = self.windows.nodes().any(|shown| *shown == view_id)
+                || self.comparison.as_ref().map_or(false, |comparison| {
+                    comparison.left == view_id || comparison.right == view_id
+                })
+                || self
+                    .occur
+                    .as_ref()
+                    .map_or(false, |occur| occur.source == view_id);
+            if in_use {
+                continue;
+            }
+            if let Some(buffer) = self.buffers.get_mut(view_id.buffer_id) {
+                if buffer.indirect_view_number(view_id.cursor_id).is_none() {
+                    buffer.release_cursor(view_id.cursor_id);
+                }
+            }
+        }
+    }
+
+    /// Remembers where the cursor was in a buffer visiting a local file,
+    /// before it's killed. The cursor of the focused window is remembered if
+    /// it shows the buffer.
+    fn remember_killed_buffer(&mut self, buffer_id: BufferId) {
+        let buffer = match self.buffers.get(buffer_id) {
+            Some(buffer) if buffer.remote().is_none() => buffer,
+            _ => return,
+        };
+        let file_path = match buffer.file_path() {
+            Some(file_path) => file_path.clone(),
+            None => return,
+        };
+        let cursor_id = self
+            .windows
+            .get_focused()
+            .filter(|view_id| view_id.buffer_id == buffer_id)
+            .map_or(CursorId::default(), |view_id| view_id.cursor_id);
+        let text = buffer.edit_tree();
+        let char_index = buffer.cursor(cursor_id).range().start;
+        let line = text.char_to_line(char_index);
+        self.killed_buffers.push(KilledBuffer {
+            file_path,
+            line,
+            column: char_index - text.line_to_char(line),
+        });
+    }
+
+    fn buffer_display_name(&self, buffer_id: BufferId) -> String {
+        let buffer = self.buffers.get(buffer_id).unwrap();
+        match (buffer.file_path(), buffer.name()) {
+            (Some(file_path), _) => file_path
+                .strip_prefix(&self.context.current_working_dir)
+                .unwrap_or(file_path)
+                .display()
+                .to_string(),
+            (None, Some(name)) => name.to_owned(),
+            (None, None) => buffer_id.to_string(),
+        }
+    }
+
+    fn save_buffer_before_quit(&mut self, buffer_id: BufferId) {
+        if let (Some(buffer), Some(pending_quit)) =
+            (self.buffers.get_mut(buffer_id), self.pending_quit.as_mut())
+        {
+            if buffer.modified_status() == ModifiedStatus::Changed {
+                buffer.handle_message(BufferMessage::SaveBufferStart);
+            }
+            pending_quit.saving.push(buffer_id);
+        }
+    }
+
+    /// Asks whether to save the next modified buffer, or exits once we've
+    /// asked about all of them
+    fn ask_to_save_next_buffer(&mut self) {
+        let buffer_id = match self
+            .pending_quit
+            .as_ref()
+            .and_then(|pending_quit| pending_quit.unasked.last().copied())
+        {
+            Some(buffer_id) => buffer_id,
+            None => return self.exit_when_saved(),
+        };
+        self.prompt_action = PromptAction::InteractiveMessage {
+            message: format!("Save {}?", self.buffer_display_name(buffer_id)).into(),
+            on_input: self
+                .context
+                .link
+                .callback(move |save| Message::QuitSaveBuffer(buffer_id, save)),
+        };
+        self.prompt_height = self.prompt_action.initial_height();
+    }
+
+    /// Exits if all the buffers we're saving were written to disk, called
+    /// again whenever a save finishes
+    fn exit_when_saved(&mut self) {
+        let pending_quit = match self.pending_quit.as_ref() {
+            Some(pending_quit) if pending_quit.unasked.is_empty() => pending_quit,
+            _ => return,
+        };
+        let statuses = pending_quit
+            .saving
+            .iter()
+            .filter_map(|buffer_id| self.buffers.get(*buffer_id))
+            .map(|buffer| buffer.modified_status());
+        let mut saving = false;
+        for status in statuses {
+            match status {
+                ModifiedStatus::Saving => saving = true,
+                ModifiedStatus::Changed => {
+                    self.pending_quit = None;
+                    self.context.log("Could not save all buffers, not exiting");
+                    return;
+                }
+                ModifiedStatus::Unchanged => {}
+            }
+        }
+        if saving {
+            self.prompt_action = PromptAction::Log {
+                message: "Saving buffers before exiting...".into(),
+            };
+            self.prompt_height = self.prompt_action.initial_height();
+        } else {
+            self.context.link.exit();
+        }
+    }
+
+    /// Renders the selected lines of a buffer, or all of them if nothing is
+    /// selected, with the syntax highlighting of the current theme
+    fn export_buffer(&self, view_id: BufferViewId, format: export::Format) -> String {
+        let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+        let text = buffer.edit_tree();
+        let selection = buffer.cursor(view_id.cursor_id).selection();
+        let lines = if selection.len() > 1 {
+            text.char_to_line(selection.start)..text.char_to_line(selection.end - 1) + 1
+        } else {
+            0..text.len_lines()
+        };
+        let query = buffer
+            .mode()
+            .grammar()
+            .and_then(|grammar| grammar.ok())
+            .and_then(|grammar| grammar.highlights.as_ref());
+        let spans = match (buffer.parse_tree(), query) {
+            (Some(parse_tree), Some(query)) => {
+                Some(query_spans(query, &parse_tree.tree, text, lines.clone()))
+            }
+            _ => None,
+        };
+        export::export(
+            format,
+            &self.themes[self.theme_index].0.buffer.syntax,
+            &self.buffer_display_name(view_id.buffer_id),
+            &export::Highlighted {
+                text,
+                lines,
+                spans,
+                line_scopes: buffer.line_scopes(),
+            },
+        )
+    }
+
+    /// Shows a buffer side by side with the focused one, highlighting the
+    /// lines which differ
+    fn compare_buffers(&mut self, left: BufferViewId, buffer_id: BufferId) {
+        if left.buffer_id == buffer_id {
+            self.context.log("Cannot compare a buffer with itself");
+            return;
+        }
+        let mut closed_views: Vec<_> = self.windows.nodes().copied().collect();
+        if let Some(comparison) = self.comparison.take() {
+            comparison.clear(&mut self.buffers);
+            closed_views.extend([comparison.left, comparison.right]);
+        }
+        self.windows.delete_all_except_focused();
+        self.release_unused_cursors(closed_views);
+        let right = BufferViewId::new(
+            buffer_id,
+            self.buffers.get_mut(buffer_id).unwrap().new_cursor(),
+        );
+        self.windows.insert_at_focused(right, FlexDirection::Row);
+        self.comparison = Some(Comparison::new(left, right));
+        self.move_to_first_difference();
+    }
+
+    /// Opens two files and compares them, see `--diff`
+    fn compare_files(&mut self, left: PathBuf, right: PathBuf) -> Result<()> {
+        self.open_file(left)?;
+        let left = self.windows.get_focused().unwrap();
+        self.open_file(right)?;
+        let right = self.windows.get_focused().unwrap();
+        self.focus_on_buffer(left.buffer_id);
+        self.compare_buffers(left, right.buffer_id);
+        Ok(())
+    }
+
+    /// Moves the cursors to the first difference between the buffers being
+    /// compared, waiting for both of them to finish loading
+    fn move_to_first_difference(&mut self) {
+        let comparison = match self.comparison.as_mut() {
+            Some(comparison) => comparison,
+            None => return,
+        };
+        let loading = [comparison.left, comparison.right].iter().any(|view_id| {
+            self.buffers
+                .get(view_id.buffer_id)
+                .map_or(false, |buffer| buffer.loading().is_some())
+        });
+        if loading {
+            return;
+        }
+        comparison.refresh(&mut self.buffers);
+        if comparison.move_to_hunk(&mut self.buffers, Direction::Forward) {
+            self.context
+                .log(format!("Found {} difference(s)", comparison.num_hunks()));
+        } else {
+            self.context.log("Buffers are identical");
+        }
+    }
+
+    /// Lets the user pick a buffer, or one of the indirect views of a buffer
+    /// if `indirect_views` is set
+    fn open_buffer_picker(
+        &mut self,
+        message: Cow<'static, str>,
+        indirect_views: bool,
+        on_select: Callback<BufferEntry>,
+    ) {
+        self.prompt_action = PromptAction::PickBuffer {
+            message,
+            entries: self
+                .buffers
+                .iter()
+                .flat_map(|buffer| {
+                    let entry = BufferEntry::new(
+                        buffer.id(),
+                        buffer.file_path().cloned(),
+                        false,
+                        buffer.edit_tree().len_bytes(),
+                        buffer.mode(),
+                        buffer.name(),
+                    );
+                    let views = if indirect_views {
+                        buffer.indirect_views()
+                    } else {
+                        &[]
+                    };
+                    let views: Vec<_> = views
+                        .iter()
+                        .map(|(number, _)| entry.clone().indirect_view(*number))
+                        .collect();
+                    iter::once(entry).chain(views)
+                })
+                .collect(),
+            on_select,
+            on_change_height: self.context.link.callback(Message::ChangePromptHeight),
+        };
+        self.prompt_height = self.prompt_action.initial_height();
+    }
+}
+
+impl Drop for Editor {
+    fn drop(&mut self) {
+        if std::thread::panicking() && self.context.config.crash_recovery_files {
+            recovery::write_recovery_files(&self.buffers);
+        }
+    }
+}
+
+impl Component for Editor {
+    type Message = Message;
+    type Properties = Properties;
+
+    fn create(properties: Properties, frame: Rect, link: ComponentLink<Self>) -> Self {
+        // Each file is opened in a window of its own, split off the previous
+        // one, then the windows are arranged in the layout asked for
+        for (index, file_path) in properties.args_files.iter().cloned().enumerate() {
+            if index > 0 {
+                link.send(Message::SplitWindow(FlexDirection::Row));
+                link.send(Message::FocusNextWindow);
+            }
+            match file_path.to_str().and_then(RemotePath::parse) {
+                _ if file_path == Path::new(STDIN_ARG) => link.send(Message::OpenStdin),
+                Some(remote) => link.send(Message::OpenRemoteFile(remote)),
+                None => link.send(Message::OpenFile(file_path)),
+            }
+        }
+        if properties.args_files.len() > 1 {
+            link.send(Message::ArrangeWindows(properties.window_layout));
+        }
+        let show_startup_buffer = properties.diff_files.is_none();
+        if let Some((left, right)) = properties.diff_files {
+            link.send(Message::CompareFiles(left, right));
+        }
+
+        let project = Project::find(&properties.current_working_dir, &properties.trusted_configs);
+        if let Some(config_path) = project
+            .as_ref()
+            .and_then(|project| project.untrusted_config.clone())
+        {
+            link.send(Message::AskTrustLocalConfig(config_path));
+        }
+        let theme_name = project
+            .as_ref()
+            .and_then(|project| project.config.theme.clone())
+            .unwrap_or_else(|| properties.config.theme.clone());
+        let recent_files = Arc::new(properties.recent_files);
+        let context = ContextHandle(Box::leak(
+            Context {
+                args_files: properties.args_files,
+                current_working_dir: properties.current_working_dir,
+                config_dir: properties.config_dir,
+                modes: properties
+                    .config
+                    .modes
+                    .iter()
+                    .cloned()
+                    .map(Mode::new)
+                    .collect(),
+                config: properties.config,
+                task_pool: properties.task_pool,
+                clipboard: properties.clipboard,
+                link,
+            }
+            .into(),
+        ));
+
+        let script_bindings = properties
+            .scripts
+            .bindings()
+            .into_iter()
+            .filter_map(
+                |(keys, command)| match bindings::parse_key_sequence(&keys) {
+                    Some(keys) => Some((keys, command)),
+                    None => {
+                        context.log(format!("Invalid key sequence `{}` for `{}`", keys, command));
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        let (plugins, plugin_errors) = PluginHost::start(
+            &context.config.plugins,
+            &context.current_working_dir,
+            &context.link,
+        );
+        if let Some(error) = plugin_errors.last() {
+            context.log(error.clone());
+        }
+
+        let theme_index = {
+            let theme = THEMES.iter().position(|(_, name)| *name == theme_name);
+            if theme.is_none() {
+                context.log(format!("Unknown theme `{}`", theme_name));
+            }
+            theme
+        }
+        .unwrap_or(0);
+
+        let mut editor = Self {
+            themes: &THEMES,
+            theme_index,
+            prompt_action: PromptAction::None,
+            prompt_height: PROMPT_INACTIVE_HEIGHT,
+            frame,
+            buffers: Buffers::new(context.clone()),
+            context,
+            windows: WindowTree::new(),
+            recent_files,
+            prompt_history: Arc::new(properties.prompt_history),
+            project,
+            trusted_configs: properties.trusted_configs,
+            asked_local_configs: HashSet::new(),
+            scripts: properties.scripts,
+            script_bindings,
+            plugins,
+            pending_plugin_picker: None,
+            symbol_index: None,
+            symbol_index_task: None,
+            pick_symbol_when_indexed: false,
+            definition_when_indexed: None,
+            jump_back: Vec::new(),
+            comparison: None,
+            killed_buffers: KilledBuffers::default(),
+            last_search: None,
+            last_search_pattern: None,
+            occur: None,
+            highlights: Highlights::default(),
+            message_log: MessageLog::default(),
+            completion: None,
+            pending_keys: None,
+            pending_quit: None,
+            terminal_title: String::new(),
+        };
+        if show_startup_buffer
+            && editor.context.args_files.is_empty()
+            && editor.context.config.startup == Startup::Scratch
+        {
+            let buffer_id = editor.buffers.add_scratch(SCRATCH_BUFFER_NAME);
+            editor.focus_on_buffer(buffer_id);
+        }
+        editor
+    }
+
+    fn resize(&mut self, frame: Rect) -> ShouldRender {
+        self.frame = frame;
+        ShouldRender::Yes
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Cancel => {
+                self.pending_quit = None;
+                self.completion = None;
+                if let Some(picker) = self.pending_plugin_picker.take() {
+                    self.plugins
+                        .respond(picker.plugin_id, picker.request_id, Ok(Value::Null));
+                }
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.highlights.clear_preview();
+                self.context.log("Cancel");
+            }
+            Message::ChangeTheme => {
+                self.theme_index = (self.theme_index + 1) % self.themes.len();
+                if !self.prompt_action.is_interactive() {
+                    self.context.log(format!(
+                        "Theme changed to {}",
+                        self.themes[self.theme_index].1
+                    ));
+                }
+            }
+            Message::OpenFilePicker(source) if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::OpenFile {
+                    source,
+                    message: None,
+                    directory: self
+                        .project
+                        .as_ref()
+                        .filter(|_| source == FileSource::Repository)
+                        .map(|project| project.root.clone()),
+                    recent_files: self.recent_files.clone(),
+                    history: Some(HistoryKind::Path),
+                    on_open: self.context.link.callback(Message::OpenFile),
+                    on_change_height: self.context.link.callback(Message::ChangePromptHeight),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::OpenProject(directory) if !self.prompt_action.is_interactive() => {
+                if let Some(project) = Project::find(&directory, &self.trusted_configs) {
+                    self.context.log(format!("Project {}", project.name()));
+                    self.set_project(project);
+                }
+                self.prompt_action = PromptAction::OpenFile {
+                    source: FileSource::Repository,
+                    message: None,
+                    directory: Some(directory),
+                    recent_files: self.recent_files.clone(),
+                    history: Some(HistoryKind::Path),
+                    on_open: self.context.link.callback(Message::OpenFile),
+                    on_change_height: self.context.link.callback(Message::ChangePromptHeight),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::ProjectPicker if !self.prompt_action.is_interactive() => {
+                let mut projects = project::recent_projects(&self.recent_files);
+                if let Some(project) = self.project.as_ref() {
+                    projects.retain(|root| *root != project.root);
+                    projects.push(project.root.clone());
+                }
+                if projects.is_empty() {
+                    self.context.log("No recent projects");
+                } else {
+                    let items: Rc<[String]> = projects
+                        .iter()
+                        .map(|root| root.to_string_lossy().into_owned())
+                        .collect();
+                    self.prompt_action = PromptAction::pick(
+                        PickerDefinition::items(
+                            "project",
+                            items,
+                            self.context.link.callback(move |index: usize| {
+                                Message::ProjectSelected(projects[index].clone())
+                            }),
+                        ),
+                        self.context.link.callback(Message::ChangePromptHeight),
+                    );
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::ProjectSelected(root) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.context.link.send(Message::OpenProject(root));
+            }
+            Message::AskTrustLocalConfig(config_path)
+                if !self.prompt_action.is_interactive()
+                    && !self.asked_local_configs.contains(&config_path) =>
+            {
+                self.asked_local_configs.insert(config_path.clone());
+                self.prompt_action = PromptAction::InteractiveMessage {
+                    message: format!("Trust the settings in {}?", config_path.display()).into(),
+                    on_input: self.context.link.callback(move |trust| {
+                        Message::TrustLocalConfig(config_path.clone(), trust)
+                    }),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::TrustLocalConfig(config_path, trust) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if !trust {
+                    self.context
+                        .log(format!("Ignoring {} this session", config_path.display()));
+                } else if let Ok(contents) = fs::read_to_string(&config_path) {
+                    self.trusted_configs.trust(config_path.clone(), &contents);
+                    if let Err(error) = self.trusted_configs.save() {
+                        log::warn!("{}", error);
+                    }
+                    let scope = local_config::scope(&config_path).unwrap_or(&config_path);
+                    let project = self
+                        .project
+                        .as_ref()
+                        .filter(|project| project.untrusted_config.as_ref() == Some(&config_path))
+                        .and_then(|project| Project::find(&project.root, &self.trusted_configs));
+                    if let Some(project) = project {
+                        self.set_project(project);
+                    }
+                    let current_working_dir = &self.context.current_working_dir;
+                    let buffer_ids: Vec<_> = self
+                        .buffers
+                        .iter()
+                        .filter(|buffer| {
+                            buffer.loading().is_none()
+                                && buffer.file_path().map_or(false, |file_path| {
+                                    current_working_dir.join(file_path).starts_with(scope)
+                                })
+                        })
+                        .map(|buffer| buffer.id())
+                        .collect();
+                    for buffer_id in buffer_ids {
+                        self.apply_buffer_settings(buffer_id);
+                    }
+                }
+            }
+            Message::FindFileInProject => match self.project.as_ref() {
+                Some(project) => {
+                    let root = project.root.clone();
+                    self.context.link.send(Message::OpenProject(root));
+                }
+                None => self.context.log("Not in a project"),
+            },
+            Message::OpenFile(path) if !parent_directory_exists(&path) => {
+                let parent = path.parent().unwrap_or(&path).to_path_buf();
+                self.prompt_action = PromptAction::InteractiveMessage {
+                    message: format!("Directory {} does not exist. Create it?", parent.display())
+                        .into(),
+                    on_input: self.context.link.callback(move |create| {
+                        Message::CreateParentDirectories(path.clone(), create)
+                    }),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::CreateParentDirectories(path, create) => {
+                let parent = path.parent().unwrap_or(&path);
+                self.prompt_action = if !create {
+                    PromptAction::None
+                } else if let Err(error) = fs::create_dir_all(parent) {
+                    PromptAction::Log {
+                        message: format!(
+                            "Could not create directory {} ({})",
+                            parent.display(),
+                            error
+                        ),
+                    }
+                } else {
+                    self.context.link.send(Message::OpenFile(path));
+                    PromptAction::None
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::OpenFile(path) => {
+                self.prompt_action = self.open_file(path).map_or_else(
+                    |error| PromptAction::Log {
+                        message: format!("Could not open file: {}", error),
+                    },
+                    |new_file| {
+                        if new_file {
+                            PromptAction::Log {
+                                message: "[New file]".into(),
+                            }
+                        } else {
+                            PromptAction::None
+                        }
+                    },
+                );
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::OpenStdin => {
+                let buffer_id = self.buffers.add_loading_read_only(STDIN_BUFFER_NAME);
+                self.focus_on_buffer(buffer_id);
+                self.spawn_load_file(buffer_id, io::stdin());
+            }
+            Message::OpenRemoteFile(remote) => match self.buffers.find_by_remote(&remote) {
+                Some(buffer_id) => self.focus_on_buffer(buffer_id),
+                None => {
+                    self.context.log(format!("Downloading {}", remote));
+                    let link = self.context.link.clone();
+                    self.context.task_pool.spawn(move |_| {
+                        let text = remote.download();
+                        link.send(Message::RemoteFileDownloaded(remote, text))
+                    });
+                }
+            },
+            Message::RemoteFileDownloaded(remote, Ok(text)) => {
+                let buffer_id = match self.buffers.find_by_remote(&remote) {
+                    Some(buffer_id) => buffer_id,
+                    None => self.buffers.add_remote(text, remote),
+                };
+                self.focus_on_buffer(buffer_id);
+                self.context.link.send(Message::Log(None));
+            }
+            Message::RemoteFileDownloaded(remote, Err(error)) => {
+                self.context
+                    .log(format!("Could not open {} ({})", remote, error));
+            }
+            Message::FileLoading(buffer_id, bytes_read) => {
+                if let Some(buffer) = self.buffers.get_mut(buffer_id) {
+                    buffer.set_loading_progress(bytes_read);
+                }
+            }
+            // The buffer may have been killed while the file was being read
+            Message::FileLoaded(buffer_id, Ok(text)) => {
+                if let Some(buffer) = self.buffers.get_mut(buffer_id) {
+                    buffer.finish_loading(text);
+                    self.apply_buffer_settings(buffer_id);
+                }
+                if self
+                    .buffers
+                    .get(buffer_id)
+                    .and_then(Buffer::lock_owner)
+                    .is_some()
+                {
+                    self.context.link.send(Message::FileLocked(buffer_id));
+                }
+                if self.comparison.as_ref().map_or(false, |comparison| {
+                    comparison.current_hunk().is_none()
+                        && (comparison.left.buffer_id == buffer_id
+                            || comparison.right.buffer_id == buffer_id)
+                }) {
+                    self.move_to_first_difference();
+                }
+            }
+            Message::FileLoaded(buffer_id, Err(error)) if self.buffers.get(buffer_id).is_some() => {
+                let name = self.buffer_display_name(buffer_id);
+                self.context.link.send(Message::KillBuffer(buffer_id));
+                self.context
+                    .log(format!("Could not open {} ({})", name, error));
+            }
+            Message::SelectBufferPicker if !self.prompt_action.is_interactive() => {
+                self.open_buffer_picker(
+                    "buffer".into(),
+                    true,
+                    self.context.link.callback(|entry: BufferEntry| {
+                        Message::SelectBuffer(entry.id, entry.indirect_view)
+                    }),
+                );
+            }
+            Message::SelectBuffer(buffer_id, indirect_view) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let cursor_id = indirect_view.and_then(|number| {
+                    self.buffers
+                        .get(buffer_id)
+                        .unwrap()
+                        .indirect_view_cursor(number)
+                });
+                match cursor_id {
+                    Some(cursor_id) if self.windows.is_empty() => {
+                        self.windows.add(BufferViewId::new(buffer_id, cursor_id))
+                    }
+                    Some(cursor_id) => {
+                        let replaced = self.windows.get_focused();
+                        self.windows
+                            .set_focused(BufferViewId::new(buffer_id, cursor_id));
+                        self.release_unused_cursors(replaced);
+                    }
+                    None => self.focus_on_buffer(buffer_id),
+                }
+            }
+            Message::KillBufferPicker if !self.prompt_action.is_interactive() => {
+                self.open_buffer_picker(
+                    "kill buffer".into(),
+                    true,
+                    self.context
+                        .link
+                        .callback(|entry: BufferEntry| match entry.indirect_view {
+                            Some(number) => Message::KillIndirectView(entry.id, number),
+                            None => Message::KillBuffer(entry.id),
+                        }),
+                );
+            }
+            Message::KillIndirectView(buffer_id, number) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let buffer = self.buffers.get_mut(buffer_id).unwrap();
+                if let Some(cursor_id) = buffer.indirect_view_cursor(number) {
+                    buffer.remove_indirect_view(cursor_id);
+                    let view_id = BufferViewId::new(buffer_id, cursor_id);
+                    let mut closed_views = vec![view_id];
+                    if self.comparison.as_ref().map_or(false, |comparison| {
+                        comparison.left == view_id || comparison.right == view_id
+                    }) {
+                        let comparison = self.comparison.take().unwrap();
+                        comparison.clear(&mut self.buffers);
+                        closed_views.extend([comparison.left, comparison.right]);
+                    }
+                    if self
+                        .occur
+                        .as_ref()
+                        .map_or(false, |occur| occur.source == view_id)
+                    {
+                        self.occur = None;
+                    }
+                    // Windows showing the view show the buffer instead
+                    self.windows.nodes_mut().for_each(|window_view_id| {
+                        if *window_view_id == view_id {
+                            *window_view_id = BufferViewId::new(buffer_id, CursorId::default());
+                        }
+                    });
+                    self.release_unused_cursors(closed_views);
+                }
+            }
+            Message::CloneIndirectView => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let (cursor_id, number) = self
+                        .buffers
+                        .get_mut(view_id.buffer_id)
+                        .unwrap()
+                        .clone_indirect_view(view_id.cursor_id);
+                    self.windows.insert_at_focused(
+                        BufferViewId::new(view_id.buffer_id, cursor_id),
+                        FlexDirection::Column,
+                    );
+                    self.windows.cycle_focus(CycleFocus::Next);
+                    self.context.log(format!(
+                        "Opened {}<{}>",
+                        self.buffer_display_name(view_id.buffer_id),
+                        number
+                    ));
+                }
+            }
+            Message::KillBuffer(buffer_id) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if self.comparison.as_ref().map_or(false, |comparison| {
+                    comparison.left.buffer_id == buffer_id
+                        || comparison.right.buffer_id == buffer_id
+                }) {
+                    let comparison = self.comparison.take().unwrap();
+                    comparison.clear(&mut self.buffers);
+                    self.release_unused_cursors([comparison.left, comparison.right]);
+                }
+                if self.occur.as_ref().map_or(false, |occur| {
+                    occur.source.buffer_id == buffer_id || occur.results == buffer_id
+                }) {
+                    self.occur = None;
+                }
+                self.remember_killed_buffer(buffer_id);
                 let removed_buffer = self.buffers.remove(buffer_id);
                 debug_assert!(removed_buffer.is_some());
                 if self.buffers.is_empty() {
                     self.windows.clear();
                 } else {
-                    let some_buffer = self.buffers.iter_mut().next().unwrap();
-                    self.windows.nodes_mut().for_each(|view_id| {
-                        if view_id.buffer_id == buffer_id {
-                            *view_id =
-                                BufferViewId::new(some_buffer.id(), some_buffer.new_cursor());
+                    let some_buffer = self.buffers.iter_mut().next().unwrap();
+                    self.windows.nodes_mut().for_each(|view_id| {
+                        if view_id.buffer_id == buffer_id {
+                            *view_id =
+                                BufferViewId::new(some_buffer.id(), some_buffer.new_cursor());
+                        }
+                    });
+                }
+            }
+            Message::ReopenKilledBuffer => {
+                let buffers = &self.buffers;
+                let killed = self
+                    .killed_buffers
+                    .pop(|file_path| buffers.find_by_path(file_path).is_some());
+                match killed {
+                    Some(killed) => match self.open_file(killed.file_path.clone()) {
+                        Ok(_) => {
+                            let view_id = self.windows.get_focused().unwrap();
+                            self.buffers
+                                .get_mut(view_id.buffer_id)
+                                .unwrap()
+                                .move_cursor_to_line(view_id.cursor_id, killed.line, killed.column);
+                            self.context
+                                .log(format!("Reopened {}", killed.file_path.display()));
+                        }
+                        Err(error) => self.context.log(format!("Could not open file: {}", error)),
+                    },
+                    None => self.context.log("No killed buffer to reopen"),
+                }
+            }
+            Message::RevertBuffer if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer_id = view_id.buffer_id;
+                    if self.buffers.get(buffer_id).unwrap().modified_status()
+                        == ModifiedStatus::Unchanged
+                    {
+                        self.context
+                            .link
+                            .send(Message::PostInteractionRevertBuffer(buffer_id, true));
+                    } else {
+                        self.prompt_action = PromptAction::InteractiveMessage {
+                            message: "Buffer has unsaved changes. Revert anyway?".into(),
+                            on_input: self.context.link.callback(move |revert| {
+                                Message::PostInteractionRevertBuffer(buffer_id, revert)
+                            }),
+                        };
+                        self.prompt_height = self.prompt_action.initial_height();
+                    }
+                }
+            }
+            Message::PostInteractionRevertBuffer(buffer_id, revert) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let (true, Some(buffer)) = (revert, self.buffers.get_mut(buffer_id)) {
+                    match buffer.revert() {
+                        Ok(()) => self.context.log("Reverted buffer from disk"),
+                        Err(error) => self.context.log(error.to_string()),
+                    }
+                }
+            }
+            Message::SaveConflict(buffer_id) if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Choice {
+                    message: format!(
+                        "{} changed on disk since it was opened.",
+                        self.buffer_display_name(buffer_id)
+                    )
+                    .into(),
+                    choices: SAVE_CONFLICT_CHOICES,
+                    on_choice: self
+                        .context
+                        .link
+                        .callback(move |choice| Message::SaveConflictChoice(buffer_id, choice)),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::SaveConflict(buffer_id) => {
+                self.context.log(format!(
+                    "{} changed on disk since it was opened, not saving",
+                    self.buffer_display_name(buffer_id)
+                ));
+            }
+            Message::SaveConflictChoice(buffer_id, choice) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let buffer = match self.buffers.get_mut(buffer_id) {
+                    Some(buffer) => buffer,
+                    None => return ShouldRender::Yes,
+                };
+                match choice {
+                    Some('o') => buffer.save_overwriting(),
+                    Some('r') => match buffer.revert() {
+                        Ok(()) => self.context.log("Reverted buffer from disk"),
+                        Err(error) => self.context.log(error.to_string()),
+                    },
+                    Some('w') => {
+                        self.prompt_action = PromptAction::Input {
+                            message: "save as".into(),
+                            on_submit: self
+                                .context
+                                .link
+                                .callback(move |path| Message::SaveBufferAs(buffer_id, path)),
+                            validator: Some(Validator::path_to_write(
+                                self.context.current_working_dir.clone(),
+                            )),
+                            history: Some(HistoryKind::Path),
+                        };
+                        self.prompt_height = self.prompt_action.initial_height();
+                    }
+                    _ => self.context.log("Cancel"),
+                }
+            }
+            Message::FileLocked(buffer_id) if !self.prompt_action.is_interactive() => {
+                let owner = match self.buffers.get(buffer_id).and_then(Buffer::lock_owner) {
+                    Some(owner) => owner,
+                    None => return ShouldRender::No,
+                };
+                self.prompt_action = PromptAction::Choice {
+                    message: format!(
+                        "{} is being edited by {}.",
+                        self.buffer_display_name(buffer_id),
+                        owner
+                    )
+                    .into(),
+                    choices: FILE_LOCKED_CHOICES,
+                    on_choice: self
+                        .context
+                        .link
+                        .callback(move |choice| Message::FileLockedChoice(buffer_id, choice)),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::FileLocked(buffer_id) => {
+                self.context.log(format!(
+                    "{} is being edited by someone else",
+                    self.buffer_display_name(buffer_id)
+                ));
+            }
+            Message::FileLockedChoice(buffer_id, choice) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let name = self.buffer_display_name(buffer_id);
+                let buffer = match self.buffers.get_mut(buffer_id) {
+                    Some(buffer) => buffer,
+                    None => return ShouldRender::Yes,
+                };
+                match choice {
+                    Some('s') => {
+                        buffer.steal_lock();
+                        self.context.log(format!("Took over the lock on {}", name));
+                    }
+                    Some('r') => {
+                        buffer.set_read_only(true);
+                        self.context.log(format!("{} is read-only", name));
+                    }
+                    _ => self.context.log("Cancel"),
+                }
+            }
+            Message::ToggleReadOnly => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    let read_only = !buffer.is_read_only();
+                    buffer.set_read_only(read_only);
+                    self.context.log(if read_only {
+                        "Buffer is read-only"
+                    } else {
+                        "Buffer is writable"
+                    });
+                }
+            }
+            Message::SavePermissionDenied(buffer_id) if !self.prompt_action.is_interactive() => {
+                let command = self
+                    .context
+                    .config
+                    .elevated_save_command
+                    .as_deref()
+                    .unwrap_or_default()
+                    .join(" ");
+                self.prompt_action = PromptAction::InteractiveMessage {
+                    message: format!(
+                        "Permission denied writing {}. Retry with `{}`?",
+                        self.buffer_display_name(buffer_id),
+                        command
+                    )
+                    .into(),
+                    on_input: self.context.link.callback(move |retry| {
+                        Message::PostInteractionSaveElevated(buffer_id, retry)
+                    }),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::SavePermissionDenied(buffer_id) => {
+                self.context.log(format!(
+                    "Permission denied writing {}",
+                    self.buffer_display_name(buffer_id)
+                ));
+            }
+            Message::PostInteractionSaveElevated(buffer_id, retry) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let (true, Some(buffer)) = (retry, self.buffers.get_mut(buffer_id)) {
+                    buffer.save_elevated();
+                }
+            }
+            Message::SaveBufferAs(buffer_id, path) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let path = path.trim();
+                if !path.is_empty() {
+                    let path = self.context.current_working_dir.join(path);
+                    self.remember_recent_file(&path);
+                    if let Some(buffer) = self.buffers.get_mut(buffer_id) {
+                        buffer.save_as(path);
+                    }
+                }
+            }
+            Message::InsertFilePicker if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::OpenFile {
+                    source: FileSource::Directory,
+                    message: Some("insert file".into()),
+                    directory: None,
+                    recent_files: self.recent_files.clone(),
+                    history: Some(HistoryKind::Path),
+                    on_open: self.context.link.callback(Message::InsertFile),
+                    on_change_height: self.context.link.callback(Message::ChangePromptHeight),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::InsertFile(path) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    let BufferViewId {
+                        buffer_id,
+                        cursor_id,
+                    } = view_id;
+                    let link = self.context.link.clone();
+                    self.context.task_pool.spawn(move |_| {
+                        let text = File::open(&path)
+                            .and_then(|file| Rope::from_reader(BufReader::new(file)));
+                        link.send(Message::FileReadForInsert(buffer_id, cursor_id, path, text))
+                    });
+                }
+            }
+            Message::FileReadForInsert(buffer_id, cursor_id, path, Ok(text)) => {
+                if let Some(buffer) = self.buffers.get_mut(buffer_id) {
+                    if buffer.is_read_only() {
+                        self.context.log("Buffer is read-only");
+                    } else {
+                        let start = buffer.cursor(cursor_id).range().start;
+                        buffer.replace_range(start..start, text);
+                        self.context.log(format!("Inserted {}", path.display()));
+                    }
+                }
+            }
+            Message::FileReadForInsert(_, _, path, Err(error)) => {
+                self.context
+                    .log(format!("Could not read {} ({})", path.display(), error));
+            }
+            Message::WriteRegionPrompt if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let selection = buffer.cursor(view_id.cursor_id).selection();
+                    if selection.len() > 1 {
+                        let text = Rope::from(buffer.edit_tree().slice(selection));
+                        self.prompt_action = PromptAction::Input {
+                            message: "write region to".into(),
+                            on_submit: self
+                                .context
+                                .link
+                                .callback(move |path| Message::WriteRegion(text.clone(), path)),
+                            validator: Some(Validator::path_to_write(
+                                self.context.current_working_dir.clone(),
+                            )),
+                            history: Some(HistoryKind::Path),
+                        };
+                        self.prompt_height = self.prompt_action.initial_height();
+                    } else {
+                        self.context.log("No selection to write");
+                    }
+                }
+            }
+            Message::WriteRegion(text, path) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let path = path.trim();
+                if !path.is_empty() {
+                    let path = self.context.current_working_dir.join(path);
+                    if path.exists() {
+                        self.prompt_action = PromptAction::InteractiveMessage {
+                            message: format!("File {} exists. Overwrite?", path.display()).into(),
+                            on_input: self.context.link.callback(move |overwrite| {
+                                Message::PostInteractionWriteRegion(
+                                    path.clone(),
+                                    text.clone(),
+                                    overwrite,
+                                )
+                            }),
+                        };
+                        self.prompt_height = self.prompt_action.initial_height();
+                    } else {
+                        self.spawn_write_region(path, text);
+                    }
+                }
+            }
+            Message::ExportPrompt if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    self.prompt_action = PromptAction::Input {
+                        message: "export to (.html or ANSI text)".into(),
+                        on_submit: self.context.link.callback(move |path| {
+                            Message::Export(view_id.buffer_id, view_id.cursor_id, path)
+                        }),
+                        validator: Some(Validator::path_to_write(
+                            self.context.current_working_dir.clone(),
+                        )),
+                        history: Some(HistoryKind::Path),
+                    };
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::Export(buffer_id, cursor_id, path) => {
+                let path = path.trim();
+                if path.is_empty() || self.buffers.get(buffer_id).is_none() {
+                    self.prompt_action = PromptAction::None;
+                    self.prompt_height = self.prompt_action.initial_height();
+                } else {
+                    let format = export::Format::from_path(Path::new(path));
+                    let output =
+                        self.export_buffer(BufferViewId::new(buffer_id, cursor_id), format);
+                    self.context
+                        .link
+                        .send(Message::WriteRegion(output.into(), path.to_owned()));
+                }
+            }
+            Message::PostInteractionWriteRegion(path, text, overwrite) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if overwrite {
+                    self.spawn_write_region(path, text);
+                }
+            }
+            Message::RegionWritten(path, Ok(())) => {
+                self.context.log(format!("Wrote {}", path.display()));
+            }
+            Message::RegionWritten(path, Err(error)) => {
+                self.context
+                    .log(format!("Could not write {} ({})", path.display(), error));
+            }
+            Message::DiffBufferWithFile => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    if let Err(error) = self.diff_buffer_with_file(view_id.buffer_id) {
+                        self.context.log(error.to_string());
+                    }
+                }
+            }
+            Message::CompareBuffersPicker if !self.prompt_action.is_interactive() => {
+                self.open_buffer_picker(
+                    "compare with".into(),
+                    false,
+                    self.context
+                        .link
+                        .callback(|entry: BufferEntry| Message::CompareBuffers(entry.id)),
+                );
+            }
+            Message::CompareBuffers(buffer_id) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(left) = self.windows.get_focused() {
+                    self.compare_buffers(left, buffer_id);
+                }
+            }
+            Message::CompareTwoBuffersPicker if !self.prompt_action.is_interactive() => {
+                self.open_buffer_picker(
+                    "compare".into(),
+                    false,
+                    self.context.link.callback(|entry: BufferEntry| {
+                        Message::CompareLeftBufferSelected(entry.id)
+                    }),
+                );
+            }
+            Message::CompareLeftBufferSelected(left) => {
+                self.open_buffer_picker(
+                    format!("compare {} with", self.buffer_display_name(left)).into(),
+                    false,
+                    self.context.link.callback(move |right: BufferEntry| {
+                        Message::CompareTwoBuffers(left, right.id)
+                    }),
+                );
+            }
+            Message::CompareTwoBuffers(left, right) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.focus_on_buffer(left);
+                if let Some(left) = self.windows.get_focused() {
+                    self.compare_buffers(left, right);
+                }
+            }
+            Message::CompareFiles(left, right) => {
+                if let Err(error) = self.compare_files(left, right) {
+                    self.context.log(format!("Could not open file: {}", error));
+                }
+            }
+            Message::CompareMoveToHunk(direction) => match self.comparison.as_mut() {
+                Some(comparison) => {
+                    if comparison.move_to_hunk(&mut self.buffers, direction) {
+                        let (index, _) = comparison.current_hunk().unwrap();
+                        self.context.log(format!(
+                            "Difference {} of {}",
+                            index + 1,
+                            comparison.num_hunks()
+                        ));
+                    } else if direction == Direction::Forward {
+                        self.context.log("No next difference");
+                    } else {
+                        self.context.log("No previous difference");
+                    }
+                }
+                None => self.context.log("Not comparing buffers"),
+            },
+            Message::CompareCopyHunk(direction) => match self.comparison.as_mut() {
+                Some(comparison) => {
+                    if let Err(error) = comparison.copy_hunk(&mut self.buffers, direction) {
+                        self.context.log(error.to_string());
+                    }
+                }
+                None => self.context.log("Not comparing buffers"),
+            },
+            Message::CompareQuit => {
+                if let Some(comparison) = self.comparison.take() {
+                    comparison.clear(&mut self.buffers);
+                    self.release_unused_cursors([comparison.left, comparison.right]);
+                    self.context.log("Done comparing buffers");
+                }
+            }
+            Message::GitMoveToHunk(direction) => match self.focused_head_diff() {
+                Ok((view_id, head_diff)) => {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    let line_index = buffer
+                        .edit_tree()
+                        .char_to_line(buffer.cursor(view_id.cursor_id).range().start);
+                    let num_hunks = head_diff.hunks().len();
+                    match head_diff.next_hunk(line_index, direction) {
+                        Some(index) => {
+                            let line_index = head_diff.hunks()[index].new.start;
+                            buffer.move_cursor_to_line(view_id.cursor_id, line_index, 0);
+                            self.context
+                                .log(format!("Hunk {} of {}", index + 1, num_hunks));
+                        }
+                        None if num_hunks == 0 => self.context.log("No changes since HEAD"),
+                        None if direction == Direction::Forward => self.context.log("No next hunk"),
+                        None => self.context.log("No previous hunk"),
+                    }
+                }
+                Err(error) => self.context.log(error.to_string()),
+            },
+            Message::GitHunksPicker if !self.prompt_action.is_interactive() => {
+                match self.focused_head_diff() {
+                    Ok((_, head_diff)) if head_diff.hunks().is_empty() => {
+                        self.context.log("No changes since HEAD")
+                    }
+                    Ok((view_id, head_diff)) => {
+                        let text = self.buffers.get(view_id.buffer_id).unwrap().edit_tree();
+                        let items: Rc<[String]> = (0..head_diff.hunks().len())
+                            .map(|index| head_diff.describe(text, index))
+                            .collect();
+                        let lines: Rc<[LineIndex]> = head_diff
+                            .hunks()
+                            .iter()
+                            .map(|hunk| hunk.new.start)
+                            .collect();
+                        self.prompt_action = PromptAction::pick(
+                            PickerDefinition::items(
+                                "hunk",
+                                items,
+                                self.context.link.callback(move |index: usize| {
+                                    Message::GitGotoHunk(lines[index])
+                                }),
+                            )
+                            .empty_messages("No matching hunks", "No changes since HEAD"),
+                            self.context.link.callback(Message::ChangePromptHeight),
+                        );
+                        self.prompt_height = self.prompt_action.initial_height();
+                    }
+                    Err(error) => self.context.log(error.to_string()),
+                }
+            }
+            Message::GitGotoHunk(line_index) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    self.buffers
+                        .get_mut(view_id.buffer_id)
+                        .unwrap()
+                        .move_cursor_to_line(view_id.cursor_id, line_index, 0);
+                }
+            }
+            Message::GitRevertHunk => match self.focused_head_diff() {
+                Ok((view_id, head_diff)) => {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    let text = buffer.edit_tree();
+                    let line_index =
+                        text.char_to_line(buffer.cursor(view_id.cursor_id).range().start);
+                    match head_diff.hunk_at_line(line_index) {
+                        _ if buffer.is_read_only() => self.context.log("Buffer is read-only"),
+                        Some(index) => {
+                            let (range, lines) = head_diff.revert_edit(text, index);
+                            buffer.replace_range(range, lines);
+                            let line_index = head_diff.hunks()[index].new.start;
+                            buffer.move_cursor_to_line(view_id.cursor_id, line_index, 0);
+                            self.context.log("Reverted hunk to HEAD");
+                        }
+                        None => self.context.log("No change at the cursor"),
+                    }
+                }
+                Err(error) => self.context.log(error.to_string()),
+            },
+            Message::DescribeBindings => {
+                let buffer_id = self
+                    .buffers
+                    .find_by_name(BINDINGS_BUFFER_NAME)
+                    .unwrap_or_else(|| {
+                        self.buffers
+                            .add_read_only(BINDINGS_BUFFER_NAME, help::describe_bindings())
+                    });
+                self.focus_on_buffer(buffer_id);
+            }
+            Message::ViewMessages => {
+                let buffer_id = self
+                    .buffers
+                    .find_by_name(MESSAGES_BUFFER_NAME)
+                    .unwrap_or_else(|| {
+                        self.buffers
+                            .add_read_only(MESSAGES_BUFFER_NAME, self.message_log.text().into())
+                    });
+                // On the latest message
+                let buffer = self.buffers.get_mut(buffer_id).unwrap();
+                let text = buffer.edit_tree();
+                let latest = text.line_to_char(text.len_lines().saturating_sub(2));
+                buffer.move_cursor_to(CursorId::default(), latest);
+                self.focus_on_buffer(buffer_id);
+            }
+            Message::SetModePicker if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let current_mode = self.buffers.get(view_id.buffer_id).unwrap().mode();
+                    let context = self.context.0;
+                    let modes = iter::once(&*PLAIN_TEXT_MODE).chain(context.modes.iter());
+                    self.prompt_action = PromptAction::pick(
+                        mode_picker(
+                            modes
+                                .map(|mode| ModeEntry {
+                                    mode,
+                                    current: ptr::eq(mode, current_mode),
+                                })
+                                .collect(),
+                            self.context.link.callback(Message::SetMode),
+                        ),
+                        self.context.link.callback(Message::ChangePromptHeight),
+                    );
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::SetMode(ModeEntry { mode, .. }) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    self.buffers
+                        .get_mut(view_id.buffer_id)
+                        .unwrap()
+                        .set_mode(mode);
+                    self.context.log(format!("Mode set to {}", mode.name));
+                }
+            }
+            Message::SetIndentationPrompt if !self.prompt_action.is_interactive() => {
+                let validator = self.windows.get_focused().map(|view_id| {
+                    let current = self.buffers.get(view_id.buffer_id).unwrap().indentation();
+                    Validator::immediate(move |input| {
+                        Validation::from_result(parse_indentation(input, current))
+                    })
+                });
+                self.prompt_action = PromptAction::Input {
+                    message: "indentation (tabs, spaces or width)".into(),
+                    on_submit: self.context.link.callback(Message::SetIndentation),
+                    validator,
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::SetIndentation(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    match parse_indentation(&input, buffer.indentation()) {
+                        Ok(indentation) => buffer.set_indentation(indentation),
+                        Err(error) => self.context.log(error.to_string()),
+                    }
+                }
+            }
+            Message::JumpToRevisionPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "jump to revision (index or label)".into(),
+                    on_submit: self.context.link.callback(Message::JumpToRevision),
+                    validator: None,
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::JumpToRevision(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let input = input.trim().trim_start_matches('#');
+                    match buffer.find_revision(input) {
+                        Some(index) => BufferCursor::new(
+                            view_id.buffer_id,
+                            view_id.cursor_id,
+                            buffer.cursor(view_id.cursor_id).clone(),
+                            self.context.link.clone(),
+                        )
+                        .jump_to_revision(index),
+                        None => self.context.log(format!("No revision `{}`", input)),
+                    }
+                }
+            }
+            Message::LabelRevisionPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "label revision (empty to remove)".into(),
+                    on_submit: self.context.link.callback(Message::LabelRevision),
+                    validator: None,
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::LabelRevision(label) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    let label = label.trim();
+                    self.buffers
+                        .get_mut(view_id.buffer_id)
+                        .unwrap()
+                        .label_revision((!label.is_empty()).then(|| label.to_owned()));
+                }
+            }
+            Message::PluginRequest(plugin_id, request) => {
+                self.handle_plugin_request(plugin_id, request);
+            }
+            Message::PluginExited(plugin_id) => {
+                log::info!("Plugin `{}` exited", self.plugins.name(plugin_id));
+                self.plugins.remove(plugin_id);
+                if self
+                    .pending_plugin_picker
+                    .as_ref()
+                    .map(|picker| picker.plugin_id)
+                    == Some(plugin_id)
+                {
+                    self.pending_plugin_picker = None;
+                    self.prompt_action = PromptAction::None;
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::PluginItemSelected(index) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(picker) = self.pending_plugin_picker.take() {
+                    self.plugins.respond(
+                        picker.plugin_id,
+                        picker.request_id,
+                        Ok(Value::String(picker.items[index].clone())),
+                    );
+                }
+            }
+            Message::OpenLinkAtPoint => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let text = buffer.edit_tree();
+                    let char_index = buffer.cursor(view_id.cursor_id).range().start;
+                    let line_index = text.char_to_line(char_index);
+                    let column = char_index - text.line_to_char(line_index);
+                    match find_links(text.line(line_index))
+                        .into_iter()
+                        .find(|link| link.range.contains(&column))
+                    {
+                        Some(link) => self.open_link(link.target),
+                        None => self.context.log("No link at point"),
+                    }
+                }
+            }
+            Message::FindFileAtPoint => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    self.find_file_at_point(view_id);
+                }
+            }
+            Message::LinksPicker if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let text = self.buffers.get(view_id.buffer_id).unwrap().edit_tree();
+                    let (items, targets): (Vec<_>, Vec<_>) = text
+                        .lines()
+                        .enumerate()
+                        .flat_map(|(line_index, line)| {
+                            find_links(line).into_iter().map(move |link| {
+                                (format!("{}: {}", line_index + 1, link.text()), link.target)
+                            })
+                        })
+                        .unzip();
+                    if items.is_empty() {
+                        self.context.log("No links in buffer");
+                    } else {
+                        let targets: Rc<[LinkTarget]> = targets.into();
+                        self.prompt_action = PromptAction::pick(
+                            PickerDefinition::items(
+                                "link",
+                                items.into(),
+                                self.context.link.callback(move |index: usize| {
+                                    Message::OpenLink(targets[index].clone())
+                                }),
+                            ),
+                            self.context.link.callback(Message::ChangePromptHeight),
+                        );
+                        self.prompt_height = self.prompt_action.initial_height();
+                    }
+                }
+            }
+            Message::OpenLink(target) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.open_link(target);
+            }
+            Message::RunScriptCommandPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "script command".into(),
+                    on_submit: self.context.link.callback(Message::RunScriptCommand),
+                    validator: None,
+                    history: Some(HistoryKind::Command),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::RunScriptCommand(command) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(expression) = command.trim().strip_prefix('=') {
+                    self.update(Message::Calc(expression.into()));
+                } else if let Err(error) = self.run_script_command(command.trim()) {
+                    self.context.log(error.to_string());
+                }
+            }
+            Message::DescribeKeyPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::ReadKeys {
+                    message: "Describe key:".into(),
+                    keys: Vec::new(),
+                    on_key: self.context.link.callback(Message::DescribeKey),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::DescribeKey(key) => {
+                if let PromptAction::ReadKeys { message, keys, .. } = &mut self.prompt_action {
+                    keys.push(key);
+                    match help::describe_key(keys) {
+                        Some(description) => {
+                            self.prompt_action = PromptAction::Log {
+                                message: description,
+                            };
+                            self.prompt_height = self.prompt_action.initial_height();
+                        }
+                        None => {
+                            *message =
+                                format!("Describe key: {}", KeySequenceSlice::new(keys, true))
+                                    .into();
+                        }
+                    }
+                }
+            }
+            Message::DescribeChar => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let text = buffer.edit_tree();
+                    let range = buffer.cursor(view_id.cursor_id).range();
+                    let highlights = buffer
+                        .mode()
+                        .grammar()
+                        .and_then(|grammar| grammar.ok())
+                        .and_then(|grammar| grammar.highlights.as_ref());
+                    let scopes = match (highlights, buffer.parse_tree()) {
+                        (Some(highlights), Some(tree)) if !range.is_empty() => {
+                            scopes_at(highlights, tree, text, text.char_to_byte(range.start))
+                        }
+                        _ => Vec::new(),
+                    };
+                    self.prompt_action = PromptAction::Log {
+                        message: help::describe_char(text, range, &scopes),
+                    };
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::InsertCharPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "insert char (hex code point)".into(),
+                    on_submit: self.context.link.callback(Message::InsertChar),
+                    validator: Some(Validator::immediate(|input| {
+                        match help::parse_code_point(input) {
+                            Some(character) => Validation::Hint(format!("{:?}", character).into()),
+                            None => Validation::Invalid("invalid code point".into()),
+                        }
+                    })),
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::InsertChar(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match help::parse_code_point(&input) {
+                    Some(character) => self.insert_char_at_cursor(character),
+                    None => self
+                        .context
+                        .log(format!("Invalid code point `{}`", input.trim())),
+                }
+            }
+            Message::InsertCharacterPicker(set) if !self.prompt_action.is_interactive() => {
+                let entries = characters(set);
+                self.prompt_action = PromptAction::pick(
+                    PickerDefinition::items(
+                        match set {
+                            CharacterSet::Emoji => "insert emoji",
+                            CharacterSet::All => "insert symbol",
+                        },
+                        entries.iter().map(CharacterEntry::label).collect(),
+                        self.context.link.callback(move |index: usize| {
+                            Message::InsertCharacter(entries[index].character)
+                        }),
+                    ),
+                    self.context.link.callback(Message::ChangePromptHeight),
+                );
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::InsertCharacter(character) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.insert_char_at_cursor(character);
+            }
+            Message::CalcPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "calc".into(),
+                    on_submit: self.context.link.callback(Message::Calc),
+                    validator: Some(Validator::immediate(calc_validation)),
+                    history: Some(HistoryKind::Calc),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::Calc(expression) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match calc::evaluate(&expression) {
+                    Ok(value) => {
+                        self.context
+                            .log(format!("{} = {}", expression.trim(), value.describe()))
+                    }
+                    Err(error) => self.context.log(error.to_string()),
+                }
+            }
+            Message::NumberLinesPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "number lines (start step width)".into(),
+                    on_submit: self.context.link.callback(Message::NumberLines),
+                    validator: Some(Validator::immediate(|input| {
+                        Validation::from_result(NumberSequence::parse(input))
+                    })),
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::NumberLines(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match (NumberSequence::parse(&input), self.windows.get_focused()) {
+                    (Ok(sequence), Some(view_id)) => {
+                        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                        if buffer.is_read_only() {
+                            self.context.log("Buffer is read-only");
+                        } else {
+                            let text = buffer.edit_tree();
+                            let selection = buffer.cursor(view_id.cursor_id).selection();
+                            let lines = selected_lines(text, selection.clone());
+                            let column = selection.start - text.line_to_char(lines.start);
+                            let (range, numbered) = sequence.insert_at_column(text, lines, column);
+                            buffer.replace_range(range, numbered);
+                        }
+                    }
+                    (Err(error), _) => self.context.log(error.to_string()),
+                    (_, None) => {}
+                }
+            }
+            Message::AlignPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "align on (delimiter or /regex/)".into(),
+                    on_submit: self.context.link.callback(Message::Align),
+                    validator: Some(Validator::immediate(|input| {
+                        Validation::from_result(align::parse_delimiter(input))
+                    })),
+                    history: Some(HistoryKind::Align),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::Align(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match (align::parse_delimiter(&input), self.windows.get_focused()) {
+                    (Ok(delimiter), Some(view_id)) => {
+                        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                        if buffer.is_read_only() {
+                            self.context.log("Buffer is read-only");
+                        } else {
+                            let text = buffer.edit_tree();
+                            let selection = buffer.cursor(view_id.cursor_id).selection();
+                            let (range, aligned) = align::align(
+                                text,
+                                selected_lines(text, selection),
+                                &delimiter,
+                                buffer.indentation().tab_width(),
+                            );
+                            if aligned != text.slice(range.clone()) {
+                                buffer.replace_range(range, aligned);
+                            }
+                        }
+                    }
+                    (Err(error), _) => self.context.log(error.to_string()),
+                    (_, None) => {}
+                }
+            }
+            Message::FillParagraph => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    if buffer.is_read_only() {
+                        self.context.log("Buffer is read-only");
+                    } else {
+                        let text = buffer.edit_tree();
+                        let cursor = buffer.cursor(view_id.cursor_id).range().start;
+                        let filled = fill::fill_paragraph(
+                            text,
+                            text.char_to_line(cursor),
+                            self.context.config.fill_column,
+                            buffer
+                                .mode()
+                                .comment
+                                .as_ref()
+                                .map(|comment| comment.token.as_str()),
+                            buffer.indentation().tab_width(),
+                        );
+                        match filled {
+                            Some((range, filled)) if filled != text.slice(range.clone()) => {
+                                buffer.replace_range(range, filled);
+                            }
+                            Some(_) => {}
+                            None => self.context.log("No paragraph to fill here"),
+                        }
+                    }
+                }
+            }
+            Message::ToggleAutoFill => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                    let auto_fill = !buffer.auto_fill();
+                    buffer.set_auto_fill(auto_fill);
+                    self.context.log(if auto_fill {
+                        format!(
+                            "Auto fill enabled at column {}",
+                            self.context.config.fill_column
+                        )
+                    } else {
+                        "Auto fill disabled".to_owned()
+                    });
+                }
+            }
+            Message::ReplacePrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "replace (string or /regex/)".into(),
+                    on_submit: self.context.link.callback(Message::ReplaceWithPrompt),
+                    validator: Some(Validator::immediate(pattern_validation)),
+                    history: Some(HistoryKind::Search),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::ReplaceWithPrompt(pattern) => {
+                self.prompt_action = PromptAction::Input {
+                    message: format!("replace {} with", pattern.trim()).into(),
+                    on_submit: self.context.link.callback(move |replacement| {
+                        Message::Replace(pattern.clone(), replacement)
+                    }),
+                    validator: None,
+                    history: None,
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::Replace(pattern, replacement) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.set_last_search(pattern.clone(), self.context.config.case_replace);
+                let pattern = search::Pattern::parse(&pattern, self.context.config.case_replace);
+                match (pattern, self.windows.get_focused()) {
+                    (Ok(pattern), Some(view_id)) => {
+                        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                        if buffer.is_read_only() {
+                            self.context.log("Buffer is read-only");
+                        } else {
+                            let range = selection_or_visible_range(buffer, view_id.cursor_id);
+                            let edits =
+                                pattern.replace_all(buffer.edit_tree(), range, &replacement);
+                            let num_replaced = edits.len();
+                            match buffer.apply_edits(edits) {
+                                Ok(()) => self.context.log(format!(
+                                    "Replaced {}",
+                                    stats::count(num_replaced, "occurrence")
+                                )),
+                                Err(error) => self.context.log(error.to_string()),
+                            }
+                        }
+                    }
+                    (Err(error), _) => self.context.log(error.to_string()),
+                    (_, None) => {}
+                }
+            }
+            Message::OccurPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "occur (string or /regex/)".into(),
+                    on_submit: self.context.link.callback(Message::Occur),
+                    validator: Some(Validator::immediate(pattern_validation)),
+                    history: Some(HistoryKind::Search),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::Occur(input) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                self.set_last_search(input.clone(), true);
+                match (
+                    search::Pattern::parse(&input, true),
+                    self.windows.get_focused(),
+                ) {
+                    (Ok(pattern), Some(view_id)) => self.occur(view_id, pattern, &input),
+                    (Err(error), _) => self.context.log(error.to_string()),
+                    (_, None) => {}
+                }
+            }
+            Message::HighlightPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::IncrementalInput {
+                    message: "highlight (string or /regex/)".into(),
+                    on_change: self.context.link.callback(Message::HighlightPreview),
+                    on_submit: self.context.link.callback(Message::HighlightColourPicker),
+                    validator: Some(Validator::immediate(pattern_validation)),
+                    history: Some(HistoryKind::Search),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::HighlightPreview(input) => {
+                let pattern = search::Pattern::parse(&input, true).ok();
+                self.highlights.set_preview(input, pattern);
+            }
+            Message::HighlightColourPicker(input) => match search::Pattern::parse(&input, true) {
+                Ok(_) => {
+                    self.prompt_action = PromptAction::pick(
+                        highlights::colour_picker(
+                            self.highlights.next_colour_index(),
+                            self.context.link.callback(move |colour_index| {
+                                Message::Highlight(input.clone(), colour_index)
+                            }),
+                        ),
+                        self.context.link.callback(Message::ChangePromptHeight),
+                    );
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+                Err(error) => {
+                    self.prompt_action = PromptAction::None;
+                    self.prompt_height = self.prompt_action.initial_height();
+                    self.highlights.clear_preview();
+                    self.context.log(error.to_string());
+                }
+            },
+            Message::Highlight(input, colour_index) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match search::Pattern::parse(&input, true) {
+                    Ok(pattern) => {
+                        let (name, colour) = HIGHLIGHT_COLOURS[colour_index];
+                        self.context
+                            .log(format!("Highlighted `{}` in {}", input.trim(), name));
+                        self.highlights.add(input, pattern, colour);
+                    }
+                    Err(error) => {
+                        self.highlights.clear_preview();
+                        self.context.log(error.to_string());
+                    }
+                }
+            }
+            Message::UnhighlightPicker if !self.prompt_action.is_interactive() => {
+                let inputs: Rc<[String]> = self.highlights.inputs().map(String::from).collect();
+                if inputs.is_empty() {
+                    self.context.log("No highlighted patterns");
+                } else {
+                    self.prompt_action = PromptAction::pick(
+                        PickerDefinition::items(
+                            "unhighlight",
+                            inputs,
+                            self.context.link.callback(Message::Unhighlight),
+                        ),
+                        self.context.link.callback(Message::ChangePromptHeight),
+                    );
+                    self.prompt_height = self.prompt_action.initial_height();
+                }
+            }
+            Message::Unhighlight(index) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(input) = self.highlights.remove(index) {
+                    self.context
+                        .log(format!("Unhighlighted `{}`", input.trim()));
+                }
+            }
+            Message::OccurGoto => {
+                let location = match (self.occur.as_ref(), self.windows.get_focused()) {
+                    (Some(occur), Some(view_id)) if view_id.buffer_id == occur.results => {
+                        let results = self.buffers.get(occur.results).unwrap();
+                        let line_index = results
+                            .edit_tree()
+                            .char_to_line(results.cursor(view_id.cursor_id).range().start);
+                        Some((occur.source, occur.location(&self.buffers, line_index)))
+                    }
+                    _ => None,
+                };
+                match location {
+                    Some((source, Some(char_index))) => {
+                        self.buffers
+                            .get_mut(source.buffer_id)
+                            .unwrap()
+                            .move_cursor_to(source.cursor_id, char_index);
+                        if !self.windows.focus(source) {
+                            let replaced = self.windows.get_focused();
+                            self.windows.set_focused(source);
+                            self.release_unused_cursors(replaced);
+                        }
+                    }
+                    Some((_, None)) => {}
+                    None => self.context.log("Buffer is read-only"),
+                }
+            }
+            Message::Stats => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let cursor = buffer.cursor(view_id.cursor_id);
+                    let scope = if cursor.selection() != cursor.range() {
+                        "Selection"
+                    } else {
+                        "Buffer"
+                    };
+                    let range = selection_or_visible_range(buffer, view_id.cursor_id);
+                    let text = buffer.edit_tree();
+                    let mut message =
+                        format!("{}: {}", scope, TextStats::of(text.slice(range.clone())));
+                    // Count the matches of the last search too
+                    if let Some(last_search) = self.last_search.as_ref() {
+                        let case_replace = self.context.config.case_replace;
+                        if let Ok(pattern) = search::Pattern::parse(last_search, case_replace) {
+                            message.push_str(&format!(
+                                ", {} of `{}`",
+                                stats::count(pattern.count_matches(text, range), "occurrence"),
+                                last_search.trim()
+                            ));
+                        }
+                    }
+                    self.context.log(message);
+                }
+            }
+            Message::CalcInsertPrompt if !self.prompt_action.is_interactive() => {
+                self.prompt_action = PromptAction::Input {
+                    message: "calc (insert)".into(),
+                    on_submit: self.context.link.callback(Message::CalcInsert),
+                    validator: Some(Validator::immediate(calc_validation)),
+                    history: Some(HistoryKind::Calc),
+                };
+                self.prompt_height = self.prompt_action.initial_height();
+            }
+            Message::CalcInsert(expression) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match (calc::evaluate(&expression), self.windows.get_focused()) {
+                    (Ok(value), Some(view_id)) => {
+                        let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
+                        if buffer.is_read_only() {
+                            self.context.log("Buffer is read-only");
+                        } else {
+                            let start = buffer.cursor(view_id.cursor_id).range().start;
+                            buffer.replace_range(start..start, Rope::from(value.to_string()));
+                        }
+                    }
+                    (Err(error), _) => self.context.log(error.to_string()),
+                    (_, None) => {}
+                }
+            }
+            Message::GotoSymbolPicker if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let tags = buffer
+                        .mode()
+                        .grammar()
+                        .and_then(|grammar| grammar.ok())
+                        .and_then(|grammar| grammar.tags.as_ref());
+                    match (tags, buffer.parse_tree()) {
+                        (Some(tags), Some(parse_tree)) => {
+                            let entries = extract_symbols(tags, parse_tree, buffer.edit_tree())
+                                .into_iter()
+                                .map(|symbol| SymbolEntry { symbol, path: None })
+                                .collect();
+                            self.open_symbol_picker(entries);
+                        }
+                        (None, _) => self.context.log(format!(
+                            "No symbol query available for {} mode",
+                            buffer.mode().name
+                        )),
+                        (_, None) => self.context.log("Buffer has not been parsed yet"),
+                    }
+                }
+            }
+            Message::GotoDefinition if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let char_index = buffer.cursor(view_id.cursor_id).range().start;
+                    match identifier_at(buffer.edit_tree(), char_index) {
+                        Some(name) => self.goto_definition(name),
+                        None => self.context.log("No identifier under the cursor"),
+                    }
+                }
+            }
+            Message::CompleteWord if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let cursor = buffer.cursor(view_id.cursor_id).range().start;
+                    let start = word_start(buffer.edit_tree(), cursor);
+                    if start == cursor {
+                        self.context.log("No word before the cursor to complete");
+                    } else {
+                        let prefix = buffer.edit_tree().slice(start..cursor).to_string();
+                        let tags = buffer
+                            .mode()
+                            .grammar()
+                            .and_then(|grammar| grammar.ok())
+                            .and_then(|grammar| grammar.tags.as_ref());
+                        let symbols = match (tags, buffer.parse_tree()) {
+                            (Some(tags), Some(parse_tree)) => {
+                                extract_symbols(tags, parse_tree, buffer.edit_tree())
+                            }
+                            _ => Vec::new(),
+                        };
+                        let candidates =
+                            word_candidates(&self.buffers, view_id, &prefix, cursor, &symbols);
+                        self.open_completion(view_id, start, is_word_char, candidates);
+                    }
+                }
+            }
+            Message::CompletePath if !self.prompt_action.is_interactive() => {
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    let cursor = buffer.cursor(view_id.cursor_id).range().start;
+                    let (start, directory) = path_before(buffer.edit_tree(), cursor);
+                    let base = buffer
+                        .file_path()
+                        .and_then(|path| path.parent())
+                        .unwrap_or(&self.context.current_working_dir);
+                    let show_hidden = start < cursor && buffer.edit_tree().char(start) == '.';
+                    let candidates = path_candidates(&directory, base, show_hidden);
+                    self.open_completion(view_id, start, is_file_name_char, candidates);
+                }
+            }
+            Message::SelectCompletion(offset) => {
+                if let Some(completion) = self.completion.as_mut() {
+                    completion.select(offset);
+                }
+            }
+            Message::AcceptCompletion => self.accept_completion(),
+            Message::JumpBack => match self.jump_back.pop() {
+                Some(JumpLocation {
+                    buffer_id,
+                    file_path,
+                    char_index,
+                }) => {
+                    let opened = if self.buffers.get(buffer_id).is_some() {
+                        self.focus_on_buffer(buffer_id);
+                        Ok(true)
+                    } else if let Some(file_path) = file_path {
+                        self.open_file(file_path).map(|_| true)
+                    } else {
+                        Ok(false)
+                    };
+                    match (opened, self.windows.get_focused()) {
+                        (Ok(true), Some(view_id)) => self
+                            .buffers
+                            .get_mut(view_id.buffer_id)
+                            .unwrap()
+                            .move_cursor_to(view_id.cursor_id, char_index),
+                        (Ok(_), _) => self.context.log("The buffer was killed"),
+                        (Err(error), _) => {
+                            self.context.log(format!("Could not open file: {}", error))
                         }
+                    }
+                }
+                None => self.context.log("No location to jump back to"),
+            },
+            Message::RenameSymbolPrompt if !self.prompt_action.is_interactive() => {
+                let view_id = match self.windows.get_focused() {
+                    Some(view_id) => view_id,
+                    None => return ShouldRender::No,
+                };
+                match self.local_variable_at(view_id) {
+                    Ok(occurrences) => {
+                        let name = self
+                            .buffers
+                            .get(view_id.buffer_id)
+                            .unwrap()
+                            .edit_tree()
+                            .slice(occurrences[0].clone())
+                            .to_string();
+                        let BufferViewId {
+                            buffer_id,
+                            cursor_id,
+                        } = view_id;
+                        self.prompt_action = PromptAction::Input {
+                            message: format!("rename {} to", name).into(),
+                            on_submit: self.context.link.callback(move |new_name| {
+                                Message::RenameSymbol(buffer_id, cursor_id, new_name)
+                            }),
+                            validator: None,
+                            history: None,
+                        };
+                        self.prompt_height = self.prompt_action.initial_height();
+                    }
+                    Err(error) => self.context.log(error.to_string()),
+                }
+            }
+            Message::RenameSymbol(buffer_id, cursor_id, new_name) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                let view_id = BufferViewId::new(buffer_id, cursor_id);
+                match self.rename_local_variable(view_id, new_name.trim()) {
+                    Ok(1) => self.context.log("Renamed 1 occurrence"),
+                    Ok(count) => self.context.log(format!("Renamed {} occurrences", count)),
+                    Err(error) => self.context.log(error.to_string()),
+                }
+            }
+            Message::GotoProjectSymbolPicker if !self.prompt_action.is_interactive() => {
+                match self.symbol_index.as_ref() {
+                    Some(index) => {
+                        let mut entries: Vec<_> = index
+                            .symbols()
+                            .map(|(path, symbol)| SymbolEntry {
+                                symbol: symbol.clone(),
+                                path: Some(path.to_path_buf()),
+                            })
+                            .collect();
+                        entries.sort_by(|lhs, rhs| {
+                            (&lhs.path, lhs.symbol.char_index)
+                                .cmp(&(&rhs.path, rhs.symbol.char_index))
+                        });
+                        self.open_symbol_picker(entries.into());
+                    }
+                    None => {
+                        self.pick_symbol_when_indexed = true;
+                        self.context.log("Indexing project symbols...");
+                    }
+                }
+                // Pick up any changes made since the index was last refreshed
+                self.refresh_symbol_index();
+            }
+            Message::SymbolIndexDone(task_id, index) if self.symbol_index_task == Some(task_id) => {
+                self.symbol_index_task = None;
+                let num_symbols = index.num_symbols();
+                self.symbol_index = Some(index);
+                if let Some(name) = self.definition_when_indexed.take() {
+                    self.goto_definition(name);
+                }
+                if std::mem::take(&mut self.pick_symbol_when_indexed) {
+                    if num_symbols == 0 {
+                        self.context.log("No symbols found in project");
+                    } else {
+                        self.context.link.send(Message::GotoProjectSymbolPicker);
+                    }
+                }
+            }
+            Message::GotoSymbol(SymbolEntry { symbol, path }) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                if let Some(view_id) = self.windows.get_focused() {
+                    let buffer = self.buffers.get(view_id.buffer_id).unwrap();
+                    if self.jump_back.len() == MAX_JUMP_BACK_LOCATIONS {
+                        self.jump_back.remove(0);
+                    }
+                    self.jump_back.push(JumpLocation {
+                        buffer_id: view_id.buffer_id,
+                        file_path: buffer.file_path().cloned(),
+                        char_index: buffer.cursor(view_id.cursor_id).range().start,
                     });
                 }
+                let opened = match path {
+                    Some(path) => self.open_file(path).map_err(|error| {
+                        self.context.log(format!("Could not open file: {}", error))
+                    }),
+                    None => Ok(false),
+                };
+                if let (Ok(_), Some(view_id)) = (opened, self.windows.get_focused()) {
+                    self.buffers
+                        .get_mut(view_id.buffer_id)
+                        .unwrap()
+                        .move_cursor_to(view_id.cursor_id, symbol.char_index);
+                }
             }
             Message::ChangePromptHeight(height) => {
                 self.prompt_height = height;
             }
+            Message::PushPromptHistory(kind, input) => self.push_prompt_history(kind, input),
             Message::FocusNextWindow => self.windows.cycle_focus(CycleFocus::Next),
             Message::FocusPreviousWindow => self.windows.cycle_focus(CycleFocus::Previous),
+            Message::FocusWindow(number) => {
+                let focused = self.windows.focus_number(number);
+                if !focused {
+                    self.context.log(format!("No window {}", number));
+                }
+            }
+            Message::FocusWindowInDirection(direction) => {
+                let focused = self.windows.focus_in_direction(direction);
+                if !focused {
+                    self.context.log(format!(
+                        "No window {}",
+                        match direction {
+                            FocusDirection::Left => "to the left",
+                            FocusDirection::Right => "to the right",
+                            FocusDirection::Up => "above",
+                            FocusDirection::Down => "below",
+                        }
+                    ));
+                }
+            }
             Message::SplitWindow(direction) if !self.buffers.is_empty() => {
                 if let Some(view_id) = self.windows.get_focused() {
                     let buffer = self.buffers.get_mut(view_id.buffer_id).unwrap();
@@ -362,61 +3607,138 @@ impl Component for Editor {
                     );
                 }
             }
+            Message::ArrangeWindows(layout) => self.windows.arrange(layout),
             Message::FullscreenWindow if !self.buffers.is_empty() => {
+                let closed_views: Vec<_> = self.windows.nodes().copied().collect();
                 self.windows.delete_all_except_focused();
+                self.release_unused_cursors(closed_views);
             }
             Message::DeleteWindow if !self.buffers.is_empty() => {
+                let closed_view = self.windows.get_focused();
                 self.windows.delete_focused();
+                self.release_unused_cursors(closed_view);
             }
-            Message::Log(message) if !self.prompt_action.is_interactive() => {
-                self.prompt_action = message
-                    .map(|message| PromptAction::Log { message })
-                    .unwrap_or(PromptAction::None);
-                self.prompt_height = self.prompt_action.initial_height();
-            }
-
-            // Quit zee but prompt to save changed buffers first
-            Message::Quit => {
-                if self
-                    .buffers
-                    .iter()
-                    .any(|buffer| buffer.modified_status() != ModifiedStatus::Unchanged)
-                {
-                    let message = "One or more buffers have changed. Exit anyway?";
-                    self.prompt_action = PromptAction::InteractiveMessage {
-                        on_input: self.context.link.callback(Message::PostInteractionQuit),
-                        message: Cow::from(message),
-                    };
+            Message::PendingKeys(keys) => self.pending_keys = keys,
+            Message::Log(message) => {
+                if let Some(message) = message.as_deref() {
+                    self.record_message(message);
+                }
+                if !self.prompt_action.is_interactive() {
+                    self.prompt_action = message
+                        .map(|message| PromptAction::Log { message })
+                        .unwrap_or(PromptAction::None);
                     self.prompt_height = self.prompt_action.initial_height();
-                } else {
-                    self.context.link.exit();
                 }
             }
 
-            // Quit regardless of the buffer modify status
-            Message::PostInteractionQuit(quit_anyway) => {
-                if quit_anyway {
+            // Quit zee but ask what to do with changed buffers first
+            Message::Quit => {
+                let modified = self.modified_buffers();
+                if modified.is_empty() {
                     self.context.link.exit();
                 } else {
-                    self.prompt_action = PromptAction::None;
+                    let mut names: Vec<_> = modified
+                        .iter()
+                        .take(MAX_LISTED_MODIFIED_BUFFERS)
+                        .map(|buffer_id| self.buffer_display_name(*buffer_id))
+                        .collect();
+                    if modified.len() > MAX_LISTED_MODIFIED_BUFFERS {
+                        names.push(format!(
+                            "and {} more",
+                            modified.len() - MAX_LISTED_MODIFIED_BUFFERS
+                        ));
+                    }
+                    self.prompt_action = PromptAction::Choice {
+                        message: format!("Modified buffers: {}. Exit?", names.join(", ")).into(),
+                        choices: QUIT_CHOICES,
+                        on_choice: self.context.link.callback(Message::QuitChoice),
+                    };
                     self.prompt_height = self.prompt_action.initial_height();
+                    self.pending_quit = Some(PendingQuit {
+                        unasked: modified.into_iter().rev().collect(),
+                        saving: Vec::new(),
+                    });
+                }
+            }
+            Message::QuitChoice(choice) => {
+                self.prompt_action = PromptAction::None;
+                self.prompt_height = self.prompt_action.initial_height();
+                match choice {
+                    // Save all
+                    Some('a') => {
+                        let unasked = self
+                            .pending_quit
+                            .as_mut()
+                            .map(|pending_quit| std::mem::take(&mut pending_quit.unasked))
+                            .unwrap_or_default();
+                        for buffer_id in unasked {
+                            self.save_buffer_before_quit(buffer_id);
+                        }
+                        self.exit_when_saved();
+                    }
+                    // Ask about each buffer
+                    Some('s') => self.ask_to_save_next_buffer(),
+                    // Quit regardless of the buffer modify status
+                    Some('d') => self.context.link.exit(),
+                    _ => {
+                        self.pending_quit = None;
+                        self.context.log("Cancel");
+                    }
+                }
+            }
+            Message::QuitSaveBuffer(buffer_id, save) => {
+                if let Some(pending_quit) = self.pending_quit.as_mut() {
+                    pending_quit.unasked.pop();
+                }
+                if save {
+                    self.save_buffer_before_quit(buffer_id);
                 }
+                self.ask_to_save_next_buffer();
+            }
+            Message::Buffer(message) => {
+                self.buffers.handle_message(message);
+                if let Some(comparison) = self.comparison.as_mut() {
+                    comparison.refresh(&mut self.buffers);
+                }
+                if let Some(occur) = self.occur.as_mut() {
+                    occur.refresh(&mut self.buffers);
+                }
+                self.refresh_completion();
+                self.exit_when_saved();
             }
-            Message::Buffer(message) => self.buffers.handle_message(message),
             _ => {}
         }
+        self.plugins.sync(&self.buffers);
+        self.update_terminal_title();
         ShouldRender::Yes
     }
 
     fn view(&self) -> Layout {
+        if self.is_too_small() {
+            return splash::too_small(
+                self.frame.size,
+                Size::new(MIN_WIDTH, MIN_HEIGHT),
+                &self.themes[self.theme_index].0.splash,
+            );
+        }
+
         let buffers = if self.windows.is_empty() {
-            Splash::item_with_key(
-                FlexBasis::Auto,
-                "splash",
-                SplashProperties {
-                    theme: Cow::Borrowed(&self.themes[self.theme_index].0.splash),
-                },
-            )
+            let theme = Cow::Borrowed(&self.themes[self.theme_index].0.splash);
+            match self.context.config.startup {
+                Startup::Dashboard => Dashboard::item_with_key(
+                    FlexBasis::Auto,
+                    "dashboard",
+                    DashboardProperties {
+                        context: self.context.clone(),
+                        theme,
+                        focused: !self.prompt_action.is_interactive(),
+                        recent_files: self.recent_files.clone(),
+                    },
+                ),
+                Startup::Splash | Startup::Scratch => {
+                    Splash::item_with_key(FlexBasis::Auto, "splash", SplashProperties { theme })
+                }
+            }
         } else {
             Item::auto(self.windows.layout(&mut |Window { id, focused, index }| {
                 let buffer = self.buffers.get(id.buffer_id).unwrap();
@@ -428,9 +3750,17 @@ impl Component for Editor {
                         focused: focused && !self.prompt_action.is_interactive(),
                         frame_id: index.one_based_index(),
                         mode: buffer.mode(),
+                        indentation: buffer.indentation(),
                         repo: buffer.repository().cloned(),
                         content: buffer.edit_tree_handle(),
                         file_path: buffer.file_path().cloned(),
+                        name: buffer.name().map(String::from),
+                        indirect_view: buffer.indirect_view_number(id.cursor_id),
+                        read_only: buffer.is_read_only(),
+                        occur_results: self
+                            .occur
+                            .as_ref()
+                            .map_or(false, |occur| occur.results == id.buffer_id),
                         cursor: BufferCursor::new(
                             id.buffer_id,
                             id.cursor_id,
@@ -438,7 +3768,27 @@ impl Component for Editor {
                             self.context.link.clone(),
                         ),
                         parse_tree: buffer.parse_tree().cloned(),
+                        highlight_cache: buffer.highlight_cache().clone(),
+                        line_metrics: buffer.line_metrics().clone(),
+                        line_scopes: buffer.line_scopes().clone(),
+                        annotations: buffer.annotations().clone(),
+                        folded_lines: buffer.folded_lines(id.cursor_id),
+                        narrowed_lines: buffer.narrowed_lines(id.cursor_id),
+                        completion: self
+                            .completion
+                            .as_ref()
+                            .filter(|completion| completion.view_id == id && focused)
+                            .map(|completion| {
+                                completion.popup(
+                                    self.themes[self.theme_index].0.buffer.completion.clone(),
+                                )
+                            }),
+                        dead_key: buffer.pending_dead_key(id.cursor_id),
+                        search_pattern: self.last_search_pattern.clone(),
+                        highlights: self.highlights.drawn(),
+                        pending_keys: self.pending_keys.clone().filter(|_| focused),
                         modified_status: buffer.modified_status(),
+                        loading: buffer.loading(),
                     },
                 )
             }))
@@ -457,15 +3807,30 @@ impl Component for Editor {
                     context: self.context.clone(),
                     theme: Cow::Borrowed(&self.themes[self.theme_index].0.prompt),
                     action: self.prompt_action.clone(),
+                    history: self.prompt_history.clone(),
+                    on_history: self
+                        .context
+                        .link
+                        .callback(|(kind, input)| Message::PushPromptHistory(kind, input)),
                 },
             ),
         ])
     }
 
     fn bindings(&self, bindings: &mut Bindings<Self>) {
+        let mut bindings = RecordedBindings::new(bindings);
         if bindings.is_empty() {
-            bindings::initialize(bindings);
+            bindings::initialize(&mut bindings);
+            for (keys, command) in self.script_bindings.iter().cloned() {
+                bindings.add(command.clone(), keys, move || {
+                    Message::RunScriptCommand(command.clone())
+                });
+            }
         }
+        // While describing a key, the prompt reads the keys instead of running
+        // the commands bound to them. Keys are ignored while the terminal is
+        // too small to show the buffers they would change.
+        bindings.set_focus(!self.prompt_action.is_read_keys() && !self.is_too_small());
     }
 
     fn notify_binding_queries(&self, queries: &[Option<NamedBindingQuery>], keys: &[Key]) {
@@ -490,6 +3855,14 @@ impl Component for Editor {
             .flatten();
         let merged_all = queries.iter().cloned().reduce(merge_queries).flatten();
 
+        let in_status_bar = self.context.config.pending_keys_in_status_bar;
+        if in_status_bar
+            && self.pending_keys.is_some()
+            && !matches!(merged_all, Some(NamedBindingQuery::PrefixOf(_)))
+        {
+            self.context.link.send(Message::PendingKeys(None));
+        }
+
         match merged_all {
             Some(NamedBindingQuery::Match(_command)) => match merged_without_self {
                 Some(NamedBindingQuery::Match(_command)) if self.prompt_action.is_log() => {
@@ -498,6 +3871,11 @@ impl Component for Editor {
                 }
                 _ => {}
             },
+            Some(NamedBindingQuery::PrefixOf(_)) if in_status_bar => {
+                self.context.link.send(Message::PendingKeys(Some(
+                    KeySequenceSlice::new(keys, true).to_string(),
+                )));
+            }
             Some(NamedBindingQuery::PrefixOf(prefix_of)) => {
                 self.context.log(format!(
                     "{} ({} commands)",
@@ -515,6 +3893,137 @@ impl Component for Editor {
     }
 }
 
+/// The selection if there is one, otherwise the part of the buffer shown in
+/// the window, i.e. the whole buffer unless it's narrowed
+fn selection_or_visible_range(buffer: &Buffer, cursor_id: CursorId) -> Range<CharIndex> {
+    let cursor = buffer.cursor(cursor_id);
+    let text = buffer.edit_tree();
+    if cursor.selection() != cursor.range() {
+        cursor.selection()
+    } else if let Some(lines) = buffer.narrowed_lines(cursor_id) {
+        text.line_to_char(lines.start)..text.line_to_char(lines.end)
+    } else {
+        0..text.len_chars()
+    }
+}
+
+/// The lines spanned by a selection. A selection ending at the start of a
+/// line doesn't include that line.
+fn selected_lines(text: &Rope, selection: Range<CharIndex>) -> Range<LineIndex> {
+    let start = text.char_to_line(selection.start);
+    let end = text.char_to_line(selection.end.min(text.len_chars()));
+    let end = if end > start && text.line_to_char(end) == selection.end {
+        end
+    } else {
+        end + 1
+    };
+    start..end.min(text.len_lines())
+}
+
+/// Points out search patterns which don't parse, e.g. an invalid regex
+fn pattern_validation(input: &str) -> Validation {
+    Validation::from_result(search::Pattern::parse(input, true))
+}
+
+/// The value of a calc expression, shown as it's typed
+fn calc_validation(input: &str) -> Validation {
+    match calc::evaluate(input) {
+        Ok(value) => Validation::Hint(format!("= {}", value.describe()).into()),
+        Err(error) => Validation::Invalid(error.to_string().into()),
+    }
+}
+
+/// Whether the directory a file would be created in exists. Paths without a
+/// parent, e.g. a bare file name, are relative to the working directory.
+fn parent_directory_exists(path: &Path) -> bool {
+    path.parent()
+        .map(|parent| parent.as_os_str().is_empty() || parent.is_dir())
+        .unwrap_or(true)
+}
+
+/// The command opening a URL in the browser, `$BROWSER` if it's set
+fn open_url_command(url: &str) -> Command {
+    let mut command = match std::env::var_os("BROWSER") {
+        Some(browser) => Command::new(browser),
+        None if cfg!(target_os = "macos") => Command::new("open"),
+        None if cfg!(windows) => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        None => Command::new("xdg-open"),
+    };
+    command.arg(url);
+    command
+}
+
+const BINDINGS_BUFFER_NAME: &str = "*Bindings*";
+const MESSAGES_BUFFER_NAME: &str = "*Messages*";
+const DIFF_BUFFER_NAME: &str = "*Diff*";
+const OCCUR_BUFFER_NAME: &str = "*Occur*";
+const SCRATCH_BUFFER_NAME: &str = "*scratch*";
+const STDIN_BUFFER_NAME: &str = "*stdin*";
+
+/// The file name which stands for standard input on the command line
+pub const STDIN_ARG: &str = "-";
+const MAX_LISTED_MODIFIED_BUFFERS: usize = 3;
+const MAX_JUMP_BACK_LOCATIONS: usize = 64;
+
+/// Below this size the windows can't be laid out sensibly, a placeholder is
+/// drawn instead
+const MIN_WIDTH: usize = 20;
+const MIN_HEIGHT: usize = 5;
+const SAVE_CONFLICT_CHOICES: &[Choice] = &[
+    Choice {
+        key: 'o',
+        description: "overwrite",
+    },
+    Choice {
+        key: 'r',
+        description: "reload from disk",
+    },
+    Choice {
+        key: 'w',
+        description: "save to a different file",
+    },
+    Choice {
+        key: 'c',
+        description: "cancel",
+    },
+];
+const FILE_LOCKED_CHOICES: &[Choice] = &[
+    Choice {
+        key: 's',
+        description: "steal the lock",
+    },
+    Choice {
+        key: 'r',
+        description: "read-only",
+    },
+    Choice {
+        key: 'c',
+        description: "cancel",
+    },
+];
+const QUIT_CHOICES: &[Choice] = &[
+    Choice {
+        key: 'a',
+        description: "save all",
+    },
+    Choice {
+        key: 's',
+        description: "choose which to save",
+    },
+    Choice {
+        key: 'd',
+        description: "discard changes",
+    },
+    Choice {
+        key: 'c',
+        description: "cancel",
+    },
+];
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct BufferViewId {
     buffer_id: BufferId,
@@ -0,0 +1,119 @@
+use ropey::Rope;
+use std::ops::Range;
+use tree_sitter::Tree;
+
+use zee_edit::{movement, CharIndex, Cursor, LineIndex, OpaqueDiff};
+use zee_grammar::Mode;
+
+use crate::syntax::symbols::enclosing_definitions;
+
+/// The lines a window is narrowed to. Only these lines are shown and the
+/// cursor can't leave them, so edits stay inside the region.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Narrowed {
+    lines: Range<LineIndex>,
+}
+
+impl Narrowed {
+    /// Narrows to the lines spanned by a range of characters
+    pub fn new(text: &Rope, range: Range<CharIndex>) -> Self {
+        let start = text.char_to_line(range.start.min(text.len_chars()));
+        let end_line = text.char_to_line(range.end.min(text.len_chars()));
+        // A range ending at the start of a line doesn't include that line
+        let end = if end_line > start && text.line_to_char(end_line) == range.end {
+            end_line
+        } else {
+            end_line + 1
+        };
+        Self {
+            lines: start..end.min(text.len_lines()),
+        }
+    }
+
+    /// Narrows to the innermost function or method around a position, or any
+    /// other kind of definition if there isn't one, as found by the mode's
+    /// `tags` query
+    pub fn to_definition(
+        mode: &Mode,
+        tree: &Tree,
+        text: &Rope,
+        char_index: CharIndex,
+    ) -> Option<Self> {
+        let tags = mode
+            .grammar()
+            .and_then(|grammar| grammar.ok())
+            .and_then(|grammar| grammar.tags.as_ref())?;
+        let definitions = enclosing_definitions(tags, tree, text, char_index);
+        let definition = definitions
+            .iter()
+            .rev()
+            .find(|definition| matches!(definition.kind.as_str(), "function" | "method"))
+            .or_else(|| definitions.last())?;
+        let range = text.byte_to_char(definition.byte_range.start)
+            ..text.byte_to_char(definition.byte_range.end);
+        Some(Self::new(text, range))
+    }
+
+    pub fn lines(&self) -> Range<LineIndex> {
+        self.lines.clone()
+    }
+
+    /// Whether the region ends before the end of the text
+    fn ends_before_end_of_text(&self, text: &Rope) -> bool {
+        self.lines.end < text.len_lines()
+    }
+
+    /// The first character the cursor can be at
+    pub fn first_char(&self, text: &Rope) -> CharIndex {
+        text.line_to_char(self.lines.start.min(text.len_lines()))
+    }
+
+    /// The last character the cursor can be at, which is the end of the
+    /// region's last line
+    pub fn last_char(&self, text: &Rope) -> CharIndex {
+        if self.ends_before_end_of_text(text) {
+            text.line_to_char(self.lines.end).saturating_sub(1)
+        } else {
+            text.len_chars()
+        }
+    }
+
+    /// Moves the cursor and the start of its selection inside the region
+    pub fn restrict(&self, text: &Rope, cursor: &mut Cursor) {
+        let (first_char, last_char) = (self.first_char(text), self.last_char(text));
+        let char_index = cursor.range().start;
+        if char_index < first_char {
+            movement::move_to_char(text, cursor, first_char);
+        } else if char_index > last_char {
+            movement::move_to_char(text, cursor, last_char);
+        }
+        cursor.restrict_selection(first_char..=last_char);
+    }
+
+    /// Updates the region after an edit, given the text after the edit and
+    /// how many lines it had before. Edits overlapping the region grow or
+    /// shrink it.
+    pub fn reconcile(&mut self, text: &Rope, num_lines_before: usize, diff: &OpaqueDiff) {
+        let num_lines = text.len_lines();
+        let edit_start = text.char_to_line(diff.char_index.min(text.len_chars()));
+        let new_edit_end =
+            text.char_to_line((diff.char_index + diff.new_char_length).min(text.len_chars()));
+        let old_edit_end = (new_edit_end + num_lines_before).saturating_sub(num_lines);
+        let shift =
+            |line_index: LineIndex| (line_index + num_lines).saturating_sub(num_lines_before);
+        if old_edit_end < self.lines.start {
+            self.lines = shift(self.lines.start)..shift(self.lines.end);
+        } else if edit_start < self.lines.end {
+            let start = self.lines.start.min(edit_start);
+            let end = shift(self.lines.end).max(new_edit_end + 1);
+            self.lines = start..end.min(num_lines);
+        }
+    }
+
+    /// Keeps the region inside the text after it changed in a way we can't
+    /// follow, e.g. undoing an edit
+    pub fn fit(&mut self, text: &Rope) {
+        let end = self.lines.end.min(text.len_lines()).max(1);
+        self.lines = self.lines.start.min(end - 1)..end;
+    }
+}
@@ -0,0 +1,105 @@
+use ropey::Rope;
+
+use zee_edit::{CharIndex, LineIndex};
+
+use super::{buffer::Buffers, search::Pattern, stats::count, BufferId, BufferViewId};
+
+/// The lines of a buffer matching a pattern, listed with their line numbers in
+/// a read-only buffer. The list is kept up to date as the buffer is edited.
+pub struct Occur {
+    pub source: BufferViewId,
+    pub results: BufferId,
+    pattern: Pattern,
+    description: String,
+    /// The line of the source buffer listed on each line of the results, after
+    /// the header
+    lines: Vec<LineIndex>,
+    version: Option<usize>,
+}
+
+impl Occur {
+    /// Lists the matching lines in the results buffer. The description, e.g.
+    /// "`foo` in main.rs", is shown in the header.
+    pub fn new(
+        source: BufferViewId,
+        results: BufferId,
+        pattern: Pattern,
+        description: String,
+        buffers: &mut Buffers,
+    ) -> Self {
+        let mut occur = Self {
+            source,
+            results,
+            pattern,
+            description,
+            lines: Vec::new(),
+            version: None,
+        };
+        occur.refresh(buffers);
+        occur
+    }
+
+    pub fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Finds the matching lines again if the source buffer changed since the
+    /// last refresh
+    pub fn refresh(&mut self, buffers: &mut Buffers) {
+        let source = match buffers.get(self.source.buffer_id) {
+            Some(source) => source,
+            None => return,
+        };
+        let version = source.edit_tree_handle().version();
+        if self.version == Some(version) {
+            return;
+        }
+        self.version = Some(version);
+
+        let text = source.edit_tree();
+        let lines: Vec<(LineIndex, String)> = (0..text.len_lines())
+            .map(|line_index| (line_index, String::from(text.line(line_index))))
+            .filter(|(_, line)| self.pattern.find(line).is_some())
+            .collect();
+        let width = lines
+            .last()
+            .map_or(1, |(line_index, _)| (line_index + 1).to_string().len());
+        let mut results = format!(
+            "{} matching {}\n",
+            count(lines.len(), "line"),
+            self.description
+        );
+        for (line_index, line) in lines.iter() {
+            results.push_str(&format!(
+                "{:>width$}: {}\n",
+                line_index + 1,
+                line.trim_end_matches(['\n', '\r']),
+                width = width
+            ));
+        }
+        self.lines = lines
+            .into_iter()
+            .map(|(line_index, _)| line_index)
+            .collect();
+
+        if let Some(buffer) = buffers.get_mut(self.results) {
+            buffer.replace_generated_text(Rope::from(results));
+        }
+    }
+
+    /// Where the match listed on a line of the results is in the source
+    /// buffer: the start of the first match on its line. `None` for the header.
+    pub fn location(&self, buffers: &Buffers, results_line: LineIndex) -> Option<CharIndex> {
+        let line_index = *self.lines.get(results_line.checked_sub(1)?)?;
+        let text = buffers.get(self.source.buffer_id)?.edit_tree();
+        if line_index >= text.len_lines() {
+            return None;
+        }
+        let line = String::from(text.line(line_index));
+        let column = self
+            .pattern
+            .find(&line)
+            .map_or(0, |range| line[..range.start].chars().count());
+        Some(text.line_to_char(line_index) + column)
+    }
+}
@@ -0,0 +1,362 @@
+//! Plugins are external programs started with the editor, which talk to it
+//! using JSON-RPC 2.0 messages over their standard input and output, one
+//! message per line.
+//!
+//! After starting a plugin, the editor sends it an `initialize` notification
+//! with the current working directory. Plugins can then
+//!
+//! - subscribe to buffer events with a `subscribe` notification, e.g.
+//!   `{"events": ["opened", "changed", "saved", "closed"]}`. The editor then
+//!   sends `buffer/opened`, `buffer/changed`, `buffer/saved` and
+//!   `buffer/closed` notifications with the buffer's id, path and version.
+//! - get the text of a buffer with a `getText` request, e.g. `{"buffer": 0}`
+//! - insert text at the cursor of the focused buffer with `insertText`, e.g.
+//!   `{"text": "hello"}`
+//! - show a message in the status area with `setStatus`, e.g.
+//!   `{"message": "hello"}`
+//! - annotate ranges of a buffer's text with `setAnnotations`, e.g.
+//!   `{"buffer": 0, "version": 3, "annotations": [{"start": 4, "end": 9,
+//!   "severity": "warning", "message": "misspelled"}]}`, where `start` and
+//!   `end` are char offsets into that version of the text and the severity is
+//!   one of `error`, `warning`, `info` or `hint`. The annotations replace
+//!   those the plugin set before, `clearAnnotations`, e.g. `{"buffer": 0}`,
+//!   removes them.
+//! - ask the user to pick from a list with an `openPicker` request, e.g.
+//!   `{"title": "colour", "items": ["red", "blue"]}`. The result is the item
+//!   selected, or `null` if the user cancelled.
+//!
+//! Reading and writing block for as long as a plugin is running, so each
+//! plugin has its own IO threads rather than occupying the task pool.
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+use zi::ComponentLink;
+
+use super::{
+    annotations::Severity,
+    buffer::{BufferId, Buffers, ModifiedStatus},
+    Editor, Message,
+};
+use crate::config::PluginConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PluginId(usize);
+
+/// A request or notification received from a plugin. Notifications don't
+/// have an `id` and don't expect a response.
+#[derive(Debug)]
+pub struct PluginRequest {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Deserialize)]
+pub struct GetTextParams {
+    pub buffer: BufferId,
+}
+
+#[derive(Deserialize)]
+pub struct SetAnnotationsParams {
+    pub buffer: BufferId,
+    pub version: usize,
+    pub annotations: Vec<AnnotationParams>,
+}
+
+#[derive(Deserialize)]
+pub struct AnnotationParams {
+    pub start: usize,
+    pub end: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct ClearAnnotationsParams {
+    pub buffer: BufferId,
+}
+
+#[derive(Deserialize)]
+pub struct InsertTextParams {
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetStatusParams {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct OpenPickerParams {
+    pub title: String,
+    pub items: Vec<String>,
+}
+
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, String> {
+    serde_json::from_value(params).map_err(|error| format!("Invalid params: {}", error))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Event {
+    Opened,
+    Changed,
+    Saved,
+    Closed,
+}
+
+struct Plugin {
+    name: String,
+    sender: Sender<Value>,
+    subscriptions: HashSet<Event>,
+}
+
+#[derive(Clone, Copy)]
+struct BufferState {
+    version: usize,
+    modified_status: ModifiedStatus,
+}
+
+pub struct PluginHost {
+    plugins: HashMap<PluginId, Plugin>,
+    buffers: HashMap<BufferId, BufferState>,
+}
+
+impl PluginHost {
+    /// Starts the configured plugins. Returns the host and a message for each
+    /// plugin which could not be started.
+    pub fn start(
+        configs: &[PluginConfig],
+        current_working_dir: &Path,
+        link: &ComponentLink<Editor>,
+    ) -> (Self, Vec<String>) {
+        let mut plugins = HashMap::new();
+        let mut errors = Vec::new();
+        for (index, config) in configs.iter().enumerate() {
+            let plugin_id = PluginId(index);
+            match Plugin::start(plugin_id, config, current_working_dir, link.clone()) {
+                Ok(plugin) => {
+                    plugins.insert(plugin_id, plugin);
+                }
+                Err(error) => errors.push(format!(
+                    "Could not start plugin `{}` ({})",
+                    config.name, error
+                )),
+            }
+        }
+        let host = Self {
+            plugins,
+            buffers: HashMap::new(),
+        };
+        host.notify_all(
+            "initialize",
+            json!({ "cwd": current_working_dir.to_string_lossy() }),
+        );
+        (host, errors)
+    }
+
+    pub fn name(&self, plugin_id: PluginId) -> &str {
+        self.plugins
+            .get(&plugin_id)
+            .map_or("<exited>", |plugin| plugin.name.as_str())
+    }
+
+    /// Forgets a plugin after its output closed
+    pub fn remove(&mut self, plugin_id: PluginId) {
+        self.plugins.remove(&plugin_id);
+    }
+
+    /// Updates the events a plugin is sent
+    pub fn subscribe(&mut self, plugin_id: PluginId, params: Value) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct Params {
+            events: HashSet<Event>,
+        }
+        let Params { events } = parse_params(params)?;
+        if let Some(plugin) = self.plugins.get_mut(&plugin_id) {
+            plugin.subscriptions = events;
+        }
+        Ok(())
+    }
+
+    pub fn respond(&self, plugin_id: PluginId, id: Value, result: Result<Value, String>) {
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": INVALID_PARAMS, "message": message },
+            }),
+        };
+        self.send(plugin_id, response);
+    }
+
+    pub fn respond_method_not_found(&self, plugin_id: PluginId, id: Value, method: &str) {
+        self.send(
+            plugin_id,
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {
+                    "code": METHOD_NOT_FOUND,
+                    "message": format!("Unknown method `{}`", method),
+                },
+            }),
+        );
+    }
+
+    /// Compares the buffers with how they were when last called and sends the
+    /// differences as events to the plugins subscribed to them
+    pub fn sync(&mut self, buffers: &Buffers) {
+        if self.plugins.is_empty() {
+            return;
+        }
+
+        let mut previous = std::mem::take(&mut self.buffers);
+        for buffer in buffers.iter() {
+            let state = BufferState {
+                version: buffer.edit_tree_handle().version(),
+                modified_status: buffer.modified_status(),
+            };
+            let params = || {
+                json!({
+                    "buffer": buffer.id(),
+                    "path": buffer.file_path().map(|path| path.to_string_lossy()),
+                    "version": state.version,
+                })
+            };
+            match previous.remove(&buffer.id()) {
+                None => self.notify_subscribed(Event::Opened, params()),
+                Some(previous) => {
+                    if previous.version != state.version {
+                        self.notify_subscribed(Event::Changed, params());
+                    }
+                    if previous.modified_status == ModifiedStatus::Saving
+                        && state.modified_status == ModifiedStatus::Unchanged
+                    {
+                        self.notify_subscribed(Event::Saved, params());
+                    }
+                }
+            }
+            self.buffers.insert(buffer.id(), state);
+        }
+        for buffer_id in previous.into_keys() {
+            self.notify_subscribed(Event::Closed, json!({ "buffer": buffer_id }));
+        }
+    }
+
+    fn notify_subscribed(&self, event: Event, params: Value) {
+        let method = match event {
+            Event::Opened => "buffer/opened",
+            Event::Changed => "buffer/changed",
+            Event::Saved => "buffer/saved",
+            Event::Closed => "buffer/closed",
+        };
+        for (plugin_id, plugin) in self.plugins.iter() {
+            if plugin.subscriptions.contains(&event) {
+                self.send(*plugin_id, notification(method, params.clone()));
+            }
+        }
+    }
+
+    fn notify_all(&self, method: &str, params: Value) {
+        for plugin_id in self.plugins.keys() {
+            self.send(*plugin_id, notification(method, params.clone()));
+        }
+    }
+
+    fn send(&self, plugin_id: PluginId, message: Value) {
+        if let Some(plugin) = self.plugins.get(&plugin_id) {
+            // The writer thread stops when the plugin exits, in which case
+            // the reader thread lets the editor know
+            plugin.sender.send(message).ok();
+        }
+    }
+}
+
+impl Plugin {
+    fn start(
+        plugin_id: PluginId,
+        config: &PluginConfig,
+        current_working_dir: &Path,
+        link: ComponentLink<Editor>,
+    ) -> io::Result<Self> {
+        let (program, arguments) = config.command.split_first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "The plugin command is empty")
+        })?;
+        let mut child = Command::new(program)
+            .args(arguments)
+            .current_dir(current_working_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let (sender, receiver) = mpsc::channel::<Value>();
+        thread::Builder::new()
+            .name(format!("plugin-{}-writer", config.name))
+            .spawn(move || {
+                for message in receiver {
+                    if writeln!(stdin, "{}", message)
+                        .and_then(|()| stdin.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })?;
+        thread::Builder::new()
+            .name(format!("plugin-{}-reader", config.name))
+            .spawn(move || {
+                for line in stdout.lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match parse_request(&line) {
+                        Some(request) => link.send(Message::PluginRequest(plugin_id, request)),
+                        None => log::warn!("Invalid message from plugin: {}", line),
+                    }
+                }
+                child.wait().ok();
+                link.send(Message::PluginExited(plugin_id));
+            })?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            sender,
+            subscriptions: HashSet::new(),
+        })
+    }
+}
+
+fn parse_request(line: &str) -> Option<PluginRequest> {
+    let mut message: Value = serde_json::from_str(line).ok()?;
+    let method = message.get("method")?.as_str()?.to_owned();
+    Some(PluginRequest {
+        id: message.get_mut("id").map(Value::take),
+        method,
+        params: message.get_mut("params").map_or(Value::Null, Value::take),
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
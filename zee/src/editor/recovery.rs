@@ -0,0 +1,56 @@
+//! Saving the unsaved changes of buffers when zee crashes. Each buffer visiting
+//! a file is written next to it as `#name#`, like Emacs' auto-save files, and
+//! the files written are listed after the panic message.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use super::buffer::{Buffers, ModifiedStatus};
+
+/// Writes the changed buffers visiting local files to recovery files. Errors
+/// are ignored, we're already crashing.
+pub fn write_recovery_files(buffers: &Buffers) {
+    for buffer in buffers.iter() {
+        let file_path = match buffer.file_path() {
+            Some(file_path)
+                if buffer.remote().is_none()
+                    && buffer.modified_status() != ModifiedStatus::Unchanged =>
+            {
+                file_path
+            }
+            _ => continue,
+        };
+        let recovery_path = match recovery_path(file_path) {
+            Some(recovery_path) => recovery_path,
+            None => continue,
+        };
+        let written = File::create(&recovery_path)
+            .and_then(|file| buffer.edit_tree().write_to(BufWriter::new(file)));
+        match written {
+            Ok(()) => RECOVERY_FILES.lock().push(recovery_path),
+            Err(error) => log::error!(
+                "Could not write recovery file `{}`: {}",
+                recovery_path.display(),
+                error
+            ),
+        }
+    }
+}
+
+/// The recovery files written while crashing, to tell the user where their
+/// changes went
+pub fn written_recovery_files() -> Vec<PathBuf> {
+    RECOVERY_FILES.lock().clone()
+}
+
+fn recovery_path(file_path: &Path) -> Option<PathBuf> {
+    let file_name = file_path.file_name()?.to_string_lossy();
+    Some(file_path.with_file_name(format!("#{}#", file_name)))
+}
+
+static RECOVERY_FILES: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(Default::default);
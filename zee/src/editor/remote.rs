@@ -0,0 +1,104 @@
+use ropey::Rope;
+use std::{
+    env, fmt,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// A file on another machine, written like scp does as `[user@]host:path`.
+/// Remote files are downloaded and uploaded with `ssh`, reusing a shared
+/// connection to each host for a while after the last transfer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemotePath {
+    pub host: String,
+    pub path: String,
+}
+
+impl RemotePath {
+    /// Parses `[user@]host:path`. Like scp, a colon after a slash is part of
+    /// a local path, as is a single letter before the colon (a Windows drive).
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (host, path) = spec.split_once(':')?;
+        let is_drive_letter = host.len() == 1 && host.chars().all(|c| c.is_ascii_alphabetic());
+        if host.is_empty() || host.contains('/') || is_drive_letter || path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: host.into(),
+            path: path.into(),
+        })
+    }
+
+    /// Downloads the file's contents. A file which doesn't exist yet is empty.
+    pub fn download(&self) -> io::Result<Rope> {
+        let path = shell_quote(&self.path);
+        let output = self
+            .ssh(&format!("if [ -e {0} ]; then cat -- {0}; fi", path))
+            .stdin(Stdio::null())
+            .output()?;
+        self.check_status(&output)?;
+        let text = String::from_utf8(output.stdout)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(text.into())
+    }
+
+    /// Replaces the file's contents
+    pub fn upload(&self, text: &Rope) -> io::Result<()> {
+        let mut child = self
+            .ssh(&format!("cat > {}", shell_quote(&self.path)))
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let written = text.write_to(&mut stdin).and_then(|()| stdin.flush());
+        drop(stdin);
+        self.check_status(&child.wait_with_output()?)?;
+        written
+    }
+
+    fn ssh(&self, remote_command: &str) -> Command {
+        let control_path = env::temp_dir().join("zee-ssh-%C");
+        let mut command = Command::new("ssh");
+        command
+            // Zee is drawing to the terminal, so ssh can't ask for passwords
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", "ControlMaster=auto"])
+            .arg("-o")
+            .arg(format!("ControlPath={}", control_path.display()))
+            .args(["-o", "ControlPersist=60"])
+            .args(["--", &self.host, remote_command])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    fn check_status(&self, output: &std::process::Output) -> io::Result<()> {
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "Could not transfer {} ({}): {}",
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ))
+    }
+}
+
+impl fmt::Display for RemotePath {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}:{}", self.host, self.path)
+    }
+}
+
+/// Quotes a path for the remote shell. A leading `~/` is left unquoted so it
+/// still refers to the home directory.
+fn shell_quote(path: &str) -> String {
+    let (prefix, path) = match path.strip_prefix("~/") {
+        Some(relative) => ("~/", relative),
+        None => ("", path),
+    };
+    format!("{}'{}'", prefix, path.replace('\'', r"'\''"))
+}
@@ -0,0 +1,170 @@
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+use std::ops::Range;
+
+use zee_edit::{batch::TextEdit, CharIndex, LineIndex};
+
+use crate::error::{Context, Result};
+
+/// What to search for, parsed from input which is either a regex between
+/// slashes, e.g. `/fo+/`, or otherwise a literal string
+pub struct Pattern {
+    regex: Regex,
+    literal: bool,
+    fold_case: bool,
+}
+
+impl Pattern {
+    /// With `fold_case`, a pattern without uppercase letters matches text in
+    /// any case, e.g. `foo` matches `Foo` and `FOO` too
+    pub fn parse(input: &str, fold_case: bool) -> Result<Self> {
+        let (pattern, literal) = match input
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Some(pattern) => (pattern.to_owned(), false),
+            None => (regex::escape(input), true),
+        };
+        if pattern.is_empty() {
+            anyhow::bail!("Nothing to search for");
+        }
+        let fold_case = fold_case && !input.chars().any(char::is_uppercase);
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(fold_case)
+            .build()
+            .with_context(|| format!("Invalid regex `{}`", pattern))?;
+        Ok(Self {
+            regex,
+            literal,
+            fold_case,
+        })
+    }
+
+    /// Where the first match in a string is, as a range of bytes
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        self.regex
+            .find(haystack)
+            .map(|matched| matched.start()..matched.end())
+    }
+
+    /// Where every match in a string is, as ranges of bytes
+    pub fn find_all<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.regex
+            .find_iter(haystack)
+            .map(|matched| matched.start()..matched.end())
+    }
+
+    /// The lines with at least one match, in order
+    pub fn matching_lines(&self, text: &Rope) -> Vec<LineIndex> {
+        let haystack = String::from(text);
+        let mut lines: Vec<_> = self
+            .regex
+            .find_iter(&haystack)
+            .map(|matched| text.byte_to_line(matched.start()))
+            .collect();
+        lines.dedup();
+        lines
+    }
+
+    pub fn count_matches(&self, text: &Rope, range: Range<CharIndex>) -> usize {
+        self.regex
+            .find_iter(&String::from(text.slice(range)))
+            .count()
+    }
+
+    /// The edits replacing every match in `range` of the text. In a regex
+    /// replacement, `$1` or `${name}` stand for the text of a capture group.
+    ///
+    /// If the pattern folds case, each replacement follows the case of the
+    /// text it replaces, like Emacs' `case-replace`: it is upper cased for an
+    /// all caps match and its words are capitalized for a capitalized match.
+    /// Lower and mixed case matches get the replacement as typed.
+    pub fn replace_all(
+        &self,
+        text: &Rope,
+        range: Range<CharIndex>,
+        replacement: &str,
+    ) -> Vec<TextEdit> {
+        let haystack = String::from(text.slice(range.clone()));
+        let start_byte = text.char_to_byte(range.start);
+        self.regex
+            .captures_iter(&haystack)
+            .map(|captures| {
+                let matched = captures.get(0).unwrap();
+                let mut new_text = String::new();
+                if self.literal {
+                    new_text.push_str(replacement);
+                } else {
+                    captures.expand(replacement, &mut new_text);
+                }
+                if self.fold_case {
+                    new_text = CasePattern::of(matched.as_str()).apply(&new_text);
+                }
+                TextEdit::new(
+                    text.byte_to_char(start_byte + matched.start())
+                        ..text.byte_to_char(start_byte + matched.end()),
+                    new_text,
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CasePattern {
+    /// Every letter is upper case, e.g. `FOO_BAR`
+    Upper,
+    /// The first letter of every word is upper case, the rest lower case,
+    /// e.g. `Foo Bar`. A single upper case letter counts as capitalized.
+    Capitalized,
+    /// Lower case, mixed case or no letters at all
+    Other,
+}
+
+impl CasePattern {
+    fn of(text: &str) -> Self {
+        let mut num_upper = 0;
+        let mut num_lower = 0;
+        let mut capitalized = true;
+        let mut previous = None;
+        for character in text.chars() {
+            let starts_word = !previous.map_or(false, char::is_alphanumeric);
+            if character.is_uppercase() {
+                num_upper += 1;
+                capitalized &= starts_word;
+            } else if character.is_lowercase() {
+                num_lower += 1;
+                capitalized &= !starts_word;
+            }
+            previous = Some(character);
+        }
+
+        if num_upper > 1 && num_lower == 0 {
+            CasePattern::Upper
+        } else if num_upper > 0 && capitalized {
+            CasePattern::Capitalized
+        } else {
+            CasePattern::Other
+        }
+    }
+
+    fn apply(self, text: &str) -> String {
+        match self {
+            CasePattern::Upper => text.to_uppercase(),
+            CasePattern::Capitalized => {
+                let mut capitalized = String::with_capacity(text.len());
+                let mut previous = None;
+                for character in text.chars() {
+                    if previous.map_or(false, char::is_alphanumeric) {
+                        capitalized.push(character);
+                    } else {
+                        capitalized.extend(character.to_uppercase());
+                    }
+                    previous = Some(character);
+                }
+                capitalized
+            }
+            CasePattern::Other => text.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::anyhow;
+use ropey::Rope;
+use std::ops::Range;
+
+use zee_edit::{CharIndex, LineIndex};
+
+use crate::error::Result;
+
+/// The numbers inserted by `number-lines`, parsed from input such as `1`,
+/// `0 10` or `1 1 3`, i.e. the start, the step and how many digits to pad
+/// each number to with zeros
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberSequence {
+    pub start: i64,
+    pub step: i64,
+    pub width: usize,
+}
+
+impl Default for NumberSequence {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            step: 1,
+            width: 0,
+        }
+    }
+}
+
+impl NumberSequence {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut sequence = Self::default();
+        let mut fields = input.split_whitespace();
+        if let Some(start) = fields.next() {
+            sequence.start = start
+                .parse()
+                .map_err(|_| anyhow!("Invalid start `{}`", start))?;
+        }
+        if let Some(step) = fields.next() {
+            sequence.step = step
+                .parse()
+                .map_err(|_| anyhow!("Invalid step `{}`", step))?;
+        }
+        if let Some(width) = fields.next() {
+            sequence.width = width
+                .parse()
+                .map_err(|_| anyhow!("Invalid width `{}`", width))?;
+        }
+        match fields.next() {
+            Some(field) => Err(anyhow!("Unexpected `{}`", field)),
+            None => Ok(sequence),
+        }
+    }
+
+    fn format(&self, index: usize) -> String {
+        let number = self.start + self.step * index as i64;
+        format!("{:0width$}", number, width = self.width)
+    }
+
+    /// The text of `lines` with the sequence inserted at `column` (counted in
+    /// characters), and the range of text it replaces. Lines shorter than the
+    /// column are padded with spaces.
+    pub fn insert_at_column(
+        &self,
+        text: &Rope,
+        lines: Range<LineIndex>,
+        column: usize,
+    ) -> (Range<CharIndex>, Rope) {
+        let range = text.line_to_char(lines.start)..text.line_to_char(lines.end);
+        let mut numbered = Rope::new();
+        for (index, line_index) in lines.enumerate() {
+            let line = String::from(text.line(line_index));
+            let line_length = line.trim_end_matches(['\n', '\r']).chars().count();
+            let split = line
+                .char_indices()
+                .nth(column.min(line_length))
+                .map_or(line.len(), |(byte_index, _)| byte_index);
+            let mut new_line = String::from(&line[..split]);
+            new_line.extend(std::iter::repeat(' ').take(column.saturating_sub(line_length)));
+            new_line.push_str(&self.format(index));
+            new_line.push_str(&line[split..]);
+            numbered.insert(numbered.len_chars(), &new_line);
+        }
+        (range, numbered)
+    }
+}
@@ -0,0 +1,59 @@
+use ropey::RopeSlice;
+use std::fmt;
+
+/// How many lines, words, characters and bytes a text has, like `wc` counts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+impl TextStats {
+    /// A line break at the end of the text doesn't start another line. Words
+    /// are separated by whitespace.
+    pub fn of(text: RopeSlice) -> Self {
+        let mut words = 0;
+        let mut in_word = false;
+        for character in text.chars() {
+            if character.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                words += 1;
+            }
+        }
+
+        let len_chars = text.len_chars();
+        let lines = match text.chars_at(len_chars).prev() {
+            None => 0,
+            Some('\n') => text.len_lines() - 1,
+            Some(_) => text.len_lines(),
+        };
+        Self {
+            lines,
+            words,
+            chars: len_chars,
+            bytes: text.len_bytes(),
+        }
+    }
+}
+
+impl fmt::Display for TextStats {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}, {}, {}, {}",
+            count(self.lines, "line"),
+            count(self.words, "word"),
+            count(self.chars, "character"),
+            count(self.bytes, "byte")
+        )
+    }
+}
+
+/// A number followed by a noun, in the plural unless the number is one
+pub fn count(number: usize, noun: &str) -> String {
+    format!("{} {}{}", number, noun, if number == 1 { "" } else { "s" })
+}
@@ -1,6 +1,8 @@
 use std::{
+    cmp::Ordering,
     fmt::Display,
-    ops::{Add, Rem},
+    iter,
+    ops::{Add, Range, Rem},
 };
 use zi::{Container, FlexDirection, Item, Layout};
 
@@ -9,6 +11,25 @@ pub(super) enum CycleFocus {
     Previous,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// How the files given on the command line are arranged in windows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum WindowLayout {
+    /// Side by side, split by vertical borders
+    Vertical,
+    /// Stacked on top of each other
+    Horizontal,
+    /// In rows of equal length, as many rows as columns or one fewer
+    Grid,
+}
+
 pub(super) struct Window<IdT> {
     pub id: IdT,
     pub focused: bool,
@@ -36,6 +57,13 @@ impl<IdT: Clone + Copy + Display> WindowTree<IdT> {
         self.num_windows = WindowIndex(0);
     }
 
+    pub fn nodes(&self) -> impl Iterator<Item = &IdT> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Window(id) => Some(id),
+            _ => None,
+        })
+    }
+
     pub fn nodes_mut(&mut self) -> impl Iterator<Item = &mut IdT> {
         self.nodes.iter_mut().filter_map(|node| match node {
             Node::Window(id) => Some(id),
@@ -84,6 +112,45 @@ impl<IdT: Clone + Copy + Display> WindowTree<IdT> {
         self.num_windows = WindowIndex(1);
     }
 
+    /// Lays out the windows again, in the same order, and focuses the first
+    pub fn arrange(&mut self, layout: WindowLayout) {
+        let windows: Vec<_> = self
+            .nodes
+            .drain(..)
+            .filter(|node| matches!(node, Node::Window(_)))
+            .collect();
+        self.focused_index = WindowIndex(0);
+        // A container holding a single window is collapsed, as `delete_focused` does
+        let container = |direction, windows: &[Node<IdT>]| -> Vec<Node<IdT>> {
+            if windows.len() > 1 {
+                iter::once(Node::ContainerStart(direction))
+                    .chain(windows.iter().copied())
+                    .chain(iter::once(Node::ContainerEnd))
+                    .collect()
+            } else {
+                windows.to_vec()
+            }
+        };
+        match layout {
+            WindowLayout::Vertical => self.nodes = windows,
+            WindowLayout::Horizontal => self.nodes = container(FlexDirection::Column, &windows),
+            WindowLayout::Grid => {
+                let num_columns = (1..)
+                    .find(|columns| columns * columns >= windows.len())
+                    .unwrap();
+                let rows: Vec<_> = windows
+                    .chunks(num_columns)
+                    .flat_map(|row| container(FlexDirection::Row, row))
+                    .collect();
+                self.nodes = if windows.len() > num_columns {
+                    container(FlexDirection::Column, &rows)
+                } else {
+                    windows
+                };
+            }
+        }
+    }
+
     pub fn insert_at_focused(&mut self, id: IdT, direction: FlexDirection) {
         if self.num_windows == WindowIndex(0) {
             return;
@@ -120,6 +187,52 @@ impl<IdT: Clone + Copy + Display> WindowTree<IdT> {
         }
     }
 
+    /// Focuses the window with the one-based `number` shown in its status bar.
+    /// Returns `false` if there's no such window.
+    pub fn focus_number(&mut self, number: usize) -> bool {
+        let exists = (1..=self.num_windows.0).contains(&number);
+        if exists {
+            self.focused_index = WindowIndex(number - 1);
+        }
+        exists
+    }
+
+    /// Focuses the nearest window in `direction` which is side by side with
+    /// the focused one, preferring the one sharing the longest edge. Returns
+    /// `false` if there's none.
+    pub fn focus_in_direction(&mut self, direction: FocusDirection) -> bool {
+        let areas = self.window_areas();
+        let focused = match areas.get(self.focused_index.0) {
+            Some(focused) => *focused,
+            None => return false,
+        };
+        let nearest = areas
+            .iter()
+            .enumerate()
+            .filter_map(|(window_index, area)| {
+                let (distance, shared_edge) = match direction {
+                    FocusDirection::Left => (focused.left - area.right, focused.rows_shared(area)),
+                    FocusDirection::Right => (area.left - focused.right, focused.rows_shared(area)),
+                    FocusDirection::Up => (focused.top - area.bottom, focused.columns_shared(area)),
+                    FocusDirection::Down => {
+                        (area.top - focused.bottom, focused.columns_shared(area))
+                    }
+                };
+                (distance >= 0.0 && shared_edge > 0.0)
+                    .then(|| (window_index, distance, shared_edge))
+            })
+            .min_by(
+                |(_, distance, shared_edge), (_, other_distance, other_shared_edge)| {
+                    compare(*distance, *other_distance)
+                        .then(compare(*other_shared_edge, *shared_edge))
+                },
+            );
+        if let Some((window_index, _, _)) = nearest {
+            self.focused_index = WindowIndex(window_index);
+        }
+        nearest.is_some()
+    }
+
     pub fn layout(&self, lay_component: &mut impl FnMut(Window<IdT>) -> Layout) -> Layout {
         let mut container_stack = Vec::new();
         let mut container = Container::empty(FlexDirection::Row);
@@ -180,6 +293,32 @@ impl<IdT: Clone + Copy + Display> WindowTree<IdT> {
         }
     }
 
+    /// Focuses the first window showing `id`. Returns `false` if there's none.
+    pub fn focus(&mut self, id: IdT) -> bool
+    where
+        IdT: PartialEq,
+    {
+        let position = self
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Window(current_id) => Some(current_id),
+                _ => None,
+            })
+            .position(|current_id| *current_id == id);
+        if let Some(position) = position {
+            self.focused_index = WindowIndex(position);
+        }
+        position.is_some()
+    }
+
+    /// Where each window is laid out, in window index order
+    fn window_areas(&self) -> Vec<Area> {
+        let mut areas = Vec::with_capacity(self.num_windows.0);
+        split_area(&self.nodes, FlexDirection::Row, Area::FULL, &mut areas);
+        areas
+    }
+
     fn find_focused_window(&self) -> NodeRef {
         self.find_window_node(self.focused_index)
     }
@@ -213,6 +352,111 @@ impl<IdT: Clone + Copy + Display> WindowTree<IdT> {
     }
 }
 
+/// Splits `area` between the windows and containers in `nodes`, equally as
+/// `layout` lays them out with `Item::auto`
+fn split_area<IdT>(
+    nodes: &[Node<IdT>],
+    direction: FlexDirection,
+    area: Area,
+    areas: &mut Vec<Area>,
+) {
+    let children = top_level_nodes(nodes);
+    for (child_index, child) in children.iter().enumerate() {
+        let child_area = area.part(direction, child_index, children.len());
+        match nodes[child.start] {
+            Node::Window(_) => areas.push(child_area),
+            Node::ContainerStart(direction) => split_area(
+                &nodes[child.start + 1..child.end - 1],
+                direction,
+                child_area,
+                areas,
+            ),
+            Node::ContainerEnd => unreachable!("a child can't start with the end of a container"),
+        }
+    }
+}
+
+/// The ranges of the windows and containers in `nodes` which aren't nested in
+/// another container. A container's range spans its start and end nodes.
+fn top_level_nodes<IdT>(nodes: &[Node<IdT>]) -> Vec<Range<usize>> {
+    let mut children = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (node_index, node) in nodes.iter().enumerate() {
+        match node {
+            Node::Window(_) if depth == 0 => children.push(node_index..node_index + 1),
+            Node::Window(_) => {}
+            Node::ContainerStart(_) => {
+                if depth == 0 {
+                    start = node_index;
+                }
+                depth += 1;
+            }
+            Node::ContainerEnd => {
+                depth -= 1;
+                if depth == 0 {
+                    children.push(start..node_index + 1);
+                }
+            }
+        }
+    }
+    children
+}
+
+fn compare(left: f64, right: f64) -> Ordering {
+    left.partial_cmp(&right).unwrap_or(Ordering::Equal)
+}
+
+/// Where a window is laid out, in fractions of the space taken by all windows
+#[derive(Clone, Copy, Debug)]
+struct Area {
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+impl Area {
+    const FULL: Self = Self {
+        left: 0.0,
+        top: 0.0,
+        right: 1.0,
+        bottom: 1.0,
+    };
+
+    /// The `index`-th of `count` equal parts the area is split in along
+    /// `direction`. Adjacent parts share the exact same edge.
+    fn part(&self, direction: FlexDirection, index: usize, count: usize) -> Self {
+        let index = match direction {
+            FlexDirection::Row | FlexDirection::Column => index,
+            FlexDirection::RowReverse | FlexDirection::ColumnReverse => count - 1 - index,
+        };
+        let edge = |start: f64, end: f64, index: usize| {
+            start + (end - start) * index as f64 / count as f64
+        };
+        match direction {
+            FlexDirection::Row | FlexDirection::RowReverse => Self {
+                left: edge(self.left, self.right, index),
+                right: edge(self.left, self.right, index + 1),
+                ..*self
+            },
+            FlexDirection::Column | FlexDirection::ColumnReverse => Self {
+                top: edge(self.top, self.bottom, index),
+                bottom: edge(self.top, self.bottom, index + 1),
+                ..*self
+            },
+        }
+    }
+
+    fn rows_shared(&self, other: &Self) -> f64 {
+        self.bottom.min(other.bottom) - self.top.max(other.top)
+    }
+
+    fn columns_shared(&self, other: &Self) -> f64 {
+        self.right.min(other.right) - self.left.max(other.left)
+    }
+}
+
 struct NodeRef {
     direction: FlexDirection,
     node_index: usize,
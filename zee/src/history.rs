@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Context, Result};
+
+/// The kinds of input prompts keeping a history of their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HistoryKind {
+    /// Paths of files opened or written, e.g. with `save as`
+    Path,
+    /// Strings and regexes searched for, e.g. `occur`
+    Search,
+    /// Script commands run with `run-script-command`
+    Command,
+    /// Expressions evaluated with `calc`
+    Calc,
+    /// Delimiters and patterns lined up with `align`
+    Align,
+}
+
+impl HistoryKind {
+    const ALL: [Self; 5] = [
+        Self::Path,
+        Self::Search,
+        Self::Command,
+        Self::Calc,
+        Self::Align,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Search => "search",
+            Self::Command => "command",
+            Self::Calc => "calc",
+            Self::Align => "align",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+/// The inputs submitted to each kind of prompt, most recent first, persisted
+/// across sessions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PromptHistory {
+    history_path: Option<PathBuf>,
+    max_entries: usize,
+    entries: HashMap<HistoryKind, Vec<String>>,
+}
+
+impl PromptHistory {
+    /// Reads the history file from the configuration directory, keeping at
+    /// most `max_entries` inputs of each kind. A missing or unreadable
+    /// history is not an error, we just start with an empty one.
+    pub fn load(config_dir: Option<&Path>, max_entries: usize) -> Self {
+        let history_path = config_dir.map(|config_dir| config_dir.join(PROMPT_HISTORY_NAME));
+        let mut entries: HashMap<_, Vec<_>> = HashMap::new();
+        let contents = history_path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map_err(|error| log::warn!("Could not read `{}` ({})", path.display(), error))
+                    .ok()
+            })
+            .unwrap_or_default();
+        for line in contents.lines() {
+            let (kind, input) = match line.split_once(' ') {
+                Some((kind, input)) => (HistoryKind::from_name(kind), input),
+                None => continue,
+            };
+            if let Some(kind) = kind {
+                let inputs = entries.entry(kind).or_default();
+                if inputs.len() < max_entries {
+                    inputs.push(input.to_owned());
+                }
+            }
+        }
+        Self {
+            history_path,
+            max_entries,
+            entries,
+        }
+    }
+
+    /// The inputs of a kind of prompt, most recent first
+    pub fn entries(&self, kind: HistoryKind) -> &[String] {
+        self.entries.get(&kind).map_or(&[], Vec::as_slice)
+    }
+
+    /// Moves an input to the front of the history of its kind. Blank inputs
+    /// are not remembered.
+    pub fn push(&mut self, kind: HistoryKind, input: String) {
+        if input.trim().is_empty() || input.contains('\n') {
+            return;
+        }
+        let inputs = self.entries.entry(kind).or_default();
+        inputs.retain(|entry| *entry != input);
+        inputs.insert(0, input);
+        inputs.truncate(self.max_entries);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let history_path = match self.history_path.as_ref() {
+            Some(history_path) => history_path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = history_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Could not create config directory `{}`", parent.display())
+            })?;
+        }
+        let mut contents = String::new();
+        for kind in HistoryKind::ALL {
+            for input in self.entries(kind) {
+                contents.push_str(kind.name());
+                contents.push(' ');
+                contents.push_str(input);
+                contents.push('\n');
+            }
+        }
+        fs::write(history_path, contents).with_context(|| {
+            format!(
+                "Could not write prompt history to `{}`",
+                history_path.display()
+            )
+        })
+    }
+}
+
+const PROMPT_HISTORY_NAME: &str = "prompt_history";
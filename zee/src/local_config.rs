@@ -0,0 +1,165 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use zee_grammar::config::{FilenamePattern, IndentationConfig};
+
+use crate::error::{Context, Result};
+
+/// Settings read from `.zee/config.ron`, overriding the editor's configuration
+/// for the files under the directory containing `.zee`. Nearer files override
+/// the settings of those further up.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename = "Local")]
+pub struct LocalConfig {
+    /// How to indent the files, rather than guessing it from their text
+    #[serde(default)]
+    pub indentation: Option<IndentationConfig>,
+    /// More file name patterns for modes, by the name of the mode, e.g.
+    /// `{"Rust": [Suffix(".rs.in")]}`
+    #[serde(default)]
+    pub mode_patterns: BTreeMap<String, Vec<FilenamePattern>>,
+}
+
+impl LocalConfig {
+    pub fn read(path: &Path, contents: &str) -> Result<Self> {
+        log::info!("Reading local configuration file `{}`", path.display());
+        ron::de::from_str(contents).with_context(|| {
+            format!(
+                "Could not parse local configuration file `{}`",
+                path.display()
+            )
+        })
+    }
+
+    /// Merges the settings of a file nearer to the files being configured
+    pub fn merge(&mut self, nearer: Self) {
+        if nearer.indentation.is_some() {
+            self.indentation = nearer.indentation;
+        }
+        for (mode_name, patterns) in nearer.mode_patterns {
+            self.mode_patterns
+                .entry(mode_name)
+                .or_default()
+                .extend(patterns);
+        }
+    }
+
+    /// The name of the mode whose patterns match a file name, if any
+    pub fn mode_name_for(&self, filename: &Path) -> Option<&str> {
+        self.mode_patterns
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches(filename)))
+            .map(|(mode_name, _)| mode_name.as_str())
+    }
+}
+
+/// The local configuration files applying to a file, furthest first. The
+/// editor's own configuration directory may be called `.zee` too, its
+/// `config.ron` is not a local one.
+pub fn find(file_path: &Path, config_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = file_path
+        .ancestors()
+        .skip(1)
+        .map(|directory| directory.join(LOCAL_CONFIG_DIR))
+        .filter(|local_config_dir| Some(local_config_dir.as_path()) != config_dir)
+        .map(|local_config_dir| local_config_dir.join(LOCAL_CONFIG_NAME))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.reverse();
+    paths
+}
+
+/// The directory whose files a local configuration file applies to
+pub fn scope(config_path: &Path) -> Option<&Path> {
+    config_path.parent()?.parent()
+}
+
+/// Local configuration files the user agreed to use, persisted across
+/// sessions. A file is trusted with the contents it had when it was accepted,
+/// if it changes we ask again.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedConfigs {
+    trusted_path: Option<PathBuf>,
+    /// Each file's path and a hash of its contents
+    configs: Vec<(PathBuf, u64)>,
+}
+
+impl TrustedConfigs {
+    /// Reads the list from the configuration directory. If it's missing or
+    /// unreadable, no file is trusted.
+    pub fn load(config_dir: Option<&Path>) -> Self {
+        let trusted_path = config_dir.map(|config_dir| config_dir.join(TRUSTED_CONFIGS_NAME));
+        let configs = trusted_path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map_err(|error| log::warn!("Could not read `{}` ({})", path.display(), error))
+                    .ok()
+            })
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| {
+                        let (hash, path) = line.split_once(' ')?;
+                        Some((PathBuf::from(path), u64::from_str_radix(hash, 16).ok()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            trusted_path,
+            configs,
+        }
+    }
+
+    pub fn is_trusted(&self, path: &Path, contents: &str) -> bool {
+        let hash = hash_contents(contents);
+        self.configs
+            .iter()
+            .any(|(trusted, trusted_hash)| trusted == path && *trusted_hash == hash)
+    }
+
+    pub fn trust(&mut self, path: PathBuf, contents: &str) {
+        self.configs.retain(|(trusted, _)| *trusted != path);
+        self.configs.push((path, hash_contents(contents)));
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let trusted_path = match self.trusted_path.as_ref() {
+            Some(trusted_path) => trusted_path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = trusted_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Could not create config directory `{}`", parent.display())
+            })?;
+        }
+        let mut contents = String::new();
+        for (path, hash) in self.configs.iter() {
+            contents.push_str(&format!("{:016x} {}\n", hash, path.to_string_lossy()));
+        }
+        fs::write(trusted_path, contents).with_context(|| {
+            format!(
+                "Could not write trusted configurations to `{}`",
+                trusted_path.display()
+            )
+        })
+    }
+}
+
+/// FNV-1a, which unlike the standard library's hasher is stable across
+/// versions of Rust, so hashes written to disk stay valid
+fn hash_contents(contents: &str) -> u64 {
+    contents.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+const LOCAL_CONFIG_DIR: &str = ".zee";
+const LOCAL_CONFIG_NAME: &str = "config.ron";
+const TRUSTED_CONFIGS_NAME: &str = "trusted_local_configs";
@@ -1,23 +1,36 @@
 #![allow(clippy::reversed_empty_ranges)]
 
+mod calc;
+mod characters;
 mod clipboard;
 mod components;
 mod config;
 mod editor;
 mod error;
+mod history;
+mod local_config;
 mod logging;
 mod panicking;
+mod project;
+mod recent;
+mod scripting;
 mod syntax;
 mod task;
+mod terminal;
 mod utils;
 mod versioned;
 
 use clap::Parser;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 use zi::ComponentExt;
 
 use crate::{
-    editor::{Editor, Properties as EditorProperties},
+    editor::{
+        lock::ReleaseLocksOnExit, Editor, Properties as EditorProperties, WindowLayout, STDIN_ARG,
+    },
     error::Result,
     task::TaskPool,
 };
@@ -26,14 +39,39 @@ use crate::{
 #[clap(about, version)]
 struct Args {
     #[clap(name = "file", parse(from_os_str))]
-    /// Open these files to edit after starting zee
+    /// Open these files to edit after starting zee. Remote files are written
+    /// like `user@host:path` and transferred with ssh, `-` reads standard
+    /// input into a read-only buffer.
     files: Vec<PathBuf>,
 
+    #[clap(short = 'O', conflicts_with_all = &["horizontal", "layout"])]
+    /// Open the files side by side, the default
+    vertical: bool,
+
+    #[clap(short = 'o', conflicts_with = "layout")]
+    /// Open the files stacked on top of each other
+    horizontal: bool,
+
+    #[clap(long = "layout", arg_enum, value_name = "LAYOUT")]
+    /// How to arrange the files in windows: side by side (`vertical`), stacked
+    /// (`horizontal`) or in a `grid` of rows
+    layout: Option<WindowLayout>,
+
     #[clap(long = "config-dir", parse(from_os_str))]
     /// Path to the zee configuration directory. Usually ~/.config/zee on
     /// Linux and `%AppData%/zee` on Windows by default.
     config_dir: Option<PathBuf>,
 
+    #[clap(
+        long = "diff",
+        number_of_values = 2,
+        value_names = &["OLD", "NEW"],
+        conflicts_with = "file",
+        parse(from_os_str)
+    )]
+    /// Compare two files side by side, highlighting the lines which differ
+    diff: Option<Vec<PathBuf>>,
+
     #[clap(long = "init")]
     /// Initialises the default configuration directory, if missing. Usually
     /// ~/.config/zee on Linux and %AppData%/zee on Windows by default. This
@@ -84,7 +122,10 @@ fn start_editor() -> Result<()> {
 
     // Finds the editor configuration. If we cannot for any reason, we'll use the
     // default ones to ensure the editor opens in any environment.
-    let editor_config = config::find_editor_config(args.config_dir);
+    let config_dir = args
+        .config_dir
+        .or_else(|| zee_grammar::config::config_dir().ok());
+    let editor_config = config::find_editor_config(config_dir.clone());
 
     // Download and build tree sitter parsers if requested
     if args.build {
@@ -98,19 +139,61 @@ fn start_editor() -> Result<()> {
         return Ok(());
     }
 
-    // Instantiate the editor, open any files specified as arguments and start the UI loop
-    zi_term::incremental()?.run_event_loop(Editor::with(EditorProperties {
+    // Keys are read from the terminal while the text comes from standard input,
+    // which must be piped in
+    let num_stdin_args = args
+        .files
+        .iter()
+        .filter(|path| *path == Path::new(STDIN_ARG))
+        .count();
+    if num_stdin_args > 1 {
+        anyhow::bail!("Standard input (`{}`) can only be opened once", STDIN_ARG);
+    } else if num_stdin_args == 1 && atty::is(atty::Stream::Stdin) {
+        anyhow::bail!(
+            "Standard input is a terminal, pipe text into zee to open it, e.g. `ls | zee -`"
+        );
+    }
+
+    let properties = EditorProperties {
         args_files: args.files,
+        window_layout: match args.layout {
+            Some(layout) => layout,
+            None if args.horizontal => WindowLayout::Horizontal,
+            None => WindowLayout::Vertical,
+        },
+        diff_files: args.diff.map(|mut files| {
+            let right = files.pop().unwrap();
+            (files.pop().unwrap(), right)
+        }),
         current_working_dir: env::current_dir()?,
-        config: editor_config,
+        config_dir: config_dir.clone(),
         task_pool: TaskPool::new()?,
-        clipboard: clipboard::create()?,
-    }))?;
+        clipboard: clipboard::create(editor_config.osc52_clipboard),
+        recent_files: recent::RecentFiles::load(config_dir.as_deref()),
+        prompt_history: history::PromptHistory::load(
+            config_dir.as_deref(),
+            editor_config.prompt_history_size,
+        ),
+        trusted_configs: local_config::TrustedConfigs::load(config_dir.as_deref()),
+        scripts: scripting::create(config_dir.as_deref())?,
+        config: editor_config,
+    };
+
+    // Instantiate the editor, open any files specified as arguments and start
+    // the UI loop. The editor sets the terminal's title, restore it after.
+    terminal::save_title();
+    let result = run_event_loop(properties);
+    terminal::restore_title();
+    result
+}
 
-    Ok(())
+fn run_event_loop(properties: EditorProperties) -> Result<()> {
+    Ok(zi_term::incremental()?.run_event_loop(Editor::with(properties))?)
 }
 
 fn main() -> Result<()> {
+    // Lock files are removed however the editor exits, including panics
+    let _release_locks = ReleaseLocksOnExit;
     panicking::print_panic_after_unwind(|| {
         start_editor().map_err(|error| {
             log::error!("Zee exited with: {}", error);
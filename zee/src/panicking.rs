@@ -6,11 +6,15 @@ use std::{
     panic::{PanicInfo, UnwindSafe},
 };
 
+use crate::{editor::recovery, terminal};
+
 pub fn print_panic_after_unwind<F: FnOnce() -> R + UnwindSafe, R>(function: F) -> R {
     std::panic::set_hook(Box::new(save_panic_backtrace_hook));
 
     match std::panic::catch_unwind(function) {
         Err(err) => {
+            // The frontend may not have had the chance to clean up
+            terminal::reset();
             eprint!("Internal zee error -- ");
             eprintln!("this is a bug, please submit an issue at https://github.com/zee-editor/zee");
             PANIC_BACKTRACE.with(|cell| {
@@ -18,6 +22,13 @@ pub fn print_panic_after_unwind<F: FnOnce() -> R + UnwindSafe, R>(function: F) -
                     eprintln!("{:?}", description)
                 }
             });
+            let recovery_files = recovery::written_recovery_files();
+            if !recovery_files.is_empty() {
+                eprintln!("Unsaved changes were written to:");
+                for path in recovery_files {
+                    eprintln!("  {}", path.display());
+                }
+            }
             std::panic::resume_unwind(err);
         }
         Ok(result) => result,
@@ -0,0 +1,115 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use zee_grammar::config::IndentationConfig;
+
+use crate::{
+    error::{Context, Result},
+    local_config::TrustedConfigs,
+    recent::RecentFiles,
+};
+
+/// A directory worked on as a whole, the root of a repository or one marked
+/// with a `.zee` file or directory. Pickers and the symbol index are scoped to
+/// the active project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Project {
+    pub root: PathBuf,
+    pub config: ProjectConfig,
+    /// The project's `project.ron` if the user hasn't trusted it yet, its
+    /// settings are left out until they do
+    pub untrusted_config: Option<PathBuf>,
+}
+
+/// Settings overriding the editor's configuration for the files in a project,
+/// read from `.zee/project.ron`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename = "Project")]
+pub struct ProjectConfig {
+    /// The theme to switch to while the project is active
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// How to indent every file in the project, rather than guessing it from
+    /// the file's text
+    #[serde(default)]
+    pub indentation: Option<IndentationConfig>,
+}
+
+impl Project {
+    /// The project containing a path, if any. Like local configuration files,
+    /// `project.ron` is only read once trusted. An unreadable one is logged and
+    /// the project's settings left at their defaults.
+    pub fn find(path: &Path, trusted_configs: &TrustedConfigs) -> Option<Self> {
+        let root = find_root(path)?.to_path_buf();
+        let config_path = root.join(PROJECT_MARKER).join(PROJECT_CONFIG_NAME);
+        let mut untrusted_config = None;
+        let config = if config_path.is_file() {
+            fs::read_to_string(&config_path)
+                .with_context(|| format!("Could not read project file `{}`", config_path.display()))
+                .and_then(|contents| {
+                    if trusted_configs.is_trusted(&config_path, &contents) {
+                        read_config(&config_path, &contents)
+                    } else {
+                        untrusted_config = Some(config_path.clone());
+                        Ok(ProjectConfig::default())
+                    }
+                })
+                .map_err(|error| log::error!("{:#}", error))
+                .unwrap_or_default()
+        } else {
+            ProjectConfig::default()
+        };
+        Some(Self {
+            root,
+            config,
+            untrusted_config,
+        })
+    }
+
+    /// The name of the project's directory
+    pub fn name(&self) -> String {
+        self.root
+            .file_name()
+            .unwrap_or(self.root.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+}
+
+/// The nearest directory containing a path which is the root of a project
+pub fn find_root(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|directory| {
+        ROOT_MARKERS
+            .iter()
+            .any(|marker| directory.join(marker).exists())
+    })
+}
+
+/// The roots of the projects containing recent files, most recent first
+pub fn recent_projects(recent_files: &RecentFiles) -> Vec<PathBuf> {
+    let mut projects: Vec<PathBuf> = Vec::new();
+    for path in recent_files.iter() {
+        let root = path.parent().and_then(find_root);
+        if let Some(root) = root.filter(|root| !projects.iter().any(|project| project == root)) {
+            projects.push(root.to_path_buf());
+        }
+    }
+    projects
+}
+
+fn read_config(path: &Path, contents: &str) -> Result<ProjectConfig> {
+    log::info!("Reading project file `{}`", path.display());
+    ron::de::from_str(contents)
+        .with_context(|| format!("Could not parse project file `{}`", path.display()))
+}
+
+const PROJECT_MARKER: &str = ".zee";
+const PROJECT_CONFIG_NAME: &str = "project.ron";
+const ROOT_MARKERS: &[&str] = &[PROJECT_MARKER, ".git", ".hg", ".svn"];
@@ -0,0 +1,83 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Context, Result};
+
+/// Files opened in the editor, most recent first, persisted across sessions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecentFiles {
+    history_path: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    /// Reads the history file from the configuration directory. A missing or
+    /// unreadable history is not an error, we just start with an empty one.
+    pub fn load(config_dir: Option<&Path>) -> Self {
+        let history_path = config_dir.map(|config_dir| config_dir.join(RECENT_FILES_NAME));
+        let paths = history_path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map_err(|error| log::warn!("Could not read `{}` ({})", path.display(), error))
+                    .ok()
+            })
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .take(MAX_RECENT_FILES)
+                    .map(PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            history_path,
+            paths,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Moves the file to the front of the history
+    pub fn push(&mut self, path: PathBuf) {
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let history_path = match self.history_path.as_ref() {
+            Some(history_path) => history_path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = history_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Could not create config directory `{}`", parent.display())
+            })?;
+        }
+        let mut contents = String::new();
+        for path in self.paths.iter() {
+            contents.push_str(&path.to_string_lossy());
+            contents.push('\n');
+        }
+        fs::write(history_path, contents).with_context(|| {
+            format!(
+                "Could not write recent files to `{}`",
+                history_path.display()
+            )
+        })
+    }
+}
+
+const RECENT_FILES_NAME: &str = "recent_files";
+const MAX_RECENT_FILES: usize = 256;
@@ -0,0 +1,180 @@
+use std::{path::Path, rc::Rc};
+
+use crate::error::Result;
+
+/// Commands and key bindings defined by the user's init script
+pub trait Scripts {
+    /// The key bindings defined by the script, as pairs of a key sequence
+    /// (e.g. `C-c u`) and the name of the command it runs
+    fn bindings(&self) -> Vec<(String, String)>;
+
+    /// Runs a command defined by the script
+    fn run(&self, command: &str, state: ScriptState) -> Result<ScriptOutput>;
+}
+
+/// The state of the editor passed to a script command. The command operates
+/// on `text`, which is the selection if there is one, or the whole buffer.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+pub struct ScriptState {
+    pub text: String,
+    pub selection: bool,
+    pub file_path: Option<String>,
+    pub mode: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScriptOutput {
+    /// Text to replace the selection (or whole buffer) with, if the command
+    /// returned any
+    pub replacement: Option<String>,
+    /// Messages printed by the command
+    pub messages: Vec<String>,
+}
+
+/// Loads `init.rhai` from the configuration directory, if it exists
+pub fn create(config_dir: Option<&Path>) -> Result<Rc<dyn Scripts>> {
+    let init_script = config_dir.map(|config_dir| config_dir.join(INIT_SCRIPT_NAME));
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "scripting")] {
+            match init_script {
+                Some(init_script) if init_script.exists() => rhai_scripts::create(&init_script),
+                _ => Ok(Rc::new(NoScripts)),
+            }
+        } else {
+            if let Some(init_script) = init_script.filter(|init_script| init_script.exists()) {
+                log::warn!(
+                    "Zee was built without the `scripting` feature, ignoring `{}`",
+                    init_script.display()
+                );
+            }
+            Ok(Rc::new(NoScripts))
+        }
+    }
+}
+
+struct NoScripts;
+
+impl Scripts for NoScripts {
+    fn bindings(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    fn run(&self, command: &str, _state: ScriptState) -> Result<ScriptOutput> {
+        anyhow::bail!("Unknown command `{}`", command)
+    }
+}
+
+const INIT_SCRIPT_NAME: &str = "init.rhai";
+
+#[cfg(feature = "scripting")]
+mod rhai_scripts {
+    use rhai::{Dynamic, Engine, FnPtr, Map, AST};
+    use std::{cell::RefCell, collections::HashMap, mem, path::Path, rc::Rc};
+
+    use super::{ScriptOutput, ScriptState, Scripts};
+    use crate::error::{Context, Result};
+
+    pub(super) fn create(init_script: &Path) -> Result<Rc<dyn Scripts>> {
+        let commands = Rc::new(RefCell::new(HashMap::new()));
+        let bindings = Rc::new(RefCell::new(Vec::new()));
+        let messages = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        {
+            let commands = Rc::clone(&commands);
+            engine.register_fn("command", move |name: &str, function: FnPtr| {
+                commands.borrow_mut().insert(name.to_owned(), function);
+            });
+        }
+        {
+            let bindings = Rc::clone(&bindings);
+            engine.register_fn("bind", move |keys: &str, command: &str| {
+                bindings
+                    .borrow_mut()
+                    .push((keys.to_owned(), command.to_owned()));
+            });
+        }
+        {
+            let messages = Rc::clone(&messages);
+            engine.on_print(move |message| messages.borrow_mut().push(message.to_owned()));
+        }
+
+        let load_error = |error| {
+            anyhow::anyhow!(
+                "Could not load init script `{}`: {}",
+                init_script.display(),
+                error
+            )
+        };
+        let ast = engine
+            .compile_file(init_script.into())
+            .map_err(load_error)?;
+        engine.run_ast(&ast).map_err(load_error)?;
+
+        let bindings = bindings.borrow().clone();
+        Ok(Rc::new(RhaiScripts {
+            engine,
+            ast,
+            commands,
+            bindings,
+            messages,
+        }))
+    }
+
+    struct RhaiScripts {
+        engine: Engine,
+        ast: AST,
+        commands: Rc<RefCell<HashMap<String, FnPtr>>>,
+        bindings: Vec<(String, String)>,
+        messages: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Scripts for RhaiScripts {
+        fn bindings(&self) -> Vec<(String, String)> {
+            self.bindings.clone()
+        }
+
+        fn run(&self, command: &str, state: ScriptState) -> Result<ScriptOutput> {
+            let function = self
+                .commands
+                .borrow()
+                .get(command)
+                .cloned()
+                .with_context(|| format!("Unknown command `{}`", command))?;
+
+            let mut editor = Map::new();
+            editor.insert("text".into(), state.text.into());
+            editor.insert("selection".into(), state.selection.into());
+            editor.insert(
+                "file_path".into(),
+                state.file_path.map_or(Dynamic::UNIT, Dynamic::from),
+            );
+            editor.insert("mode".into(), state.mode.into());
+            editor.insert("line".into(), (state.line as rhai::INT).into());
+            editor.insert("column".into(), (state.column as rhai::INT).into());
+
+            let result = function.call::<Dynamic>(&self.engine, &self.ast, (editor,));
+            let messages = mem::take(&mut *self.messages.borrow_mut());
+            let result =
+                result.map_err(|error| anyhow::anyhow!("`{}` failed: {}", command, error))?;
+            let replacement = if result.is::<()>() {
+                None
+            } else {
+                Some(result.into_string().map_err(|type_name| {
+                    anyhow::anyhow!(
+                        "`{}` returned {}, expected a string or nothing",
+                        command,
+                        type_name
+                    )
+                })?)
+            };
+            Ok(ScriptOutput {
+                replacement,
+                messages,
+            })
+        }
+    }
+}
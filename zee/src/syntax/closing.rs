@@ -0,0 +1,88 @@
+//! Finding the keyword a construct left open expects, e.g. `fi` after an `if`
+//! in a shell script, for languages closing constructs with keywords rather
+//! than braces. Tree-sitter either parses the construct with a missing
+//! closing keyword, or gives up and wraps its tokens in an error node, so
+//! both are looked for around the cursor.
+
+use ropey::Rope;
+use tree_sitter::{Node, Tree};
+
+use zee_edit::{CharIndex, LineIndex};
+use zee_grammar::config::ClosingKeywordsConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closing<'a> {
+    pub keyword: &'a str,
+    /// The line of the opening keyword, whose indentation the closing keyword
+    /// is given
+    pub line: LineIndex,
+}
+
+/// The closing keyword expected by the innermost construct left open before
+/// a position, if any
+pub fn expected_closing<'a>(
+    config: &'a ClosingKeywordsConfig,
+    tree: &Tree,
+    text: &Rope,
+    char_index: CharIndex,
+) -> Option<Closing<'a>> {
+    if !tree.root_node().has_error() {
+        return None;
+    }
+    let byte_index = text.char_to_byte(char_index.min(text.len_chars()));
+    let mut node = tree
+        .root_node()
+        .descendant_for_byte_range(byte_index.saturating_sub(1), byte_index);
+    while let Some(current) = node {
+        if current.has_error() {
+            let mut open = Vec::new();
+            if let Some(closing) = scan_children(config, current, byte_index, &mut open) {
+                return Some(closing);
+            }
+            if let Some(&closing) = open.last() {
+                return Some(closing);
+            }
+        }
+        node = current.parent();
+    }
+    None
+}
+
+/// Goes through the keywords among the children of a node starting before
+/// `byte_index`, keeping the constructs still open in `open`. The tokens of
+/// error nodes are gone through as if they were children of the node. Stops
+/// at a closing keyword the parser found missing.
+fn scan_children<'a>(
+    config: &'a ClosingKeywordsConfig,
+    node: Node,
+    byte_index: usize,
+    open: &mut Vec<Closing<'a>>,
+) -> Option<Closing<'a>> {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.is_missing() {
+            if let Some(keyword) = config.as_closing(child.kind()) {
+                let line = open
+                    .last()
+                    .map_or(node.start_position().row, |opening| opening.line);
+                return Some(Closing { keyword, line });
+            }
+        } else if child.start_byte() >= byte_index {
+            break;
+        } else if child.is_error() {
+            if let Some(closing) = scan_children(config, child, byte_index, open) {
+                return Some(closing);
+            }
+        } else if child.is_named() {
+            continue;
+        } else if let Some(keyword) = config.closing(child.kind()) {
+            open.push(Closing {
+                keyword,
+                line: child.start_position().row,
+            });
+        } else if open.last().map(|opening| opening.keyword) == Some(child.kind()) {
+            open.pop();
+        }
+    }
+    None
+}
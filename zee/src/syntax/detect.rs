@@ -0,0 +1,99 @@
+//! Hints about the language of a file found in its contents, used when
+//! picking a mode in addition to the file name.
+
+use ropey::{Rope, RopeSlice};
+
+/// The mode named by an Emacs modeline on the first line (or the second,
+/// after a shebang), e.g. `-*- mode: python -*-`, or by a Vim modeline in the
+/// first or last few lines, e.g. `vim: set ft=sh:`
+pub fn modeline(text: &Rope) -> Option<String> {
+    let num_lines = text.len_lines();
+    let emacs_mode = (0..num_lines.min(2))
+        .map(|line_index| line_prefix(text.line(line_index)))
+        .find_map(|line| emacs_modeline(&line));
+    if emacs_mode.is_some() {
+        return emacs_mode;
+    }
+
+    let last_lines = (num_lines.saturating_sub(VIM_MODELINES)..num_lines)
+        .filter(|&line_index| line_index >= VIM_MODELINES)
+        .map(|line_index| line_prefix(text.line(line_index)));
+    (0..num_lines.min(VIM_MODELINES))
+        .map(|line_index| line_prefix(text.line(line_index)))
+        .chain(last_lines)
+        .find_map(|line| vim_modeline(&line))
+}
+
+/// The name of the interpreter in a `#!` line, without any version number,
+/// e.g. `python` for `#!/usr/bin/env python3`
+pub fn shebang(text: &Rope) -> Option<String> {
+    if text.len_lines() == 0 {
+        return None;
+    }
+    let line = line_prefix(text.line(0));
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (!interpreter.is_empty()).then(|| interpreter.to_owned())
+}
+
+/// The language of a few well known document formats, recognised by how the
+/// text starts, e.g. an XML prologue
+pub fn sniff(text: &Rope) -> Option<&'static str> {
+    let start: String = text.chars().take(SNIFF_CHARS).collect();
+    let start = start.trim_start_matches('\u{feff}').trim_start();
+    let lowercase = start.to_lowercase();
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        Some("html")
+    } else if start.starts_with("<?xml") {
+        Some("xml")
+    } else if start.starts_with("%YAML") {
+        Some("yaml")
+    } else {
+        None
+    }
+}
+
+fn emacs_modeline(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+    if !variables.contains(':') {
+        // The short form, `-*- python -*-`
+        return Some(variables.trim().to_owned()).filter(|mode| !mode.is_empty());
+    }
+    variables.split(';').find_map(|variable| {
+        let (name, value) = variable.split_once(':')?;
+        (name.trim().eq_ignore_ascii_case("mode")).then(|| value.trim().to_owned())
+    })
+}
+
+fn vim_modeline(line: &str) -> Option<String> {
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let start = line.find(marker)?;
+        let preceded_by_space = line[..start]
+            .chars()
+            .next_back()
+            .map_or(true, char::is_whitespace);
+        preceded_by_space.then(|| &line[start + marker.len()..])
+    })?;
+    options
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .find_map(|option| {
+            let (name, value) = option.split_once('=')?;
+            matches!(name, "ft" | "filetype" | "syn" | "syntax").then(|| value.to_owned())
+        })
+        .filter(|mode| !mode.is_empty())
+}
+
+/// The start of a line, which is all we need to look at for hints. This
+/// avoids copying very long lines, e.g. in minified files.
+fn line_prefix(line: RopeSlice) -> String {
+    line.chars().take(MAX_LINE_CHARS).collect()
+}
+
+const VIM_MODELINES: usize = 5;
+const MAX_LINE_CHARS: usize = 256;
+const SNIFF_CHARS: usize = 64;
@@ -1,12 +1,20 @@
+use ropey::Rope;
+use std::{ops::Range, rc::Rc};
+use tree_sitter::{Query, QueryCursor, Tree};
 use zi::terminal::{Background, Style};
 
-use zee_edit::{CharIndex, Cursor};
+use zee_edit::{ByteIndex, CharIndex, Cursor, LineIndex, OpaqueDiff};
+
+use super::parse::{edit_tree, RopeProvider};
+use crate::utils::LineCache;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Theme {
     pub cursor_focused: Style,
     pub cursor_unfocused: Style,
     pub selection_background: Background,
+    /// The cell after the end of a selected line, where the line break is
+    pub selection_end_of_line: Style,
     pub text: Style,
     pub text_current_line: Style,
     pub code_char: Style,
@@ -23,6 +31,155 @@ pub struct Theme {
     pub code_string: Style,
     pub code_type: Style,
     pub code_variant: Style,
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    pub diff_changed: Style,
+    pub indent_guide: Style,
+    pub annotation_error: Style,
+    pub annotation_warning: Style,
+    pub annotation_info: Style,
+    pub annotation_hint: Style,
+}
+
+/// A highlighting scope applied to whole lines, regardless of the syntax tree,
+/// e.g. to colour the added and removed lines of a diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineScope {
+    pub lines: Range<LineIndex>,
+    pub scope: &'static str,
+}
+
+/// A highlights query capture on a line, with the bytes relative to the start
+/// of the line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeSpan {
+    pub bytes: Range<ByteIndex>,
+    pub scope: &'static str,
+}
+
+/// The highlighting spans of the lines of a buffer which have been drawn, so
+/// they're only matched against the highlights query once, rather than on
+/// every frame.
+///
+/// Edits drop the lines they touch, the lines after them are moved along.
+/// When a new parse tree arrives, only the lines whose syntax changed are
+/// dropped, as found by comparing it with the tree the spans came from.
+pub struct HighlightCache {
+    /// The version of the parse tree the spans were computed from
+    version: Option<usize>,
+    /// That parse tree, edited along with the text since
+    tree: Option<Tree>,
+    lines: LineCache<Rc<[ScopeSpan]>>,
+}
+
+impl HighlightCache {
+    pub fn new(text: &Rope) -> Self {
+        Self {
+            version: None,
+            tree: None,
+            lines: LineCache::new(text),
+        }
+    }
+
+    /// Forgets every line, e.g. after the text was replaced or parsed afresh
+    pub fn clear(&mut self, text: &Rope) {
+        self.version = None;
+        self.tree = None;
+        self.lines.clear(text);
+    }
+
+    /// Updates the cache after an edit, given the text after the edit
+    pub fn reconcile(&mut self, text: &Rope, diff: &OpaqueDiff) {
+        self.lines.reconcile(text, diff);
+        if let Some(tree) = self.tree.as_mut().filter(|_| !diff.is_empty()) {
+            edit_tree(tree, diff);
+        }
+    }
+
+    /// The highlighting spans of each line, querying the lines which aren't
+    /// cached yet in one go
+    pub fn spans(
+        &mut self,
+        query: &'static Query,
+        tree: &Tree,
+        version: usize,
+        text: &Rope,
+        line_indices: &[LineIndex],
+    ) -> Vec<Rc<[ScopeSpan]>> {
+        if self.version != Some(version) {
+            match self.tree.as_ref() {
+                Some(old_tree) => {
+                    for range in old_tree.changed_ranges(tree) {
+                        let end_byte = range.end_byte.min(text.len_bytes());
+                        let start_byte = range.start_byte.min(end_byte);
+                        let lines = text.byte_to_line(start_byte)..=text.byte_to_line(end_byte);
+                        self.lines.remove(lines);
+                    }
+                }
+                None => self.lines.clear(text),
+            }
+            self.version = Some(version);
+            self.tree = Some(tree.clone());
+        }
+
+        let missing = line_indices
+            .iter()
+            .filter(|line_index| !self.lines.contains(**line_index));
+        if let (Some(first), Some(last)) = (missing.clone().min(), missing.max()) {
+            let lines = *first..last + 1;
+            for (line_index, spans) in lines.clone().zip(query_spans(query, tree, text, lines)) {
+                self.lines.insert(line_index, spans.into());
+            }
+        }
+
+        line_indices
+            .iter()
+            .map(|line_index| self.lines.get(*line_index).unwrap().clone())
+            .collect()
+    }
+}
+
+/// Matches the highlights query against a range of lines, splitting the first
+/// capture of each match at line breaks. The spans of a line are in the order
+/// of the matches, which may overlap.
+pub fn query_spans(
+    query: &'static Query,
+    tree: &Tree,
+    text: &Rope,
+    lines: Range<LineIndex>,
+) -> Vec<Vec<ScopeSpan>> {
+    let line_to_byte = |line_index| {
+        text.try_line_to_byte(line_index)
+            .unwrap_or_else(|_| text.len_bytes())
+    };
+    let line_starts: Vec<ByteIndex> = (lines.start..=lines.end).map(line_to_byte).collect();
+    let mut spans = vec![Vec::new(); lines.len()];
+
+    let mut query_cursor = QueryCursor::new();
+    query_cursor.set_byte_range(line_starts[0]..line_starts[lines.len()]);
+    for query_match in query_cursor.matches(query, tree.root_node(), RopeProvider(text.slice(..))) {
+        let capture = match query_match.captures.first() {
+            Some(capture) => capture,
+            None => continue,
+        };
+        let bytes = capture.node.byte_range();
+        let scope = query.capture_names()[usize::try_from(capture.index).unwrap()].as_str();
+        // The first line the capture is on, at least partly
+        let first = line_starts[1..].partition_point(|line_end| *line_end <= bytes.start);
+        for (line_spans, line_bytes) in spans
+            .iter_mut()
+            .zip(line_starts.windows(2))
+            .skip(first)
+            .take_while(|(_, line_bytes)| line_bytes[0] < bytes.end)
+        {
+            line_spans.push(ScopeSpan {
+                bytes: bytes.start.max(line_bytes[0]) - line_bytes[0]
+                    ..bytes.end.min(line_bytes[1]) - line_bytes[0],
+                scope,
+            });
+        }
+    }
+    spans
 }
 
 #[inline]
@@ -35,16 +192,54 @@ pub fn text_style_at_char(
     scope: &str,
     is_error: bool,
 ) -> Style {
+    let style = if is_error {
+        theme.code_invalid
+    } else {
+        scope_style(theme, scope)
+    };
+
+    if char_index == cursor.range().start || cursor.range().contains(&char_index) {
+        let cursor_style = if focused {
+            theme.cursor_focused
+        } else {
+            theme.cursor_unfocused
+        };
+        Style {
+            background: cursor_style.background,
+            foreground: cursor_style.foreground,
+            bold: style.bold,
+            underline: style.underline,
+        }
+    } else {
+        let background = if line_under_cursor && focused {
+            theme.text_current_line.background
+        } else {
+            theme.text.background
+        };
+        Style {
+            background,
+            foreground: style.foreground,
+            bold: style.bold,
+            underline: style.underline,
+        }
+    }
+}
+
+/// The style of text with a highlighting scope, e.g. `keyword.control`
+pub fn scope_style(theme: &Theme, scope: &str) -> Style {
     let starts = |pattern| scope.starts_with(pattern);
 
-    let style = match () {
-        _ if is_error => theme.code_invalid,
+    match () {
         _ if scope.is_empty() => theme.text,
         _ if starts("error") => theme.code_invalid,
         _ if starts("attribute") => theme.code_macro_call,
         _ if starts("comment.block") => theme.code_comment_doc,
         _ if starts("comment") => theme.code_comment,
         _ if starts("constructor") => theme.code_variant,
+        // Diffs
+        _ if starts("diff.plus") => theme.diff_added,
+        _ if starts("diff.minus") => theme.diff_removed,
+        _ if starts("diff.delta") => theme.diff_changed,
         // Constants
         _ if starts("constant.character") => theme.code_char,
         _ if starts("constant.numeric") => theme.code_function_call,
@@ -55,6 +250,8 @@ pub fn text_style_at_char(
         _ if starts("function") => theme.code_function_call,
         _ if starts("keyword.control.import") => theme.code_keyword,
         _ if starts("keyword") => theme.code_keyword,
+        // URLs and paths, see `syntax::links`
+        _ if starts("link") => theme.code_link,
         _ if starts("operator") => theme.code_operator,
         _ if starts("property") => theme.code_function_call,
         _ if starts("punctuation.bracket") => theme.code_operator,
@@ -75,33 +272,30 @@ pub fn text_style_at_char(
         _ if starts("text.uri") => theme.code_operator,
 
         _ => theme.text,
-    };
-
-    if char_index == cursor.range().start || cursor.range().contains(&char_index) {
-        let cursor_style = if focused {
-            theme.cursor_focused
-        } else {
-            theme.cursor_unfocused
-        };
-        Style {
-            background: cursor_style.background,
-            foreground: cursor_style.foreground,
-            bold: style.bold,
-            underline: style.underline,
-        }
-    } else {
-        let background = if cursor.selection().contains(&char_index) {
-            theme.selection_background
-        } else if line_under_cursor && focused {
-            theme.text_current_line.background
-        } else {
-            theme.text.background
-        };
-        Style {
-            background,
-            foreground: style.foreground,
-            bold: style.bold,
-            underline: style.underline,
-        }
     }
 }
+
+/// The names of the highlights query captures around a position, outermost
+/// first, e.g. `["function", "string"]`
+pub fn scopes_at<'query>(
+    query: &'query Query,
+    tree: &Tree,
+    text: &Rope,
+    byte_index: ByteIndex,
+) -> Vec<&'query str> {
+    let mut query_cursor = QueryCursor::new();
+    query_cursor.set_byte_range(byte_index..byte_index + 1);
+    let mut captures: Vec<_> = query_cursor
+        .matches(query, tree.root_node(), RopeProvider(text.slice(..)))
+        .flat_map(|query_match| query_match.captures.iter())
+        .filter(|capture| capture.node.byte_range().contains(&byte_index))
+        .map(|capture| (capture.node.byte_range(), capture.index))
+        .collect();
+    captures.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+    let mut scopes: Vec<&str> = captures
+        .into_iter()
+        .map(|(_, index)| query.capture_names()[index as usize].as_str())
+        .collect();
+    scopes.dedup();
+    scopes
+}
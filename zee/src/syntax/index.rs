@@ -0,0 +1,115 @@
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use ropey::Rope;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tree_sitter::Parser;
+
+use zee_grammar::Mode;
+
+use super::symbols::{extract_symbols, Symbol};
+use crate::error::{Context, Result};
+
+/// The symbols defined in the files of a project, e.g. a git repository
+#[derive(Clone, Debug)]
+pub struct SymbolIndex {
+    root: PathBuf,
+    files: HashMap<PathBuf, IndexedFile>,
+}
+
+#[derive(Clone, Debug)]
+struct IndexedFile {
+    modified: SystemTime,
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolIndex {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn num_symbols(&self) -> usize {
+        self.files.values().map(|file| file.symbols.len()).sum()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = (&Path, &Symbol)> {
+        self.files.iter().flat_map(|(path, file)| {
+            file.symbols
+                .iter()
+                .map(move |symbol| (path.as_path(), symbol))
+        })
+    }
+
+    /// Walks the project and returns an up to date index. Only files which
+    /// changed since they were last indexed are parsed again. Meant to be run
+    /// in the task pool, files are parsed in parallel.
+    pub fn refresh(&self, modes: &'static [Mode]) -> Self {
+        let paths: Vec<_> = WalkBuilder::new(&self.root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map_or(false, |kind| kind.is_file()))
+            .map(|entry| entry.into_path())
+            .take(MAX_FILES_IN_INDEX)
+            .collect();
+
+        let files = paths
+            .into_par_iter()
+            .filter_map(|path| {
+                let mode = modes.iter().find(|mode| mode.matches_by_filename(&path))?;
+                let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+                match self.files.get(&path) {
+                    Some(indexed) if indexed.modified == modified => Some((path, indexed.clone())),
+                    _ => index_file(mode, &path)
+                        .map_err(|error| log::debug!("{}", error))
+                        .ok()
+                        .flatten()
+                        .map(|symbols| (path, IndexedFile { modified, symbols })),
+                }
+            })
+            .collect();
+
+        Self {
+            root: self.root.clone(),
+            files,
+        }
+    }
+}
+
+/// Parses a file and extracts its symbols. Returns `None` if the file's mode
+/// doesn't have a grammar with a `tags` query.
+fn index_file(mode: &Mode, path: &Path) -> Result<Option<Vec<Symbol>>> {
+    let grammar = match mode.grammar().and_then(|grammar| grammar.ok()) {
+        Some(grammar) => grammar,
+        None => return Ok(None),
+    };
+    let tags = match grammar.tags.as_ref() {
+        Some(tags) => tags,
+        None => return Ok(None),
+    };
+
+    let text = Rope::from_reader(BufReader::new(
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?,
+    ))?;
+    let mut parser = Parser::new();
+    parser.set_language(grammar.language)?;
+    let tree = parser
+        .parse_with(
+            &mut |byte_index, _| {
+                let (chunk, chunk_byte_index, _, _) = text.chunk_at_byte(byte_index);
+                &chunk.as_bytes()[byte_index - chunk_byte_index..]
+            },
+            None,
+        )
+        .with_context(|| format!("Could not parse {}", path.display()))?;
+    Ok(Some(extract_symbols(tags, &tree, &text)))
+}
+
+const MAX_FILES_IN_INDEX: usize = 16384;
@@ -0,0 +1,94 @@
+//! URLs and file paths in text, found with regular expressions regardless of
+//! the language of the buffer.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use ropey::RopeSlice;
+use std::{borrow::Cow, ops::Range};
+
+use zee_edit::CharIndex;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkTarget {
+    Url(String),
+    Path(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// Where the link is, relative to the start of the text searched
+    pub range: Range<CharIndex>,
+    pub target: LinkTarget,
+}
+
+impl Link {
+    pub fn text(&self) -> &str {
+        match self.target {
+            LinkTarget::Url(ref url) => url,
+            LinkTarget::Path(ref path) => path,
+        }
+    }
+}
+
+/// Finds the links on a line. Paths must be absolute with at least two
+/// components, or start with `~/`, `./` or `../`, so that operators and
+/// closing tags aren't mistaken for them.
+pub fn find_links(line: RopeSlice) -> Vec<Link> {
+    if line.len_bytes() > MAX_LINE_BYTES {
+        return Vec::new();
+    }
+    let text: Cow<str> = line.into();
+    let char_range =
+        |range: Range<usize>| line.byte_to_char(range.start)..line.byte_to_char(range.end);
+
+    let mut links: Vec<_> = URL
+        .find_iter(&text)
+        .map(|url| {
+            let url = trim_trailing_punctuation(&text[url.range()], url.start());
+            Link {
+                range: char_range(url.clone()),
+                target: LinkTarget::Url(text[url].to_owned()),
+            }
+        })
+        .collect();
+    let num_urls = links.len();
+    for path in PATH
+        .captures_iter(&text)
+        .filter_map(|captures| captures.get(1))
+    {
+        let path = trim_trailing_punctuation(&text[path.range()], path.start());
+        let range = char_range(path.clone());
+        let inside_url = links[..num_urls]
+            .iter()
+            .any(|url| url.range.start < range.end && range.start < url.range.end);
+        if !inside_url {
+            links.push(Link {
+                range,
+                target: LinkTarget::Path(text[path].to_owned()),
+            });
+        }
+    }
+    links.sort_by_key(|link| link.range.start);
+    links
+}
+
+/// The byte range of a match without any punctuation ending a sentence
+/// around it, e.g. the full stop after a URL
+fn trim_trailing_punctuation(matched: &str, start: usize) -> Range<usize> {
+    let trimmed = matched.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    start..start + trimmed.len()
+}
+
+static URL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`()\[\]{}]+|\bmailto:[^\s<>"'`()\[\]{}]+"#)
+        .unwrap()
+});
+
+static PATH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?:^|[\s"'`(\[{<=,:])((?:~|\.\.?)/[\w.~+@%-]+(?:/[\w.~+@%-]+)*/?|/[\w.~+@%-]+(?:/[\w.~+@%-]+)+/?)"#,
+    )
+    .unwrap()
+});
+
+const MAX_LINE_BYTES: usize = 4096;
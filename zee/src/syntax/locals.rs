@@ -0,0 +1,121 @@
+use ropey::Rope;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+use tree_sitter::{Query, QueryCursor, Tree};
+
+use zee_edit::{ByteIndex, CharIndex};
+
+use super::parse::RopeProvider;
+
+/// The occurrences of the local variable at a position, its definition and
+/// every reference to it, as found by a mode's `locals` query. `None` if
+/// there's no local variable at the position, e.g. on a global or a keyword.
+///
+/// Following tree-sitter's conventions, the query captures `@local.scope`,
+/// `@local.definition` and `@local.reference`, or the older `@scope`,
+/// `@definition` and `@reference`. A reference refers to the latest
+/// definition with the same name in the innermost scope which has one, so a
+/// variable shadowed in a nested scope is a different variable.
+pub fn local_occurrences(
+    query: &Query,
+    tree: &Tree,
+    text: &Rope,
+    char_index: CharIndex,
+) -> Option<Vec<Range<ByteIndex>>> {
+    let capture_names = query.capture_names();
+    let mut query_cursor = QueryCursor::new();
+    let mut captures: Vec<(Role, Range<ByteIndex>)> = query_cursor
+        .captures(query, tree.root_node(), RopeProvider(text.slice(..)))
+        .filter_map(|(query_match, index)| {
+            let capture = query_match.captures[index];
+            let role = Role::from_capture_name(&capture_names[capture.index as usize])?;
+            Some((role, capture.node.byte_range()))
+        })
+        .collect();
+    // Scopes come before what they contain, and definitions before the
+    // references captured on the same node, which aren't references
+    captures.sort_by_key(|(role, range)| (range.start, Reverse(range.end), *role));
+    captures.dedup();
+    let definitions: HashSet<Range<ByteIndex>> = captures
+        .iter()
+        .filter(|(role, _)| *role == Role::Definition)
+        .map(|(_, range)| range.clone())
+        .collect();
+
+    // The scopes around the current capture, innermost last, each with the
+    // definitions made in it so far by name. Definitions are numbered in the
+    // order they appear.
+    let mut scopes: Vec<(Range<ByteIndex>, HashMap<String, usize>)> =
+        vec![(0..text.len_bytes(), HashMap::new())];
+    let mut occurrences: Vec<(usize, Range<ByteIndex>)> = Vec::new();
+    let mut num_definitions = 0;
+    for (role, range) in captures {
+        while scopes.len() > 1 && scopes[scopes.len() - 1].0.end <= range.start {
+            scopes.pop();
+        }
+        let name = || text.byte_slice(range.clone()).to_string();
+        match role {
+            Role::Scope => scopes.push((range, HashMap::new())),
+            Role::Definition => {
+                let (_, scope_definitions) = scopes.last_mut().unwrap();
+                scope_definitions.insert(name(), num_definitions);
+                occurrences.push((num_definitions, range));
+                num_definitions += 1;
+            }
+            Role::Reference if !definitions.contains(&range) => {
+                let name = name();
+                let definition = scopes
+                    .iter()
+                    .rev()
+                    .find_map(|(_, scope_definitions)| scope_definitions.get(&name));
+                if let Some(definition) = definition {
+                    occurrences.push((*definition, range));
+                }
+            }
+            Role::Reference => {}
+        }
+    }
+
+    // The cursor may be right after the name, as after typing it
+    let byte_index = text.char_to_byte(char_index);
+    let (definition, _) = occurrences
+        .iter()
+        .find(|(_, range)| range.contains(&byte_index))
+        .or_else(|| {
+            occurrences
+                .iter()
+                .find(|(_, range)| range.end == byte_index)
+        })?;
+    Some(
+        occurrences
+            .iter()
+            .filter(|(other, _)| other == definition)
+            .map(|(_, range)| range.clone())
+            .collect(),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Scope,
+    Definition,
+    Reference,
+}
+
+impl Role {
+    fn from_capture_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("local.").unwrap_or(name);
+        if name == "scope" {
+            Some(Self::Scope)
+        } else if name == "definition" || name.starts_with("definition.") {
+            Some(Self::Definition)
+        } else if name == "reference" {
+            Some(Self::Reference)
+        } else {
+            None
+        }
+    }
+}
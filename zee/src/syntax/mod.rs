@@ -1,4 +1,11 @@
+pub mod closing;
+pub mod detect;
 pub mod highlight;
+pub mod index;
+pub mod links;
+pub mod locals;
 pub mod parse;
+pub mod sexp;
+pub mod symbols;
 
 pub use parse::{ParseTree, ParserPool};
@@ -1,4 +1,4 @@
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
 use std::{
     fmt,
     ops::{Deref, DerefMut},
@@ -8,7 +8,8 @@ use std::{
     },
 };
 use tree_sitter::{
-    InputEdit as TreeSitterInputEdit, Language, Parser, Point as TreeSitterPoint, Tree,
+    InputEdit as TreeSitterInputEdit, Language, Node, Parser, Point as TreeSitterPoint,
+    TextProvider, Tree,
 };
 
 use zee_edit::OpaqueDiff;
@@ -59,11 +60,27 @@ impl DerefMut for ParseTree {
     }
 }
 
+type OnParse = Box<dyn FnOnce(Result<ParserStatus>) + Send + 'static>;
+
+/// A parse requested while another one was running, started when it finishes
+struct QueuedParse {
+    text: Rope,
+    fresh: bool,
+    on_parse: OnParse,
+}
+
+/// Parses the text of a buffer in the background, with at most one parse in
+/// flight. Parses requested in the meantime are coalesced, only the latest text
+/// is parsed once the running parse is done.
 pub struct ParserPool {
     pub tree: Option<ParseTree>,
     language: Language,
     parsers: Vec<CancelableParser>,
     current_parse_task: Option<(TaskId, CancelFlag)>,
+    queued_parse: Option<QueuedParse>,
+    /// The edits made since the current parse task started, which are applied
+    /// to its tree when it's done so it can be reused for the next parse
+    edits_since_spawn: Vec<OpaqueDiff>,
 }
 
 impl ParserPool {
@@ -73,6 +90,8 @@ impl ParserPool {
             parsers: vec![],
             tree: None,
             current_parse_task: None,
+            queued_parse: None,
+            edits_since_spawn: Vec::new(),
         }
     }
 
@@ -87,6 +106,9 @@ impl ParserPool {
         }
     }
 
+    /// Parses the text, incrementally from the current tree unless `fresh`. If
+    /// a parse is already running, the text is queued instead, replacing any
+    /// text queued before it.
     pub fn spawn(
         &mut self,
         task_pool: &TaskPool,
@@ -94,6 +116,27 @@ impl ParserPool {
         fresh: bool,
         on_parse: impl FnOnce(Result<ParserStatus>) + Send + 'static,
     ) {
+        let cancel_flag = match self.current_parse_task.as_ref() {
+            Some((_, cancel_flag)) => cancel_flag,
+            None => return self.spawn_now(task_pool, text, fresh, Box::new(on_parse)),
+        };
+        let fresh = fresh
+            || self
+                .queued_parse
+                .as_ref()
+                .map_or(false, |queued| queued.fresh);
+        // The running parse's tree would be thrown away, so stop it early
+        if fresh {
+            cancel_flag.set();
+        }
+        self.queued_parse = Some(QueuedParse {
+            text,
+            fresh,
+            on_parse: Box::new(on_parse),
+        });
+    }
+
+    fn spawn_now(&mut self, task_pool: &TaskPool, text: Rope, fresh: bool, on_parse: OnParse) {
         let mut parser = self.parsers.pop().unwrap_or_else(|| {
             let mut parser = Parser::new();
             parser
@@ -130,22 +173,30 @@ impl ParserPool {
                 }),
             });
         });
-        if let Some((_, old_cancel_flag)) = self.current_parse_task.as_ref() {
-            old_cancel_flag.set();
-        }
         self.current_parse_task = Some((task_id, cancel_flag));
+        self.edits_since_spawn.clear();
     }
 
-    pub fn handle_parse_syntax_done(&mut self, version: usize, status: ParserStatus) {
+    /// Stores the tree from a finished parse task and starts the queued parse,
+    /// if any
+    pub fn handle_parse_syntax_done(
+        &mut self,
+        task_pool: &TaskPool,
+        version: usize,
+        status: ParserStatus,
+    ) {
         let ParserStatus {
             task_id,
             parser,
             parsed,
         } = status;
 
-        // Collect the parser for later reuse
+        // Collect the parser for later reuse, unless it was started for a
+        // different language before the buffer's mode changed
         parser.cancel_flag().clear();
-        self.parsers.push(parser);
+        if parser.language() == Some(self.language) {
+            self.parsers.push(parser);
+        }
 
         // If we weren't waiting for this task, return
         if self
@@ -157,34 +208,64 @@ impl ParserPool {
             return;
         }
         self.current_parse_task = None;
+        let queued_parse = self.queued_parse.take();
 
-        // If the parser task hasn't been cancelled, store the new syntax tree
-        if let Some(ParsedSyntax { tree, text }) = parsed {
-            assert!(tree.root_node().end_byte() == text.len_bytes());
-            self.tree = Some(ParseTree { version, tree });
+        // If the parser task hasn't been cancelled, store the new syntax tree,
+        // unless it's about to be replaced by a fresh parse. The edits made
+        // while parsing are applied to it, as they were to the old tree.
+        match parsed {
+            Some(_) if queued_parse.as_ref().map_or(false, |queued| queued.fresh) => {}
+            Some(ParsedSyntax { tree, text }) => {
+                assert!(tree.root_node().end_byte() == text.len_bytes());
+                let mut tree = ParseTree { version, tree };
+                for diff in self.edits_since_spawn.drain(..) {
+                    edit_tree(&mut tree, &diff);
+                }
+                self.tree = Some(tree);
+            }
+            None => {}
+        }
+
+        if let Some(QueuedParse {
+            text,
+            fresh,
+            on_parse,
+        }) = queued_parse
+        {
+            if fresh {
+                self.tree = None;
+            }
+            self.spawn_now(task_pool, text, fresh, on_parse);
         }
     }
 
     pub fn edit(&mut self, diff: &OpaqueDiff) {
-        match self.tree {
-            Some(ref mut tree) if !diff.is_empty() => {
-                tree.edit(&TreeSitterInputEdit {
-                    start_byte: diff.byte_index,
-                    old_end_byte: diff.byte_index + diff.old_byte_length,
-                    new_end_byte: diff.byte_index + diff.new_byte_length,
-                    // I don't use tree sitter's line/col tracking; I'm assuming
-                    // here that passing in dummy values doesn't cause any other
-                    // problem apart from incorrect line/col after editing a tree.
-                    start_position: TreeSitterPoint::new(0, 0),
-                    old_end_position: TreeSitterPoint::new(0, 0),
-                    new_end_position: TreeSitterPoint::new(0, 0),
-                });
-            }
-            _ => {}
+        if diff.is_empty() {
+            return;
+        }
+        if let Some(tree) = self.tree.as_mut() {
+            edit_tree(tree, diff);
+        }
+        if self.current_parse_task.is_some() {
+            self.edits_since_spawn.push(diff.clone());
         }
     }
 }
 
+pub(super) fn edit_tree(tree: &mut Tree, diff: &OpaqueDiff) {
+    tree.edit(&TreeSitterInputEdit {
+        start_byte: diff.byte_index,
+        old_end_byte: diff.byte_index + diff.old_byte_length,
+        new_end_byte: diff.byte_index + diff.new_byte_length,
+        // I don't use tree sitter's line/col tracking; I'm assuming
+        // here that passing in dummy values doesn't cause any other
+        // problem apart from incorrect line/col after editing a tree.
+        start_position: TreeSitterPoint::new(0, 0),
+        old_end_position: TreeSitterPoint::new(0, 0),
+        new_end_position: TreeSitterPoint::new(0, 0),
+    });
+}
+
 #[derive(Clone)]
 struct CancelFlag(Arc<AtomicUsize>);
 
@@ -242,5 +323,33 @@ impl DerefMut for CancelableParser {
     }
 }
 
+/// Gives tree-sitter queries access to the text of a rope
+pub struct RopeProvider<'a>(pub RopeSlice<'a>);
+
+impl<'a> TextProvider<'a> for RopeProvider<'a> {
+    type I = ChunksBytes<'a>;
+
+    #[inline]
+    fn text(&mut self, node: Node) -> Self::I {
+        let fragment = self.0.byte_slice(node.start_byte()..node.end_byte());
+        ChunksBytes {
+            chunks: fragment.chunks(),
+        }
+    }
+}
+
+pub struct ChunksBytes<'a> {
+    chunks: ropey::iter::Chunks<'a>,
+}
+
+impl<'a> Iterator for ChunksBytes<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(str::as_bytes)
+    }
+}
+
 const CANCEL_FLAG_UNSET: usize = 0;
 const CANCEL_FLAG_SET: usize = 1;
@@ -0,0 +1,93 @@
+//! Moving over and killing whole syntax nodes, like Emacs' sexps: an
+//! argument, an expression or a statement. The node after a position is the
+//! outermost one starting at the first non-blank character after it, so the
+//! motions never leave the node they start in.
+
+use ropey::Rope;
+use tree_sitter::{Node, Tree};
+
+use zee_edit::{CharIndex, Direction};
+
+/// Where the node after a position ends, or the one before it starts
+pub fn node_boundary(
+    tree: &Tree,
+    text: &Rope,
+    char_index: CharIndex,
+    direction: Direction,
+) -> Option<CharIndex> {
+    match direction {
+        Direction::Forward => next_node_end(tree, text, char_index),
+        Direction::Backward => previous_node_start(tree, text, char_index),
+    }
+}
+
+/// Where the node after a position ends. `None` at the end of the enclosing
+/// node, e.g. before a closing parenthesis.
+fn next_node_end(tree: &Tree, text: &Rope, char_index: CharIndex) -> Option<CharIndex> {
+    let mut start = char_index.min(text.len_chars());
+    while start < text.len_chars() && text.char(start).is_whitespace() {
+        start += 1;
+    }
+    if start == text.len_chars() {
+        return None;
+    }
+
+    let byte_index = text.char_to_byte(start);
+    let mut node = tree
+        .root_node()
+        .descendant_for_byte_range(byte_index, byte_index + 1)?;
+    // In the middle of a token, only the rest of it
+    if node.start_byte() == byte_index {
+        while let Some(parent) = node
+            .parent()
+            .filter(|parent| parent.start_byte() == byte_index && parent.parent().is_some())
+        {
+            node = parent;
+        }
+    }
+    while is_separator(node) {
+        node = node.next_sibling()?;
+    }
+    if !node.is_named() && node.next_sibling().is_none() && node.prev_sibling().is_some() {
+        return None;
+    }
+    Some(text.byte_to_char(node.end_byte()))
+}
+
+/// Where the node before a position starts. `None` at the start of the
+/// enclosing node, e.g. after an opening parenthesis.
+fn previous_node_start(tree: &Tree, text: &Rope, char_index: CharIndex) -> Option<CharIndex> {
+    let mut end = char_index.min(text.len_chars());
+    while end > 0 && text.char(end - 1).is_whitespace() {
+        end -= 1;
+    }
+    if end == 0 {
+        return None;
+    }
+
+    let byte_index = text.char_to_byte(end);
+    let mut node = tree
+        .root_node()
+        .descendant_for_byte_range(byte_index - 1, byte_index)?;
+    // In the middle of a token, only the start of it
+    if node.end_byte() == byte_index {
+        while let Some(parent) = node
+            .parent()
+            .filter(|parent| parent.end_byte() == byte_index && parent.parent().is_some())
+        {
+            node = parent;
+        }
+    }
+    while is_separator(node) {
+        node = node.prev_sibling()?;
+    }
+    if !node.is_named() && node.prev_sibling().is_none() && node.next_sibling().is_some() {
+        return None;
+    }
+    Some(text.byte_to_char(node.start_byte()))
+}
+
+/// Punctuation between nodes, skipped over rather than moved over on its own
+fn is_separator(node: Node) -> bool {
+    !node.is_named() && matches!(node.kind(), "," | ";")
+}
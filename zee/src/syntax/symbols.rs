@@ -0,0 +1,135 @@
+use ropey::Rope;
+use std::ops::Range;
+use tree_sitter::{Node, Query, QueryCursor, Tree};
+
+use zee_edit::{ByteIndex, CharIndex, LineIndex};
+
+use super::parse::RopeProvider;
+
+/// A definition found by a mode's `tags` query, e.g. a function, a type or a
+/// heading in a markdown document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: String,
+    pub char_index: CharIndex,
+    pub line_index: LineIndex,
+}
+
+/// Extracts the definitions captured by a `tags` query, in the order they
+/// appear in the text. Following tree-sitter's tagging conventions, a match
+/// must capture the definition as `@definition.<kind>` and its name as `@name`.
+pub fn extract_symbols(query: &Query, tree: &Tree, text: &Rope) -> Vec<Symbol> {
+    let name_index = match query.capture_index_for_name("name") {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let capture_names = query.capture_names();
+
+    let mut query_cursor = QueryCursor::new();
+    let mut symbols: Vec<Symbol> = query_cursor
+        .matches(query, tree.root_node(), RopeProvider(text.slice(..)))
+        .filter_map(|query_match| {
+            let name = query_match
+                .captures
+                .iter()
+                .find(|capture| capture.index == name_index)?
+                .node;
+            let kind = query_match.captures.iter().find_map(|capture| {
+                capture_names[capture.index as usize].strip_prefix("definition.")
+            })?;
+            let char_index = text.byte_to_char(name.start_byte());
+            Some(Symbol {
+                name: node_text(text, &name),
+                kind: kind.to_owned(),
+                char_index,
+                line_index: text.char_to_line(char_index),
+            })
+        })
+        .collect();
+    symbols.sort_by_key(|symbol| symbol.char_index);
+    symbols.dedup();
+    symbols
+}
+
+/// A definition around a position in the text, see `enclosing_definitions`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: String,
+    pub byte_range: Range<ByteIndex>,
+}
+
+/// The definitions enclosing a position, outermost first, e.g. an `impl`
+/// block followed by one of its methods
+pub fn enclosing_definitions(
+    query: &Query,
+    tree: &Tree,
+    text: &Rope,
+    char_index: CharIndex,
+) -> Vec<Definition> {
+    let name_index = match query.capture_index_for_name("name") {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let capture_names = query.capture_names();
+    let byte_index = text.char_to_byte(char_index);
+
+    let mut query_cursor = QueryCursor::new();
+    query_cursor.set_byte_range(byte_index..byte_index + 1);
+    let mut definitions: Vec<_> = query_cursor
+        .matches(query, tree.root_node(), RopeProvider(text.slice(..)))
+        .filter_map(|query_match| {
+            let name = query_match
+                .captures
+                .iter()
+                .find(|capture| capture.index == name_index)?
+                .node;
+            let (kind, definition) = query_match.captures.iter().find_map(|capture| {
+                let kind = capture_names[capture.index as usize].strip_prefix("definition.")?;
+                Some((kind, capture.node))
+            })?;
+            definition
+                .byte_range()
+                .contains(&byte_index)
+                .then(|| Definition {
+                    name: node_text(text, &name),
+                    kind: kind.to_owned(),
+                    byte_range: definition.byte_range(),
+                })
+        })
+        .collect();
+    definitions.sort_by_key(|definition| {
+        (
+            definition.byte_range.start,
+            std::cmp::Reverse(definition.byte_range.end),
+        )
+    });
+    definitions.dedup();
+    definitions
+}
+
+/// The identifier at a position, or right before it as after typing it
+pub fn identifier_at(text: &Rope, char_index: CharIndex) -> Option<String> {
+    let is_identifier_char = |character: char| character.is_alphanumeric() || character == '_';
+    let char_index = char_index.min(text.len_chars());
+    let start = text
+        .chars_at(char_index)
+        .reversed()
+        .take_while(|character| is_identifier_char(*character))
+        .count();
+    let end = text
+        .chars_at(char_index)
+        .take_while(|character| is_identifier_char(*character))
+        .count();
+    let identifier = text.slice(char_index - start..char_index + end).to_string();
+    (!identifier.is_empty() && !identifier.starts_with(|character: char| character.is_numeric()))
+        .then(|| identifier)
+}
+
+fn node_text(text: &Rope, node: &Node) -> String {
+    text.byte_slice(node.start_byte()..node.end_byte())
+        .to_string()
+        .trim()
+        .to_owned()
+}
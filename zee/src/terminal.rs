@@ -0,0 +1,49 @@
+//! Escape sequences zee writes to the terminal itself, for features zi's
+//! frontends don't support. They're written between frames, so they don't
+//! interleave with drawing.
+
+use std::io::{self, Write};
+
+/// Saves the terminal's title on the terminal's title stack, so it can be
+/// restored on exit
+pub fn save_title() {
+    write_sequence("\x1b[22;0t");
+}
+
+/// Restores the title saved by `save_title`
+pub fn restore_title() {
+    write_sequence("\x1b[23;0t");
+}
+
+pub fn set_title(title: &str) {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    write_sequence(&format!("\x1b]2;{}\x07", title));
+}
+
+/// Puts the terminal back the way it was before zee started, in case a crash
+/// left it on the alternate screen or in raw mode: resets the colours, shows
+/// the cursor, stops mouse and bracketed paste reporting and leaves the
+/// alternate screen. Raw mode is turned off with `stty`.
+pub fn reset() {
+    write_sequence(
+        "\x1b[0m\x1b[?25h\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?2004l\x1b[?1049l",
+    );
+    #[cfg(unix)]
+    if let Ok(tty) = std::fs::File::open("/dev/tty") {
+        let _ = std::process::Command::new("stty")
+            .arg("sane")
+            .stdin(tty)
+            .status();
+    }
+}
+
+fn write_sequence(sequence: &str) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(error) = stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        log::warn!("Could not write to the terminal: {}", error);
+    }
+}
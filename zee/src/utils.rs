@@ -1,4 +1,13 @@
 use ropey::Rope;
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    mem,
+    ops::RangeInclusive,
+    rc::Rc,
+};
+
+use zee_edit::{graphemes::GraphemeMetrics, LineIndex, OpaqueDiff};
 
 #[derive(Copy)]
 pub struct StaticRefEq<T: 'static>(&'static T);
@@ -29,8 +38,160 @@ impl<T> From<&'static T> for StaticRefEq<T> {
     }
 }
 
+/// Values computed for some lines of a text, kept in place as the text is
+/// edited. An edit drops the lines it touches, the lines after them are
+/// moved along.
+pub struct LineCache<T> {
+    lines: HashMap<LineIndex, T>,
+    /// How many lines the text had as of the last edit
+    num_lines: usize,
+}
+
+impl<T> LineCache<T> {
+    pub fn new(text: &Rope) -> Self {
+        Self {
+            lines: HashMap::new(),
+            num_lines: text.len_lines(),
+        }
+    }
+
+    /// Forgets every line, e.g. after the text was replaced
+    pub fn clear(&mut self, text: &Rope) {
+        self.lines.clear();
+        self.num_lines = text.len_lines();
+    }
+
+    /// Updates the cache after an edit, given the text after the edit
+    pub fn reconcile(&mut self, text: &Rope, diff: &OpaqueDiff) {
+        let num_lines_before = mem::replace(&mut self.num_lines, text.len_lines());
+        if self.lines.is_empty() || diff.is_empty() {
+            return;
+        }
+        let num_lines = self.num_lines;
+        let edit_start = text.char_to_line(diff.char_index.min(text.len_chars()));
+        let new_edit_end =
+            text.char_to_line((diff.char_index + diff.new_char_length).min(text.len_chars()));
+        let old_edit_end = (new_edit_end + num_lines_before).saturating_sub(num_lines);
+        self.lines = mem::take(&mut self.lines)
+            .into_iter()
+            .filter_map(|(line_index, value)| {
+                if old_edit_end < line_index {
+                    Some(((line_index + num_lines) - num_lines_before, value))
+                } else {
+                    (line_index < edit_start).then(|| (line_index, value))
+                }
+            })
+            .collect();
+    }
+
+    #[inline]
+    pub fn get(&self, line_index: LineIndex) -> Option<&T> {
+        self.lines.get(&line_index)
+    }
+
+    #[inline]
+    pub fn contains(&self, line_index: LineIndex) -> bool {
+        self.lines.contains_key(&line_index)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, line_index: LineIndex, value: T) {
+        self.lines.insert(line_index, value);
+    }
+
+    /// Forgets a range of lines
+    pub fn remove(&mut self, line_indices: RangeInclusive<LineIndex>) {
+        self.lines
+            .retain(|line_index, _| !line_indices.contains(line_index));
+    }
+
+    #[inline]
+    pub fn get_or_insert_with(&mut self, line_index: LineIndex, make: impl FnOnce() -> T) -> &T {
+        self.lines.entry(line_index).or_insert_with(make)
+    }
+}
+
+/// The graphemes of the lines of a buffer which have been drawn, so redrawing
+/// a line which hasn't changed doesn't segment it again
+pub struct LineMetricsCache {
+    /// The tab width the graphemes were measured with
+    tab_width: usize,
+    lines: LineCache<Rc<[GraphemeMetrics]>>,
+}
+
+impl LineMetricsCache {
+    pub fn new(text: &Rope) -> Self {
+        Self {
+            tab_width: 0,
+            lines: LineCache::new(text),
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self, text: &Rope) {
+        self.lines.clear(text);
+    }
+
+    #[inline]
+    pub fn reconcile(&mut self, text: &Rope, diff: &OpaqueDiff) {
+        self.lines.reconcile(text, diff);
+    }
+
+    /// The graphemes of a line, measuring it if it isn't cached
+    pub fn metrics(
+        &mut self,
+        text: &Rope,
+        tab_width: usize,
+        line_index: LineIndex,
+    ) -> Rc<[GraphemeMetrics]> {
+        if self.tab_width != tab_width {
+            self.tab_width = tab_width;
+            self.lines.clear(text);
+        }
+        self.lines
+            .get_or_insert_with(line_index, || {
+                zee_edit::graphemes::grapheme_metrics(tab_width, &text.line(line_index)).collect()
+            })
+            .clone()
+    }
+}
+
 pub fn ensure_trailing_newline_with_content(text: &mut Rope) {
     if text.len_chars() == 0 || text.char(text.len_chars() - 1) != '\n' {
         text.insert_char(text.len_chars(), '\n');
     }
 }
+
+/// Wraps a reader, calling `on_progress` with the number of bytes read so far
+/// after every `PROGRESS_INTERVAL_BYTES`
+pub struct ProgressReader<ReaderT, CallbackT> {
+    reader: ReaderT,
+    bytes_read: u64,
+    last_reported: u64,
+    on_progress: CallbackT,
+}
+
+impl<ReaderT, CallbackT: FnMut(u64)> ProgressReader<ReaderT, CallbackT> {
+    pub fn new(reader: ReaderT, on_progress: CallbackT) -> Self {
+        Self {
+            reader,
+            bytes_read: 0,
+            last_reported: 0,
+            on_progress,
+        }
+    }
+}
+
+impl<ReaderT: Read, CallbackT: FnMut(u64)> Read for ProgressReader<ReaderT, CallbackT> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let num_bytes = self.reader.read(buffer)?;
+        self.bytes_read += num_bytes as u64;
+        if self.bytes_read - self.last_reported >= PROGRESS_INTERVAL_BYTES {
+            self.last_reported = self.bytes_read;
+            (self.on_progress)(self.bytes_read);
+        }
+        Ok(num_bytes)
+    }
+}
+
+const PROGRESS_INTERVAL_BYTES: u64 = 1 << 20;